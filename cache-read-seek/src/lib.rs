@@ -1,9 +1,10 @@
 use std::{
-    collections::{hash_map::Entry, HashMap},
+    collections::HashMap,
     io::{Read, Seek, SeekFrom},
 };
 
-const SIZE: u64 = 0x1000;
+/// The size of the cached blocks
+pub const SIZE: u64 = 0x1000;
 
 pub struct CachedReadSeek<R: Read + Seek> {
     reader: R,
@@ -17,38 +18,47 @@ impl<R: Read + Seek> CachedReadSeek<R> {
             cache: HashMap::new(),
         }
     }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
 }
 
 impl<R: Read + Seek> Read for CachedReadSeek<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         let start = self.stream_position()?;
         let total_size = self.reader.seek(SeekFrom::End(0))?;
-        let delta = start % SIZE;
-        let cache_start = start - delta;
-        let cache_read_size = match total_size.checked_sub(cache_start) {
-            Some(x) => SIZE.min(x) as usize,
-            None => {
-                self.seek(SeekFrom::Start(start))?;
-                return Ok(0);
+        if start >= total_size || buf.is_empty() {
+            self.seek(SeekFrom::Start(start))?;
+            return Ok(0);
+        }
+        let end = total_size.min(start.saturating_add(buf.len() as u64));
+        let cache_start = start - start % SIZE;
+        if !self.cache.contains_key(&cache_start) {
+            // Adjacent missing blocks are read together, up to the first cached one
+            let mut miss_end = cache_start + SIZE;
+            while miss_end < end && !self.cache.contains_key(&miss_end) {
+                miss_end += SIZE;
             }
-        };
-        let entry = self.cache.entry(cache_start);
-        let data = match entry {
-            Entry::Occupied(ref data) => data.get(),
-            Entry::Vacant(entry) => {
-                let mut data = [0; SIZE as usize];
-                self.reader.seek(SeekFrom::Start(cache_start))?;
-                self.reader.read_exact(&mut data[..cache_read_size])?;
-                entry.insert(data)
+            let mut data = vec![0; (miss_end.min(total_size) - cache_start) as usize];
+            self.reader.seek(SeekFrom::Start(cache_start))?;
+            self.reader.read_exact(&mut data)?;
+            for (i, chunk) in data.chunks(SIZE as usize).enumerate() {
+                let mut block = [0; SIZE as usize];
+                block[..chunk.len()].copy_from_slice(chunk);
+                self.cache.insert(cache_start + i as u64 * SIZE, block);
             }
-        };
-        let delta = delta as usize;
-        let n = buf
-            .len()
-            .min((total_size - start) as usize)
-            .min(SIZE as usize - delta);
-        buf[..n].copy_from_slice(&data[delta..delta + n]);
-        self.reader.seek(SeekFrom::Start(start + n as u64))?;
+        }
+        let mut n = 0;
+        let mut pos = start;
+        while let Some(block) = self.cache.get(&(pos - pos % SIZE)).filter(|_| pos < end) {
+            let delta = (pos % SIZE) as usize;
+            let len = (SIZE as usize - delta).min((end - pos) as usize);
+            buf[n..n + len].copy_from_slice(&block[delta..delta + len]);
+            n += len;
+            pos += len as u64;
+        }
+        self.reader.seek(SeekFrom::Start(pos))?;
         Ok(n)
     }
 }
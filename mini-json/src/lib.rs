@@ -0,0 +1,211 @@
+//! A small JSON parser, sufficient for the manifests and headers of archive formats.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// Members in document order
+    Object(Vec<(String, Value)>),
+}
+
+/// Upper bound on nesting, so that malicious documents can't overflow the stack
+const MAX_DEPTH: usize = 128;
+
+impl Value {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let mut parser = Parser { data, pos: 0 };
+        let value = parser.value(0)?;
+        parser.whitespace();
+        (parser.pos == data.len()).then_some(value)
+    }
+
+    /// Returns the member `key` of an object.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(String, Value)]> {
+        match self {
+            Value::Object(members) => Some(members),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Returns a number that is a non-negative integer, exactly representable as `f64`.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Number(n) if *n >= 0.0 && n.fract() == 0.0 && *n <= (1u64 << 53) as f64 => {
+                Some(*n as u64)
+            }
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn whitespace(&mut self) {
+        while self
+            .data
+            .get(self.pos)
+            .is_some_and(|b| b" \t\r\n".contains(b))
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, token: &[u8]) -> bool {
+        let matches = self.data[self.pos..].starts_with(token);
+        if matches {
+            self.pos += token.len();
+        }
+        matches
+    }
+
+    fn value(&mut self, depth: usize) -> Option<Value> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        self.whitespace();
+        match *self.data.get(self.pos)? {
+            b'{' => {
+                self.pos += 1;
+                let mut members = Vec::new();
+                self.whitespace();
+                if !self.eat(b"}") {
+                    loop {
+                        self.whitespace();
+                        let key = self.string()?;
+                        self.whitespace();
+                        if !self.eat(b":") {
+                            return None;
+                        }
+                        members.push((key, self.value(depth + 1)?));
+                        self.whitespace();
+                        if self.eat(b"}") {
+                            break;
+                        }
+                        if !self.eat(b",") {
+                            return None;
+                        }
+                    }
+                }
+                Some(Value::Object(members))
+            }
+            b'[' => {
+                self.pos += 1;
+                let mut values = Vec::new();
+                self.whitespace();
+                if !self.eat(b"]") {
+                    loop {
+                        values.push(self.value(depth + 1)?);
+                        self.whitespace();
+                        if self.eat(b"]") {
+                            break;
+                        }
+                        if !self.eat(b",") {
+                            return None;
+                        }
+                    }
+                }
+                Some(Value::Array(values))
+            }
+            b'"' => Some(Value::String(self.string()?)),
+            _ if self.eat(b"null") => Some(Value::Null),
+            _ if self.eat(b"true") => Some(Value::Bool(true)),
+            _ if self.eat(b"false") => Some(Value::Bool(false)),
+            _ => {
+                let start = self.pos;
+                while self
+                    .data
+                    .get(self.pos)
+                    .is_some_and(|b| b"+-.eE0123456789".contains(b))
+                {
+                    self.pos += 1;
+                }
+                let number = std::str::from_utf8(&self.data[start..self.pos]).ok()?;
+                Some(Value::Number(number.parse().ok()?))
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Option<u16> {
+        let digits = std::str::from_utf8(self.data.get(self.pos..self.pos + 4)?).ok()?;
+        self.pos += 4;
+        u16::from_str_radix(digits, 16).ok()
+    }
+
+    fn string(&mut self) -> Option<String> {
+        if !self.eat(b"\"") {
+            return None;
+        }
+        let mut out = Vec::new();
+        loop {
+            let b = *self.data.get(self.pos)?;
+            self.pos += 1;
+            match b {
+                b'"' => break,
+                b'\\' => {
+                    let escape = *self.data.get(self.pos)?;
+                    self.pos += 1;
+                    let c = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let unit = self.hex4()?;
+                            let mut units = vec![unit];
+                            // Characters outside the BMP are escaped as surrogate pairs
+                            if (0xd800..0xdc00).contains(&unit) && self.eat(b"\\u") {
+                                units.push(self.hex4()?);
+                            }
+                            let s = String::from_utf16_lossy(&units);
+                            out.extend_from_slice(s.as_bytes());
+                            continue;
+                        }
+                        _ => return None,
+                    };
+                    out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                b => out.push(b),
+            }
+        }
+        String::from_utf8(out).ok()
+    }
+}
@@ -0,0 +1,184 @@
+use mini_json::Value;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom},
+    rc::Rc,
+};
+use vfs::bytes::u32_at;
+use window_read_seek::WindowReadSeek;
+
+/// Upper bound on the JSON header, which is read into memory
+const MAX_HEADER_LEN: u32 = 256 << 20;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed reading from backing IO")]
+    Io(#[from] std::io::Error),
+    #[error("invalid asar header")]
+    InvalidHeader,
+    #[error("file is stored outside the archive, in the .unpacked directory")]
+    Unpacked,
+    #[error("entry not found")]
+    NotFound,
+    #[error("entry is not a regular file")]
+    NotAFile,
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    metadata: vfs::Metadata,
+    /// Offset of the data relative to the end of the header, or `None` for unpacked files
+    offset: Option<u64>,
+}
+
+type Index = HashMap<Vec<u8>, Entry>;
+
+fn add_entries(index: &mut Index, dir: &[u8], files: &Value) -> Result<(), Error> {
+    let metadata = |file_type, len, mode| vfs::Metadata {
+        file_type,
+        len,
+        mode,
+        mtime: None,
+    };
+    for (name, node) in files.as_object().ok_or(Error::InvalidHeader)? {
+        if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+            return Err(Error::InvalidHeader);
+        }
+        let path = if dir.is_empty() {
+            name.as_bytes().to_vec()
+        } else {
+            [dir, b"/", name.as_bytes()].concat()
+        };
+        if let Some(files) = node.get("files") {
+            add_entries(index, &path, files)?;
+            let entry = Entry {
+                metadata: metadata(vfs::FileType::Dir, 0, None),
+                offset: None,
+            };
+            index.insert(path, entry);
+        } else if node.get("link").is_some() {
+            let entry = Entry {
+                metadata: metadata(vfs::FileType::SymLink, 0, None),
+                offset: None,
+            };
+            index.insert(path, entry);
+        } else {
+            let len = node
+                .get("size")
+                .and_then(Value::as_u64)
+                .ok_or(Error::InvalidHeader)?;
+            let executable = node.get("executable").and_then(Value::as_bool);
+            let mode = if executable == Some(true) {
+                0o755
+            } else {
+                0o644
+            };
+            let offset = if node.get("unpacked").and_then(Value::as_bool) == Some(true) {
+                None
+            } else {
+                // Offsets are strings, as they may exceed the integers of JavaScript
+                let offset = node.get("offset").and_then(Value::as_str);
+                Some(
+                    offset
+                        .and_then(|s| s.parse().ok())
+                        .ok_or(Error::InvalidHeader)?,
+                )
+            };
+            let entry = Entry {
+                metadata: metadata(vfs::FileType::File, len, Some(mode)),
+                offset,
+            };
+            index.insert(path, entry);
+        }
+    }
+    Ok(())
+}
+
+/// Reads the header, returning the index and the offset of the file data.
+fn read_index<R: Read + Seek>(reader: &mut R) -> Result<(Index, u64), Error> {
+    // The header is a Chromium pickle holding the size of another pickle, which holds the JSON
+    let mut sizes = [0; 16];
+    reader.seek(SeekFrom::Start(0))?;
+    reader.read_exact(&mut sizes)?;
+    let header_len = u32_at(&sizes, 4);
+    let json_len = u32_at(&sizes, 12);
+    if u32_at(&sizes, 0) != 4 || json_len > MAX_HEADER_LEN || json_len > header_len {
+        return Err(Error::InvalidHeader);
+    }
+    let mut json = vec![0; json_len as usize];
+    reader.read_exact(&mut json)?;
+    let header = Value::parse(&json).ok_or(Error::InvalidHeader)?;
+    let files = header.get("files").ok_or(Error::InvalidHeader)?;
+    let mut index = Index::new();
+    add_entries(&mut index, b"", files)?;
+    Ok((index, 8 + header_len as u64))
+}
+
+/// An Electron application archive. Files marked as unpacked are listed, but their data lives
+/// next to the archive and can't be opened.
+pub struct AsarFs<R: Read + Seek> {
+    reader: Rc<RefCell<R>>,
+    index: Option<(Index, u64)>,
+}
+
+impl<R: Read + Seek> AsarFs<R> {
+    fn entry(&mut self, path: &[u8]) -> Result<(Entry, u64), Error> {
+        if self.index.is_none() {
+            self.index = Some(read_index(&mut *self.reader.borrow_mut())?);
+        }
+        let (index, data_offset) = self.index.as_ref().unwrap();
+        let path = vfs::path::normalize(path);
+        if path.is_empty() {
+            let root = Entry {
+                metadata: vfs::Metadata {
+                    file_type: vfs::FileType::Dir,
+                    len: 0,
+                    mode: None,
+                    mtime: None,
+                },
+                offset: None,
+            };
+            return Ok((root, *data_offset));
+        }
+        let entry = index.get(&path).cloned().ok_or(Error::NotFound)?;
+        Ok((entry, *data_offset))
+    }
+}
+
+impl<R: Read + Seek> vfs::IoBackedFs<R> for AsarFs<R> {
+    type Password = ();
+
+    fn from_io(io: R, _password: Self::Password) -> Self {
+        Self {
+            reader: Rc::new(RefCell::new(io)),
+            index: None,
+        }
+    }
+}
+
+impl<R: Read + Seek> vfs::Fs for AsarFs<R> {
+    type Path = [u8];
+    type Error = Error;
+    type File = WindowReadSeek<R>;
+
+    fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Self::Error> {
+        Ok(self.entry(path)?.0.metadata)
+    }
+
+    fn open(&mut self, path: &[u8]) -> Result<Self::File, Self::Error> {
+        let (entry, data_offset) = self.entry(path)?;
+        if entry.metadata.file_type != vfs::FileType::File {
+            return Err(Error::NotAFile);
+        }
+        let offset = entry.offset.ok_or(Error::Unpacked)?;
+        let start = data_offset
+            .checked_add(offset)
+            .ok_or(Error::InvalidHeader)?;
+        Ok(WindowReadSeek::new(
+            self.reader.clone(),
+            start,
+            entry.metadata.len,
+        ))
+    }
+}
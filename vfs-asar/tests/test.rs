@@ -0,0 +1,61 @@
+use std::io::{Cursor, Read};
+use vfs::{Fs, IoBackedFs};
+
+fn asar(header: &str, data: &[u8]) -> Vec<u8> {
+    let padded_len = header.len().next_multiple_of(4) as u32;
+    let mut asar = Vec::new();
+    for size in [4, padded_len + 8, padded_len + 4, header.len() as u32] {
+        asar.extend_from_slice(&size.to_le_bytes());
+    }
+    asar.extend_from_slice(header.as_bytes());
+    asar.resize(16 + padded_len as usize, 0);
+    asar.extend_from_slice(data);
+    asar
+}
+
+fn read(fs: &mut vfs_asar::AsarFs<Cursor<Vec<u8>>>, path: &str) -> String {
+    let mut contents = String::new();
+    let mut file = fs.open(path.as_bytes()).unwrap();
+    file.read_to_string(&mut contents).unwrap();
+    contents
+}
+
+#[test]
+fn test() {
+    let header = r#"{"files":{
+        "package.json":{"size":15,"offset":"0"},
+        "lib":{"files":{
+            "main.js":{"size":7,"offset":"15","executable":true,
+                "integrity":{"algorithm":"SHA256","hash":"00","blockSize":4194304,"blocks":["00"]}},
+            "native.node":{"size":100,"unpacked":true}}},
+        "current":{"link":"lib"}}}"#;
+    let mut fs = vfs_asar::AsarFs::from_io(
+        Cursor::new(asar(header, b"{\"name\":\"app\"}\nmain();")),
+        (),
+    );
+    assert_eq!(read(&mut fs, "package.json"), "{\"name\":\"app\"}\n");
+    assert_eq!(read(&mut fs, "/lib/main.js"), "main();");
+    assert_eq!(fs.metadata(b"lib/main.js").unwrap().mode, Some(0o755));
+    assert_eq!(fs.metadata(b"").unwrap().file_type, vfs::FileType::Dir);
+    assert_eq!(fs.metadata(b"lib").unwrap().file_type, vfs::FileType::Dir);
+    assert_eq!(
+        fs.metadata(b"current").unwrap().file_type,
+        vfs::FileType::SymLink
+    );
+    assert_eq!(fs.metadata(b"lib/native.node").unwrap().len, 100);
+    assert!(matches!(
+        fs.open(b"lib/native.node"),
+        Err(vfs_asar::Error::Unpacked)
+    ));
+    assert!(matches!(fs.open(b"lib"), Err(vfs_asar::Error::NotAFile)));
+    assert!(matches!(
+        fs.metadata(b"missing"),
+        Err(vfs_asar::Error::NotFound)
+    ));
+
+    let mut fs = vfs_asar::AsarFs::from_io(Cursor::new(asar(r#"{"files":{"a":{}}}"#, b"")), ());
+    assert!(matches!(
+        fs.metadata(b"a"),
+        Err(vfs_asar::Error::InvalidHeader)
+    ));
+}
@@ -0,0 +1,85 @@
+use crate::{Checkpoint, Error, Format, Index};
+use bzip2::{Decompress, Status};
+use std::{
+    cell::RefCell,
+    io::{Read, Seek},
+    rc::Rc,
+};
+use window_read_seek::WindowReadSeek;
+
+const MAGIC: &[u8; 3] = b"BZh";
+
+pub(crate) struct Bzip2;
+
+impl Format for Bzip2 {
+    type State = ();
+    type Decoder<R: Read + Seek> = bzip2::read::BzDecoder<WindowReadSeek<R>>;
+
+    const NAME: &'static str = "bzip2";
+
+    /// Decodes the whole file once to find where each concatenated stream starts.
+    fn index<R: Read + Seek>(
+        reader: &Rc<RefCell<R>>,
+        compressed_len: u64,
+    ) -> Result<Index<()>, Error> {
+        let mut checkpoints = Vec::new();
+        let mut compressed = 0;
+        let mut uncompressed = 0;
+        let mut input = vec![0; 1 << 16];
+        let mut output = vec![0; 1 << 16];
+        loop {
+            let mut window =
+                WindowReadSeek::new(reader.clone(), compressed, compressed_len - compressed);
+            let mut magic = [0; 3];
+            // Anything other than another stream after the first is trailing garbage
+            if window.read_exact(&mut magic).is_err() || magic != *MAGIC {
+                if checkpoints.is_empty() {
+                    return Err(Error::InvalidStream(Self::NAME));
+                }
+                break;
+            }
+            window.rewind()?;
+            checkpoints.push(Checkpoint {
+                compressed,
+                uncompressed,
+                unit_start: true,
+                state: (),
+            });
+            let mut decompress = Decompress::new(false);
+            let (mut start, mut end) = (0, 0);
+            loop {
+                if start == end {
+                    start = 0;
+                    end = window.read(&mut input)?;
+                    if end == 0 {
+                        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+                    }
+                }
+                let (total_in, total_out) = (decompress.total_in(), decompress.total_out());
+                let status = decompress
+                    .decompress(&input[start..end], &mut output)
+                    .map_err(|_| Error::InvalidStream(Self::NAME))?;
+                start += (decompress.total_in() - total_in) as usize;
+                if status == Status::StreamEnd {
+                    break;
+                }
+                if decompress.total_in() == total_in && decompress.total_out() == total_out {
+                    return Err(Error::InvalidStream(Self::NAME));
+                }
+            }
+            compressed += decompress.total_in();
+            uncompressed += decompress.total_out();
+        }
+        Ok(Index {
+            checkpoints,
+            len: uncompressed,
+        })
+    }
+
+    fn decoder<R: Read + Seek>(
+        data: WindowReadSeek<R>,
+        _state: &(),
+    ) -> Result<Self::Decoder<R>, Error> {
+        Ok(bzip2::read::BzDecoder::new(data))
+    }
+}
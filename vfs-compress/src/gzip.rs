@@ -0,0 +1,344 @@
+use crate::{invalid_data, Checkpoint, Error, Format, Index};
+use miniz_oxide::inflate::{
+    core::{
+        decompress,
+        inflate_flags::{TINFL_FLAG_HAS_MORE_INPUT, TINFL_FLAG_IGNORE_ADLER32},
+        DecompressorOxide,
+    },
+    TINFLStatus,
+};
+use std::{
+    cell::RefCell,
+    io::{Read, Seek, SeekFrom},
+    rc::Rc,
+};
+use window_read_seek::WindowReadSeek;
+
+const MAGIC: [u8; 2] = [0x1f, 0x8b];
+const FLAG_HCRC: u8 = 1 << 1;
+const FLAG_EXTRA: u8 = 1 << 2;
+const FLAG_NAME: u8 = 1 << 3;
+const FLAG_COMMENT: u8 = 1 << 4;
+
+/// Length of a BGZF block with nothing compressed: the header with only the `BC` subfield, and
+/// the CRC-32 and length trailer
+const BGZF_MIN_BLOCK_LEN: u64 = 10 + 2 + 6 + 8;
+/// Size of the deflate history window, which is also used as the circular output buffer
+const WINDOW_LEN: usize = 1 << 15;
+/// Distance in decompressed bytes between inflate state snapshots
+const SPAN: u64 = 1 << 22;
+
+#[derive(Clone)]
+pub(crate) struct InflateState {
+    decompressor: Box<DecompressorOxide>,
+    window: Box<[u8; WINDOW_LEN]>,
+    window_pos: usize,
+}
+
+/// Buffered input that tracks how much of the backing reader has been consumed.
+struct Input<R: Read> {
+    reader: R,
+    buf: Box<[u8; 1 << 16]>,
+    start: usize,
+    end: usize,
+    eof: bool,
+    consumed: u64,
+}
+
+impl<R: Read> Input<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: Box::new([0; 1 << 16]),
+            start: 0,
+            end: 0,
+            eof: false,
+            consumed: 0,
+        }
+    }
+
+    fn fill(&mut self) -> std::io::Result<&[u8]> {
+        if self.start == self.end && !self.eof {
+            self.start = 0;
+            self.end = self.reader.read(&mut self.buf[..])?;
+            self.eof = self.end == 0;
+        }
+        Ok(&self.buf[self.start..self.end])
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.start += n;
+        self.consumed += n as u64;
+    }
+
+    fn byte(&mut self) -> std::io::Result<Option<u8>> {
+        let b = self.fill()?.first().copied();
+        if b.is_some() {
+            self.consume(1);
+        }
+        Ok(b)
+    }
+
+    fn read_exact<const N: usize>(&mut self) -> std::io::Result<[u8; N]> {
+        let mut buf = [0; N];
+        for b in &mut buf {
+            *b = self.byte()?.ok_or(std::io::ErrorKind::UnexpectedEof)?;
+        }
+        Ok(buf)
+    }
+
+    fn skip_string(&mut self) -> std::io::Result<()> {
+        while self.byte()?.ok_or(std::io::ErrorKind::UnexpectedEof)? != 0 {}
+        Ok(())
+    }
+}
+
+/// Member header fields that matter for indexing.
+struct Header {
+    /// Compressed size of the member, if it is a BGZF block
+    bgzf_block_len: Option<u64>,
+}
+
+fn read_header<R: Read>(input: &mut Input<R>) -> std::io::Result<Header> {
+    let [id1, id2, method, flags, _, _, _, _, _, _] = input.read_exact::<10>()?;
+    if [id1, id2] != MAGIC || method != 8 {
+        return Err(invalid_data("invalid gzip member header"));
+    }
+    let mut bgzf_block_len = None;
+    if flags & FLAG_EXTRA != 0 {
+        let len = u16::from_le_bytes(input.read_exact::<2>()?);
+        let mut extra = vec![0; len as usize];
+        for b in &mut extra {
+            *b = input.read_exact::<1>()?[0];
+        }
+        let mut i = 0;
+        while i + 4 <= extra.len() {
+            let subfield_len = u16::from_le_bytes([extra[i + 2], extra[i + 3]]) as usize;
+            if extra[i..i + 2] == *b"BC" && subfield_len == 2 && i + 6 <= extra.len() {
+                let block_size = u16::from_le_bytes([extra[i + 4], extra[i + 5]]);
+                bgzf_block_len = Some(block_size as u64 + 1);
+            }
+            i += 4 + subfield_len;
+        }
+    }
+    if flags & FLAG_NAME != 0 {
+        input.skip_string()?;
+    }
+    if flags & FLAG_COMMENT != 0 {
+        input.skip_string()?;
+    }
+    if flags & FLAG_HCRC != 0 {
+        input.read_exact::<2>()?;
+    }
+    Ok(Header { bgzf_block_len })
+}
+
+/// Decodes a single gzip member, either from its header or from an inflate state snapshot.
+pub(crate) struct MemberDecoder<R: Read> {
+    input: Input<R>,
+    state: InflateState,
+    /// Checksum of the output so far, if decoding started at the beginning of the member
+    crc: Option<crc32fast::Hasher>,
+    len: u64,
+    pending: std::ops::Range<usize>,
+    done: bool,
+}
+
+impl<R: Read> MemberDecoder<R> {
+    fn new(reader: R, state: Option<&InflateState>) -> std::io::Result<Self> {
+        let mut input = Input::new(reader);
+        let (state, crc) = match state {
+            Some(state) => (state.clone(), None),
+            None => {
+                read_header(&mut input)?;
+                let state = InflateState {
+                    decompressor: Box::default(),
+                    window: Box::new([0; WINDOW_LEN]),
+                    window_pos: 0,
+                };
+                (state, Some(crc32fast::Hasher::new()))
+            }
+        };
+        Ok(Self {
+            input,
+            state,
+            crc,
+            len: 0,
+            pending: 0..0,
+            done: false,
+        })
+    }
+
+    /// Checks the member trailer once the deflate stream ends.
+    fn finish(&mut self) -> std::io::Result<()> {
+        let trailer = self.input.read_exact::<8>()?;
+        let crc = u32::from_le_bytes(trailer[..4].try_into().unwrap());
+        let len = u32::from_le_bytes(trailer[4..].try_into().unwrap());
+        if let Some(hasher) = self.crc.take() {
+            if hasher.finalize() != crc || self.len as u32 != len {
+                return Err(invalid_data("gzip member checksum mismatch"));
+            }
+        }
+        self.done = true;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for MemberDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pending.is_empty() && !self.done {
+            self.input.fill()?;
+            let input = &self.input.buf[self.input.start..self.input.end];
+            let flags = if self.input.eof {
+                TINFL_FLAG_IGNORE_ADLER32
+            } else {
+                TINFL_FLAG_HAS_MORE_INPUT | TINFL_FLAG_IGNORE_ADLER32
+            };
+            let state = &mut self.state;
+            let (status, consumed, produced) = decompress(
+                &mut state.decompressor,
+                input,
+                &mut state.window[..],
+                state.window_pos,
+                flags,
+            );
+            self.input.consume(consumed);
+            self.pending = state.window_pos..state.window_pos + produced;
+            state.window_pos = (state.window_pos + produced) % WINDOW_LEN;
+            match status {
+                TINFLStatus::Done => {
+                    if let Some(crc) = &mut self.crc {
+                        crc.update(&self.state.window[self.pending.clone()]);
+                    }
+                    self.len += produced as u64;
+                    self.finish()?;
+                    break;
+                }
+                TINFLStatus::HasMoreOutput | TINFLStatus::NeedsMoreInput => {}
+                TINFLStatus::FailedCannotMakeProgress => {
+                    return Err(std::io::ErrorKind::UnexpectedEof.into())
+                }
+                status => return Err(invalid_data(format!("inflate failed: {status:?}"))),
+            }
+            if let Some(crc) = &mut self.crc {
+                crc.update(&self.state.window[self.pending.clone()]);
+            }
+            self.len += produced as u64;
+        }
+        let n = buf.len().min(self.pending.len());
+        let start = self.pending.start;
+        buf[..n].copy_from_slice(&self.state.window[start..start + n]);
+        self.pending.start += n;
+        Ok(n)
+    }
+}
+
+pub(crate) struct Gzip;
+
+/// Indexes BGZF files by hopping between block headers, without decompressing anything.
+fn bgzf_index<R: Read + Seek>(
+    reader: &Rc<RefCell<R>>,
+    compressed_len: u64,
+) -> Result<Option<Index<Option<InflateState>>>, Error> {
+    let mut checkpoints = Vec::new();
+    let mut compressed = 0;
+    let mut uncompressed = 0;
+    while compressed < compressed_len {
+        let window = WindowReadSeek::new(reader.clone(), compressed, compressed_len - compressed);
+        let Header {
+            bgzf_block_len: Some(block_len),
+        } = read_header(&mut Input::new(window.clone()))?
+        else {
+            return Ok(None);
+        };
+        // Too short to hold its own header and trailer, so not BGZF after all
+        if block_len < BGZF_MIN_BLOCK_LEN {
+            return Ok(None);
+        }
+        let mut window = window;
+        window.seek(SeekFrom::Start(block_len - 4))?;
+        let mut isize = [0; 4];
+        window.read_exact(&mut isize)?;
+        checkpoints.push(Checkpoint {
+            compressed,
+            uncompressed,
+            unit_start: true,
+            state: None,
+        });
+        compressed += block_len;
+        uncompressed += u32::from_le_bytes(isize) as u64;
+    }
+    Ok(Some(Index {
+        checkpoints,
+        len: uncompressed,
+    }))
+}
+
+impl Format for Gzip {
+    type State = Option<InflateState>;
+    type Decoder<R: Read + Seek> = MemberDecoder<WindowReadSeek<R>>;
+
+    const NAME: &'static str = "gzip";
+
+    fn index<R: Read + Seek>(
+        reader: &Rc<RefCell<R>>,
+        compressed_len: u64,
+    ) -> Result<Index<Option<InflateState>>, Error> {
+        if let Some(index) = bgzf_index(reader, compressed_len)? {
+            return Ok(index);
+        }
+        let mut checkpoints = Vec::new();
+        let mut compressed = 0;
+        let mut uncompressed = 0;
+        let mut buf = vec![0; 1 << 16];
+        loop {
+            let mut window =
+                WindowReadSeek::new(reader.clone(), compressed, compressed_len - compressed);
+            let mut magic = [0; 2];
+            // Anything other than another member after the first is trailing garbage
+            if window.read_exact(&mut magic).is_err() || magic != MAGIC {
+                if checkpoints.is_empty() {
+                    return Err(Error::InvalidStream(Self::NAME));
+                }
+                break;
+            }
+            window.rewind()?;
+            checkpoints.push(Checkpoint {
+                compressed,
+                uncompressed,
+                unit_start: true,
+                state: None,
+            });
+            let mut decoder = MemberDecoder::new(window, None)?;
+            let mut next_snapshot = uncompressed + SPAN;
+            loop {
+                let n = decoder.read(&mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                uncompressed += n as u64;
+                if uncompressed >= next_snapshot && decoder.pending.is_empty() && !decoder.done {
+                    checkpoints.push(Checkpoint {
+                        compressed: compressed + decoder.input.consumed,
+                        uncompressed,
+                        unit_start: false,
+                        state: Some(decoder.state.clone()),
+                    });
+                    next_snapshot = uncompressed + SPAN;
+                }
+            }
+            compressed += decoder.input.consumed;
+        }
+        Ok(Index {
+            checkpoints,
+            len: uncompressed,
+        })
+    }
+
+    fn decoder<R: Read + Seek>(
+        data: WindowReadSeek<R>,
+        state: &Option<InflateState>,
+    ) -> Result<Self::Decoder<R>, Error> {
+        Ok(MemberDecoder::new(data, state.as_ref())?)
+    }
+}
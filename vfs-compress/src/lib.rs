@@ -0,0 +1,310 @@
+mod bzip2;
+mod gzip;
+mod lz4;
+mod xz;
+mod zstd;
+
+use cache_read_seek::CachedReadSeek;
+use std::{
+    cell::RefCell,
+    io::{Read, Seek, SeekFrom},
+    rc::Rc,
+};
+use window_read_seek::WindowReadSeek;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed reading from backing IO")]
+    Io(#[from] std::io::Error),
+    #[error("invalid {0} stream")]
+    InvalidStream(&'static str),
+    #[error("entry not found")]
+    NotFound,
+}
+
+/// A position in the compressed stream that decoding can resume from.
+#[derive(Clone)]
+struct Checkpoint<S> {
+    compressed: u64,
+    uncompressed: u64,
+    /// Whether an independently decodable unit (a gzip member, zstd frame, xz block, ...) starts
+    /// here, as opposed to somewhere in the middle of one
+    unit_start: bool,
+    state: S,
+}
+
+struct Index<S> {
+    checkpoints: Vec<Checkpoint<S>>,
+    len: u64,
+}
+
+impl<S> Index<S> {
+    /// Finds the end of the compressed unit containing `checkpoint`, which is where the next
+    /// unit starts.
+    fn unit_end(&self, checkpoint: usize, compressed_len: u64) -> u64 {
+        self.checkpoints[checkpoint + 1..]
+            .iter()
+            .find(|c| c.unit_start)
+            .map_or(compressed_len, |c| c.compressed)
+    }
+}
+
+/// A single stream compression format.
+trait Format {
+    type State: Clone;
+    type Decoder<R: Read + Seek>: Read;
+
+    const NAME: &'static str;
+
+    /// Builds the checkpoint index, either from a seek table stored in the stream or by
+    /// decoding the whole stream once.
+    fn index<R: Read + Seek>(
+        reader: &Rc<RefCell<R>>,
+        compressed_len: u64,
+    ) -> Result<Index<Self::State>, Error>;
+
+    /// Creates a decoder for the rest of the unit that `data` spans, resuming with the state
+    /// recorded at the checkpoint.
+    fn decoder<R: Read + Seek>(
+        data: WindowReadSeek<R>,
+        state: &Self::State,
+    ) -> Result<Self::Decoder<R>, Error>;
+}
+
+fn invalid_data(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+}
+
+struct Decoding<R: Read + Seek, F: Format> {
+    decoder: F::Decoder<R>,
+    checkpoint: usize,
+    offset: u64,
+}
+
+/// Decompressed view of a stream. Reads resume decoding from the closest checkpoint at or
+/// before the read offset, unless the current decoder is already closer.
+struct CachelessFile<R: Read + Seek, F: Format> {
+    reader: Rc<RefCell<R>>,
+    compressed_len: u64,
+    index: Rc<Index<F::State>>,
+    decoding: Option<Decoding<R, F>>,
+    offset: u64,
+}
+
+impl<R: Read + Seek, F: Format> CachelessFile<R, F> {
+    fn resume(&mut self, checkpoint: usize) -> std::io::Result<()> {
+        let c = &self.index.checkpoints[checkpoint];
+        let end = self.index.unit_end(checkpoint, self.compressed_len);
+        let data = WindowReadSeek::new(self.reader.clone(), c.compressed, end - c.compressed);
+        self.decoding = Some(Decoding {
+            decoder: F::decoder(data, &c.state).map_err(std::io::Error::other)?,
+            checkpoint,
+            offset: c.uncompressed,
+        });
+        Ok(())
+    }
+
+    /// Decodes into `buf`, moving on to the next unit whenever the current one ends.
+    fn decode(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let decoding = self.decoding.as_mut().unwrap();
+            let n = decoding.decoder.read(buf)?;
+            decoding.offset += n as u64;
+            if n != 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            let offset = decoding.offset;
+            let next = self.index.checkpoints[decoding.checkpoint + 1..]
+                .iter()
+                .position(|c| c.unit_start)
+                .map(|i| decoding.checkpoint + 1 + i)
+                .filter(|&i| self.index.checkpoints[i].uncompressed == offset)
+                .ok_or_else(|| invalid_data(format!("{} stream ended early", F::NAME)))?;
+            self.resume(next)?;
+        }
+    }
+}
+
+impl<R: Read + Seek, F: Format> Read for CachelessFile<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.index.len;
+        if self.offset >= len {
+            return Ok(0);
+        }
+        let offset = self.offset;
+        let closest = self
+            .index
+            .checkpoints
+            .partition_point(|c| c.uncompressed <= offset)
+            .saturating_sub(1);
+        let closest_offset = self.index.checkpoints[closest].uncompressed;
+        let usable = self
+            .decoding
+            .as_ref()
+            .is_some_and(|d| d.offset <= offset && closest_offset <= d.offset);
+        if !usable {
+            self.resume(closest)?;
+        }
+        let mut skip_buf = [0; 4096];
+        while self.decoding.as_ref().unwrap().offset < offset {
+            let skip = offset - self.decoding.as_ref().unwrap().offset;
+            let n = skip.min(skip_buf.len() as u64) as usize;
+            self.decode(&mut skip_buf[..n])?;
+        }
+        let n = (buf.len() as u64).min(len - offset) as usize;
+        let n = self.decode(&mut buf[..n])?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek, F: Format> Seek for CachelessFile<R, F> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.offset = vfs::io::seek_position(pos, self.offset, self.index.len)?;
+        Ok(self.offset)
+    }
+}
+
+struct StreamFs<R: Read + Seek, F: Format> {
+    reader: Rc<RefCell<R>>,
+    compressed_len: u64,
+    index: Option<Rc<Index<F::State>>>,
+}
+
+impl<R: Read + Seek, F: Format> StreamFs<R, F> {
+    fn new(io: R) -> Self {
+        Self {
+            reader: Rc::new(RefCell::new(io)),
+            compressed_len: 0,
+            index: None,
+        }
+    }
+
+    /// Returns the index, building it on first access. The decompressed stream is the only file,
+    /// at the root path.
+    fn index(&mut self, path: &[u8]) -> Result<Rc<Index<F::State>>, Error> {
+        if !vfs::path::normalize(path).is_empty() {
+            return Err(Error::NotFound);
+        }
+        if self.index.is_none() {
+            self.compressed_len = self.reader.borrow_mut().seek(SeekFrom::End(0))?;
+            self.index = Some(Rc::new(F::index(&self.reader, self.compressed_len)?));
+        }
+        Ok(self.index.clone().unwrap())
+    }
+
+    fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Error> {
+        let index = self.index(path)?;
+        Ok(vfs::Metadata {
+            file_type: vfs::FileType::File,
+            len: index.len,
+            mode: None,
+            mtime: None,
+        })
+    }
+
+    fn open(&mut self, path: &[u8]) -> Result<CachedReadSeek<CachelessFile<R, F>>, Error> {
+        let index = self.index(path)?;
+        Ok(CachedReadSeek::new(CachelessFile {
+            reader: self.reader.clone(),
+            compressed_len: self.compressed_len,
+            index,
+            decoding: None,
+            offset: 0,
+        }))
+    }
+}
+
+enum FileInner<R: Read + Seek> {
+    Gzip(CachedReadSeek<CachelessFile<R, gzip::Gzip>>),
+    Xz(CachedReadSeek<CachelessFile<R, xz::Xz>>),
+    Zstd(Box<CachedReadSeek<CachelessFile<R, zstd::Zstd>>>),
+    Bzip2(CachedReadSeek<CachelessFile<R, bzip2::Bzip2>>),
+    Lz4(CachedReadSeek<CachelessFile<R, lz4::Lz4>>),
+}
+
+pub struct File<R: Read + Seek>(FileInner<R>);
+
+impl<R: Read + Seek> Read for File<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match &mut self.0 {
+            FileInner::Gzip(x) => x.read(buf),
+            FileInner::Xz(x) => x.read(buf),
+            FileInner::Zstd(x) => x.read(buf),
+            FileInner::Bzip2(x) => x.read(buf),
+            FileInner::Lz4(x) => x.read(buf),
+        }
+    }
+}
+
+impl<R: Read + Seek> Seek for File<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match &mut self.0 {
+            FileInner::Gzip(x) => x.seek(pos),
+            FileInner::Xz(x) => x.seek(pos),
+            FileInner::Zstd(x) => x.seek(pos),
+            FileInner::Bzip2(x) => x.seek(pos),
+            FileInner::Lz4(x) => x.seek(pos),
+        }
+    }
+}
+
+macro_rules! impl_fs {
+    ($(#[$attr:meta])* $T:ident, $F:ty, $Variant:ident) => {
+        $(#[$attr])*
+        pub struct $T<R: Read + Seek>(StreamFs<R, $F>);
+
+        impl<R: Read + Seek> vfs::IoBackedFs<R> for $T<R> {
+            type Password = ();
+
+            fn from_io(io: R, _password: Self::Password) -> Self {
+                Self(StreamFs::new(io))
+            }
+        }
+
+        impl<R: Read + Seek> vfs::Fs for $T<R> {
+            type Path = [u8];
+            type Error = Error;
+            type File = File<R>;
+
+            fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Error> {
+                self.0.metadata(path)
+            }
+
+            fn open(&mut self, path: &[u8]) -> Result<Self::File, Error> {
+                Ok(File(FileInner::$Variant(self.0.open(path)?.into())))
+            }
+        }
+    };
+}
+
+impl_fs!(
+    /// A gzip stream, seekable through BGZF block offsets or inflate state snapshots.
+    GzipFs,
+    gzip::Gzip,
+    Gzip
+);
+impl_fs!(
+    /// An xz stream, seekable at block boundaries using the index of each stream.
+    XzFs,
+    xz::Xz,
+    Xz
+);
+impl_fs!(
+    /// A zstd stream, seekable at frame boundaries or using the seekable format's seek table.
+    ZstdFs,
+    zstd::Zstd,
+    Zstd
+);
+impl_fs!(
+    /// A bzip2 stream, seekable at stream boundaries (e.g. in pbzip2 output).
+    Bzip2Fs,
+    bzip2::Bzip2,
+    Bzip2
+);
+impl_fs!(
+    /// An LZ4 frame stream, seekable at frame boundaries.
+    Lz4Fs,
+    lz4::Lz4,
+    Lz4
+);
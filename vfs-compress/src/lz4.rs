@@ -0,0 +1,119 @@
+use crate::{Checkpoint, Error, Format, Index};
+use lz4_flex::frame::FrameDecoder;
+use std::{
+    cell::RefCell,
+    io::{Read, Seek, SeekFrom},
+    rc::Rc,
+};
+use window_read_seek::WindowReadSeek;
+
+const MAGIC: u32 = 0x184d2204;
+const LEGACY_MAGIC: u32 = 0x184c2102;
+const SKIPPABLE_MAGIC: std::ops::RangeInclusive<u32> = 0x184d2a50..=0x184d2a5f;
+
+const FLAG_DICTIONARY_ID: u8 = 1 << 0;
+const FLAG_CONTENT_CHECKSUM: u8 = 1 << 2;
+const FLAG_CONTENT_SIZE: u8 = 1 << 3;
+const FLAG_BLOCK_CHECKSUMS: u8 = 1 << 4;
+
+fn read_u32<R: Read>(reader: &mut R) -> std::io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+/// Walks the block headers of the frame after the reader's position, returning its compressed
+/// length and its content size if the header declares it.
+fn read_frame_layout<R: Read + Seek>(reader: &mut R) -> Result<(u64, Option<u64>), Error> {
+    let start = reader.stream_position()? - 4;
+    let mut descriptor = [0; 2];
+    reader.read_exact(&mut descriptor)?;
+    let [flags, _] = descriptor;
+    let content_size = if flags & FLAG_CONTENT_SIZE != 0 {
+        let mut buf = [0; 8];
+        reader.read_exact(&mut buf)?;
+        Some(u64::from_le_bytes(buf))
+    } else {
+        None
+    };
+    if flags & FLAG_DICTIONARY_ID != 0 {
+        reader.seek(SeekFrom::Current(4))?;
+    }
+    // Header checksum
+    reader.seek(SeekFrom::Current(1))?;
+    let block_checksum_len = if flags & FLAG_BLOCK_CHECKSUMS != 0 {
+        4
+    } else {
+        0
+    };
+    loop {
+        let block_len = read_u32(reader)? & 0x7fffffff;
+        if block_len == 0 {
+            break;
+        }
+        reader.seek(SeekFrom::Current(block_len as i64 + block_checksum_len))?;
+    }
+    if flags & FLAG_CONTENT_CHECKSUM != 0 {
+        reader.seek(SeekFrom::Current(4))?;
+    }
+    Ok((reader.stream_position()? - start, content_size))
+}
+
+pub(crate) struct Lz4;
+
+impl Format for Lz4 {
+    type State = ();
+    type Decoder<R: Read + Seek> = FrameDecoder<WindowReadSeek<R>>;
+
+    const NAME: &'static str = "lz4";
+
+    /// Every frame becomes a checkpoint, with frames that don't declare their content size being
+    /// decoded to find it. Legacy frames have no end mark, so they extend to the end of the file.
+    fn index<R: Read + Seek>(
+        reader: &Rc<RefCell<R>>,
+        compressed_len: u64,
+    ) -> Result<Index<()>, Error> {
+        let mut checkpoints = Vec::new();
+        let (mut compressed, mut uncompressed) = (0, 0);
+        while compressed < compressed_len {
+            let mut window =
+                WindowReadSeek::new(reader.clone(), compressed, compressed_len - compressed);
+            let magic = read_u32(&mut window)?;
+            let (frame_len, content_size) = match magic {
+                MAGIC => read_frame_layout(&mut window)?,
+                LEGACY_MAGIC => (window.len(), None),
+                magic if SKIPPABLE_MAGIC.contains(&magic) => {
+                    compressed += 8 + read_u32(&mut window)? as u64;
+                    continue;
+                }
+                _ => return Err(Error::InvalidStream(Self::NAME)),
+            };
+            let content_size = match content_size {
+                Some(size) => size,
+                None => {
+                    let frame = WindowReadSeek::new(reader.clone(), compressed, frame_len);
+                    std::io::copy(&mut Self::decoder(frame, &())?, &mut std::io::sink())?
+                }
+            };
+            checkpoints.push(Checkpoint {
+                compressed,
+                uncompressed,
+                unit_start: true,
+                state: (),
+            });
+            compressed += frame_len;
+            uncompressed += content_size;
+        }
+        Ok(Index {
+            checkpoints,
+            len: uncompressed,
+        })
+    }
+
+    fn decoder<R: Read + Seek>(
+        data: WindowReadSeek<R>,
+        _state: &(),
+    ) -> Result<Self::Decoder<R>, Error> {
+        Ok(FrameDecoder::new(data))
+    }
+}
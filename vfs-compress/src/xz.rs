@@ -0,0 +1,191 @@
+use crate::{Checkpoint, Error, Format, Index};
+use std::{
+    cell::RefCell,
+    io::{Chain, Cursor, Read, Seek, SeekFrom, Take},
+    rc::Rc,
+};
+use window_read_seek::WindowReadSeek;
+
+const HEADER_MAGIC: [u8; 6] = [0xfd, b'7', b'z', b'X', b'Z', 0];
+const FOOTER_MAGIC: [u8; 2] = *b"YZ";
+const HEADER_LEN: u64 = 12;
+const FOOTER_LEN: u64 = 12;
+
+/// Everything needed to decode a block on its own.
+#[derive(Clone)]
+pub(crate) struct Block {
+    /// The stream flags, which determine the check type
+    stream_flags: [u8; 2],
+    unpadded_len: u64,
+    uncompressed_len: u64,
+}
+
+fn read_vli(buf: &[u8], i: &mut usize) -> Result<u64, Error> {
+    let mut n = 0;
+    for shift in (0..63).step_by(7) {
+        let b = *buf.get(*i).ok_or(Error::InvalidStream(Xz::NAME))?;
+        *i += 1;
+        n |= ((b & 0x7f) as u64) << shift;
+        if b & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err(Error::InvalidStream(Xz::NAME))
+}
+
+fn write_vli(buf: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        buf.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    buf.push(n as u8);
+}
+
+fn pad4(len: u64) -> u64 {
+    len.div_ceil(4) * 4
+}
+
+/// Returns the blocks of the stream ending at `end` as `(offset, block)` pairs, along with the
+/// offset of the stream.
+fn read_stream<R: Read + Seek>(
+    reader: &mut R,
+    end: u64,
+) -> Result<(Vec<(u64, Block)>, u64), Error> {
+    let footer_start = end
+        .checked_sub(FOOTER_LEN)
+        .ok_or(Error::InvalidStream(Xz::NAME))?;
+    let mut footer = [0; FOOTER_LEN as usize];
+    reader.seek(SeekFrom::Start(footer_start))?;
+    reader.read_exact(&mut footer)?;
+    if footer[10..] != FOOTER_MAGIC
+        || crc32fast::hash(&footer[4..10]) != u32::from_le_bytes(footer[..4].try_into().unwrap())
+    {
+        return Err(Error::InvalidStream(Xz::NAME));
+    }
+    let index_len = (u32::from_le_bytes(footer[4..8].try_into().unwrap()) as u64 + 1) * 4;
+    let stream_flags = [footer[8], footer[9]];
+    let index_start = footer_start
+        .checked_sub(index_len)
+        .ok_or(Error::InvalidStream(Xz::NAME))?;
+    let mut index = vec![0; index_len as usize];
+    reader.seek(SeekFrom::Start(index_start))?;
+    reader.read_exact(&mut index)?;
+    let (index, crc) = index.split_at(index.len() - 4);
+    if index.first() != Some(&0)
+        || crc32fast::hash(index) != u32::from_le_bytes(crc.try_into().unwrap())
+    {
+        return Err(Error::InvalidStream(Xz::NAME));
+    }
+    let mut i = 1;
+    let count = read_vli(index, &mut i)?;
+    let mut records = Vec::new();
+    for _ in 0..count {
+        let unpadded_len = read_vli(index, &mut i)?;
+        let uncompressed_len = read_vli(index, &mut i)?;
+        records.push(Block {
+            stream_flags,
+            unpadded_len,
+            uncompressed_len,
+        });
+    }
+    let blocks_len: u64 = records.iter().map(|b| pad4(b.unpadded_len)).sum();
+    let stream_start = index_start
+        .checked_sub(blocks_len + HEADER_LEN)
+        .ok_or(Error::InvalidStream(Xz::NAME))?;
+    let mut header = [0; HEADER_LEN as usize];
+    reader.seek(SeekFrom::Start(stream_start))?;
+    reader.read_exact(&mut header)?;
+    if header[..6] != HEADER_MAGIC || header[6..8] != stream_flags {
+        return Err(Error::InvalidStream(Xz::NAME));
+    }
+    let mut offset = stream_start + HEADER_LEN;
+    let blocks = records
+        .into_iter()
+        .map(|block| {
+            let entry = (offset, block);
+            offset += pad4(entry.1.unpadded_len);
+            entry
+        })
+        .collect();
+    Ok((blocks, stream_start))
+}
+
+pub(crate) struct Xz;
+
+type BlockStream<R> = Chain<Chain<Cursor<Vec<u8>>, Take<WindowReadSeek<R>>>, Cursor<Vec<u8>>>;
+
+impl Format for Xz {
+    type State = Block;
+    type Decoder<R: Read + Seek> = xz2::read::XzDecoder<BlockStream<R>>;
+
+    const NAME: &'static str = "xz";
+
+    /// Reads the index of each concatenated stream, walking backwards from the end. Every block
+    /// becomes a checkpoint, so files written by multithreaded xz or pixz are fully seekable.
+    fn index<R: Read + Seek>(
+        reader: &Rc<RefCell<R>>,
+        compressed_len: u64,
+    ) -> Result<Index<Block>, Error> {
+        let mut reader = reader.borrow_mut();
+        let mut streams = Vec::new();
+        let mut end = compressed_len;
+        while end > 0 {
+            // Streams may be separated by padding in multiples of four null bytes
+            let mut padding = [0; 4];
+            reader.seek(SeekFrom::Start(end.saturating_sub(4)))?;
+            reader.read_exact(&mut padding)?;
+            if padding == [0; 4] {
+                end -= 4;
+                continue;
+            }
+            let (blocks, start) = read_stream(&mut *reader, end)?;
+            streams.push(blocks);
+            end = start;
+        }
+        let mut checkpoints = Vec::new();
+        let mut uncompressed = 0;
+        for (compressed, block) in streams.into_iter().rev().flatten() {
+            let len = block.uncompressed_len;
+            checkpoints.push(Checkpoint {
+                compressed,
+                uncompressed,
+                unit_start: true,
+                state: block,
+            });
+            uncompressed += len;
+        }
+        Ok(Index {
+            checkpoints,
+            len: uncompressed,
+        })
+    }
+
+    /// Wraps the block in a synthetic single-block stream, so that liblzma validates it like any
+    /// other stream.
+    fn decoder<R: Read + Seek>(
+        data: WindowReadSeek<R>,
+        block: &Block,
+    ) -> Result<Self::Decoder<R>, Error> {
+        let mut header = HEADER_MAGIC.to_vec();
+        header.extend(block.stream_flags);
+        header.extend(crc32fast::hash(&block.stream_flags).to_le_bytes());
+
+        let mut index = vec![0];
+        write_vli(&mut index, 1);
+        write_vli(&mut index, block.unpadded_len);
+        write_vli(&mut index, block.uncompressed_len);
+        index.resize(pad4(index.len() as u64) as usize, 0);
+        index.extend(crc32fast::hash(&index).to_le_bytes());
+        let mut footer = ((index.len() / 4 - 1) as u32).to_le_bytes().to_vec();
+        footer.extend(block.stream_flags);
+        let mut trailer = index;
+        trailer.extend(crc32fast::hash(&footer).to_le_bytes());
+        trailer.extend(footer);
+        trailer.extend(FOOTER_MAGIC);
+
+        let stream = Cursor::new(header)
+            .chain(data.take(pad4(block.unpadded_len)))
+            .chain(Cursor::new(trailer));
+        Ok(xz2::read::XzDecoder::new(stream))
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{Checkpoint, Error, Format, Index};
+use ruzstd::decoding::{FrameDecoder, StreamingDecoder};
+use std::{
+    cell::RefCell,
+    io::{Read, Seek, SeekFrom},
+    rc::Rc,
+};
+use window_read_seek::WindowReadSeek;
+
+const FRAME_MAGIC: u32 = 0xfd2fb528;
+const SKIPPABLE_MAGIC: std::ops::RangeInclusive<u32> = 0x184d2a50..=0x184d2a5f;
+const SEEK_TABLE_MAGIC: u32 = 0x8f92eab1;
+const SEEK_TABLE_FOOTER_LEN: u64 = 9;
+
+fn read_u32<R: Read>(reader: &mut R) -> std::io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+/// Builds checkpoints from the seek table of the zstd seekable format, if there is one.
+fn seek_table_index<R: Read + Seek>(
+    reader: &mut R,
+    compressed_len: u64,
+) -> Result<Option<Index<()>>, Error> {
+    let Some(footer_start) = compressed_len.checked_sub(SEEK_TABLE_FOOTER_LEN) else {
+        return Ok(None);
+    };
+    let mut footer = [0; SEEK_TABLE_FOOTER_LEN as usize];
+    reader.seek(SeekFrom::Start(footer_start))?;
+    reader.read_exact(&mut footer)?;
+    if u32::from_le_bytes(footer[5..].try_into().unwrap()) != SEEK_TABLE_MAGIC {
+        return Ok(None);
+    }
+    let frame_count = u32::from_le_bytes(footer[..4].try_into().unwrap()) as u64;
+    let entry_len = if footer[4] & 0x80 != 0 { 12 } else { 8 };
+    let entries_start = footer_start
+        .checked_sub(frame_count * entry_len)
+        .ok_or(Error::InvalidStream(Zstd::NAME))?;
+    let mut entries = vec![0; (frame_count * entry_len) as usize];
+    reader.seek(SeekFrom::Start(entries_start))?;
+    reader.read_exact(&mut entries)?;
+    let mut checkpoints = Vec::new();
+    let (mut compressed, mut uncompressed) = (0, 0);
+    for entry in entries.chunks_exact(entry_len as usize) {
+        checkpoints.push(Checkpoint {
+            compressed,
+            uncompressed,
+            unit_start: true,
+            state: (),
+        });
+        compressed += u32::from_le_bytes(entry[..4].try_into().unwrap()) as u64;
+        uncompressed += u32::from_le_bytes(entry[4..8].try_into().unwrap()) as u64;
+    }
+    Ok(Some(Index {
+        checkpoints,
+        len: uncompressed,
+    }))
+}
+
+/// Walks the block headers of the frame at the reader's position, returning its compressed
+/// length and its content size if the header declares it.
+fn read_frame_layout<R: Read + Seek>(reader: &mut R) -> Result<(u64, Option<u64>), Error> {
+    let start = reader.stream_position()? - 4;
+    let mut descriptor = [0; 1];
+    reader.read_exact(&mut descriptor)?;
+    let descriptor = descriptor[0];
+    let single_segment = descriptor & 0x20 != 0;
+    let has_checksum = descriptor & 0x04 != 0;
+    let dict_id_len = [0, 1, 2, 4][(descriptor & 0x03) as usize];
+    let content_size_len = match descriptor >> 6 {
+        0 if single_segment => 1,
+        0 => 0,
+        1 => 2,
+        2 => 4,
+        _ => 8,
+    };
+    let window_descriptor_len = if single_segment { 0 } else { 1 };
+    reader.seek(SeekFrom::Current(window_descriptor_len + dict_id_len))?;
+    let content_size = match content_size_len {
+        0 => None,
+        len => {
+            let mut buf = [0; 8];
+            reader.read_exact(&mut buf[..len])?;
+            let size = u64::from_le_bytes(buf);
+            Some(if len == 2 { size + 256 } else { size })
+        }
+    };
+    loop {
+        let mut header = [0; 4];
+        reader.read_exact(&mut header[..3])?;
+        let header = u32::from_le_bytes(header);
+        let last = header & 1 != 0;
+        let block_len = match (header >> 1) & 3 {
+            // RLE blocks store a single byte
+            1 => 1,
+            3 => return Err(Error::InvalidStream(Zstd::NAME)),
+            _ => header >> 3,
+        };
+        reader.seek(SeekFrom::Current(block_len as i64))?;
+        if last {
+            break;
+        }
+    }
+    if has_checksum {
+        reader.seek(SeekFrom::Current(4))?;
+    }
+    Ok((reader.stream_position()? - start, content_size))
+}
+
+pub(crate) struct Zstd;
+
+impl Format for Zstd {
+    type State = ();
+    type Decoder<R: Read + Seek> = StreamingDecoder<WindowReadSeek<R>, FrameDecoder>;
+
+    const NAME: &'static str = "zstd";
+
+    /// Uses the seek table if present. Otherwise every frame becomes a checkpoint, with frames
+    /// that don't declare their content size being decoded to find it.
+    fn index<R: Read + Seek>(
+        reader: &Rc<RefCell<R>>,
+        compressed_len: u64,
+    ) -> Result<Index<()>, Error> {
+        if let Some(index) = seek_table_index(&mut *reader.borrow_mut(), compressed_len)? {
+            return Ok(index);
+        }
+        let mut checkpoints = Vec::new();
+        let (mut compressed, mut uncompressed) = (0, 0);
+        while compressed < compressed_len {
+            let mut window =
+                WindowReadSeek::new(reader.clone(), compressed, compressed_len - compressed);
+            let magic = read_u32(&mut window)?;
+            if SKIPPABLE_MAGIC.contains(&magic) {
+                compressed += 8 + read_u32(&mut window)? as u64;
+                continue;
+            }
+            if magic != FRAME_MAGIC {
+                return Err(Error::InvalidStream(Self::NAME));
+            }
+            let (frame_len, content_size) = read_frame_layout(&mut window)?;
+            let content_size = match content_size {
+                Some(size) => size,
+                None => {
+                    let frame = WindowReadSeek::new(reader.clone(), compressed, frame_len);
+                    std::io::copy(&mut Self::decoder(frame, &())?, &mut std::io::sink())?
+                }
+            };
+            checkpoints.push(Checkpoint {
+                compressed,
+                uncompressed,
+                unit_start: true,
+                state: (),
+            });
+            compressed += frame_len;
+            uncompressed += content_size;
+        }
+        Ok(Index {
+            checkpoints,
+            len: uncompressed,
+        })
+    }
+
+    fn decoder<R: Read + Seek>(
+        data: WindowReadSeek<R>,
+        _state: &(),
+    ) -> Result<Self::Decoder<R>, Error> {
+        StreamingDecoder::new(data).map_err(|_| Error::InvalidStream(Self::NAME))
+    }
+}
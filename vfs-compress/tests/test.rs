@@ -0,0 +1,85 @@
+use std::io::{Read, Seek, SeekFrom};
+use vfs::{Fs, IoBackedFs};
+
+fn check<F>(path: &std::path::Path, data: &[u8])
+where
+    F: IoBackedFs<std::fs::File, Password = ()> + Fs<Path = [u8]>,
+    F::File: Seek,
+    F::Error: std::fmt::Debug,
+{
+    let mut fs = F::from_io(std::fs::File::open(path).unwrap(), ());
+    assert_eq!(
+        fs.metadata(b"").unwrap(),
+        vfs::Metadata {
+            file_type: vfs::FileType::File,
+            len: data.len() as u64,
+            mode: None,
+            mtime: None,
+        }
+    );
+    assert!(fs.metadata(b"a").is_err());
+    let mut file = fs.open(b"/").unwrap();
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents).unwrap();
+    assert!(contents == data);
+    // Seek backwards across units and snapshots, and into the middle of them
+    let mut buf = [0; 100];
+    for offset in [data.len() as u64 - 50, 5 << 20, 12345, (9 << 20) + 7, 0] {
+        file.seek(SeekFrom::Start(offset)).unwrap();
+        let n = file.read(&mut buf).unwrap();
+        assert!(n > 0);
+        assert_eq!(buf[..n], data[offset as usize..][..n]);
+    }
+}
+
+fn compress(tmp: &std::path::Path, name: &str, args: &[&str], parts: &[&std::path::Path]) {
+    let mut out = Vec::new();
+    for part in parts {
+        let output = std::process::Command::new(args[0])
+            .args(&args[1..])
+            .arg(part)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        out.extend(output.stdout);
+    }
+    std::fs::write(tmp.join(name), out).unwrap();
+}
+
+#[test]
+fn test() {
+    let tmp = tempfile::tempdir().unwrap();
+    let tmp = tmp.path();
+    // Compressible but not trivially so, to get several deflate blocks per snapshot span
+    let mut data = Vec::new();
+    let mut x = 1u32;
+    while data.len() < 11 << 20 {
+        x = x.wrapping_mul(1103515245).wrapping_add(12345);
+        data.extend(format!("line {} {}\n", data.len(), x >> 20).as_bytes());
+    }
+    let (first, second) = data.split_at(6 << 20);
+    std::fs::write(tmp.join("first"), first).unwrap();
+    std::fs::write(tmp.join("second"), second).unwrap();
+    let parts = [tmp.join("first"), tmp.join("second")];
+    let parts = [parts[0].as_path(), parts[1].as_path()];
+
+    compress(tmp, "data.gz", &["gzip", "-c"], &parts);
+    check::<vfs_compress::GzipFs<_>>(&tmp.join("data.gz"), &data);
+    compress(tmp, "data.xz", &["xz", "-c", "--block-size=1MiB"], &parts);
+    check::<vfs_compress::XzFs<_>>(&tmp.join("data.xz"), &data);
+    compress(tmp, "data.zst", &["zstd", "-c", "-q"], &parts);
+    check::<vfs_compress::ZstdFs<_>>(&tmp.join("data.zst"), &data);
+    compress(tmp, "data.bz2", &["bzip2", "-c"], &parts);
+    check::<vfs_compress::Bzip2Fs<_>>(&tmp.join("data.bz2"), &data);
+    compress(tmp, "data.lz4", &["lz4", "-c", "-q"], &parts);
+    check::<vfs_compress::Lz4Fs<_>>(&tmp.join("data.lz4"), &data);
+
+    // A BGZF subfield giving a block too short for its header is read as plain gzip
+    let gzip = std::fs::read(tmp.join("data.gz")).unwrap();
+    let mut bogus = gzip[..10].to_vec();
+    bogus[3] |= 1 << 2;
+    bogus.extend([6, 0, b'B', b'C', 2, 0, 0, 0]);
+    bogus.extend(&gzip[10..]);
+    let mut fs = vfs_compress::GzipFs::from_io(std::io::Cursor::new(bogus), ());
+    assert_eq!(fs.metadata(b"").unwrap().len, data.len() as u64);
+}
@@ -0,0 +1,116 @@
+use proc_macro::{Literal, TokenStream, TokenTree};
+use std::path::{Path, PathBuf};
+
+const COMPRESSION_LEVEL: u8 = 9;
+
+struct Args {
+    dir: String,
+    compress: bool,
+}
+
+fn parse_args(input: TokenStream) -> Result<Args, String> {
+    let usage =
+        "expected a directory like `embed_dir!(\"assets\")` or `embed_dir!(\"assets\", compress)`";
+    let mut tokens = input.into_iter();
+    let dir = match tokens.next() {
+        Some(TokenTree::Literal(literal)) => {
+            let literal = literal.to_string();
+            // Escapes are not worth supporting for paths
+            match literal.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+                Some(dir) if !dir.contains('\\') => dir.to_string(),
+                _ => return Err(usage.to_string()),
+            }
+        }
+        _ => return Err(usage.to_string()),
+    };
+    let compress = match (tokens.next(), tokens.next()) {
+        (None, None) => false,
+        (Some(TokenTree::Punct(comma)), Some(TokenTree::Ident(ident)))
+            if comma.as_char() == ',' && ident.to_string() == "compress" =>
+        {
+            true
+        }
+        _ => return Err(usage.to_string()),
+    };
+    if tokens.next().is_some() {
+        return Err(usage.to_string());
+    }
+    Ok(Args { dir, compress })
+}
+
+/// Lists the entries below `dir` depth first, with paths relative to `root`.
+fn walk(
+    root: &Path,
+    dir: &Path,
+    entries: &mut Vec<(Vec<u8>, Option<PathBuf>)>,
+) -> Result<(), String> {
+    let error = |e: std::io::Error| format!("failed reading {}: {e}", dir.display());
+    let mut children = std::fs::read_dir(dir)
+        .map_err(error)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(error)?;
+    children.sort();
+    for child in children {
+        let relative = child.strip_prefix(root).unwrap();
+        let path = relative
+            .iter()
+            .map(|c| c.as_encoded_bytes())
+            .collect::<Vec<_>>()
+            .join(b"/".as_slice());
+        // Symlinks are followed, so the target is embedded in their place
+        if std::fs::metadata(&child).map_err(error)?.is_dir() {
+            entries.push((path, None));
+            walk(root, &child, entries)?;
+        } else {
+            entries.push((path, Some(child)));
+        }
+    }
+    Ok(())
+}
+
+fn expand(input: TokenStream) -> Result<String, String> {
+    let args = parse_args(input)?;
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").map_err(|e| e.to_string())?;
+    let root = Path::new(&manifest_dir).join(&args.dir);
+    let mut entries = vec![(Vec::new(), None)];
+    walk(&root, &root, &mut entries)?;
+    // The file system looks entries up by binary search
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut code = String::from("::vfs_embed::EmbeddedFs::new(&[");
+    for (path, file) in entries {
+        let path = Literal::byte_string(&path);
+        let Some(file) = file else {
+            code += &format!("({path}, ::vfs_embed::Data::Dir),");
+            continue;
+        };
+        let file_str = file
+            .to_str()
+            .ok_or(format!("non-UTF-8 path {}", file.display()))?;
+        let include = format!("include_bytes!({})", Literal::string(file_str));
+        if args.compress {
+            let data =
+                std::fs::read(&file).map_err(|e| format!("failed reading {file_str}: {e}"))?;
+            let compressed = miniz_oxide::deflate::compress_to_vec(&data, COMPRESSION_LEVEL);
+            // Including the file anyway makes cargo rebuild when it changes
+            code += &format!(
+                "({path}, {{ const _: &[u8] = {include}; ::vfs_embed::Data::Compressed {{ data: {}, len: {} }} }}),",
+                Literal::byte_string(&compressed),
+                data.len(),
+            );
+        } else {
+            code += &format!("({path}, ::vfs_embed::Data::File({include})),");
+        }
+    }
+    code += "])";
+    Ok(code)
+}
+
+/// Embeds a directory, relative to the crate root, as a `vfs_embed::EmbeddedFs`. With
+/// `compress`, file contents are deflated at build time and inflated on open.
+#[proc_macro]
+pub fn embed_dir(input: TokenStream) -> TokenStream {
+    let code = expand(input).unwrap_or_else(|e| format!("compile_error!({})", Literal::string(&e)));
+    code.parse().unwrap()
+}
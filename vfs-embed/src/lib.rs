@@ -0,0 +1,84 @@
+use std::{borrow::Cow, io::Cursor};
+
+/// Embeds a directory into the binary, as in `embed_dir!("assets")` or
+/// `embed_dir!("assets", compress)`. The path is relative to the crate root. Files added to the
+/// directory are only picked up once the embedding crate is rebuilt.
+pub use vfs_embed_macros::embed_dir;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("invalid compressed data")]
+    InvalidData,
+    #[error("entry not found")]
+    NotFound,
+    #[error("entry is not a regular file")]
+    NotAFile,
+}
+
+/// The contents of an embedded entry.
+#[derive(Debug)]
+pub enum Data {
+    Dir,
+    File(&'static [u8]),
+    /// Raw deflate data, and the length it inflates to
+    Compressed {
+        data: &'static [u8],
+        len: usize,
+    },
+}
+
+/// A directory tree baked into the binary by [`embed_dir!`].
+#[derive(Debug, Clone, Copy)]
+pub struct EmbeddedFs {
+    /// Entries sorted by path, starting with the root
+    entries: &'static [(&'static [u8], Data)],
+}
+
+impl EmbeddedFs {
+    #[doc(hidden)]
+    pub const fn new(entries: &'static [(&'static [u8], Data)]) -> Self {
+        Self { entries }
+    }
+
+    fn data(&self, path: &[u8]) -> Result<&'static Data, Error> {
+        let path = vfs::path::normalize(path);
+        let i = self
+            .entries
+            .binary_search_by(|(p, _)| (*p).cmp(path.as_slice()))
+            .map_err(|_| Error::NotFound)?;
+        Ok(&self.entries[i].1)
+    }
+}
+
+impl vfs::Fs for EmbeddedFs {
+    type Path = [u8];
+    type Error = Error;
+    type File = Cursor<Cow<'static, [u8]>>;
+
+    fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Self::Error> {
+        let (file_type, len) = match self.data(path)? {
+            Data::Dir => (vfs::FileType::Dir, 0),
+            Data::File(data) => (vfs::FileType::File, data.len()),
+            Data::Compressed { len, .. } => (vfs::FileType::File, *len),
+        };
+        Ok(vfs::Metadata {
+            file_type,
+            len: len as u64,
+            mode: None,
+            mtime: None,
+        })
+    }
+
+    fn open(&mut self, path: &[u8]) -> Result<Self::File, Self::Error> {
+        let data = match self.data(path)? {
+            Data::Dir => return Err(Error::NotAFile),
+            Data::File(data) => Cow::Borrowed(*data),
+            Data::Compressed { data, len } => {
+                let data = miniz_oxide::inflate::decompress_to_vec_with_limit(data, *len)
+                    .map_err(|_| Error::InvalidData)?;
+                Cow::Owned(data)
+            }
+        };
+        Ok(Cursor::new(data))
+    }
+}
@@ -0,0 +1,48 @@
+use std::io::{Read, Seek, SeekFrom};
+use vfs::Fs;
+use vfs_embed::{embed_dir, EmbeddedFs};
+
+static ASSETS: EmbeddedFs = embed_dir!("tests/assets");
+
+fn read(fs: &mut EmbeddedFs, path: &str) -> String {
+    let mut contents = String::new();
+    let mut file = fs.open(path.as_bytes()).unwrap();
+    file.read_to_string(&mut contents).unwrap();
+    contents
+}
+
+#[test]
+fn test() {
+    for mut fs in [ASSETS, embed_dir!("tests/assets", compress)] {
+        assert_eq!(read(&mut fs, "config.toml"), "port = 8080\n");
+        assert_eq!(
+            read(&mut fs, "/templates/greeting.txt"),
+            "Hello, {{name}}!\n"
+        );
+        assert_eq!(
+            read(&mut fs, "templates/large.txt"),
+            "repeated line\n".repeat(200)
+        );
+        let metadata = fs.metadata(b"templates/large.txt").unwrap();
+        assert_eq!(metadata.file_type, vfs::FileType::File);
+        assert_eq!(metadata.len, 14 * 200);
+        assert_eq!(fs.metadata(b"").unwrap().file_type, vfs::FileType::Dir);
+        assert_eq!(
+            fs.metadata(b"templates").unwrap().file_type,
+            vfs::FileType::Dir
+        );
+        let mut file = fs.open(b"templates/large.txt").unwrap();
+        file.seek(SeekFrom::End(-5)).unwrap();
+        let mut tail = String::new();
+        file.read_to_string(&mut tail).unwrap();
+        assert_eq!(tail, "line\n");
+        assert!(matches!(
+            fs.metadata(b"missing"),
+            Err(vfs_embed::Error::NotFound)
+        ));
+        assert!(matches!(
+            fs.open(b"templates"),
+            Err(vfs_embed::Error::NotAFile)
+        ));
+    }
+}
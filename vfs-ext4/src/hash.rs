@@ -0,0 +1,139 @@
+//! Directory entry name hashes used to order htree indexes, as in `fs/ext4/hash.c`.
+
+const HASH_LEGACY: u8 = 0;
+const HASH_HALF_MD4: u8 = 1;
+const HASH_TEA: u8 = 2;
+const HASH_LEGACY_UNSIGNED: u8 = 3;
+const HASH_HALF_MD4_UNSIGNED: u8 = 4;
+const HASH_TEA_UNSIGNED: u8 = 5;
+const DEFAULT_SEED: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+const HTREE_EOF: u32 = 0x7fffffff;
+
+/// Widens a name byte the way the kernel does for the signed or unsigned hash variants, which
+/// only differ for non-ASCII names.
+fn widen(b: u8, signed: bool) -> u32 {
+    if signed {
+        b as i8 as i32 as u32
+    } else {
+        b as u32
+    }
+}
+
+fn legacy(name: &[u8], signed: bool) -> u32 {
+    let (mut hash0, mut hash1) = (0x12a3fe2d_u32, 0x37abe8f9_u32);
+    for &b in name {
+        let mut hash = hash1.wrapping_add(hash0 ^ widen(b, signed).wrapping_mul(7152373));
+        if hash & 0x80000000 != 0 {
+            hash = hash.wrapping_sub(0x7fffffff);
+        }
+        hash1 = hash0;
+        hash0 = hash;
+    }
+    hash0 << 1
+}
+
+/// Packs up to `out.len() * 4` bytes of the rest of the name into words, padding with the
+/// length of the rest of the name.
+fn str2hashbuf(name: &[u8], signed: bool, out: &mut [u32]) {
+    let len = name.len() as u32;
+    let mut pad = len | (len << 8);
+    pad |= pad << 16;
+    out.fill(pad);
+    let mut val = pad;
+    let max_len = out.len() * 4;
+    let mut words = out.iter_mut();
+    for (i, &b) in name.iter().take(max_len).enumerate() {
+        val = widen(b, signed).wrapping_add(val << 8);
+        if i % 4 == 3 {
+            *words.next().unwrap() = val;
+            val = pad;
+        }
+    }
+    if let Some(word) = words.next() {
+        *word = val;
+    }
+}
+
+fn half_md4_transform(buf: &mut [u32; 4], input: &[u32; 8]) {
+    let f = |x: u32, y: u32, z: u32| z ^ (x & (y ^ z));
+    let g = |x: u32, y: u32, z: u32| (x & y).wrapping_add((x ^ y) & z);
+    let h = |x: u32, y: u32, z: u32| x ^ y ^ z;
+    let [mut a, mut b, mut c, mut d] = *buf;
+    let round = |f: &dyn Fn(u32, u32, u32) -> u32, a: u32, b: u32, c: u32, d: u32, x: u32, s| {
+        a.wrapping_add(f(b, c, d)).wrapping_add(x).rotate_left(s)
+    };
+    const K2: u32 = 0x5a827999;
+    const K3: u32 = 0x6ed9eba1;
+    for i in [0, 4] {
+        a = round(&f, a, b, c, d, input[i], 3);
+        d = round(&f, d, a, b, c, input[i + 1], 7);
+        c = round(&f, c, d, a, b, input[i + 2], 11);
+        b = round(&f, b, c, d, a, input[i + 3], 19);
+    }
+    for i in [0, 1] {
+        a = round(&g, a, b, c, d, input[1 - i].wrapping_add(K2), 3);
+        d = round(&g, d, a, b, c, input[3 - i].wrapping_add(K2), 5);
+        c = round(&g, c, d, a, b, input[5 - i].wrapping_add(K2), 9);
+        b = round(&g, b, c, d, a, input[7 - i].wrapping_add(K2), 13);
+    }
+    for i in [0, 1] {
+        a = round(&h, a, b, c, d, input[3 - 2 * i].wrapping_add(K3), 3);
+        d = round(&h, d, a, b, c, input[7 - 2 * i].wrapping_add(K3), 9);
+        c = round(&h, c, d, a, b, input[2 - 2 * i].wrapping_add(K3), 11);
+        b = round(&h, b, c, d, a, input[6 - 2 * i].wrapping_add(K3), 15);
+    }
+    buf[0] = buf[0].wrapping_add(a);
+    buf[1] = buf[1].wrapping_add(b);
+    buf[2] = buf[2].wrapping_add(c);
+    buf[3] = buf[3].wrapping_add(d);
+}
+
+fn tea_transform(buf: &mut [u32; 4], input: &[u32; 4]) {
+    const DELTA: u32 = 0x9e3779b9;
+    let [a, b, c, d] = *input;
+    let (mut b0, mut b1) = (buf[0], buf[1]);
+    let mut sum = 0_u32;
+    for _ in 0..16 {
+        sum = sum.wrapping_add(DELTA);
+        b0 = b0.wrapping_add(
+            (b1 << 4).wrapping_add(a) ^ b1.wrapping_add(sum) ^ (b1 >> 5).wrapping_add(b),
+        );
+        b1 = b1.wrapping_add(
+            (b0 << 4).wrapping_add(c) ^ b0.wrapping_add(sum) ^ (b0 >> 5).wrapping_add(d),
+        );
+    }
+    buf[0] = buf[0].wrapping_add(b0);
+    buf[1] = buf[1].wrapping_add(b1);
+}
+
+/// Computes the major hash of a name, or returns `None` for hash versions that aren't
+/// supported, such as the SipHash used by casefolded directories.
+pub(crate) fn dirhash(name: &[u8], version: u8, seed: [u32; 4]) -> Option<u32> {
+    let mut buf = if seed == [0; 4] { DEFAULT_SEED } else { seed };
+    let hash = match version {
+        HASH_LEGACY | HASH_LEGACY_UNSIGNED => legacy(name, version == HASH_LEGACY),
+        HASH_HALF_MD4 | HASH_HALF_MD4_UNSIGNED => {
+            let mut input = [0; 8];
+            for start in (0..name.len()).step_by(32) {
+                str2hashbuf(&name[start..], version == HASH_HALF_MD4, &mut input);
+                half_md4_transform(&mut buf, &input);
+            }
+            buf[1]
+        }
+        HASH_TEA | HASH_TEA_UNSIGNED => {
+            let mut input = [0; 4];
+            for start in (0..name.len()).step_by(16) {
+                str2hashbuf(&name[start..], version == HASH_TEA, &mut input);
+                tea_transform(&mut buf, &input);
+            }
+            buf[0]
+        }
+        _ => return None,
+    };
+    let hash = hash & !1;
+    Some(if hash == HTREE_EOF << 1 {
+        (HTREE_EOF - 1) << 1
+    } else {
+        hash
+    })
+}
@@ -0,0 +1,675 @@
+mod hash;
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom},
+    rc::Rc,
+    time::{Duration, SystemTime},
+};
+use vfs::{
+    bytes::{u16_at, u32_at},
+    io::read_at,
+};
+
+const SUPERBLOCK_OFFSET: u64 = 1024;
+const SUPERBLOCK_LEN: usize = 1024;
+const MAGIC: u16 = 0xef53;
+const ROOT_INODE: u32 = 2;
+
+const INCOMPAT_COMPRESSION: u32 = 0x1;
+const INCOMPAT_FILETYPE: u32 = 0x2;
+const INCOMPAT_JOURNAL_DEV: u32 = 0x8;
+const INCOMPAT_META_BG: u32 = 0x10;
+const INCOMPAT_64BIT: u32 = 0x80;
+const INCOMPAT_DIRDATA: u32 = 0x1000;
+const INCOMPAT_UNSUPPORTED: u32 =
+    INCOMPAT_COMPRESSION | INCOMPAT_JOURNAL_DEV | INCOMPAT_META_BG | INCOMPAT_DIRDATA;
+const FLAGS_UNSIGNED_HASH: u32 = 0x2;
+
+const INODE_INDEX_FL: u32 = 0x1000;
+const INODE_EXTENTS_FL: u32 = 0x80000;
+const INODE_INLINE_DATA_FL: u32 = 0x10000000;
+const INODE_BLOCK_LEN: usize = 60;
+const GOOD_OLD_INODE_LEN: usize = 128;
+
+const S_IFMT: u16 = 0o170000;
+const S_IFREG: u16 = 0o100000;
+const S_IFDIR: u16 = 0o040000;
+const S_IFLNK: u16 = 0o120000;
+
+const EXTENT_MAGIC: u16 = 0xf30a;
+const EXTENT_MAX_DEPTH: u16 = 5;
+/// Extents longer than this are uninitialized, and read as zeros
+const EXTENT_INIT_MAX_LEN: u16 = 32768;
+const XATTR_MAGIC: u32 = 0xea020000;
+const XATTR_INDEX_SYSTEM: u8 = 7;
+const DX_BLOCK_MASK: u32 = 0x0fffffff;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed reading from backing IO")]
+    Io(#[from] std::io::Error),
+    #[error("invalid ext2/3/4 superblock")]
+    InvalidSuperblock,
+    #[error("unsupported incompatible features {0:#x}")]
+    UnsupportedFeatures(u32),
+    #[error("invalid extent tree")]
+    InvalidExtentTree,
+    #[error("entry not found")]
+    NotFound,
+    #[error("entry is not a regular file")]
+    NotAFile,
+}
+
+struct Superblock {
+    block_size: u64,
+    first_data_block: u64,
+    inodes_per_group: u32,
+    inode_size: usize,
+    desc_size: usize,
+    is_64bit: bool,
+    /// Whether directory entries store the file type, leaving one byte for the name length
+    filetype: bool,
+    hash_seed: [u32; 4],
+    unsigned_hash: bool,
+}
+
+impl Superblock {
+    fn parse(buf: &[u8]) -> Result<Self, Error> {
+        if u16_at(buf, 0x38) != MAGIC {
+            return Err(Error::InvalidSuperblock);
+        }
+        let log_block_size = u32_at(buf, 0x18);
+        if log_block_size > 6 {
+            return Err(Error::InvalidSuperblock);
+        }
+        let incompat = u32_at(buf, 0x60);
+        if incompat & INCOMPAT_UNSUPPORTED != 0 {
+            return Err(Error::UnsupportedFeatures(incompat & INCOMPAT_UNSUPPORTED));
+        }
+        let is_64bit = incompat & INCOMPAT_64BIT != 0;
+        let inode_size = if u32_at(buf, 0x4c) == 0 {
+            GOOD_OLD_INODE_LEN
+        } else {
+            u16_at(buf, 0x58) as usize
+        };
+        let inodes_per_group = u32_at(buf, 0x28);
+        if inode_size < GOOD_OLD_INODE_LEN || inodes_per_group == 0 {
+            return Err(Error::InvalidSuperblock);
+        }
+        Ok(Self {
+            block_size: 1024 << log_block_size,
+            first_data_block: u32_at(buf, 0x14) as u64,
+            inodes_per_group,
+            inode_size,
+            desc_size: if is_64bit {
+                (u16_at(buf, 0xfe) as usize).max(32)
+            } else {
+                32
+            },
+            is_64bit,
+            filetype: incompat & INCOMPAT_FILETYPE != 0,
+            hash_seed: [0, 1, 2, 3].map(|i| u32_at(buf, 0xec + i * 4)),
+            unsigned_hash: u32_at(buf, 0x160) & FLAGS_UNSIGNED_HASH != 0,
+        })
+    }
+}
+
+/// A run of logical file blocks stored in consecutive physical blocks.
+#[derive(Clone, Copy)]
+struct Extent {
+    logical: u64,
+    len: u64,
+    physical: u64,
+    /// Uninitialized extents are allocated but read as zeros
+    initialized: bool,
+}
+
+/// Appends a block to a list of extents, extending the last extent if it is contiguous.
+fn push_block(extents: &mut Vec<Extent>, logical: u64, physical: u64) {
+    if let Some(last) = extents.last_mut() {
+        if last.logical + last.len == logical && last.physical + last.len == physical {
+            last.len += 1;
+            return;
+        }
+    }
+    extents.push(Extent {
+        logical,
+        len: 1,
+        physical,
+        initialized: true,
+    });
+}
+
+struct Inode {
+    /// The raw on-disk inode
+    raw: Vec<u8>,
+}
+
+impl Inode {
+    fn mode(&self) -> u16 {
+        u16_at(&self.raw, 0)
+    }
+
+    fn flags(&self) -> u32 {
+        u32_at(&self.raw, 0x20)
+    }
+
+    fn len(&self) -> u64 {
+        u32_at(&self.raw, 0x4) as u64 | (u32_at(&self.raw, 0x6c) as u64) << 32
+    }
+
+    fn block(&self) -> &[u8] {
+        &self.raw[0x28..0x28 + INODE_BLOCK_LEN]
+    }
+
+    fn file_type(&self) -> Option<vfs::FileType> {
+        match self.mode() & S_IFMT {
+            S_IFREG => Some(vfs::FileType::File),
+            S_IFDIR => Some(vfs::FileType::Dir),
+            S_IFLNK => Some(vfs::FileType::SymLink),
+            // Device nodes, FIFOs and sockets have no representation in `vfs::FileType`
+            _ => None,
+        }
+    }
+
+    /// Length of the extra inode fields past the original 128 bytes, as far as the inode holds
+    /// them.
+    fn extra_len(&self) -> usize {
+        if self.raw.len() >= GOOD_OLD_INODE_LEN + 2 {
+            (u16_at(&self.raw, 0x80) as usize).min(self.raw.len() - GOOD_OLD_INODE_LEN)
+        } else {
+            0
+        }
+    }
+
+    fn mtime(&self) -> Option<SystemTime> {
+        let mut secs = u32_at(&self.raw, 0x10) as i32 as i64;
+        let mut nanos = 0;
+        // The extra field holds the nanoseconds and two more bits of the epoch
+        if self.extra_len() >= 0x0c {
+            let extra = u32_at(&self.raw, 0x88);
+            secs += ((extra & 3) as i64) << 32;
+            nanos = extra >> 2;
+        }
+        let time = if secs >= 0 {
+            SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64))
+        } else {
+            SystemTime::UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs()))
+        };
+        time?.checked_add(Duration::from_nanos(nanos as u64))
+    }
+
+    /// Returns the value of an extended attribute stored in the inode's extra space.
+    fn inline_xattr(&self, index: u8, name: &[u8]) -> Option<&[u8]> {
+        let start = GOOD_OLD_INODE_LEN + self.extra_len();
+        let area = self.raw.get(start..)?;
+        if area.len() < 4 || u32_at(area, 0) != XATTR_MAGIC {
+            return None;
+        }
+        let entries = &area[4..];
+        let mut i = 0;
+        while i + 16 <= entries.len() && u32_at(entries, i) != 0 {
+            let name_len = entries[i] as usize;
+            let entry_name = entries.get(i + 16..i + 16 + name_len)?;
+            if entries[i + 1] == index && entry_name == name {
+                let offset = u16_at(entries, i + 2) as usize;
+                let len = u32_at(entries, i + 8) as usize;
+                return entries.get(offset..offset + len);
+            }
+            i += (16 + name_len).div_ceil(4) * 4;
+        }
+        None
+    }
+
+    /// Returns the contents of an inode whose data is stored inline, in the block map area and
+    /// the `system.data` extended attribute.
+    fn inline_data(&self) -> Option<Vec<u8>> {
+        if self.flags() & INODE_INLINE_DATA_FL == 0 {
+            return None;
+        }
+        let mut data = self.block().to_vec();
+        data.extend(
+            self.inline_xattr(XATTR_INDEX_SYSTEM, b"data")
+                .unwrap_or_default(),
+        );
+        data.truncate(self.len() as usize);
+        Some(data)
+    }
+}
+
+/// Scans a block of linear directory entries for `name`, returning its inode number.
+fn find_dirent(data: &[u8], name: &[u8], filetype: bool) -> Option<u32> {
+    let mut i = 0;
+    while i + 8 <= data.len() {
+        let inode = u32_at(data, i);
+        let rec_len = u16_at(data, i + 4) as usize;
+        let name_len = if filetype {
+            data[i + 6] as usize
+        } else {
+            u16_at(data, i + 6) as usize
+        };
+        if rec_len < 8 {
+            break;
+        }
+        if inode != 0 && data.get(i + 8..i + 8 + name_len) == Some(name) {
+            return Some(inode);
+        }
+        i += rec_len;
+    }
+    None
+}
+
+struct Image<R: Read + Seek> {
+    reader: Rc<RefCell<R>>,
+    superblock: Superblock,
+    /// Inode table locations by block group
+    inode_tables: HashMap<u32, u64>,
+}
+
+impl<R: Read + Seek> Image<R> {
+    fn open(reader: Rc<RefCell<R>>) -> Result<Self, Error> {
+        let superblock = read_at(&reader, SUPERBLOCK_OFFSET, SUPERBLOCK_LEN)?;
+        Ok(Self {
+            reader,
+            superblock: Superblock::parse(&superblock)?,
+            inode_tables: HashMap::new(),
+        })
+    }
+
+    fn read_block(&self, block: u64) -> std::io::Result<Vec<u8>> {
+        let block_size = self.superblock.block_size;
+        read_at(&self.reader, block * block_size, block_size as usize)
+    }
+
+    fn inode_table(&mut self, group: u32) -> Result<u64, Error> {
+        if let Some(&table) = self.inode_tables.get(&group) {
+            return Ok(table);
+        }
+        let sb = &self.superblock;
+        // The group descriptor table starts in the block after the superblock
+        let pos = (sb.first_data_block + 1) * sb.block_size + group as u64 * sb.desc_size as u64;
+        let desc = read_at(&self.reader, pos, sb.desc_size)?;
+        let mut table = u32_at(&desc, 0x8) as u64;
+        if sb.is_64bit && sb.desc_size >= 64 {
+            table |= (u32_at(&desc, 0x28) as u64) << 32;
+        }
+        self.inode_tables.insert(group, table);
+        Ok(table)
+    }
+
+    fn inode(&mut self, number: u32) -> Result<Inode, Error> {
+        let index = number.checked_sub(1).ok_or(Error::NotFound)?;
+        let group = index / self.superblock.inodes_per_group;
+        let table = self.inode_table(group)?;
+        let sb = &self.superblock;
+        let pos =
+            table * sb.block_size + (index % sb.inodes_per_group) as u64 * sb.inode_size as u64;
+        Ok(Inode {
+            raw: read_at(&self.reader, pos, sb.inode_size)?,
+        })
+    }
+
+    fn extent_node(
+        &self,
+        node: &[u8],
+        depth_limit: u16,
+        out: &mut Vec<Extent>,
+    ) -> Result<(), Error> {
+        if node.len() < 12 || u16_at(node, 0) != EXTENT_MAGIC {
+            return Err(Error::InvalidExtentTree);
+        }
+        let entries = u16_at(node, 2) as usize;
+        let depth = u16_at(node, 6);
+        if depth > depth_limit {
+            return Err(Error::InvalidExtentTree);
+        }
+        for i in 0..entries {
+            let entry = node
+                .get(12 + i * 12..24 + i * 12)
+                .ok_or(Error::InvalidExtentTree)?;
+            if depth == 0 {
+                let len = u16_at(entry, 4);
+                let initialized = len <= EXTENT_INIT_MAX_LEN;
+                out.push(Extent {
+                    logical: u32_at(entry, 0) as u64,
+                    len: if initialized {
+                        len
+                    } else {
+                        len - EXTENT_INIT_MAX_LEN
+                    } as u64,
+                    physical: (u16_at(entry, 6) as u64) << 32 | u32_at(entry, 8) as u64,
+                    initialized,
+                });
+            } else {
+                let child = (u16_at(entry, 8) as u64) << 32 | u32_at(entry, 4) as u64;
+                self.extent_node(&self.read_block(child)?, depth - 1, out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Collects the blocks of an indirect block, which points at further indirect blocks for
+    /// `level` > 1.
+    fn indirect_blocks(
+        &self,
+        block: u32,
+        level: u32,
+        logical: &mut u64,
+        block_count: u64,
+        out: &mut Vec<Extent>,
+    ) -> Result<(), Error> {
+        let pointers_per_block = self.superblock.block_size / 4;
+        if block == 0 {
+            // A hole spanning everything this block would have mapped
+            *logical += pointers_per_block.pow(level);
+            return Ok(());
+        }
+        let data = self.read_block(block as u64)?;
+        for pointer in data.chunks_exact(4).map(|p| u32_at(p, 0)) {
+            if *logical >= block_count {
+                break;
+            }
+            if level > 1 {
+                self.indirect_blocks(pointer, level - 1, logical, block_count, out)?;
+            } else {
+                if pointer != 0 {
+                    push_block(out, *logical, pointer as u64);
+                }
+                *logical += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Maps the logical blocks of an inode to physical blocks, using either the extent tree or
+    /// the block map of ext2/3.
+    fn extents(&self, inode: &Inode) -> Result<Vec<Extent>, Error> {
+        let mut extents = Vec::new();
+        if inode.flags() & INODE_EXTENTS_FL != 0 {
+            self.extent_node(inode.block(), EXTENT_MAX_DEPTH, &mut extents)?;
+            extents.sort_by_key(|e| e.logical);
+            return Ok(extents);
+        }
+        let block_count = inode.len().div_ceil(self.superblock.block_size);
+        let pointers: Vec<u32> = inode
+            .block()
+            .chunks_exact(4)
+            .map(|p| u32_at(p, 0))
+            .collect();
+        let mut logical = 0;
+        for &pointer in &pointers[..12] {
+            if logical >= block_count {
+                break;
+            }
+            if pointer != 0 {
+                push_block(&mut extents, logical, pointer as u64);
+            }
+            logical += 1;
+        }
+        for (level, &pointer) in (1..=3).zip(&pointers[12..]) {
+            if logical >= block_count {
+                break;
+            }
+            self.indirect_blocks(pointer, level, &mut logical, block_count, &mut extents)?;
+        }
+        Ok(extents)
+    }
+
+    /// Reads a logical block of a file, which is all zeros for holes.
+    fn read_logical_block(&self, extents: &[Extent], logical: u64) -> Result<Vec<u8>, Error> {
+        let extent = extents
+            .iter()
+            .find(|e| e.logical <= logical && logical < e.logical + e.len);
+        Ok(match extent {
+            Some(e) if e.initialized => self.read_block(e.physical + logical - e.logical)?,
+            _ => vec![0; self.superblock.block_size as usize],
+        })
+    }
+
+    /// Walks the htree index of a directory to the leaf blocks that may contain `name`. Returns
+    /// `None` if the hash isn't supported or the index is corrupt, in which case the directory
+    /// must be scanned.
+    fn htree_leaves(&self, extents: &[Extent], name: &[u8]) -> Result<Option<Vec<u64>>, Error> {
+        let root = self.read_logical_block(extents, 0)?;
+        // The root block starts with `.` and `..` entries, followed by the index root info
+        let mut version = root[0x1c];
+        let info_len = root[0x1d] as usize;
+        let levels = root[0x1e];
+        if self.superblock.unsigned_hash && version <= 2 {
+            version += 3;
+        }
+        let Some(hash) = hash::dirhash(name, version, self.superblock.hash_seed) else {
+            return Ok(None);
+        };
+        let mut node = root;
+        let mut start = 0x18 + info_len;
+        for level in 0..=levels {
+            let Some(limits) = node.get(start..start + 4) else {
+                return Ok(None);
+            };
+            let limit = u16_at(limits, 0) as usize;
+            let count = u16_at(limits, 2) as usize;
+            if count == 0 || count > limit || start + count * 8 > node.len() {
+                return Ok(None);
+            }
+            // The first entry's hash field holds the limit and count, and its hash is 0
+            let entries: Vec<(u32, u32)> = (0..count)
+                .map(|i| {
+                    let hash = if i == 0 {
+                        0
+                    } else {
+                        u32_at(&node, start + i * 8)
+                    };
+                    (hash, u32_at(&node, start + i * 8 + 4) & DX_BLOCK_MASK)
+                })
+                .collect();
+            let index = entries
+                .partition_point(|&(h, _)| h <= hash)
+                .saturating_sub(1);
+            if level == levels {
+                let mut leaves = vec![entries[index].1 as u64];
+                // Names with colliding hashes can continue into the next leaf, which is then
+                // marked by the low bit of its hash
+                for &(next_hash, block) in &entries[index + 1..] {
+                    if next_hash & 1 == 0 || next_hash & !1 != hash {
+                        break;
+                    }
+                    leaves.push(block as u64);
+                }
+                return Ok(Some(leaves));
+            }
+            node = self.read_logical_block(extents, entries[index].1 as u64)?;
+            // Interior nodes start with an empty directory entry spanning the block
+            start = 8;
+        }
+        unreachable!()
+    }
+
+    fn lookup_in_dir(&self, dir: &Inode, name: &[u8]) -> Result<Option<u32>, Error> {
+        let filetype = self.superblock.filetype;
+        if let Some(data) = dir.inline_data() {
+            // Inline directories start with the parent inode number instead of `.` and `..`
+            return Ok(find_dirent(
+                data.get(4..).unwrap_or_default(),
+                name,
+                filetype,
+            ));
+        }
+        let extents = self.extents(dir)?;
+        let leaves = if dir.flags() & INODE_INDEX_FL != 0 {
+            self.htree_leaves(&extents, name)?
+        } else {
+            None
+        };
+        let leaves =
+            leaves.unwrap_or_else(|| (0..dir.len().div_ceil(self.superblock.block_size)).collect());
+        for block in leaves {
+            let data = self.read_logical_block(&extents, block)?;
+            if let Some(inode) = find_dirent(&data, name, filetype) {
+                return Ok(Some(inode));
+            }
+        }
+        Ok(None)
+    }
+
+    fn lookup(&mut self, path: &[u8]) -> Result<Inode, Error> {
+        let mut inode = self.inode(ROOT_INODE)?;
+        for component in vfs::path::components(path) {
+            if inode.file_type() != Some(vfs::FileType::Dir) {
+                return Err(Error::NotFound);
+            }
+            let number = self
+                .lookup_in_dir(&inode, component)?
+                .ok_or(Error::NotFound)?;
+            inode = self.inode(number)?;
+        }
+        Ok(inode)
+    }
+}
+
+/// Reads a file through its extents, filling holes and uninitialized extents with zeros.
+struct MappedFile<R: Read + Seek> {
+    reader: Rc<RefCell<R>>,
+    block_size: u64,
+    extents: Vec<Extent>,
+    len: u64,
+    offset: u64,
+}
+
+impl<R: Read + Seek> Read for MappedFile<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.offset);
+        let max = (buf.len() as u64).min(remaining) as usize;
+        if max == 0 {
+            return Ok(0);
+        }
+        let block = self.offset / self.block_size;
+        let next = self.extents.partition_point(|e| e.logical + e.len <= block);
+        let n = match self.extents.get(next) {
+            Some(e) if e.logical <= block => {
+                let end = (e.logical + e.len) * self.block_size;
+                let n = (max as u64).min(end - self.offset) as usize;
+                if e.initialized {
+                    let pos =
+                        e.physical * self.block_size + self.offset - e.logical * self.block_size;
+                    let mut reader = self.reader.borrow_mut();
+                    reader.seek(SeekFrom::Start(pos))?;
+                    reader.read(&mut buf[..n])?
+                } else {
+                    buf[..n].fill(0);
+                    n
+                }
+            }
+            e => {
+                let end = e.map_or(u64::MAX, |e| e.logical * self.block_size);
+                let n = (max as u64).min(end - self.offset) as usize;
+                buf[..n].fill(0);
+                n
+            }
+        };
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for MappedFile<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.offset = vfs::io::seek_position(pos, self.offset, self.len)?;
+        Ok(self.offset)
+    }
+}
+
+enum FileInner<R: Read + Seek> {
+    Mapped(MappedFile<R>),
+    Inline(std::io::Cursor<Vec<u8>>),
+}
+
+pub struct File<R: Read + Seek>(FileInner<R>);
+
+impl<R: Read + Seek> Read for File<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match &mut self.0 {
+            FileInner::Mapped(x) => x.read(buf),
+            FileInner::Inline(x) => x.read(buf),
+        }
+    }
+}
+
+impl<R: Read + Seek> Seek for File<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match &mut self.0 {
+            FileInner::Mapped(x) => x.seek(pos),
+            FileInner::Inline(x) => x.seek(pos),
+        }
+    }
+}
+
+/// A read-only ext2, ext3 or ext4 filesystem image. Lookups walk directories from the root,
+/// using htree indexes where possible, and files are read through their extents or block maps.
+pub struct Ext4Fs<R: Read + Seek> {
+    reader: Rc<RefCell<R>>,
+    image: Option<Image<R>>,
+}
+
+impl<R: Read + Seek> Ext4Fs<R> {
+    fn image(&mut self) -> Result<&mut Image<R>, Error> {
+        if self.image.is_none() {
+            self.image = Some(Image::open(self.reader.clone())?);
+        }
+        Ok(self.image.as_mut().unwrap())
+    }
+}
+
+impl<R: Read + Seek> vfs::IoBackedFs<R> for Ext4Fs<R> {
+    type Password = ();
+
+    fn from_io(io: R, _password: Self::Password) -> Self {
+        Self {
+            reader: Rc::new(RefCell::new(io)),
+            image: None,
+        }
+    }
+}
+
+impl<R: Read + Seek> vfs::Fs for Ext4Fs<R> {
+    type Path = [u8];
+    type Error = Error;
+    type File = File<R>;
+
+    fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Self::Error> {
+        let inode = self.image()?.lookup(path)?;
+        let file_type = inode.file_type().ok_or(Error::NotFound)?;
+        Ok(vfs::Metadata {
+            file_type,
+            len: match file_type {
+                vfs::FileType::File => inode.len(),
+                _ => 0,
+            },
+            mode: Some((inode.mode() & 0o7777) as u32),
+            mtime: inode.mtime(),
+        })
+    }
+
+    fn open(&mut self, path: &[u8]) -> Result<Self::File, Self::Error> {
+        let reader = self.reader.clone();
+        let image = self.image()?;
+        let inode = image.lookup(path)?;
+        match inode.file_type() {
+            Some(vfs::FileType::File) => {}
+            Some(_) => return Err(Error::NotAFile),
+            None => return Err(Error::NotFound),
+        }
+        if let Some(data) = inode.inline_data() {
+            return Ok(File(FileInner::Inline(std::io::Cursor::new(data))));
+        }
+        Ok(File(FileInner::Mapped(MappedFile {
+            reader,
+            block_size: image.superblock.block_size,
+            extents: image.extents(&inode)?,
+            len: inode.len(),
+            offset: 0,
+        })))
+    }
+}
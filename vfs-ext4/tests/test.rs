@@ -0,0 +1,150 @@
+use std::{
+    io::{Read, Seek, SeekFrom, Write},
+    os::unix::fs::PermissionsExt,
+};
+use vfs::{Fs, IoBackedFs};
+
+fn read(fs: &mut vfs_ext4::Ext4Fs<std::fs::File>, path: &[u8]) -> Vec<u8> {
+    let mut contents = Vec::new();
+    fs.open(path).unwrap().read_to_end(&mut contents).unwrap();
+    contents
+}
+
+fn check(image: &std::path::Path, big: &[u8]) {
+    let mut fs = vfs_ext4::Ext4Fs::from_io(std::fs::File::open(image).unwrap(), ());
+    let metadata = fs.metadata(b"a/b/small").unwrap();
+    assert_eq!(metadata.file_type, vfs::FileType::File);
+    assert_eq!(metadata.len, 6);
+    assert_eq!(metadata.mode, Some(0o640));
+    assert!(metadata.mtime.is_some());
+    assert_eq!(read(&mut fs, b"/a/b/small"), b"small\n");
+    assert_eq!(fs.metadata(b"a/b").unwrap().file_type, vfs::FileType::Dir);
+    assert_eq!(fs.metadata(b"").unwrap().file_type, vfs::FileType::Dir);
+    assert_eq!(
+        fs.metadata(b"link").unwrap().file_type,
+        vfs::FileType::SymLink
+    );
+    assert!(fs.metadata(b"a/missing").is_err());
+    assert!(fs.metadata(b"a/b/small/x").is_err());
+    assert!(fs.open(b"a").is_err());
+
+    assert!(read(&mut fs, b"big") == big);
+    let mut file = fs.open(b"big").unwrap();
+    let mut buf = [0; 100];
+    for offset in [big.len() as u64 - 50, 12345, 300_000, 0] {
+        file.seek(SeekFrom::Start(offset)).unwrap();
+        let n = file.read(&mut buf).unwrap();
+        assert!(n > 0);
+        assert_eq!(buf[..n], big[offset as usize..][..n]);
+    }
+
+    let sparse = read(&mut fs, b"sparse");
+    assert_eq!(sparse.len(), (5 << 20) + 4);
+    assert!(sparse[..5 << 20].iter().all(|&b| b == 0));
+    assert_eq!(&sparse[5 << 20..], b"tail");
+
+    // Large enough for an htree index after `e2fsck -D`
+    for i in (0..2000).step_by(97) {
+        let name = format!("many/file-with-a-longish-name-{i}");
+        assert_eq!(read(&mut fs, name.as_bytes()), name.as_bytes());
+    }
+    assert!(fs.metadata(b"many/file-with-a-longish-name-2000").is_err());
+}
+
+fn mkfs(tmp: &std::path::Path, name: &str, args: &[&str]) -> std::path::PathBuf {
+    let image = tmp.join(name);
+    let status = std::process::Command::new("mke2fs")
+        .args(["-q", "-F", "-d"])
+        .arg(tmp.join("root"))
+        .args(args)
+        .arg(&image)
+        .arg("16M")
+        .status()
+        .unwrap();
+    assert!(status.success());
+    // Rebuilds directories with htree indexes
+    let status = std::process::Command::new("e2fsck")
+        .args(["-fyD"])
+        .arg(&image)
+        .stdout(std::process::Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.code().unwrap() <= 1);
+    image
+}
+
+#[test]
+fn test() {
+    let tmp = tempfile::tempdir().unwrap();
+    let tmp = tmp.path();
+    let root = tmp.join("root");
+    std::fs::create_dir_all(root.join("a/b")).unwrap();
+    std::fs::write(root.join("a/b/small"), b"small\n").unwrap();
+    std::fs::set_permissions(
+        root.join("a/b/small"),
+        std::fs::Permissions::from_mode(0o640),
+    )
+    .unwrap();
+    // Past the direct and single indirect blocks of a 1 KiB block map
+    let mut big = Vec::new();
+    let mut x = 1u32;
+    while big.len() < 400_000 {
+        x = x.wrapping_mul(1103515245).wrapping_add(12345);
+        big.push((x >> 16) as u8);
+    }
+    std::fs::write(root.join("big"), &big).unwrap();
+    let mut sparse = std::fs::File::create(root.join("sparse")).unwrap();
+    sparse.seek(SeekFrom::Start(5 << 20)).unwrap();
+    sparse.write_all(b"tail").unwrap();
+    std::os::unix::fs::symlink("a/b/small", root.join("link")).unwrap();
+    std::fs::create_dir(root.join("many")).unwrap();
+    for i in 0..2000 {
+        let name = format!("many/file-with-a-longish-name-{i}");
+        std::fs::write(root.join(&name), &name).unwrap();
+    }
+
+    let ext4 = mkfs(tmp, "ext4.img", &["-t", "ext4"]);
+    check(&ext4, &big);
+    check(
+        &mkfs(
+            tmp,
+            "inline.img",
+            &["-t", "ext4", "-b", "1024", "-O", "inline_data"],
+        ),
+        &big,
+    );
+    check(&mkfs(tmp, "ext2.img", &["-t", "ext2", "-b", "1024"]), &big);
+    check(
+        &mkfs(tmp, "ext3.img", &["-t", "ext3", "-O", "^dir_index"]),
+        &big,
+    );
+
+    // An htree root claiming more entries than it holds is scanned past instead
+    let output = std::process::Command::new("debugfs")
+        .args(["-R", "bmap many 0"])
+        .arg(&ext4)
+        .stderr(std::process::Stdio::null())
+        .output()
+        .unwrap();
+    let root_block: u64 = std::str::from_utf8(&output.stdout)
+        .unwrap()
+        .trim()
+        .parse()
+        .unwrap();
+    let mut image = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&ext4)
+        .unwrap();
+    let mut log_block_len = [0; 4];
+    image.seek(SeekFrom::Start(1024 + 24)).unwrap();
+    image.read_exact(&mut log_block_len).unwrap();
+    let block_len = 1024 << u32::from_le_bytes(log_block_len);
+    // After `.`, `..` and the 8-byte root info, the first entry holds the limit and count
+    image
+        .seek(SeekFrom::Start(root_block * block_len + 0x20 + 2))
+        .unwrap();
+    image.write_all(&u16::MAX.to_le_bytes()).unwrap();
+    drop(image);
+    check(&ext4, &big);
+}
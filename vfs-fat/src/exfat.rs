@@ -0,0 +1,120 @@
+//! exFAT boot sectors and directory entry sets.
+
+use crate::{
+    dos_timestamp, u16_at, u32_at, u64_at, DirEntry, Entry, Error, Kind, RootDir, Volume,
+    DIR_ENTRY_LEN, FIRST_CLUSTER,
+};
+
+const TYPE_FILE: u8 = 0x85;
+const TYPE_STREAM: u8 = 0xc0;
+const TYPE_NAME: u8 = 0xc1;
+/// Entry types without this bit are unused or deleted
+const TYPE_IN_USE: u8 = 0x80;
+const ATTR_READ_ONLY: u16 = 0x01;
+const ATTR_DIRECTORY: u16 = 0x10;
+const STREAM_NO_FAT_CHAIN: u8 = 0x02;
+const UTC_OFFSET_VALID: u8 = 0x80;
+
+pub(crate) fn volume(boot: &[u8]) -> Result<Volume, Error> {
+    let sector_shift = boot[108];
+    let cluster_shift = boot[109];
+    if !(9..=12).contains(&sector_shift) || sector_shift + cluster_shift > 25 {
+        return Err(Error::InvalidBootSector);
+    }
+    let sector_len = 1u64 << sector_shift;
+    let volume_len = u64_at(boot, 72)
+        .checked_mul(sector_len)
+        .ok_or(Error::InvalidBootSector)?;
+    let cluster_len = sector_len << cluster_shift;
+    let fat_start = u32_at(boot, 80) as u64 * sector_len;
+    let fat_len = u32_at(boot, 84) as u64 * sector_len;
+    let data_start = u32_at(boot, 88) as u64 * sector_len;
+    let cluster_count = u32_at(boot, 92);
+    // The FAT holds an entry for each cluster and the two reserved ones, before the cluster heap,
+    // which ends within the volume
+    if (cluster_count as u64 + FIRST_CLUSTER as u64) * 4 > fat_len
+        || fat_start + fat_len > data_start
+        || data_start + cluster_count as u64 * cluster_len > volume_len
+    {
+        return Err(Error::InvalidBootSector);
+    }
+    Ok(Volume {
+        kind: Kind::ExFat,
+        cluster_len,
+        fat_start,
+        fat_len,
+        data_start,
+        cluster_count,
+        root: RootDir::Cluster(u32_at(boot, 96)),
+    })
+}
+
+/// Parses a file entry set, which holds a file entry, a stream extension
+/// entry and the name entries.
+fn entry_set(set: &[&[u8]]) -> Option<DirEntry> {
+    let (file, stream, names) = match set {
+        [file, stream, names @ ..] if stream[0] == TYPE_STREAM => (file, stream, names),
+        _ => return None,
+    };
+    let name_len = stream[3] as usize;
+    let units: Vec<u16> = names
+        .iter()
+        .take_while(|name| name[0] == TYPE_NAME)
+        .flat_map(|name| name[2..].chunks_exact(2).map(|u| u16_at(u, 0)))
+        .take(name_len)
+        .collect();
+    if units.len() < name_len {
+        return None;
+    }
+    let utc_offset = match file[23] {
+        offset if offset & UTC_OFFSET_VALID != 0 => ((offset << 1) as i8) >> 1,
+        _ => 0,
+    };
+    let attributes = u16_at(file, 4);
+    let modified = u32_at(file, 12);
+    Some(DirEntry {
+        name: String::from_utf16_lossy(&units),
+        short_name: None,
+        entry: Entry {
+            file_type: if attributes & ATTR_DIRECTORY != 0 {
+                vfs::FileType::Dir
+            } else {
+                vfs::FileType::File
+            },
+            first_cluster: u32_at(stream, 20),
+            len: u64_at(stream, 24),
+            valid_len: u64_at(stream, 8),
+            contiguous: stream[1] & STREAM_NO_FAT_CHAIN != 0,
+            read_only: attributes & ATTR_READ_ONLY != 0,
+            mtime: dos_timestamp(
+                (modified >> 16) as u16,
+                modified as u16,
+                file[21],
+                utc_offset,
+            ),
+        },
+    })
+}
+
+pub(crate) fn dir(data: &[u8]) -> Vec<DirEntry> {
+    let raw: Vec<&[u8]> = data.chunks_exact(DIR_ENTRY_LEN).collect();
+    let mut entries = Vec::new();
+    let mut i = 0;
+    while let Some(entry) = raw.get(i) {
+        match entry[0] {
+            0 => break,
+            TYPE_FILE => {
+                let secondary_count = entry[1] as usize;
+                let set = raw.get(i..=i + secondary_count).unwrap_or_default();
+                // Sets with entries that aren't in use are incomplete, and are skipped
+                if set.len() == secondary_count + 1 && set.iter().all(|e| e[0] & TYPE_IN_USE != 0) {
+                    entries.extend(entry_set(set));
+                    i += secondary_count;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    entries
+}
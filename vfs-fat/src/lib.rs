@@ -0,0 +1,643 @@
+mod exfat;
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom},
+    rc::Rc,
+    time::{Duration, SystemTime},
+};
+use vfs::{
+    bytes::{u16_at, u32_at, u64_at},
+    io::read_at,
+    time::days_from_civil,
+};
+
+const BOOT_SIGNATURE: [u8; 2] = [0x55, 0xaa];
+const EXFAT_NAME: &[u8; 8] = b"EXFAT   ";
+const FAT12_MAX_CLUSTERS: u32 = 4084;
+const FAT16_MAX_CLUSTERS: u32 = 65524;
+const FIRST_CLUSTER: u32 = 2;
+/// FAT regions are read and cached in chunks of this size. FAT12 tables are always smaller, so
+/// their 12-bit entries never straddle two chunks.
+const FAT_CHUNK_LEN: u64 = 64 << 10;
+const DIR_ENTRY_LEN: usize = 32;
+
+const ATTR_READ_ONLY: u8 = 0x01;
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_LONG_NAME: u8 = 0x0f;
+const DELETED: u8 = 0xe5;
+/// Stands for a leading 0xe5 byte in short names, which would otherwise mark a deleted entry
+const ESCAPED_DELETED: u8 = 0x05;
+const LAST_LONG_ENTRY: u8 = 0x40;
+const CASE_LOWER_BASE: u8 = 0x08;
+const CASE_LOWER_EXT: u8 = 0x10;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed reading from backing IO")]
+    Io(#[from] std::io::Error),
+    #[error("invalid FAT or exFAT boot sector")]
+    InvalidBootSector,
+    #[error("invalid cluster chain")]
+    InvalidClusterChain,
+    #[error("entry not found")]
+    NotFound,
+    #[error("entry is not a regular file")]
+    NotAFile,
+}
+
+/// Parses an MS-DOS date and time, with an extra count of 10 ms units. FAT stores local time
+/// without a zone, so it is taken as UTC unless an offset in 15 minute intervals is given.
+fn dos_timestamp(date: u16, time: u16, centis: u8, utc_offset: i8) -> Option<SystemTime> {
+    let (year, month, day) = (
+        1980 + (date >> 9) as i64,
+        (date >> 5 & 0xf) as i64,
+        date & 0x1f,
+    );
+    if !(1..=12).contains(&month) || day == 0 {
+        return None;
+    }
+    let secs = days_from_civil(year, month, day as i64) * 86400
+        + (time >> 11) as i64 * 3600
+        + (time >> 5 & 0x3f) as i64 * 60
+        + (time & 0x1f) as i64 * 2
+        - utc_offset as i64 * 15 * 60;
+    SystemTime::UNIX_EPOCH
+        .checked_add(Duration::from_secs(secs.try_into().ok()?))?
+        .checked_add(Duration::from_millis(centis as u64 * 10))
+}
+
+/// Compares names the way FAT does, ignoring case.
+fn name_matches(name: &str, component: &[u8]) -> bool {
+    let component = String::from_utf8_lossy(component);
+    name.chars()
+        .flat_map(char::to_lowercase)
+        .eq(component.chars().flat_map(char::to_lowercase))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Fat12,
+    Fat16,
+    Fat32,
+    ExFat,
+}
+
+/// Where the root directory is stored.
+#[derive(Clone, Copy)]
+enum RootDir {
+    /// The fixed region of FAT12 and FAT16, as an image offset and length
+    Region(u64, u64),
+    Cluster(u32),
+}
+
+struct Volume {
+    kind: Kind,
+    cluster_len: u64,
+    /// Image offset and length of the first FAT
+    fat_start: u64,
+    fat_len: u64,
+    /// Image offset of the first cluster
+    data_start: u64,
+    cluster_count: u32,
+    root: RootDir,
+}
+
+impl Volume {
+    fn parse(boot: &[u8]) -> Result<Self, Error> {
+        if boot[510..512] != BOOT_SIGNATURE {
+            return Err(Error::InvalidBootSector);
+        }
+        if &boot[3..11] == EXFAT_NAME {
+            return exfat::volume(boot);
+        }
+        let sector_len = u16_at(boot, 11) as u64;
+        let sectors_per_cluster = boot[13] as u64;
+        let reserved_sectors = u16_at(boot, 14) as u64;
+        let fats = boot[16] as u64;
+        let root_entries = u16_at(boot, 17) as u64;
+        let total_sectors = match u16_at(boot, 19) {
+            0 => u32_at(boot, 32) as u64,
+            n => n as u64,
+        };
+        let fat_sectors = match u16_at(boot, 22) {
+            0 => u32_at(boot, 36) as u64,
+            n => n as u64,
+        };
+        if !sector_len.is_power_of_two()
+            || !(512..=4096).contains(&sector_len)
+            || !sectors_per_cluster.is_power_of_two()
+            || fats == 0
+        {
+            return Err(Error::InvalidBootSector);
+        }
+        let root_len = root_entries * DIR_ENTRY_LEN as u64;
+        let root_start = (reserved_sectors + fats * fat_sectors) * sector_len;
+        let data_start = root_start + root_len.next_multiple_of(sector_len);
+        let cluster_count = (total_sectors * sector_len)
+            .checked_sub(data_start)
+            .ok_or(Error::InvalidBootSector)?
+            / (sectors_per_cluster * sector_len);
+        let cluster_count = u32::try_from(cluster_count).map_err(|_| Error::InvalidBootSector)?;
+        // The FAT type is determined by the number of clusters alone
+        let kind = if cluster_count <= FAT12_MAX_CLUSTERS {
+            Kind::Fat12
+        } else if cluster_count <= FAT16_MAX_CLUSTERS {
+            Kind::Fat16
+        } else {
+            Kind::Fat32
+        };
+        Ok(Self {
+            kind,
+            cluster_len: sectors_per_cluster * sector_len,
+            fat_start: reserved_sectors * sector_len,
+            fat_len: fat_sectors * sector_len,
+            data_start,
+            cluster_count,
+            root: match kind {
+                Kind::Fat32 => RootDir::Cluster(u32_at(boot, 44)),
+                _ => RootDir::Region(root_start, root_len),
+            },
+        })
+    }
+
+    fn cluster_offset(&self, cluster: u32) -> u64 {
+        self.data_start + (cluster - FIRST_CLUSTER) as u64 * self.cluster_len
+    }
+
+    fn is_valid_cluster(&self, cluster: u32) -> bool {
+        let first = FIRST_CLUSTER as u64;
+        (first..first + self.cluster_count as u64).contains(&(cluster as u64))
+    }
+}
+
+/// A run of file clusters stored in consecutive clusters of the image.
+#[derive(Clone, Copy)]
+struct Run {
+    /// Index of the first cluster within the file
+    logical: u64,
+    len: u64,
+    cluster: u32,
+}
+
+/// Appends a cluster to a list of runs, extending the last run if it is contiguous.
+fn push_cluster(runs: &mut Vec<Run>, logical: u64, cluster: u32) {
+    if let Some(last) = runs.last_mut() {
+        if last.logical + last.len == logical && last.cluster as u64 + last.len == cluster as u64 {
+            last.len += 1;
+            return;
+        }
+    }
+    runs.push(Run {
+        logical,
+        len: 1,
+        cluster,
+    });
+}
+
+#[derive(Clone)]
+struct Entry {
+    file_type: vfs::FileType,
+    first_cluster: u32,
+    /// Length of the data, which exFAT also records for directories
+    len: u64,
+    /// Bytes past this length read as zeros, which exFAT uses for preallocated files
+    valid_len: u64,
+    /// Whether the clusters are consecutive and have no FAT chain, which only exFAT allows
+    contiguous: bool,
+    read_only: bool,
+    mtime: Option<SystemTime>,
+}
+
+impl Entry {
+    /// FAT has no permissions, so this is the mode that Linux shows with the default umask
+    /// and the read-only attribute applied.
+    fn mode(&self) -> u32 {
+        let mode = match self.file_type {
+            vfs::FileType::Dir => 0o755,
+            _ => 0o644,
+        };
+        if self.read_only {
+            mode & !0o222
+        } else {
+            mode
+        }
+    }
+}
+
+struct DirEntry {
+    name: String,
+    /// The 8.3 name of FAT entries that also have a long name
+    short_name: Option<String>,
+    entry: Entry,
+}
+
+type Dir = Rc<Vec<DirEntry>>;
+
+/// Parses the 8.3 name of a FAT directory entry, applying the lower case flags set by Windows.
+fn short_name(raw: &[u8]) -> String {
+    let mut base = raw[..8].to_vec();
+    if base[0] == ESCAPED_DELETED {
+        base[0] = DELETED;
+    }
+    let mut ext = raw[8..11].to_vec();
+    if raw[12] & CASE_LOWER_BASE != 0 {
+        base.make_ascii_lowercase();
+    }
+    if raw[12] & CASE_LOWER_EXT != 0 {
+        ext.make_ascii_lowercase();
+    }
+    let trim = |s: &[u8]| String::from_utf8_lossy(s.trim_ascii_end()).into_owned();
+    let (base, ext) = (trim(&base), trim(&ext));
+    if ext.is_empty() {
+        base
+    } else {
+        format!("{base}.{ext}")
+    }
+}
+
+/// The checksum of an 8.3 name stored in each of its long name entries.
+fn short_name_checksum(raw: &[u8]) -> u8 {
+    raw[..11]
+        .iter()
+        .fold(0u8, |sum, &b| sum.rotate_right(1).wrapping_add(b))
+}
+
+/// Parses FAT directory entries, assembling long names from the entries preceding each short
+/// entry.
+fn fat_dir(data: &[u8]) -> Vec<DirEntry> {
+    let mut entries = Vec::new();
+    // Long name parts in the order found, which is from the end of the name
+    let mut long_name: Vec<u16> = Vec::new();
+    let mut checksum = None;
+    for raw in data.chunks_exact(DIR_ENTRY_LEN) {
+        match raw[0] {
+            0 => break,
+            DELETED => {
+                long_name.clear();
+                continue;
+            }
+            _ => {}
+        }
+        let attributes = raw[11];
+        if attributes & 0x3f == ATTR_LONG_NAME {
+            if raw[0] & LAST_LONG_ENTRY != 0 {
+                long_name.clear();
+                checksum = Some(raw[13]);
+            }
+            let units = [1..11, 14..26, 28..32]
+                .map(|range| raw[range].to_vec())
+                .concat();
+            let mut part: Vec<u16> = units.chunks_exact(2).map(|u| u16_at(u, 0)).collect();
+            part.extend(long_name);
+            long_name = part;
+            continue;
+        }
+        let long = std::mem::take(&mut long_name);
+        if attributes & ATTR_VOLUME_ID != 0 {
+            continue;
+        }
+        let short = short_name(raw);
+        if short == "." || short == ".." {
+            continue;
+        }
+        let long = Some(long)
+            .filter(|long| !long.is_empty() && checksum == Some(short_name_checksum(raw)))
+            .map(|long| {
+                // Long names are terminated by a NUL and padded with 0xffff
+                let end = long.iter().position(|&u| u == 0).unwrap_or(long.len());
+                String::from_utf16_lossy(&long[..end])
+            });
+        let is_dir = attributes & ATTR_DIRECTORY != 0;
+        let len = u32_at(raw, 28) as u64;
+        let entry = Entry {
+            file_type: if is_dir {
+                vfs::FileType::Dir
+            } else {
+                vfs::FileType::File
+            },
+            first_cluster: (u16_at(raw, 20) as u32) << 16 | u16_at(raw, 26) as u32,
+            len,
+            valid_len: len,
+            contiguous: false,
+            read_only: attributes & ATTR_READ_ONLY != 0,
+            mtime: dos_timestamp(u16_at(raw, 24), u16_at(raw, 22), 0, 0),
+        };
+        entries.push(match long {
+            Some(name) => DirEntry {
+                name,
+                short_name: Some(short),
+                entry,
+            },
+            None => DirEntry {
+                name: short,
+                short_name: None,
+                entry,
+            },
+        });
+    }
+    entries
+}
+
+struct Image<R: Read + Seek> {
+    reader: Rc<RefCell<R>>,
+    volume: Volume,
+    /// FAT chunks by index
+    fat: HashMap<u64, Vec<u8>>,
+    /// Directories by first cluster, with 0 for the fixed root directory region
+    dirs: HashMap<u32, Dir>,
+}
+
+impl<R: Read + Seek> Image<R> {
+    fn open(reader: Rc<RefCell<R>>) -> Result<Self, Error> {
+        let boot = read_at(&reader, 0, 512)?;
+        Ok(Self {
+            volume: Volume::parse(&boot)?,
+            reader,
+            fat: HashMap::new(),
+            dirs: HashMap::new(),
+        })
+    }
+
+    fn fat_bytes(&mut self, offset: u64, len: usize) -> Result<&[u8], Error> {
+        let chunk = offset / FAT_CHUNK_LEN;
+        if !self.fat.contains_key(&chunk) {
+            let start = chunk * FAT_CHUNK_LEN;
+            let chunk_len = FAT_CHUNK_LEN.min(self.volume.fat_len.saturating_sub(start));
+            let data = read_at(
+                &self.reader,
+                self.volume.fat_start + start,
+                chunk_len as usize,
+            )?;
+            self.fat.insert(chunk, data);
+        }
+        let start = (offset % FAT_CHUNK_LEN) as usize;
+        self.fat[&chunk]
+            .get(start..start + len)
+            .ok_or(Error::InvalidClusterChain)
+    }
+
+    /// Returns the cluster following `cluster` in its chain, or `None` at the end of the chain.
+    fn next_cluster(&mut self, cluster: u32) -> Result<Option<u32>, Error> {
+        let next = match self.volume.kind {
+            Kind::Fat12 => {
+                let offset = cluster as u64 + cluster as u64 / 2;
+                let pair = u16_at(self.fat_bytes(offset, 2)?, 0);
+                (if cluster % 2 == 1 {
+                    pair >> 4
+                } else {
+                    pair & 0xfff
+                }) as u32
+            }
+            Kind::Fat16 => u16_at(self.fat_bytes(cluster as u64 * 2, 2)?, 0) as u32,
+            Kind::Fat32 => u32_at(self.fat_bytes(cluster as u64 * 4, 4)?, 0) & 0x0fffffff,
+            Kind::ExFat => u32_at(self.fat_bytes(cluster as u64 * 4, 4)?, 0),
+        };
+        let end_of_chain = match self.volume.kind {
+            Kind::Fat12 => 0xff8,
+            Kind::Fat16 => 0xfff8,
+            Kind::Fat32 => 0x0ffffff8,
+            Kind::ExFat => 0xfffffff8,
+        };
+        if next >= end_of_chain {
+            Ok(None)
+        } else if self.volume.is_valid_cluster(next) {
+            Ok(Some(next))
+        } else {
+            Err(Error::InvalidClusterChain)
+        }
+    }
+
+    /// Maps the clusters of an entry, following its FAT chain unless it is contiguous.
+    fn runs(&mut self, entry: &Entry) -> Result<Vec<Run>, Error> {
+        let mut runs = Vec::new();
+        let cluster_len = self.volume.cluster_len;
+        // Empty files have no clusters
+        if entry.first_cluster == 0 {
+            return Ok(runs);
+        }
+        if !self.volume.is_valid_cluster(entry.first_cluster) {
+            return Err(Error::InvalidClusterChain);
+        }
+        if entry.contiguous {
+            let len = entry.len.div_ceil(cluster_len);
+            if entry.first_cluster as u64 + len
+                > FIRST_CLUSTER as u64 + self.volume.cluster_count as u64
+            {
+                return Err(Error::InvalidClusterChain);
+            }
+            runs.push(Run {
+                logical: 0,
+                len,
+                cluster: entry.first_cluster,
+            });
+            return Ok(runs);
+        }
+        let mut cluster = Some(entry.first_cluster);
+        let mut logical = 0;
+        while let Some(current) = cluster {
+            // A chain longer than the volume must loop
+            if logical >= self.volume.cluster_count as u64 {
+                return Err(Error::InvalidClusterChain);
+            }
+            push_cluster(&mut runs, logical, current);
+            logical += 1;
+            // The rest of a file's chain, if any, isn't needed
+            if entry.file_type == vfs::FileType::File && logical * cluster_len >= entry.len {
+                break;
+            }
+            cluster = self.next_cluster(current)?;
+        }
+        Ok(runs)
+    }
+
+    fn dir(&mut self, entry: &Entry) -> Result<Dir, Error> {
+        if let Some(dir) = self.dirs.get(&entry.first_cluster) {
+            return Ok(dir.clone());
+        }
+        let data = match self.volume.root {
+            RootDir::Region(start, len) if entry.first_cluster == 0 => {
+                read_at(&self.reader, start, len as usize)?
+            }
+            _ => {
+                let mut data = Vec::new();
+                for run in self.runs(entry)? {
+                    let len = run.len * self.volume.cluster_len;
+                    data.extend(read_at(
+                        &self.reader,
+                        self.volume.cluster_offset(run.cluster),
+                        len as usize,
+                    )?);
+                }
+                data
+            }
+        };
+        let entries = Rc::new(if self.volume.kind == Kind::ExFat {
+            exfat::dir(&data)
+        } else {
+            fat_dir(&data)
+        });
+        self.dirs.insert(entry.first_cluster, entries.clone());
+        Ok(entries)
+    }
+
+    fn root(&self) -> Entry {
+        let first_cluster = match self.volume.root {
+            RootDir::Region(..) => 0,
+            RootDir::Cluster(cluster) => cluster,
+        };
+        Entry {
+            file_type: vfs::FileType::Dir,
+            first_cluster,
+            len: 0,
+            valid_len: 0,
+            contiguous: false,
+            read_only: false,
+            mtime: None,
+        }
+    }
+
+    fn lookup(&mut self, path: &[u8]) -> Result<Entry, Error> {
+        let mut entry = self.root();
+        for component in vfs::path::components(path) {
+            if entry.file_type != vfs::FileType::Dir {
+                return Err(Error::NotFound);
+            }
+            let dir = self.dir(&entry)?;
+            entry = dir
+                .iter()
+                .find(|e| {
+                    name_matches(&e.name, component)
+                        || e.short_name
+                            .as_ref()
+                            .is_some_and(|name| name_matches(name, component))
+                })
+                .ok_or(Error::NotFound)?
+                .entry
+                .clone();
+        }
+        Ok(entry)
+    }
+}
+
+/// Reads a file through its cluster runs, reading zeros past the valid data length.
+pub struct File<R: Read + Seek> {
+    reader: Rc<RefCell<R>>,
+    data_start: u64,
+    cluster_len: u64,
+    runs: Vec<Run>,
+    len: u64,
+    valid_len: u64,
+    offset: u64,
+}
+
+impl<R: Read + Seek> Read for File<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.offset);
+        let max = (buf.len() as u64).min(remaining) as usize;
+        if max == 0 {
+            return Ok(0);
+        }
+        if self.offset >= self.valid_len {
+            buf[..max].fill(0);
+            self.offset += max as u64;
+            return Ok(max);
+        }
+        let cluster = self.offset / self.cluster_len;
+        let index = self.runs.partition_point(|r| r.logical + r.len <= cluster);
+        let run = self
+            .runs
+            .get(index)
+            .filter(|r| r.logical <= cluster)
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "cluster chain is shorter than the file",
+                )
+            })?;
+        let run_start = run.logical * self.cluster_len;
+        let run_end = (run_start + run.len * self.cluster_len).min(self.valid_len);
+        let n = (max as u64).min(run_end - self.offset) as usize;
+        let pos = self.data_start
+            + (run.cluster - FIRST_CLUSTER) as u64 * self.cluster_len
+            + (self.offset - run_start);
+        let mut reader = self.reader.borrow_mut();
+        reader.seek(SeekFrom::Start(pos))?;
+        let n = reader.read(&mut buf[..n])?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for File<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.offset = vfs::io::seek_position(pos, self.offset, self.len)?;
+        Ok(self.offset)
+    }
+}
+
+/// A FAT12, FAT16, FAT32 or exFAT image. Names are matched case-insensitively against both long
+/// and short names, and files are read through their cluster chains.
+pub struct FatFs<R: Read + Seek> {
+    reader: Rc<RefCell<R>>,
+    image: Option<Image<R>>,
+}
+
+impl<R: Read + Seek> FatFs<R> {
+    fn image(&mut self) -> Result<&mut Image<R>, Error> {
+        if self.image.is_none() {
+            self.image = Some(Image::open(self.reader.clone())?);
+        }
+        Ok(self.image.as_mut().unwrap())
+    }
+}
+
+impl<R: Read + Seek> vfs::IoBackedFs<R> for FatFs<R> {
+    type Password = ();
+
+    fn from_io(io: R, _password: Self::Password) -> Self {
+        Self {
+            reader: Rc::new(RefCell::new(io)),
+            image: None,
+        }
+    }
+}
+
+impl<R: Read + Seek> vfs::Fs for FatFs<R> {
+    type Path = [u8];
+    type Error = Error;
+    type File = File<R>;
+
+    fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Self::Error> {
+        let entry = self.image()?.lookup(path)?;
+        Ok(vfs::Metadata {
+            file_type: entry.file_type,
+            len: match entry.file_type {
+                vfs::FileType::File => entry.len,
+                _ => 0,
+            },
+            mode: Some(entry.mode()),
+            mtime: entry.mtime,
+        })
+    }
+
+    fn open(&mut self, path: &[u8]) -> Result<Self::File, Self::Error> {
+        let reader = self.reader.clone();
+        let image = self.image()?;
+        let entry = image.lookup(path)?;
+        if entry.file_type != vfs::FileType::File {
+            return Err(Error::NotAFile);
+        }
+        Ok(File {
+            reader,
+            data_start: image.volume.data_start,
+            cluster_len: image.volume.cluster_len,
+            runs: image.runs(&entry)?,
+            len: entry.len,
+            valid_len: entry.valid_len,
+            offset: 0,
+        })
+    }
+}
@@ -0,0 +1,283 @@
+# Writes the images of the vfs-fat tests next to this script, gzipped since most of them is
+# empty clusters.
+import gzip, os, struct
+
+DIR = os.path.dirname(os.path.abspath(__file__))
+
+SECTOR = 512
+DATE = ((2023 - 1980) << 9) | (4 << 5) | 5
+TIME = (6 << 11) | (7 << 5) | (8 // 2)
+
+def patterned(n, seed):
+    # The period doesn't divide the cluster length, so misordered clusters show
+    return bytes((i * 7 + seed) % 251 for i in range(n))
+
+README = b"Hello world! (readme)\n"
+LONG = b"Hello world! (long name)\n"
+LOWER = b"Hello world! (lower)\n"
+NESTED = b"Hello world! (nested)\n"
+READ_ONLY = b"Hello world! (read-only)\n"
+
+def short_checksum(name11):
+    s = 0
+    for b in name11:
+        s = (((s & 1) << 7) | (s >> 1)) + b & 0xff
+    return s
+
+def short_entry(name11, attr, cluster, size, case=0):
+    return (name11 + bytes([attr, case, 0]) + struct.pack("<HHH", TIME, DATE, DATE)
+            + struct.pack("<HHHHI", cluster >> 16, TIME, DATE, cluster & 0xffff, size))
+
+def lfn_entries(name, name11):
+    units = list(name.encode("utf-16-le"))
+    units = [units[i] | units[i + 1] << 8 for i in range(0, len(units), 2)]
+    if len(units) % 13:
+        units.append(0)
+    while len(units) % 13:
+        units.append(0xffff)
+    parts = [units[i:i + 13] for i in range(0, len(units), 13)]
+    out = bytearray()
+    checksum = short_checksum(name11)
+    for n in range(len(parts), 0, -1):
+        part = parts[n - 1]
+        raw = struct.pack("<13H", *part)
+        ordinal = n | (0x40 if n == len(parts) else 0)
+        out += bytes([ordinal]) + raw[:10] + bytes([0x0f, 0, checksum]) + raw[10:22] + b"\0\0" + raw[22:26]
+    return bytes(out)
+
+def fat_image(kind, spc, reserved, root_entries, total, fat_sectors):
+    cluster_len = spc * SECTOR
+    root_sectors = root_entries * 32 // SECTOR
+    data_start = (reserved + 2 * fat_sectors + root_sectors) * SECTOR
+    clusters = (total * SECTOR - data_start) // cluster_len
+    img = bytearray(total * SECTOR)
+    fat = {0: {12: 0xff8, 16: 0xfff8, 32: 0x0ffffff8}[kind], 1: {12: 0xfff, 16: 0xffff, 32: 0x0fffffff}[kind]}
+    eoc = fat[1]
+    next_free = [3 if kind == 32 else 2]
+
+    def write_chain(chain, data):
+        for i, c in enumerate(chain):
+            fat[c] = chain[i + 1] if i + 1 < len(chain) else eoc
+            pos = data_start + (c - 2) * cluster_len
+            chunk = data[i * cluster_len:(i + 1) * cluster_len]
+            img[pos:pos + len(chunk)] = chunk
+
+    def alloc(data, chain=None):
+        if chain is None:
+            n = max(1, -(-len(data) // cluster_len))
+            chain = list(range(next_free[0], next_free[0] + n))
+        next_free[0] = max(next_free[0], max(chain) + 1)
+        write_chain(chain, data)
+        return chain[0]
+
+    files = {}
+    files["readme"] = alloc(README)
+    files["long"] = alloc(LONG)
+    files["lower"] = alloc(LOWER)
+    files["ro"] = alloc(READ_ONLY)
+    # Out of order and with gaps between the clusters
+    fragmented = patterned(cluster_len * 2 + 100, kind)
+    base = next_free[0]
+    files["frag"] = alloc(fragmented, [base + 2, base, base + 4])
+    sub_cluster = next_free[0]
+    nested = sub_cluster + 1
+    root_cluster = 2 if kind == 32 else 0
+
+    sub = bytearray()
+    sub += short_entry(b".          ", 0x10, sub_cluster, 0)
+    sub += short_entry(b"..         ", 0x10, root_cluster if kind != 32 else 0, 0)
+    sub += lfn_entries("nested file.txt", b"NESTED~1TXT") + short_entry(b"NESTED~1TXT", 0x20, nested, len(NESTED))
+    alloc(bytes(sub).ljust(cluster_len, b"\0"), [sub_cluster])
+    alloc(NESTED, [nested])
+
+    root = bytearray()
+    root += short_entry(b"TESTVOL    ", 0x08, 0, 0)
+    root += short_entry(b"README  TXT", 0x20, files["readme"], len(README))
+    # A deleted entry with a stale long name, both to be skipped
+    root += bytes([0xe5]) + lfn_entries("Deleted.txt", b"DELETED TXT")[1:]
+    root += bytes([0xe5]) + short_entry(b"DELETED TXT", 0x20, 0, 0)[1:]
+    root += lfn_entries("Long File Name.txt", b"LONGFI~1TXT") + short_entry(b"LONGFI~1TXT", 0x20, files["long"], len(LONG))
+    root += short_entry(b"LOWER   TXT", 0x20, files["lower"], len(LOWER), 0x18)
+    root += short_entry(b"RO      TXT", 0x21, files["ro"], len(READ_ONLY))
+    root += lfn_entries("Fragmented.bin", b"FRAGME~1BIN") + short_entry(b"FRAGME~1BIN", 0x20, files["frag"], len(fragmented))
+    root += lfn_entries("Sub Directory", b"SUBDIR~1   ") + short_entry(b"SUBDIR~1   ", 0x10, sub_cluster, 0)
+    if kind == 32:
+        write_chain([2], bytes(root).ljust(cluster_len, b"\0"))
+    else:
+        pos = (reserved + 2 * fat_sectors) * SECTOR
+        img[pos:pos + len(root)] = root
+
+    boot = bytearray(SECTOR)
+    boot[0:3] = b"\xeb\x3c\x90"
+    boot[3:11] = b"MSWIN4.1"
+    struct.pack_into("<HBHBHHBHHHII", boot, 11, SECTOR, spc, reserved, 2, root_entries,
+                     total if total < 65536 else 0, 0xf8, fat_sectors if kind != 32 else 0,
+                     32, 2, 0, total if total >= 65536 else 0)
+    if kind == 32:
+        struct.pack_into("<IHHIHH", boot, 36, fat_sectors, 0, 0, 2, 1, 6)
+        struct.pack_into("<BBBI", boot, 64, 0x80, 0, 0x29, 0x12345678)
+        boot[71:82] = b"TESTVOL    "
+        boot[82:90] = b"FAT32   "
+    else:
+        struct.pack_into("<BBBI", boot, 36, 0x80, 0, 0x29, 0x12345678)
+        boot[43:54] = b"TESTVOL    "
+        boot[54:62] = b"FAT%d   " % kind
+    boot[510:512] = b"\x55\xaa"
+    img[0:SECTOR] = boot
+    if kind == 32:
+        fsinfo = bytearray(SECTOR)
+        struct.pack_into("<I", fsinfo, 0, 0x41615252)
+        struct.pack_into("<III", fsinfo, 484, 0x61417272, 0xffffffff, 0xffffffff)
+        struct.pack_into("<I", fsinfo, 508, 0xaa550000)
+        img[SECTOR:2 * SECTOR] = fsinfo
+        img[6 * SECTOR:7 * SECTOR] = boot
+        img[7 * SECTOR:8 * SECTOR] = fsinfo
+
+    fat_bytes = bytearray(fat_sectors * SECTOR)
+    for c, v in fat.items():
+        if kind == 12:
+            off = c + c // 2
+            pair = struct.unpack_from("<H", fat_bytes, off)[0]
+            pair = (pair & 0x000f) | v << 4 if c % 2 else (pair & 0xf000) | v
+            struct.pack_into("<H", fat_bytes, off, pair)
+        elif kind == 16:
+            struct.pack_into("<H", fat_bytes, c * 2, v)
+        else:
+            struct.pack_into("<I", fat_bytes, c * 4, v)
+    for i in range(2):
+        pos = (reserved + i * fat_sectors) * SECTOR
+        img[pos:pos + len(fat_bytes)] = fat_bytes
+    return bytes(img), clusters
+
+for kind, args in [(12, (1, 1, 64, 200, 1)), (16, (1, 1, 64, 4200, 17)), (32, (1, 32, 0, 66600, 521))]:
+    img, clusters = fat_image(kind, *args)
+    with open(os.path.join(DIR, f"fat{kind}.img.gz"), "wb") as f:
+        f.write(gzip.compress(img, 9, mtime=0))
+
+def exfat_image():
+    shift, cluster_shift = 9, 3
+    cluster_len = SECTOR << cluster_shift
+    fat_offset, fat_length, heap_offset, cluster_count = 24, 8, 32, 64
+    total = heap_offset + (cluster_count << cluster_shift)
+    img = bytearray(total * SECTOR)
+    fat = {0: 0xfffffff8, 1: 0xffffffff}
+    used = set()
+
+    def cluster_pos(c):
+        return (heap_offset << shift) + (c - 2) * cluster_len
+
+    def write(chain, data, fat_chain=True):
+        for i, c in enumerate(chain):
+            used.add(c)
+            if fat_chain:
+                fat[c] = chain[i + 1] if i + 1 < len(chain) else 0xffffffff
+            chunk = data[i * cluster_len:(i + 1) * cluster_len]
+            img[cluster_pos(c):cluster_pos(c) + len(chunk)] = chunk
+
+    upcase = [c - 32 if 0x61 <= c <= 0x7a else c for c in range(128)]
+    upcase_bytes = struct.pack("<128H", *upcase)
+    upcase_checksum = 0
+    for b in upcase_bytes:
+        upcase_checksum = ((upcase_checksum >> 1) | (upcase_checksum & 1) << 31) + b & 0xffffffff
+
+    def name_hash(name):
+        h = 0
+        for b in name.upper().encode("utf-16-le"):
+            h = ((h >> 1) | (h & 1) << 15) + b & 0xffff
+        return h
+
+    # 2023-04-05 06:07:08 and 150 10 ms units at UTC-5, so 11:07:09.5 UTC. The creation and
+    # access offsets differ, so that using the wrong one shows.
+    stamp = (2023 - 1980) << 25 | 4 << 21 | 5 << 16 | 6 << 11 | 7 << 5 | 8 // 2
+    utc_minus_5 = 0x80 | (-20 & 0x7f)
+
+    def entry_set(name, attributes, cluster, length, valid_len, contiguous):
+        units = name.encode("utf-16-le")
+        file = bytearray(32)
+        file[0] = 0x85
+        file[1] = 1 + -(-len(name) // 15)
+        struct.pack_into("<H", file, 4, attributes)
+        struct.pack_into("<III", file, 8, stamp, stamp, stamp)
+        file[20:25] = bytes([0, 150, 0x80, utc_minus_5, 0x80 | 16])
+        stream = bytearray(32)
+        stream[0] = 0xc0
+        stream[1] = 0x01 | (0x02 if contiguous else 0)
+        stream[3] = len(name)
+        struct.pack_into("<H", stream, 4, name_hash(name))
+        struct.pack_into("<Q", stream, 8, valid_len)
+        struct.pack_into("<IQ", stream, 20, cluster, length)
+        names = bytearray()
+        for i in range(0, len(units), 30):
+            names += bytes([0xc1, 0]) + units[i:i + 30].ljust(30, b"\0")
+        entries = file + stream + names
+        checksum = 0
+        for i, b in enumerate(entries):
+            if i in (2, 3):
+                continue
+            checksum = ((checksum >> 1) | (checksum & 1) << 15) + b & 0xffff
+        struct.pack_into("<H", entries, 2, checksum)
+        return bytes(entries)
+
+    bitmap_cluster, upcase_cluster, root_cluster = 2, 3, 4
+    fragmented = patterned(cluster_len * 2 + 100, 64)
+    contiguous = patterned(cluster_len + 1000, 65)
+    frag_chain = [7, 5, 9]
+    write(frag_chain, fragmented)
+    write([10, 11], contiguous, fat_chain=False)
+    # Preallocated past its valid length, which reads as zeros
+    write([12, 13], README)
+    write([15], NESTED)
+
+    sub = entry_set("nested.txt", 0x20, 15, len(NESTED), len(NESTED), True)
+    write([14], sub.ljust(cluster_len, b"\0"), fat_chain=False)
+
+    root = bytearray()
+    label = "TestVol".encode("utf-16-le")
+    root += bytes([0x83, 7]) + label.ljust(30, b"\0")
+    root += bytes([0x81, 0]) + bytes(18) + struct.pack("<IQ", bitmap_cluster, cluster_count // 8)
+    root += bytes([0x82]) + bytes(3) + struct.pack("<I", upcase_checksum) + bytes(12) + struct.pack("<IQ", upcase_cluster, len(upcase_bytes))
+    root += entry_set("Fragmented File.bin", 0x20, frag_chain[0], len(fragmented), len(fragmented), False)
+    root += entry_set("Contiguous.bin", 0x21, 10, len(contiguous), len(contiguous), True)
+    root += entry_set("Preallocated.txt", 0x20, 12, cluster_len * 2, len(README), True)
+    root += entry_set("Sub Directory", 0x10, 14, cluster_len, cluster_len, True)
+    # A deleted set, whose entry types have the in-use bit cleared
+    root += bytes(b & 0x7f if i % 32 == 0 else b for i, b in enumerate(
+        entry_set("Deleted.txt", 0x20, 0, 0, 0, False)))
+    write([root_cluster], bytes(root).ljust(cluster_len, b"\0"))
+
+    bitmap = bytearray(cluster_count // 8)
+    for c in used | {bitmap_cluster, upcase_cluster}:
+        bitmap[(c - 2) // 8] |= 1 << ((c - 2) % 8)
+    write([bitmap_cluster], bytes(bitmap))
+    write([upcase_cluster], upcase_bytes)
+
+    boot = bytearray(SECTOR)
+    boot[0:3] = b"\xeb\x76\x90"
+    boot[3:11] = b"EXFAT   "
+    struct.pack_into("<QQIIIII", boot, 64, 0, total, fat_offset, fat_length, heap_offset, cluster_count, root_cluster)
+    struct.pack_into("<IHH", boot, 100, 0x12345678, 0x0100, 0)
+    boot[108:113] = bytes([shift, cluster_shift, 1, 0x80, 0])
+    boot[510:512] = b"\x55\xaa"
+    region = bytearray(boot)
+    for _ in range(8):
+        ext = bytearray(SECTOR)
+        ext[508:512] = b"\x00\x00\x55\xaa"
+        region += ext
+    region += bytes(2 * SECTOR)
+    checksum = 0
+    for i, b in enumerate(region):
+        if i in (106, 107, 112):
+            continue
+        checksum = ((checksum >> 1) | (checksum & 1) << 31) + b & 0xffffffff
+    region += struct.pack("<I", checksum) * (SECTOR // 4)
+    img[0:len(region)] = region
+    img[len(region):2 * len(region)] = region
+
+    fat_bytes = bytearray(fat_length * SECTOR)
+    for c, v in fat.items():
+        struct.pack_into("<I", fat_bytes, c * 4, v)
+    img[fat_offset * SECTOR:(fat_offset + fat_length) * SECTOR] = fat_bytes
+    return bytes(img)
+
+with open(os.path.join(DIR, "exfat.img.gz"), "wb") as f:
+    f.write(gzip.compress(exfat_image(), 9, mtime=0))
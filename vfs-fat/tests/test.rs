@@ -0,0 +1,165 @@
+use std::{
+    io::{Read, Seek, SeekFrom},
+    time::{Duration, SystemTime},
+};
+use vfs::{Fs, IoBackedFs};
+
+type Image = vfs_fat::FatFs<std::io::Cursor<Vec<u8>>>;
+
+/// Opens an image written by tests/data/generate.py. They are gzipped, since FAT16 and FAT32 need
+/// thousands of clusters.
+fn image(name: &str) -> Image {
+    Image::from_io(std::io::Cursor::new(image_data(name)), ())
+}
+
+fn image_data(name: &str) -> Vec<u8> {
+    let file = std::fs::File::open(format!("tests/data/{name}.img.gz")).unwrap();
+    let mut data = Vec::new();
+    flate2::read::GzDecoder::new(file)
+        .read_to_end(&mut data)
+        .unwrap();
+    data
+}
+
+fn read(fs: &mut Image, path: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    fs.open(path.as_bytes())
+        .unwrap()
+        .read_to_end(&mut data)
+        .unwrap();
+    data
+}
+
+fn patterned(len: usize, seed: usize) -> Vec<u8> {
+    (0..len).map(|i| ((i * 7 + seed) % 251) as u8).collect()
+}
+
+#[test]
+fn test() {
+    // The same tree on each FAT type: short names, long names spanning two entries, a name
+    // with the lower case flags, a read-only file and a file in clusters out of order
+    let mtime = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_680_674_828));
+    for (kind, seed) in [("fat12", 12), ("fat16", 16), ("fat32", 32)] {
+        let mut fs = image(kind);
+        assert_eq!(
+            fs.metadata(b"").unwrap(),
+            vfs::Metadata {
+                file_type: vfs::FileType::Dir,
+                len: 0,
+                mode: Some(0o755),
+                mtime: None,
+            }
+        );
+        assert_eq!(
+            fs.metadata(b"Long File Name.txt").unwrap(),
+            vfs::Metadata {
+                file_type: vfs::FileType::File,
+                len: 25,
+                mode: Some(0o644),
+                mtime,
+            }
+        );
+        assert_eq!(
+            fs.metadata(b"ro.txt").unwrap(),
+            vfs::Metadata {
+                file_type: vfs::FileType::File,
+                len: 25,
+                mode: Some(0o444),
+                mtime,
+            }
+        );
+        assert_eq!(
+            fs.metadata(b"sub directory").unwrap(),
+            vfs::Metadata {
+                file_type: vfs::FileType::Dir,
+                len: 0,
+                mode: Some(0o755),
+                mtime,
+            }
+        );
+        assert_eq!(read(&mut fs, "README.TXT"), b"Hello world! (readme)\n");
+        assert_eq!(
+            read(&mut fs, "long file name.txt"),
+            b"Hello world! (long name)\n"
+        );
+        assert_eq!(read(&mut fs, "LONGFI~1.TXT"), b"Hello world! (long name)\n");
+        assert_eq!(read(&mut fs, "lower.txt"), b"Hello world! (lower)\n");
+        assert_eq!(
+            read(&mut fs, "Sub Directory/nested file.txt"),
+            b"Hello world! (nested)\n"
+        );
+        assert_eq!(read(&mut fs, "Fragmented.bin"), patterned(1124, seed));
+        let mut file = fs.open(b"Fragmented.bin").unwrap();
+        let mut buf = [0; 16];
+        file.seek(SeekFrom::Start(1020)).unwrap();
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, patterned(1124, seed)[1020..1036]);
+        for missing in ["Deleted.txt", "TESTVOL", "Sub Directory/missing"] {
+            assert!(matches!(
+                fs.metadata(missing.as_bytes()),
+                Err(vfs_fat::Error::NotFound)
+            ));
+        }
+        assert!(matches!(
+            fs.open(b"Sub Directory"),
+            Err(vfs_fat::Error::NotAFile)
+        ));
+    }
+
+    // exFAT with a file in a FAT chain out of order, a contiguous file without a chain, a file
+    // preallocated past its valid length and a modification time at UTC-5
+    let mut fs = image("exfat");
+    let mtime = SystemTime::UNIX_EPOCH + Duration::from_millis(1_680_692_829_500);
+    assert_eq!(
+        fs.metadata(b"Fragmented File.bin").unwrap(),
+        vfs::Metadata {
+            file_type: vfs::FileType::File,
+            len: 8292,
+            mode: Some(0o644),
+            mtime: Some(mtime),
+        }
+    );
+    assert_eq!(
+        fs.metadata(b"contiguous.bin").unwrap(),
+        vfs::Metadata {
+            file_type: vfs::FileType::File,
+            len: 5096,
+            mode: Some(0o444),
+            mtime: Some(mtime),
+        }
+    );
+    assert_eq!(
+        fs.metadata(b"Sub Directory").unwrap().file_type,
+        vfs::FileType::Dir
+    );
+    assert_eq!(read(&mut fs, "Fragmented File.bin"), patterned(8292, 64));
+    assert_eq!(read(&mut fs, "Contiguous.bin"), patterned(5096, 65));
+    let mut file = fs.open(b"Contiguous.bin").unwrap();
+    let mut buf = [0; 16];
+    file.seek(SeekFrom::Start(4090)).unwrap();
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, patterned(5096, 65)[4090..4106]);
+    let mut preallocated = b"Hello world! (readme)\n".to_vec();
+    preallocated.resize(8192, 0);
+    assert_eq!(read(&mut fs, "Preallocated.txt"), preallocated);
+    assert_eq!(
+        read(&mut fs, "sub directory/NESTED.TXT"),
+        b"Hello world! (nested)\n"
+    );
+    assert!(matches!(
+        fs.metadata(b"Deleted.txt"),
+        Err(vfs_fat::Error::NotFound)
+    ));
+
+    // Cluster counts and FAT lengths past what the volume holds
+    let data = image_data("exfat");
+    for (offset, value) in [(92, u32::MAX), (92, 1 << 20), (84, u32::MAX)] {
+        let mut data = data.clone();
+        data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        let mut fs = Image::from_io(std::io::Cursor::new(data), ());
+        assert!(matches!(
+            fs.metadata(b"contiguous.bin"),
+            Err(vfs_fat::Error::InvalidBootSector)
+        ));
+    }
+}
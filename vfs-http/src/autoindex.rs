@@ -0,0 +1,193 @@
+//! Directory listings generated by web servers, such as nginx's `autoindex`, Apache's
+//! `mod_autoindex`, lighttpd's `mod_dirlisting` and Python's `http.server`.
+
+use std::{
+    collections::BTreeMap,
+    time::{Duration, SystemTime},
+};
+use vfs::time::days_from_civil;
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// Whether `html` looks like a generated directory listing, going by its title.
+pub fn is_index_page(html: &str) -> bool {
+    let html = html.to_ascii_lowercase();
+    ["<title>index of ", "<title>directory listing for "]
+        .iter()
+        .any(|title| html.contains(title))
+}
+
+/// Parses a date like `17-Oct-2024` (nginx, older Apache), `2024-10-17` (Apache) or
+/// `2024-Oct-17` (lighttpd) into days since the Unix epoch.
+fn parse_date(s: &str) -> Option<i64> {
+    let [a, b, c] = s.split('-').collect::<Vec<_>>().try_into().ok()?;
+    let month = match MONTHS.iter().position(|m| m.eq_ignore_ascii_case(b)) {
+        Some(i) => i as i64 + 1,
+        None => b.parse().ok()?,
+    };
+    let (year, day) = if a.len() == 4 { (a, c) } else { (c, a) };
+    let (year, day) = (year.parse().ok()?, day.parse().ok()?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    Some(days_from_civil(year, month, day))
+}
+
+/// Parses a time like `10:20` or `10:20:30` into seconds since midnight.
+fn parse_time(s: &str) -> Option<i64> {
+    let mut fields = s.split(':').map(|f| f.parse::<i64>().ok());
+    let hour = fields.next()??;
+    let minute = fields.next()??;
+    let second = fields.next().unwrap_or(Some(0))?;
+    if fields.next().is_some() || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    Some(hour * 3600 + minute * 60 + second)
+}
+
+/// Parses a size in bytes, or a rounded one like `1.2K` or `12M` into an approximation.
+fn parse_size(s: &str) -> Option<u64> {
+    let s = s.strip_suffix(['B', 'b']).unwrap_or(s);
+    let (number, shift) = match s.char_indices().last()? {
+        (i, 'K' | 'k') => (&s[..i], 10),
+        (i, 'M') => (&s[..i], 20),
+        (i, 'G') => (&s[..i], 30),
+        (i, 'T') => (&s[..i], 40),
+        _ => (s, 0),
+    };
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit() || b == b'.') {
+        return None;
+    }
+    if shift == 0 {
+        return number.parse().ok();
+    }
+    let size = number.parse::<f64>().ok()? * (1u64 << shift) as f64;
+    Some(size as u64)
+}
+
+/// Finds the modification time and size in the columns following an entry's link.
+fn parse_columns(text: &str) -> (Option<SystemTime>, Option<u64>) {
+    let tokens = text.split_whitespace().collect::<Vec<_>>();
+    for (i, pair) in tokens.windows(2).enumerate() {
+        let (Some(days), Some(secs)) = (parse_date(pair[0]), parse_time(pair[1])) else {
+            continue;
+        };
+        let secs = days * 86400 + secs;
+        // Listings show server times, which are taken as UTC
+        let mtime = if secs >= 0 {
+            SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64))
+        } else {
+            SystemTime::UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs()))
+        };
+        // Directories have `-` in place of a size
+        let size = tokens.get(i + 2).and_then(|s| parse_size(s));
+        return (mtime, size);
+    }
+    (None, None)
+}
+
+fn decode_entities(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&")
+}
+
+fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            // Tags separate columns, as in tables
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&nbsp;", " ")
+}
+
+/// Returns the value of the `href` attribute of an `<a ...>` tag.
+fn href(tag: &str) -> Option<String> {
+    let start = tag.to_ascii_lowercase().find("href=")? + "href=".len();
+    let value = &tag[start..];
+    let value = match value.chars().next()? {
+        quote @ ('"' | '\'') => value[1..].split(quote).next()?,
+        _ => value.split([' ', '>']).next()?,
+    };
+    Some(decode_entities(value))
+}
+
+/// Returns the entry name that a link points to, and whether it's a directory. Only links to
+/// direct children count, which leaves out parent directory, sorting and external links.
+fn entry_name(href: &str) -> Option<(&str, bool)> {
+    let href = href.strip_prefix("./").unwrap_or(href);
+    let (name, is_dir) = match href.strip_suffix('/') {
+        Some(name) => (name, true),
+        None => (href, false),
+    };
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '?', '#', ':']) {
+        return None;
+    }
+    Some((name, is_dir))
+}
+
+/// Parses the entries of a listing, sorted by name. Names are as they appear in links, so
+/// percent-encoded.
+pub fn parse(html: &str) -> Vec<vfs::DirEntry> {
+    // Lowercasing keeps byte offsets, so positions found in it apply to `html`
+    let lower = html.to_ascii_lowercase();
+    let mut entries = BTreeMap::new();
+    let mut pos = 0;
+    while let Some(start) = lower[pos..].find("<a ").map(|i| pos + i) {
+        let Some(tag_end) = lower[start..].find('>').map(|i| start + i) else {
+            break;
+        };
+        pos = tag_end + 1;
+        let Some(href) = href(&html[start..tag_end]) else {
+            continue;
+        };
+        let Some((name, is_dir)) = entry_name(&href) else {
+            continue;
+        };
+        // The columns of a row follow its link, up to the next link or the end of the row
+        let row_end = ["<a ", "\n", "</tr"]
+            .iter()
+            .filter_map(|s| lower[pos..].find(s))
+            .min()
+            .map_or(html.len(), |i| pos + i);
+        let (mtime, size) = parse_columns(&strip_tags(&html[pos..row_end]));
+        let metadata = vfs::Metadata {
+            file_type: if is_dir {
+                vfs::FileType::Dir
+            } else {
+                vfs::FileType::File
+            },
+            len: if is_dir { 0 } else { size.unwrap_or(0) },
+            mode: None,
+            mtime,
+        };
+        // Apache links each entry twice, from its icon and from its name, the latter followed
+        // by the columns
+        entries
+            .entry(name.as_bytes().to_vec())
+            .and_modify(|m: &mut vfs::Metadata| {
+                if metadata.mtime.is_some() {
+                    *m = metadata.clone();
+                }
+            })
+            .or_insert(metadata);
+    }
+    entries
+        .into_iter()
+        .map(|(name, metadata)| vfs::DirEntry { name, metadata })
+        .collect()
+}
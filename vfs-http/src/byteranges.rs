@@ -0,0 +1,56 @@
+//! Responses for byte ranges, as a single `Content-Range` or several `multipart/byteranges`
+//! parts.
+
+use std::ops::Range;
+
+/// Parses a `Content-Range` value, such as `bytes 0-99/1234`, into its range.
+pub fn content_range(value: &str) -> Option<Range<u64>> {
+    let (range, _total) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+    let (first, last) = range.split_once('-')?;
+    let (first, last): (u64, u64) = (first.parse().ok()?, last.parse().ok()?);
+    (first <= last).then(|| first..last + 1)
+}
+
+/// Returns the boundary of a `multipart/byteranges` content type.
+pub fn boundary(content_type: &str) -> Option<&str> {
+    let (mime, params) = content_type.split_once(';')?;
+    if !mime.trim().eq_ignore_ascii_case("multipart/byteranges") {
+        return None;
+    }
+    params.split(';').find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        let is_boundary = name.trim().eq_ignore_ascii_case("boundary");
+        is_boundary.then(|| value.trim().trim_matches('"'))
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Splits a `multipart/byteranges` body into the offsets and data of its parts.
+pub fn parse(body: &[u8], boundary: &str) -> Option<Vec<(u64, Vec<u8>)>> {
+    let delimiter = format!("--{boundary}");
+    let mut parts = Vec::new();
+    let mut rest = body;
+    loop {
+        rest = &rest[find(rest, delimiter.as_bytes())? + delimiter.len()..];
+        // The last delimiter is followed by `--`
+        if rest.starts_with(b"--") {
+            return Some(parts);
+        }
+        let headers_len = find(rest, b"\r\n\r\n")? + 4;
+        let headers = std::str::from_utf8(&rest[..headers_len]).ok()?;
+        let range = headers.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            let is_content_range = name.trim().eq_ignore_ascii_case("content-range");
+            is_content_range.then(|| content_range(value)).flatten()
+        })?;
+        // Data is taken by length, as it may contain the delimiter
+        let data_end = headers_len.checked_add(usize::try_from(range.end - range.start).ok()?)?;
+        parts.push((range.start, rest.get(headers_len..data_end)?.to_vec()));
+        rest = &rest[data_end..];
+    }
+}
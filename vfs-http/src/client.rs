@@ -0,0 +1,325 @@
+//! The HTTP client a filesystem sends its requests with, and the builder configuring it.
+
+use crate::{disk_cache::DiskCache, netrc, Error};
+use reqwest::{
+    blocking::{ClientBuilder, RequestBuilder, Response},
+    header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+    Certificate, Identity, Proxy, StatusCode, Url,
+};
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+const DEFAULT_MAX_READAHEAD: u64 = 8 << 20;
+
+/// Credentials sent to a host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Credentials {
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+    Bearer(String),
+}
+
+#[derive(Debug, Clone, Default)]
+struct CredentialsByHost {
+    hosts: HashMap<String, Credentials>,
+    /// Credentials for hosts without their own
+    default: Option<Credentials>,
+}
+
+impl CredentialsByHost {
+    fn insert(&mut self, host: Option<String>, credentials: Credentials) {
+        match host {
+            Some(host) => {
+                self.hosts.insert(host, credentials);
+            }
+            None => self.default = Some(credentials),
+        }
+    }
+
+    fn get(&self, url: &Url) -> Option<&Credentials> {
+        url.host_str()
+            .and_then(|host| self.hosts.get(host))
+            .or(self.default.as_ref())
+    }
+}
+
+/// How requests are retried after connection errors, timeouts, server errors and
+/// `429 Too Many Requests`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Retry {
+    /// How many times a request is retried before giving up
+    pub max_retries: u32,
+    /// The wait before the first retry, which doubles with each one after
+    pub initial_backoff: Duration,
+    /// The longest wait between retries, which also caps waits asked for with `Retry-After`
+    pub max_backoff: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl Retry {
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// Returns the wait a response asks for, in seconds or until a date.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    match value.parse() {
+        Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(_) => httpdate::parse_http_date(value)
+            .ok()?
+            .duration_since(SystemTime::now())
+            .ok(),
+    }
+}
+
+#[derive(Clone)]
+pub struct Client {
+    client: reqwest::blocking::Client,
+    credentials: CredentialsByHost,
+    /// Whether basic authentication is sent over plain HTTP, where anyone on the way can read it
+    basic_auth_over_http: bool,
+    pub retry: Retry,
+    /// The most a read requests ahead of what it needs
+    pub max_readahead: u64,
+    /// Whether prefetched ranges are requested together
+    pub multi_range: bool,
+    pub disk_cache: Option<DiskCache>,
+}
+
+impl Client {
+    fn authorize(&self, request: RequestBuilder, url: &str) -> RequestBuilder {
+        let Ok(url) = Url::parse(url) else {
+            return request;
+        };
+        match self.credentials.get(&url) {
+            Some(Credentials::Basic { username, password })
+                if url.scheme() == "https" || self.basic_auth_over_http =>
+            {
+                request.basic_auth(username, password.as_ref())
+            }
+            Some(Credentials::Bearer(token)) => request.bearer_auth(token),
+            _ => request,
+        }
+    }
+
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        self.authorize(self.client.get(url), url)
+    }
+
+    pub fn head(&self, url: &str) -> RequestBuilder {
+        self.authorize(self.client.head(url), url)
+    }
+
+    /// Sends `request`, retrying as configured. After the last retry, the error or the response
+    /// with an error status is returned.
+    pub fn send(&self, request: RequestBuilder) -> reqwest::Result<Response> {
+        let mut retry = 0;
+        loop {
+            let result = request
+                .try_clone()
+                .expect("requests have no streamed bodies")
+                .send();
+            let wait = match &result {
+                Ok(response)
+                    if response.status().is_server_error()
+                        || response.status() == StatusCode::TOO_MANY_REQUESTS =>
+                {
+                    retry_after(response).unwrap_or_else(|| self.retry.backoff(retry))
+                }
+                Err(err) if err.is_connect() || err.is_timeout() || err.is_request() => {
+                    self.retry.backoff(retry)
+                }
+                _ => return result,
+            };
+            if retry >= self.retry.max_retries {
+                return result;
+            }
+            std::thread::sleep(wait.min(self.retry.max_backoff));
+            retry += 1;
+        }
+    }
+}
+
+/// Configures the client of an `HttpFs` or `HttpsFs`, which their `builder` functions return.
+pub struct Builder<T> {
+    client: ClientBuilder,
+    headers: HeaderMap,
+    credentials: CredentialsByHost,
+    basic_auth_over_http: bool,
+    retry: Retry,
+    max_readahead: u64,
+    multi_range: bool,
+    disk_cache: Option<DiskCache>,
+    fs: PhantomData<T>,
+}
+
+impl<T> Builder<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            client: ClientBuilder::new(),
+            headers: HeaderMap::new(),
+            credentials: CredentialsByHost::default(),
+            basic_auth_over_http: false,
+            retry: Retry::default(),
+            max_readahead: DEFAULT_MAX_READAHEAD,
+            multi_range: false,
+            disk_cache: None,
+            fs: PhantomData,
+        }
+    }
+
+    /// Adds a header to every request.
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Sends `credentials` to `host`, or to hosts without their own credentials if `None`.
+    pub fn credentials(mut self, host: Option<&str>, credentials: Credentials) -> Self {
+        self.credentials
+            .insert(host.map(str::to_string), credentials);
+        self
+    }
+
+    /// Sends basic authentication to hosts without their own credentials.
+    pub fn basic_auth(self, username: &str, password: Option<&str>) -> Self {
+        let credentials = Credentials::Basic {
+            username: username.to_string(),
+            password: password.map(str::to_string),
+        };
+        self.credentials(None, credentials)
+    }
+
+    /// Sends a bearer token to hosts without their own credentials.
+    pub fn bearer_auth(self, token: &str) -> Self {
+        self.credentials(None, Credentials::Bearer(token.to_string()))
+    }
+
+    /// Adds the credentials of a netrc file's contents for the hosts named in it. Its `default`
+    /// entry is ignored.
+    pub fn netrc(mut self, contents: &str) -> Self {
+        for (host, credentials) in netrc::parse(contents) {
+            self.credentials.insert(Some(host), credentials);
+        }
+        self
+    }
+
+    /// Adds the credentials of the netrc file at `$NETRC`, or `~/.netrc`, like `curl --netrc`,
+    /// if there is one.
+    pub fn default_netrc(self) -> Self {
+        match netrc::read_default() {
+            Some(contents) => self.netrc(&contents),
+            None => self,
+        }
+    }
+
+    /// Sends basic authentication over plain HTTP too, rather than only over HTTPS.
+    pub fn basic_auth_over_http(mut self, enabled: bool) -> Self {
+        self.basic_auth_over_http = enabled;
+        self
+    }
+
+    /// Trusts the certificate authorities of a PEM bundle, on top of the system ones.
+    pub fn root_certificates_pem(mut self, pem: &[u8]) -> Result<Self, Error> {
+        for certificate in Certificate::from_pem_bundle(pem).map_err(Error::Client)? {
+            self.client = self.client.add_root_certificate(certificate);
+        }
+        Ok(self)
+    }
+
+    /// Authenticates with a client certificate and its PKCS #8 private key, both PEM-encoded.
+    pub fn identity_pem(mut self, certificate: &[u8], key: &[u8]) -> Result<Self, Error> {
+        let identity = Identity::from_pkcs8_pem(certificate, key).map_err(Error::Client)?;
+        self.client = self.client.identity(identity);
+        Ok(self)
+    }
+
+    /// Authenticates with a client certificate and private key from a PKCS #12 archive.
+    pub fn identity_pkcs12(mut self, der: &[u8], password: &str) -> Result<Self, Error> {
+        let identity = Identity::from_pkcs12_der(der, password).map_err(Error::Client)?;
+        self.client = self.client.identity(identity);
+        Ok(self)
+    }
+
+    /// Sends every request through a proxy, such as `http://proxy:3128`, instead of the ones
+    /// from the environment.
+    pub fn proxy(mut self, url: &str) -> Result<Self, Error> {
+        self.client = self.client.proxy(Proxy::all(url).map_err(Error::Client)?);
+        Ok(self)
+    }
+
+    /// Limits the time of each request, from connecting to reading the whole response.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.client = self.client.timeout(timeout);
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.client = self.client.connect_timeout(timeout);
+        self
+    }
+
+    pub fn retry(mut self, retry: Retry) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Limits readahead, which doubles the length of each range request while a file is read
+    /// sequentially, starting from what the read needs. 0 disables readahead.
+    pub fn max_readahead(mut self, len: u64) -> Self {
+        self.max_readahead = len;
+        self
+    }
+
+    /// Prefetches ranges with a single `multipart/byteranges` request, rather than a request per
+    /// range. Not every server supports these.
+    pub fn multi_range(mut self, enabled: bool) -> Self {
+        self.multi_range = enabled;
+        self
+    }
+
+    /// Keeps downloaded blocks of files in `dir`, to be read again by later runs as long as the
+    /// files keep their ETag or Last-Modified. Files with neither aren't cached. Several
+    /// processes may share a directory, whose least recently used blocks are removed when it
+    /// grows past `max_size` bytes.
+    pub fn disk_cache(mut self, dir: impl Into<PathBuf>, max_size: u64) -> Self {
+        self.disk_cache = Some(DiskCache::new(dir.into(), max_size));
+        self
+    }
+
+    pub(crate) fn build_client(self) -> Result<Client, Error> {
+        Ok(Client {
+            client: self
+                .client
+                .default_headers(self.headers)
+                .build()
+                .map_err(Error::Client)?,
+            credentials: self.credentials,
+            basic_auth_over_http: self.basic_auth_over_http,
+            retry: self.retry,
+            max_readahead: self.max_readahead,
+            multi_range: self.multi_range,
+            disk_cache: self.disk_cache,
+        })
+    }
+}
@@ -0,0 +1,166 @@
+//! Blocks of remote files kept on disk, so that later runs don't download them again.
+//!
+//! Each version of a file, as told by its URL and validator, has a directory named after their
+//! hash, holding the key it's for and a file per block. Blocks are written to temporary files
+//! and renamed into place, so processes sharing a cache never see partial ones, and their
+//! modification times are bumped on reads for the least recently used ones to be evicted.
+
+use std::{
+    fs::{self, File},
+    io::{ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
+
+/// The size of the blocks kept on disk, which requests are aligned to
+pub const BLOCK_SIZE: u64 = 64 << 10;
+
+const KEY_FILE: &str = "key";
+
+/// FNV-1a, which unlike `DefaultHasher` is the same across Rust releases.
+fn hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Writes `data` to `path` through a temporary file, so that it appears whole or not at all.
+fn write_atomically(dir: &Path, path: PathBuf, data: &[u8]) -> std::io::Result<()> {
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(data)?;
+    file.as_file().set_modified(SystemTime::now())?;
+    file.persist(path)?;
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct DiskCache {
+    dir: PathBuf,
+    max_size: u64,
+    /// Bytes written since the last eviction, shared by the files of a filesystem
+    written: Arc<AtomicU64>,
+}
+
+impl DiskCache {
+    pub fn new(dir: PathBuf, max_size: u64) -> Self {
+        Self {
+            dir,
+            max_size,
+            written: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Returns the entry of `url` at the version with `validator`, its ETag or Last-Modified,
+    /// unless its directory belongs to another key.
+    pub fn entry(&self, url: &str, validator: &str) -> Option<Entry> {
+        let key = format!("{url}\n{validator}");
+        let entry = Entry {
+            cache: self.clone(),
+            dir: self.dir.join(format!("{:016x}", hash(key.as_bytes()))),
+            key,
+        };
+        match fs::read(entry.dir.join(KEY_FILE)) {
+            Ok(key) if key != entry.key.as_bytes() => None,
+            _ => Some(entry),
+        }
+    }
+
+    /// Removes the least recently used blocks until the cache fits its size limit.
+    pub fn evict(&self) -> std::io::Result<()> {
+        self.written.store(0, Ordering::Relaxed);
+        let mut blocks = Vec::new();
+        let mut entries = Vec::new();
+        let mut size = 0;
+        let dirs = match fs::read_dir(&self.dir) {
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            dirs => dirs?,
+        };
+        for dir in dirs {
+            let dir = dir?;
+            if !dir.file_type()?.is_dir() {
+                continue;
+            }
+            entries.push(dir.path());
+            // Entries may be removed by other processes while listing them
+            for block in fs::read_dir(dir.path())?.flatten() {
+                let Ok(metadata) = block.metadata() else {
+                    continue;
+                };
+                if block.file_name() != KEY_FILE {
+                    size += metadata.len();
+                    blocks.push((metadata.modified()?, metadata.len(), block.path()));
+                }
+            }
+        }
+        blocks.sort();
+        for (_, len, path) in blocks {
+            if size <= self.max_size {
+                break;
+            }
+            if fs::remove_file(path).is_ok() {
+                size -= len;
+            }
+        }
+        for dir in entries {
+            let is_empty = fs::read_dir(&dir).is_ok_and(|mut files| {
+                files.all(|file| file.is_ok_and(|f| f.file_name() == KEY_FILE))
+            });
+            if is_empty {
+                let _ = fs::remove_file(dir.join(KEY_FILE));
+                let _ = fs::remove_dir(dir);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The cached blocks of a version of a file.
+pub struct Entry {
+    cache: DiskCache,
+    dir: PathBuf,
+    key: String,
+}
+
+impl Entry {
+    fn block_path(&self, offset: u64) -> PathBuf {
+        self.dir.join(format!("{offset:016x}"))
+    }
+
+    /// Returns the block at `offset`, if it's cached and `len` bytes long as expected.
+    pub fn read(&self, offset: u64, len: u64) -> Option<Vec<u8>> {
+        let mut file = File::open(self.block_path(offset)).ok()?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).ok()?;
+        // Marks the block as recently used
+        let _ = file.set_modified(SystemTime::now());
+        (data.len() as u64 == len).then_some(data)
+    }
+
+    /// Caches the block at `offset`. Failures are ignored, as the cache is only an optimization.
+    /// The cache is trimmed once a sixteenth of its size has been written since the last time,
+    /// rather than on every open, as that lists the whole cache directory.
+    pub fn write(&self, offset: u64, data: &[u8]) {
+        let _ = self.try_write(offset, data);
+        let written = self
+            .cache
+            .written
+            .fetch_add(data.len() as u64, Ordering::Relaxed);
+        if written + data.len() as u64 > self.cache.max_size / 16 {
+            let _ = self.cache.evict();
+        }
+    }
+
+    fn try_write(&self, offset: u64, data: &[u8]) -> std::io::Result<()> {
+        // The directory is created on the first write, or again after being evicted
+        let key_path = self.dir.join(KEY_FILE);
+        if !key_path.exists() {
+            fs::create_dir_all(&self.dir)?;
+            write_atomically(&self.dir, key_path, self.key.as_bytes())?;
+        }
+        write_atomically(&self.dir, self.block_path(offset), data)
+    }
+}
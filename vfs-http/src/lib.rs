@@ -1,14 +1,36 @@
+mod autoindex;
+mod byteranges;
+mod client;
+mod disk_cache;
+mod netrc;
+mod spool;
+
 use std::{
-    io::{Read, Seek, SeekFrom},
+    collections::BTreeMap,
+    io::{ErrorKind, Read, Seek, SeekFrom},
     num::ParseIntError,
+    ops::Range,
+    time::SystemTime,
 };
 
 use cache_read_seek::CachedReadSeek;
+use client::Client;
+pub use client::{Builder, Credentials, Retry};
+pub use reqwest::header::{HeaderName, HeaderValue};
 use reqwest::{
-    blocking::{Client, RequestBuilder},
-    header::{HeaderValue, ToStrError, CONTENT_LENGTH, RANGE},
-    StatusCode,
+    blocking::Response,
+    header::{
+        ToStrError, ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_RANGE,
+        LAST_MODIFIED, RANGE,
+    },
+    StatusCode, Url,
 };
+use spool::SpooledResponse;
+
+/// How much of an HTML page is read to tell whether it's a directory listing
+const SNIFF_LEN: u64 = 4096;
+/// The most a file holds of received ranges that weren't read yet
+const MAX_SEGMENTS_LEN: u64 = 64 << 20;
 
 pub struct HttpFs(Client);
 
@@ -20,22 +42,114 @@ pub enum Error {
     ContentLength(#[from] ContentLengthError),
     #[error("failed sending HTTP request")]
     HttpRequest(#[from] reqwest::Error),
+    #[error("failed configuring HTTP client")]
+    Client(#[source] reqwest::Error),
+    #[error("failed reading HTTP response")]
+    Io(#[from] std::io::Error),
+    #[error("not a directory listing")]
+    NotADirectory,
+    #[error("remote file changed while reading it")]
+    Changed,
+    #[error("unexpected HTTP status {0}")]
+    UnexpectedStatus(StatusCode),
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum ContentLengthError {
-    #[error(transparent)]
-    SendRequest(#[from] reqwest::Error),
+    #[error("missing content length header")]
+    Missing,
     #[error(transparent)]
     ToStr(#[from] ToStrError),
     #[error(transparent)]
     ParseInt(#[from] ParseIntError),
 }
 
-fn content_length(client: &Client, url: &str) -> Result<u64, ContentLengthError> {
-    Ok(client.get(url).send()?.headers()[CONTENT_LENGTH]
-        .to_str()?
-        .parse()?)
+/// What a server reports about a URL in its response headers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpMetadata {
+    /// The length of the whole body, if the server tells
+    pub len: Option<u64>,
+    pub etag: Option<String>,
+    pub last_modified: Option<SystemTime>,
+    pub content_type: Option<String>,
+    /// Whether the server serves byte ranges
+    pub accept_ranges: bool,
+}
+
+fn header<'r>(response: &'r Response, name: &HeaderName) -> Option<&'r str> {
+    response.headers().get(name).and_then(|v| v.to_str().ok())
+}
+
+impl HttpMetadata {
+    fn from_response(response: &Response) -> Result<Self, ContentLengthError> {
+        let partial = response.status() == StatusCode::PARTIAL_CONTENT;
+        let len = if partial {
+            // The total follows the range, as in `bytes 0-0/1234`, or is `*` when unknown
+            let content_range = response
+                .headers()
+                .get(CONTENT_RANGE)
+                .ok_or(ContentLengthError::Missing)?
+                .to_str()?;
+            match content_range.rsplit('/').next() {
+                Some("*") | None => None,
+                Some(total) => Some(total.parse()?),
+            }
+        } else {
+            response
+                .headers()
+                .get(CONTENT_LENGTH)
+                .map(|v| v.to_str()?.parse().map_err(ContentLengthError::from))
+                .transpose()?
+        };
+        Ok(Self {
+            len,
+            etag: header(response, &ETAG).map(str::to_string),
+            last_modified: header(response, &LAST_MODIFIED)
+                .and_then(|v| httpdate::parse_http_date(v).ok()),
+            content_type: header(response, &CONTENT_TYPE).map(str::to_string),
+            accept_ranges: partial || header(response, &ACCEPT_RANGES) == Some("bytes"),
+        })
+    }
+}
+
+/// Requests the headers of `url` with HEAD, or for servers that don't allow it, with a GET of
+/// its first byte.
+fn head(client: &Client, url: &str) -> Result<Response, Error> {
+    let response = client.send(client.head(url))?;
+    if !matches!(
+        response.status(),
+        StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+    ) {
+        return Ok(response.error_for_status()?);
+    }
+    Ok(client
+        .send(client.get(url).header(RANGE, "bytes=0-0"))?
+        .error_for_status()?)
+}
+
+/// Whether `url` is for a directory, which is the case when its path ends with a slash. Where
+/// the server redirects to is left out, as files are also redirected to such paths, like those of
+/// login pages.
+fn is_dir_url(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| url.path().ends_with('/'))
+}
+
+/// Requests the whole body of `url` to spool, for files whose length the server doesn't tell.
+fn spool_whole(client: &Client, url: &str) -> Result<SpooledResponse, Error> {
+    let response = client.send(client.get(url))?.error_for_status()?;
+    Ok(SpooledResponse::new(response, u64::MAX)?)
+}
+
+/// Whether the start of the HTML page at `url` is titled like a directory listing.
+fn is_index_page(client: &Client, url: &str) -> Result<bool, Error> {
+    let mut head = Vec::new();
+    let range = format!("bytes=0-{}", SNIFF_LEN - 1);
+    client
+        .send(client.get(url).header(RANGE, range))?
+        .error_for_status()?
+        .take(SNIFF_LEN)
+        .read_to_end(&mut head)?;
+    Ok(autoindex::is_index_page(&String::from_utf8_lossy(&head)))
 }
 
 fn path_to_url(use_https: bool, path: &str) -> String {
@@ -43,92 +157,492 @@ fn path_to_url(use_https: bool, path: &str) -> String {
     format!("{protocol}://{path}")
 }
 
+fn http_metadata(client: &Client, use_https: bool, path: &str) -> Result<HttpMetadata, Error> {
+    let url = path_to_url(use_https, path);
+    Ok(HttpMetadata::from_response(&head(client, &url)?)?)
+}
+
 fn metadata(client: &Client, use_https: bool, path: &str) -> Result<vfs::Metadata, Error> {
     let url = path_to_url(use_https, path);
+    let response = head(client, &url)?;
+    let http_metadata = HttpMetadata::from_response(&response)?;
+    let is_html = http_metadata
+        .content_type
+        .as_ref()
+        .is_some_and(|v| v.starts_with("text/html"));
+    if is_dir_url(&url) || is_html && is_index_page(client, &url)? {
+        return Ok(vfs::Metadata {
+            file_type: vfs::FileType::Dir,
+            len: 0,
+            mode: None,
+            mtime: http_metadata.last_modified,
+        });
+    }
     Ok(vfs::Metadata {
-        len: content_length(client, &url)?,
+        len: match http_metadata.len {
+            Some(len) => len,
+            None => spool_whole(client, &url)?.finish()?,
+        },
         file_type: vfs::FileType::File,
+        mode: None,
+        mtime: http_metadata.last_modified,
     })
 }
 
 fn open(client: &Client, use_https: bool, path: &str) -> Result<HttpFile, Error> {
     let url = path_to_url(use_https, path);
+    let response = head(client, &url)?;
+    let http_metadata = HttpMetadata::from_response(&response)?;
+    let version = Version::from_response(&response);
+    let disk = client
+        .disk_cache
+        .as_ref()
+        .zip(version.if_range())
+        .and_then(|(disk_cache, validator)| disk_cache.entry(&url, validator));
+    // Without a length, the body is downloaded whole to find it, and then read from there
+    let (size, spool) = match http_metadata.len {
+        Some(len) => (len, None),
+        None => {
+            let mut spool = spool_whole(client, &url)?;
+            (spool.finish()?, Some(spool))
+        }
+    };
     Ok(HttpFile(CachedReadSeek::new(CachelessHttpFile {
-        size: content_length(client, &url)?,
+        size,
         offset: 0,
-        request: client.get(url),
+        client: client.clone(),
+        url,
+        version,
+        // Servers that don't advertise ranges may still serve them, so only an explicit refusal
+        // is taken at its word
+        ranges: spool.is_none() && header(&response, &ACCEPT_RANGES) != Some("none"),
+        spool,
+        segments: BTreeMap::new(),
+        segments_len: 0,
+        request_len: 0,
+        request_end: 0,
+        disk,
     })))
 }
 
+fn read_dir(client: &Client, use_https: bool, path: &str) -> Result<Vec<vfs::DirEntry>, Error> {
+    let url = path_to_url(use_https, path);
+    let mut response = client.send(client.get(&url))?.error_for_status()?;
+    let is_dir_url = is_dir_url(&url);
+    let mut body = Vec::new();
+    response.read_to_end(&mut body)?;
+    let body = String::from_utf8_lossy(&body);
+    if !is_dir_url && !autoindex::is_index_page(&body) {
+        return Err(Error::NotADirectory);
+    }
+    Ok(autoindex::parse(&body))
+}
+
 macro_rules! impl_fs {
-    ($T:ty) => {
+    ($T:ident, $use_https:literal) => {
         impl vfs::Fs for $T {
             type Path = str;
             type Error = Error;
             type File = HttpFile;
 
             fn metadata(&mut self, path: &str) -> Result<vfs::Metadata, Error> {
-                metadata(&self.0, false, path)
+                metadata(&self.0, $use_https, path)
             }
 
             fn open(&mut self, path: &str) -> Result<Self::File, Error> {
-                open(&self.0, false, path)
+                open(&self.0, $use_https, path)
+            }
+        }
+
+        /// Lists directories from the index pages that web servers generate for them. Sizes
+        /// may be rounded or missing, in which case they are 0, and names are percent-encoded.
+        impl vfs::ReadDir for $T {
+            fn read_dir(&mut self, path: &str) -> Result<Vec<vfs::DirEntry>, Error> {
+                read_dir(&self.0, $use_https, path)
+            }
+        }
+
+        impl $T {
+            pub fn builder() -> Builder<Self> {
+                Builder::new()
+            }
+
+            /// Returns what the server reports about `path` in its response headers.
+            pub fn http_metadata(&self, path: &str) -> Result<HttpMetadata, Error> {
+                http_metadata(&self.0, $use_https, path)
+            }
+        }
+
+        impl Builder<$T> {
+            pub fn build(self) -> Result<$T, Error> {
+                Ok($T(self.build_client()?))
             }
         }
 
         impl vfs::StandaloneFs for $T {
             fn new() -> Self {
-                Self(Client::new())
+                Self::builder()
+                    .build()
+                    .expect("failed building HTTP client")
             }
         }
     };
 }
 
-impl_fs!(HttpFs);
-impl_fs!(HttpsFs);
+impl_fs!(HttpFs, false);
+impl_fs!(HttpsFs, true);
+
+/// The validators of the version of a remote file that was opened.
+struct Version {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl Version {
+    fn from_response(response: &Response) -> Self {
+        Self {
+            etag: header(response, &ETAG).map(str::to_string),
+            last_modified: header(response, &LAST_MODIFIED).map(str::to_string),
+        }
+    }
+
+    /// Returns the `If-Range` value, for which a weak ETag doesn't do.
+    fn if_range(&self) -> Option<&str> {
+        self.etag
+            .as_deref()
+            .filter(|etag| !etag.starts_with("W/"))
+            .or(self.last_modified.as_deref())
+    }
+
+    /// Whether `response` is for this version, as far as its headers tell.
+    fn matches(&self, response: &Response) -> bool {
+        let other = Self::from_response(response);
+        let same = |a: &Option<String>, b: &Option<String>| a.is_none() || b.is_none() || a == b;
+        same(&self.etag, &other.etag) && same(&self.last_modified, &other.last_modified)
+    }
+}
+
+/// Whether an error reading a response body is worth retrying, as a dropped connection is.
+/// Errors sending a request aren't, as `Client::send` has already retried them.
+fn is_transient(err: &std::io::Error) -> bool {
+    let kinds = [
+        ErrorKind::TimedOut,
+        ErrorKind::ConnectionReset,
+        ErrorKind::ConnectionAborted,
+        ErrorKind::BrokenPipe,
+        ErrorKind::UnexpectedEof,
+    ];
+    match err.get_ref() {
+        Some(inner) if matches!(inner.downcast_ref::<Error>(), Some(Error::HttpRequest(_))) => {
+            false
+        }
+        // Reading the body fails with the error of the underlying connection
+        Some(inner) if inner.is::<reqwest::Error>() => true,
+        _ => kinds.contains(&err.kind()),
+    }
+}
 
 struct CachelessHttpFile {
     size: u64,
     offset: u64,
-    request: RequestBuilder,
+    client: Client,
+    url: String,
+    version: Version,
+    /// Whether to request ranges, which stops once the server ignores one
+    ranges: bool,
+    /// The whole body, once requested without a range
+    spool: Option<SpooledResponse>,
+    /// Ranges that were received but not yet read, by offset
+    segments: BTreeMap<u64, Vec<u8>>,
+    /// The total length of `segments`
+    segments_len: u64,
+    /// The length of the last range request, which readahead grows from
+    request_len: u64,
+    /// Where the last range request ended, which a sequential read continues from
+    request_end: u64,
+    /// The blocks of this version of the file on disk
+    disk: Option<disk_cache::Entry>,
 }
 
 impl Seek for CachelessHttpFile {
     fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
-        let add_err = || {
-            std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "invalid seek to a negative or overflowing position",
-            )
-        };
-        self.offset = match pos {
-            SeekFrom::Start(offset) => Ok(offset),
-            SeekFrom::End(offset) => self.size.checked_add_signed(offset).ok_or_else(add_err),
-            SeekFrom::Current(offset) => self.offset.checked_add_signed(offset).ok_or_else(add_err),
-        }?;
+        self.offset = vfs::io::seek_position(pos, self.offset, self.size)?;
         Ok(self.offset)
     }
 }
 
-impl Read for CachelessHttpFile {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        let range = HeaderValue::from_str(&format!(
-            "bytes={}-{}",
-            self.offset,
-            self.offset + buf.len() as u64
-        ))
-        .expect("Invalid range HTTP header value");
-        let mut response = self
-            .request
-            .try_clone()
-            .unwrap()
-            .header(RANGE, range)
-            .send()
-            .unwrap();
-        if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+impl CachelessHttpFile {
+    /// Sends a request for `ranges`, or the whole body if the server doesn't serve ranges.
+    fn request(&self, ranges: &[Range<u64>]) -> std::io::Result<Response> {
+        let mut request = self.client.get(&self.url);
+        if self.ranges {
+            let ranges: Vec<_> = ranges
+                .iter()
+                .map(|range| format!("{}-{}", range.start, range.end - 1))
+                .collect();
+            let range = HeaderValue::from_str(&format!("bytes={}", ranges.join(",")))
+                .expect("Invalid range HTTP header value");
+            request = request.header(RANGE, range);
+            // A changed file is sent whole, rather than a range of the new version
+            if let Some(if_range) = self.version.if_range() {
+                request = request.header(IF_RANGE, if_range);
+            }
+        }
+        let response = self
+            .client
+            .send(request)
+            .map_err(|err| std::io::Error::other(Error::HttpRequest(err)))?;
+        if response.status() != StatusCode::RANGE_NOT_SATISFIABLE
+            && !self.version.matches(&response)
+        {
+            return Err(std::io::Error::other(Error::Changed));
+        }
+        Ok(response)
+    }
+
+    /// Keeps the whole body of `response`, to serve this and later reads.
+    fn spool(&mut self, response: Response) -> std::io::Result<&mut SpooledResponse> {
+        self.ranges = false;
+        Ok(self
+            .spool
+            .insert(SpooledResponse::new(response, self.size)?))
+    }
+
+    /// Reads from a received range at the current offset, if there is one.
+    fn read_segment(&mut self, buf: &mut [u8]) -> Option<usize> {
+        let (&start, data) = self.segments.range(..=self.offset).next_back()?;
+        let data = data.get((self.offset - start) as usize..)?;
+        if data.is_empty() {
+            return None;
+        }
+        let n = buf.len().min(data.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        if n == data.len() {
+            self.remove_segment(start);
+        }
+        Some(n)
+    }
+
+    /// Keeps a received range, dropping the ranges furthest from the current offset while they
+    /// hold more than `MAX_SEGMENTS_LEN` bytes.
+    fn insert_segment(&mut self, start: u64, data: Vec<u8>) {
+        self.segments_len += data.len() as u64;
+        if let Some(old) = self.segments.insert(start, data) {
+            self.segments_len -= old.len() as u64;
+        }
+        while self.segments_len > MAX_SEGMENTS_LEN && self.segments.len() > 1 {
+            let first = *self.segments.keys().next().unwrap();
+            let last = *self.segments.keys().next_back().unwrap();
+            match self.offset.saturating_sub(first) > last.saturating_sub(self.offset) {
+                true => self.remove_segment(first),
+                false => self.remove_segment(last),
+            }
+        }
+    }
+
+    fn remove_segment(&mut self, start: u64) {
+        if let Some(data) = self.segments.remove(&start) {
+            self.segments_len -= data.len() as u64;
+        }
+    }
+
+    /// Returns how much to request for a read of `len` bytes, doubling the last request while
+    /// reads continue where it ended.
+    fn request_len(&mut self, len: u64) -> u64 {
+        let sequential = self.request_len > 0 && self.offset == self.request_end;
+        let len = match sequential {
+            true => (self.request_len.saturating_mul(2))
+                .min(self.client.max_readahead)
+                .max(len),
+            false => len,
+        };
+        let len = len.min(self.size - self.offset);
+        self.request_len = len;
+        len
+    }
+
+    /// Widens `range` to whole blocks, of the disk cache if there is one so that they can be
+    /// kept there, or else of `CachedReadSeek`.
+    fn align(&self, range: Range<u64>) -> Range<u64> {
+        let block = match self.disk {
+            Some(_) => disk_cache::BLOCK_SIZE,
+            None => cache_read_seek::SIZE,
+        };
+        let start = range.start - range.start % block;
+        start..(range.end.div_ceil(block) * block).min(self.size)
+    }
+
+    /// Loads the blocks of an aligned `range` that are on disk, returning the runs of the others.
+    fn load_cached(&mut self, range: Range<u64>) -> Vec<Range<u64>> {
+        let Some(disk) = &self.disk else {
+            return vec![range];
+        };
+        let mut loaded = Vec::new();
+        let mut missing: Vec<Range<u64>> = Vec::new();
+        for start in range.clone().step_by(disk_cache::BLOCK_SIZE as usize) {
+            let end = (start + disk_cache::BLOCK_SIZE).min(range.end);
+            match disk.read(start, end - start) {
+                Some(data) => loaded.push((start, data)),
+                None => match missing.last_mut() {
+                    Some(last) if last.end == start => last.end = end,
+                    _ => missing.push(start..end),
+                },
+            }
+        }
+        for (start, data) in loaded {
+            self.insert_segment(start, data);
+        }
+        missing
+    }
+
+    /// Keeps the whole blocks of data received from `start` on disk.
+    fn store(&self, start: u64, data: &[u8]) {
+        let Some(disk) = &self.disk else {
+            return;
+        };
+        if !start.is_multiple_of(disk_cache::BLOCK_SIZE) {
+            return;
+        }
+        for (i, block) in data.chunks(disk_cache::BLOCK_SIZE as usize).enumerate() {
+            let offset = start + i as u64 * disk_cache::BLOCK_SIZE;
+            // The last block of the file is the only one that may be shorter
+            if block.len() as u64 == disk_cache::BLOCK_SIZE
+                || offset + block.len() as u64 == self.size
+            {
+                disk.write(offset, block);
+            }
+        }
+    }
+
+    /// Reads from the current offset with at most a single request.
+    fn read_once(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if let Some(n) = self.read_segment(buf) {
+            return Ok(n);
+        }
+        if buf.is_empty() || self.offset >= self.size {
             return Ok(0);
         }
-        let n = response.read(buf)?;
+        let len = self.request_len(buf.len() as u64);
+        let range = self.align(self.offset..self.offset + len);
+        // Blocks on disk are read from there, and the request stops at the first one after
+        let missing = self.load_cached(range);
+        if let Some(n) = self.read_segment(buf) {
+            return Ok(n);
+        }
+        let Some(range) = missing.into_iter().next() else {
+            return Ok(0);
+        };
+        self.request_end = range.end;
+        let response = self.request(std::slice::from_ref(&range))?;
+        match response.status() {
+            StatusCode::RANGE_NOT_SATISFIABLE => return Ok(0),
+            StatusCode::OK => {
+                let offset = self.offset;
+                return self.spool(response)?.read_at(buf, offset);
+            }
+            StatusCode::PARTIAL_CONTENT => {}
+            status => return Err(std::io::Error::other(Error::UnexpectedStatus(status))),
+        }
+        let mut data = Vec::new();
+        let result = response
+            .take(range.end - range.start)
+            .read_to_end(&mut data);
+        // What arrived before an error is kept, so that a retry resumes after it
+        if data.is_empty() {
+            result?;
+            return Ok(0);
+        }
+        self.store(range.start, &data);
+        self.insert_segment(range.start, data);
+        Ok(self.read_segment(buf).unwrap_or(0))
+    }
+
+    /// Requests `ranges` together, keeping them to serve later reads.
+    fn fetch(&mut self, ranges: &[Range<u64>]) -> std::io::Result<()> {
+        let mut response = self.request(ranges)?;
+        match response.status() {
+            StatusCode::OK => {
+                self.spool(response)?;
+                return Ok(());
+            }
+            StatusCode::PARTIAL_CONTENT => {}
+            status => return Err(std::io::Error::other(Error::UnexpectedStatus(status))),
+        }
+        let boundary = header(&response, &CONTENT_TYPE)
+            .and_then(byteranges::boundary)
+            .map(str::to_string);
+        let range = header(&response, &CONTENT_RANGE).and_then(byteranges::content_range);
+        let mut body = Vec::new();
+        response.read_to_end(&mut body)?;
+        // Servers may answer with a single range, such as when they merge the requested ones
+        let parts = match (boundary, range) {
+            (Some(boundary), _) => byteranges::parse(&body, &boundary),
+            (None, Some(range)) => Some(vec![(range.start, body)]),
+            (None, None) => None,
+        };
+        let parts = parts.ok_or_else(|| {
+            std::io::Error::new(ErrorKind::InvalidData, "invalid byte ranges response")
+        })?;
+        for (start, data) in parts {
+            self.store(start, &data);
+            self.insert_segment(start, data);
+        }
+        Ok(())
+    }
+
+    fn prefetch(&mut self, ranges: &[Range<u64>]) -> std::io::Result<()> {
+        if !self.ranges {
+            return Ok(());
+        }
+        // Whole cache blocks are fetched, as reads ask for those
+        let mut ranges: Vec<_> = ranges
+            .iter()
+            .map(|range| self.align(range.clone()))
+            .filter(|range| range.start < range.end)
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+        let mut merged: Vec<Range<u64>> = Vec::new();
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        let missing: Vec<_> = merged
+            .into_iter()
+            .flat_map(|range| self.load_cached(range))
+            .collect();
+        if self.client.multi_range && missing.len() > 1 {
+            return self.fetch(&missing);
+        }
+        for range in missing {
+            self.fetch(std::slice::from_ref(&range))?;
+            if !self.ranges {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Read for CachelessHttpFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut retry = 0;
+        let n = loop {
+            let result = match &mut self.spool {
+                // The spooled response can't be resumed, as the server doesn't serve ranges
+                Some(spool) => break spool.read_at(buf, self.offset)?,
+                None => self.read_once(buf),
+            };
+            match result {
+                // A new request resumes where the failed one left off
+                Err(err) if is_transient(&err) && retry < self.client.retry.max_retries => {
+                    std::thread::sleep(self.client.retry.backoff(retry));
+                    retry += 1;
+                }
+                result => break result?,
+            }
+        };
         self.offset += n as u64;
         Ok(n)
     }
@@ -147,3 +661,12 @@ impl Read for HttpFile {
         self.0.read(buf)
     }
 }
+
+impl HttpFile {
+    /// Fetches byte ranges ahead of reading them, such as the scattered entries of an archive.
+    /// Adjacent ranges are merged, and the rest are requested together if the filesystem was
+    /// built with `multi_range`, or else one by one.
+    pub fn prefetch(&mut self, ranges: &[Range<u64>]) -> std::io::Result<()> {
+        self.0.get_mut().prefetch(ranges)
+    }
+}
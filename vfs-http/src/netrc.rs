@@ -0,0 +1,89 @@
+//! The netrc file format, as read by curl, wget and ftp, for credentials by host.
+
+use crate::Credentials;
+
+/// Returns the contents of the netrc file at `$NETRC`, or `~/.netrc`, if there is one.
+pub fn read_default() -> Option<String> {
+    let path = match std::env::var_os("NETRC") {
+        Some(path) => path.into(),
+        None => std::path::Path::new(&std::env::var_os("HOME")?).join(".netrc"),
+    };
+    std::fs::read_to_string(path).ok()
+}
+
+struct Entry<'a> {
+    /// `None` for the `default` entry
+    host: Option<&'a str>,
+    login: Option<&'a str>,
+    password: Option<&'a str>,
+}
+
+/// Parses netrc contents into credentials by host. The `default` entry is left out, since it
+/// would send its credentials to every other host.
+pub fn parse(contents: &str) -> Vec<(String, Credentials)> {
+    let mut tokens = Vec::new();
+    let mut in_macro = false;
+    for line in contents.lines() {
+        // Macro definitions run until an empty line
+        if in_macro {
+            in_macro = !line.trim().is_empty();
+            continue;
+        }
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        for token in line.split_whitespace() {
+            if token == "macdef" {
+                in_macro = true;
+                break;
+            }
+            tokens.push(token);
+        }
+    }
+
+    let mut entries = Vec::new();
+    let mut entry: Option<Entry> = None;
+    let mut tokens = tokens.into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            "machine" | "default" => {
+                entries.extend(entry.take());
+                let host = match token {
+                    "machine" => match tokens.next() {
+                        Some(host) => Some(host),
+                        None => break,
+                    },
+                    _ => None,
+                };
+                entry = Some(Entry {
+                    host,
+                    login: None,
+                    password: None,
+                });
+            }
+            "login" | "password" => {
+                let value = tokens.next();
+                match (&mut entry, token) {
+                    (Some(entry), "login") => entry.login = value,
+                    (Some(entry), _) => entry.password = value,
+                    (None, _) => {}
+                }
+            }
+            // Values of other keys, such as `account`, are skipped
+            _ => {
+                tokens.next();
+            }
+        }
+    }
+    entries.extend(entry);
+    entries
+        .into_iter()
+        .filter_map(|entry| {
+            let credentials = Credentials::Basic {
+                username: entry.login?.to_string(),
+                password: entry.password.map(str::to_string),
+            };
+            Some((entry.host?.to_string(), credentials))
+        })
+        .collect()
+}
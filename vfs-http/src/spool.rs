@@ -0,0 +1,69 @@
+//! Random access to a response body downloaded sequentially, for servers that don't serve byte
+//! ranges.
+
+use reqwest::blocking::Response;
+use std::{fs::File, io::Read, os::unix::fs::FileExt};
+
+/// Bodies up to this length are kept in memory rather than in a temporary file
+const MEMORY_LIMIT: u64 = 16 << 20;
+
+enum Storage {
+    Memory(Vec<u8>),
+    File(File),
+}
+
+/// A response whose body is downloaded as far as reads need, keeping what has been downloaded
+/// so that seeking back doesn't need another request.
+pub struct SpooledResponse {
+    response: Response,
+    storage: Storage,
+    /// How much of the body has been downloaded
+    len: u64,
+}
+
+impl SpooledResponse {
+    /// Spools `response`, a body of `size` bytes starting from offset 0.
+    pub fn new(response: Response, size: u64) -> std::io::Result<Self> {
+        let storage = if size <= MEMORY_LIMIT {
+            Storage::Memory(Vec::new())
+        } else {
+            Storage::File(tempfile::tempfile()?)
+        };
+        Ok(Self {
+            response,
+            storage,
+            len: 0,
+        })
+    }
+
+    /// Downloads the rest of the body, returning its length.
+    pub fn finish(&mut self) -> std::io::Result<u64> {
+        self.read_at(&mut [], u64::MAX)?;
+        Ok(self.len)
+    }
+
+    pub fn read_at(&mut self, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+        let end = offset.saturating_add(buf.len() as u64);
+        let mut chunk = [0; 16384];
+        while self.len < end {
+            let n = self.response.read(&mut chunk)?;
+            if n == 0 {
+                break;
+            }
+            match &mut self.storage {
+                Storage::Memory(data) => data.extend_from_slice(&chunk[..n]),
+                Storage::File(file) => file.write_all_at(&chunk[..n], self.len)?,
+            }
+            self.len += n as u64;
+        }
+        if offset >= self.len {
+            return Ok(0);
+        }
+        let n = buf.len().min((self.len - offset) as usize);
+        match &mut self.storage {
+            Storage::Memory(data) => buf[..n].copy_from_slice(&data[offset as usize..][..n]),
+            Storage::File(file) => file.read_exact_at(&mut buf[..n], offset)?,
+        }
+        Ok(n)
+    }
+}
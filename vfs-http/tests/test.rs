@@ -1,4 +1,181 @@
-use vfs::{Fs, StandaloneFs};
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+use vfs::{Fs, ReadDir, StandaloneFs};
+use vfs_http::{HeaderName, HeaderValue};
+
+const NGINX_INDEX: &str = r#"<html>
+<head><title>Index of /pub/</title></head>
+<body>
+<h1>Index of /pub/</h1><hr><pre><a href="../">../</a>
+<a href="docs/">docs/</a>                                              17-Oct-2024 10:20                   -
+<a href="a%20b.txt">a b.txt</a>                                            01-Jan-2024 00:00                  12
+</pre><hr></body>
+</html>
+"#;
+
+const APACHE_INDEX: &str = r#"<!DOCTYPE HTML PUBLIC "-//W3C//DTD HTML 3.2 Final//EN">
+<html>
+<head>
+<title>Index of /mirror</title>
+</head>
+<body>
+<h1>Index of /mirror</h1>
+<table>
+<tr><th valign="top"><img src="/icons/blank.gif" alt="[ICO]"></th><th><a href="?C=N;O=D">Name</a></th><th><a href="?C=M;O=A">Last modified</a></th><th><a href="?C=S;O=A">Size</a></th></tr>
+<tr><td valign="top"><img src="/icons/back.gif" alt="[PARENTDIR]"></td><td><a href="/">Parent Directory</a></td><td>&nbsp;</td><td align="right">  - </td></tr>
+<tr><td valign="top"><a href="big.iso"><img src="/icons/unknown.gif" alt="[   ]"></a></td><td><a href="big.iso">big.iso</a></td><td align="right">2023-05-06 07:08  </td><td align="right">1.5G</td></tr>
+<tr><td valign="top"><a href="sub/"><img src="/icons/folder.gif" alt="[DIR]"></a></td><td><a href="sub/">sub/</a></td><td align="right">2023-05-06 07:09  </td><td align="right">  - </td></tr>
+</table>
+</body></html>
+"#;
+
+/// GET requests of files from servers that ignore ranges
+static FULL_GETS: AtomicUsize = AtomicUsize::new(0);
+
+/// Requests by path
+static REQUESTS: Mutex<BTreeMap<String, usize>> = Mutex::new(BTreeMap::new());
+
+/// Serves canned responses on a local port, returning its address.
+fn serve() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut ranges = Vec::new();
+            let mut request_headers = Vec::new();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                let (name, value) = line.split_once(':').unwrap();
+                let (name, value) = (name.to_ascii_lowercase(), value.trim().to_string());
+                if let Some(bytes) = (name == "range").then(|| value.strip_prefix("bytes=")) {
+                    for range in bytes.unwrap().split(',') {
+                        let (start, end) = range.split_once('-').unwrap();
+                        ranges.push((
+                            start.parse::<usize>().unwrap(),
+                            end.parse::<usize>().unwrap(),
+                        ));
+                    }
+                }
+                request_headers.push((name, value));
+                line.clear();
+            }
+            let has_header =
+                |name: &str, value: &str| request_headers.contains(&(name.into(), value.into()));
+            let mut request_line = request_line.split(' ');
+            let (method, path) = (request_line.next().unwrap(), request_line.next().unwrap());
+            let count = {
+                let mut requests = REQUESTS.lock().unwrap();
+                let count = requests.entry(path.to_string()).or_default();
+                *count += 1;
+                *count
+            };
+            let digits = "0123456789".repeat(2000);
+            let changing_etag = format!("ETag: \"v{}\"\r\n", if count <= 2 { 1 } else { 2 });
+            let (mut status, headers, mut body) = match path {
+                "/pub" => ("301 Moved Permanently", "Location: /pub/\r\n", ""),
+                "/pub/" => ("200 OK", "Content-Type: text/html\r\n", NGINX_INDEX),
+                "/pub/a%20b.txt" => (
+                    "200 OK",
+                    "Content-Type: text/plain\r\nETag: \"abc\"\r\nLast-Modified: Wed, 21 Oct 2015 07:28:00 GMT\r\n",
+                    "hello world\n",
+                ),
+                "/moved.bin" => ("302 Found", "Location: /landing/\r\n", ""),
+                "/landing/" => ("200 OK", "Content-Type: text/plain\r\n", "welcome"),
+                "/mirror" => ("200 OK", "Content-Type: text/html\r\n", APACHE_INDEX),
+                "/no-head.bin" if method == "HEAD" => ("405 Method Not Allowed", "", ""),
+                "/no-head.bin" => ("200 OK", "", "0123456789"),
+                "/no-range.bin" => ("200 OK", "", digits.as_str()),
+                // user:pass
+                "/private" if has_header("authorization", "Basic dXNlcjpwYXNz") => {
+                    ("200 OK", "", "secret")
+                }
+                "/private" => ("401 Unauthorized", "", ""),
+                "/tokens" if has_header("x-token", "abc") && has_header("authorization", "Bearer t") => {
+                    ("200 OK", "", "ok")
+                }
+                "/ranges-none.bin" => ("200 OK", "Accept-Ranges: none\r\n", digits.as_str()),
+                "/flaky.bin" if count % 2 == 1 => {
+                    ("503 Service Unavailable", "Retry-After: 0\r\n", "")
+                }
+                "/flaky.bin" | "/truncated.bin" => ("200 OK", "", "0123456789"),
+                "/changing.bin" => ("200 OK", changing_etag.as_str(), digits.as_str()),
+                "/sequential.bin" | "/coalesced.bin" | "/scattered.bin" => {
+                    ("200 OK", "", digits.as_str())
+                }
+                "/cached.bin" | "/evicted.bin" => ("200 OK", "ETag: \"c\"\r\n", digits.as_str()),
+                "/chunked.bin" => ("200 OK", "Transfer-Encoding: chunked\r\n", digits.as_str()),
+                _ => ("404 Not Found", "", ""),
+            };
+            let truncated = path == "/truncated.bin" && count % 2 == 0;
+            // Ranges of another version of the file are answered with the whole file
+            let if_range_fails = request_headers
+                .iter()
+                .any(|(name, value)| name == "if-range" && !headers.contains(value.as_str()));
+            let ignores_ranges = matches!(path, "/no-range.bin" | "/ranges-none.bin");
+            if ignores_ranges && method == "GET" {
+                FULL_GETS.fetch_add(1, Ordering::SeqCst);
+            }
+            let mut headers = headers.to_string();
+            let multipart;
+            if status == "200 OK" && !ignores_ranges && !if_range_fails {
+                match ranges[..] {
+                    [] => {}
+                    [(start, end)] => {
+                        let end = end.min(body.len() - 1);
+                        headers +=
+                            &format!("Content-Range: bytes {start}-{end}/{}\r\n", body.len());
+                        status = "206 Partial Content";
+                        body = &body[start..=end];
+                    }
+                    _ => {
+                        let mut parts = String::new();
+                        for &(start, end) in &ranges {
+                            let end = end.min(body.len() - 1);
+                            parts += &format!(
+                                "--XYZ\r\nContent-Range: bytes {start}-{end}/{}\r\n\r\n{}\r\n",
+                                body.len(),
+                                &body[start..=end]
+                            );
+                        }
+                        multipart = parts + "--XYZ--\r\n";
+                        headers += "Content-Type: multipart/byteranges; boundary=XYZ\r\n";
+                        status = "206 Partial Content";
+                        body = &multipart;
+                    }
+                }
+            }
+            let chunked = headers.contains("Transfer-Encoding: chunked");
+            let content_length = match chunked {
+                true => String::new(),
+                false => format!("Content-Length: {}\r\n", body.len()),
+            };
+            write!(
+                stream,
+                "HTTP/1.1 {status}\r\n{headers}{content_length}Connection: close\r\n\r\n"
+            )
+            .unwrap();
+            if method != "HEAD" && !truncated {
+                match chunked {
+                    true => write!(stream, "{:x}\r\n{body}\r\n0\r\n\r\n", body.len()).unwrap(),
+                    false => stream.write_all(body.as_bytes()).unwrap(),
+                }
+            }
+        }
+    });
+    addr
+}
 
 #[test]
 fn test() {
@@ -8,10 +185,226 @@ fn test() {
         fs.metadata(url).unwrap(),
         vfs::Metadata {
             file_type: vfs::FileType::File,
-            len: 1256
+            len: 1256,
+            mode: None,
+            mtime: None,
         }
     );
     assert!(std::io::read_to_string(fs.open(url).unwrap())
         .unwrap()
         .contains("Example Domain"));
 }
+
+#[test]
+fn local_server() {
+    let addr = serve();
+    let mut fs = vfs_http::HttpFs::new();
+    let dir = |len| vfs::Metadata {
+        file_type: vfs::FileType::Dir,
+        len,
+        mode: None,
+        mtime: None,
+    };
+    assert_eq!(fs.metadata(&format!("{addr}/pub")).unwrap(), dir(0));
+    assert_eq!(fs.metadata(&format!("{addr}/mirror")).unwrap(), dir(0));
+    // Only the path asked for tells a directory, not where the server redirects to
+    assert_eq!(
+        fs.metadata(&format!("{addr}/moved.bin")).unwrap().file_type,
+        vfs::FileType::File
+    );
+    let last_modified = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1445412480));
+    assert_eq!(
+        fs.metadata(&format!("{addr}/pub/a%20b.txt")).unwrap(),
+        vfs::Metadata {
+            file_type: vfs::FileType::File,
+            len: 12,
+            mode: None,
+            mtime: last_modified,
+        }
+    );
+    assert_eq!(
+        fs.http_metadata(&format!("{addr}/pub/a%20b.txt")).unwrap(),
+        vfs_http::HttpMetadata {
+            len: Some(12),
+            etag: Some("\"abc\"".to_string()),
+            last_modified,
+            content_type: Some("text/plain".to_string()),
+            accept_ranges: false,
+        }
+    );
+    // Servers rejecting HEAD are asked for a single byte instead
+    let no_head = fs.http_metadata(&format!("{addr}/no-head.bin")).unwrap();
+    assert_eq!((no_head.len, no_head.accept_ranges), (Some(10), true));
+    assert_eq!(fs.metadata(&format!("{addr}/no-head.bin")).unwrap().len, 10);
+    assert!(fs.metadata(&format!("{addr}/missing")).is_err());
+
+    // Servers ignoring ranges send the whole body once, which later reads are served from
+    let digits = "0123456789".repeat(2000);
+    for (path, gets) in [("no-range.bin", 1), ("ranges-none.bin", 2)] {
+        let mut file = fs.open(&format!("{addr}/{path}")).unwrap();
+        let mut buf = [0; 10];
+        file.seek(SeekFrom::Start(15003)).unwrap();
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"3456789012");
+        file.rewind().unwrap();
+        assert_eq!(std::io::read_to_string(file).unwrap(), digits);
+        assert_eq!(FULL_GETS.load(Ordering::SeqCst), gets);
+    }
+
+    // Files without a length are downloaded whole to find it
+    assert_eq!(
+        fs.metadata(&format!("{addr}/chunked.bin")).unwrap().len,
+        digits.len() as u64
+    );
+    let mut file = fs.open(&format!("{addr}/chunked.bin")).unwrap();
+    file.seek(SeekFrom::End(-5)).unwrap();
+    assert_eq!(std::io::read_to_string(&mut file).unwrap(), "56789");
+    file.rewind().unwrap();
+    assert_eq!(std::io::read_to_string(file).unwrap(), digits);
+
+    let at = |secs| Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+    let entries = fs.read_dir(&format!("{addr}/pub/")).unwrap();
+    assert_eq!(
+        entries,
+        [
+            vfs::DirEntry {
+                name: b"a%20b.txt".to_vec(),
+                metadata: vfs::Metadata {
+                    file_type: vfs::FileType::File,
+                    len: 12,
+                    mode: None,
+                    mtime: at(1704067200),
+                },
+            },
+            vfs::DirEntry {
+                name: b"docs".to_vec(),
+                metadata: vfs::Metadata {
+                    mtime: at(1729160400),
+                    ..dir(0)
+                },
+            },
+        ]
+    );
+    let entries = fs.read_dir(&format!("{addr}/mirror")).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].name, b"big.iso");
+    assert_eq!(entries[0].metadata.len, 3 << 29);
+    assert_eq!(entries[0].metadata.mtime, at(1683356880));
+    assert_eq!(entries[1].name, b"sub");
+    assert_eq!(entries[1].metadata.file_type, vfs::FileType::Dir);
+    assert!(fs.read_dir(&format!("{addr}/pub/a%20b.txt")).is_err());
+
+    // Credentials go to the hosts they are for, never from the netrc `default` entry and only
+    // over plain HTTP when asked to
+    assert!(fs.metadata(&format!("{addr}/private")).is_err());
+    let netrc_fs = |netrc: &str, over_http: bool| {
+        vfs_http::HttpFs::builder()
+            .netrc(netrc)
+            .basic_auth_over_http(over_http)
+            .build()
+            .unwrap()
+    };
+    let mut fs = netrc_fs("machine 127.0.0.1 login user password pass", true);
+    assert_eq!(fs.metadata(&format!("{addr}/private")).unwrap().len, 6);
+    let mut fs = netrc_fs("machine 127.0.0.1 login user password pass", false);
+    assert!(fs.metadata(&format!("{addr}/private")).is_err());
+    let mut fs = netrc_fs("machine 127.0.0.1 login user password wrong", true);
+    assert!(fs.metadata(&format!("{addr}/private")).is_err());
+    let mut fs = netrc_fs(
+        "machine a.test login a password b\ndefault login user password pass",
+        true,
+    );
+    assert!(fs.metadata(&format!("{addr}/private")).is_err());
+    let mut fs = vfs_http::HttpFs::builder()
+        .header(
+            HeaderName::from_static("x-token"),
+            HeaderValue::from_static("abc"),
+        )
+        .bearer_auth("t")
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap();
+    assert_eq!(fs.metadata(&format!("{addr}/tokens")).unwrap().len, 2);
+
+    // Transient failures are retried, resuming reads where they failed
+    let retry = vfs_http::Retry {
+        max_retries: 2,
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(10),
+    };
+    let mut fs = vfs_http::HttpFs::builder()
+        .retry(retry.clone())
+        .build()
+        .unwrap();
+    for path in ["flaky.bin", "truncated.bin"] {
+        let file = fs.open(&format!("{addr}/{path}")).unwrap();
+        assert_eq!(std::io::read_to_string(file).unwrap(), "0123456789");
+    }
+    let requests = |path: &str| REQUESTS.lock().unwrap()[path];
+
+    // Sequential reads request twice as much each time, after a HEAD request when opening
+    let mut file = fs.open(&format!("{addr}/sequential.bin")).unwrap();
+    let mut buf = [0; 4096];
+    let mut read = Vec::new();
+    loop {
+        let n = file.read(&mut buf).unwrap();
+        if n == 0 {
+            break;
+        }
+        read.extend_from_slice(&buf[..n]);
+    }
+    assert_eq!(read, digits.as_bytes());
+    assert_eq!(requests("/sequential.bin"), 1 + 3);
+    // Adjacent missing blocks are requested together
+    let mut file = fs.open(&format!("{addr}/coalesced.bin")).unwrap();
+    let mut buf = [0; 12000];
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf[..], &digits.as_bytes()[..12000]);
+    assert_eq!(requests("/coalesced.bin"), 1 + 1);
+    // Scattered ranges are prefetched with a single request
+    let mut fs = vfs_http::HttpFs::builder()
+        .multi_range(true)
+        .build()
+        .unwrap();
+    let mut file = fs.open(&format!("{addr}/scattered.bin")).unwrap();
+    file.prefetch(&[15000..15010, 10..20, 16000..16010])
+        .unwrap();
+    let mut buf = [0; 10];
+    for offset in [10, 15000, 16000] {
+        file.seek(SeekFrom::Start(offset)).unwrap();
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"0123456789");
+    }
+    assert_eq!(requests("/scattered.bin"), 1 + 1);
+
+    // Blocks are kept on disk for later runs, until they are the least recently used ones past
+    // the size limit
+    let dir = tempfile::tempdir().unwrap();
+    let read_cached = |path: &str| {
+        let mut fs = vfs_http::HttpFs::builder()
+            .disk_cache(dir.path(), 30000)
+            .build()
+            .unwrap();
+        let file = fs.open(&format!("{addr}/{path}")).unwrap();
+        assert_eq!(std::io::read_to_string(file).unwrap(), digits);
+    };
+    read_cached("cached.bin");
+    read_cached("cached.bin");
+    assert_eq!(requests("/cached.bin"), 1 + 1 + 1);
+    read_cached("evicted.bin");
+    read_cached("cached.bin");
+    assert_eq!(requests("/cached.bin"), 1 + 1 + 1 + 1 + 1);
+    read_cached("cached.bin");
+    assert_eq!(requests("/cached.bin"), 1 + 1 + 1 + 1 + 1 + 1);
+
+    let mut fs = vfs_http::HttpFs::builder().retry(retry).build().unwrap();
+    let mut file = fs.open(&format!("{addr}/changing.bin")).unwrap();
+    let mut buf = [0; 10];
+    file.read_exact(&mut buf).unwrap();
+    file.seek(SeekFrom::Start(10000)).unwrap();
+    let err = file.read_exact(&mut buf).unwrap_err();
+    assert!(matches!(
+        err.get_ref().unwrap().downcast_ref(),
+        Some(vfs_http::Error::Changed)
+    ));
+}
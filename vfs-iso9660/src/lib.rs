@@ -0,0 +1,588 @@
+mod susp;
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom},
+    rc::Rc,
+    time::{Duration, SystemTime},
+};
+use vfs::{
+    bytes::{u16_at, u32_at},
+    io::read_at,
+    time::days_from_civil,
+};
+use window_read_seek::WindowReadSeek;
+
+const SECTOR_LEN: u64 = 2048;
+const FIRST_DESCRIPTOR_SECTOR: u64 = 16;
+const STANDARD_IDENTIFIER: &[u8; 5] = b"CD001";
+const DESCRIPTOR_PRIMARY: u8 = 1;
+const DESCRIPTOR_SUPPLEMENTARY: u8 = 2;
+const DESCRIPTOR_TERMINATOR: u8 = 255;
+const JOLIET_ESCAPES: [&[u8; 3]; 3] = [b"%/@", b"%/C", b"%/E"];
+const FLAG_DIRECTORY: u8 = 1 << 1;
+const FLAG_MULTI_EXTENT: u8 = 1 << 7;
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+const S_IFDIR: u32 = 0o040000;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed reading from backing IO")]
+    Io(#[from] std::io::Error),
+    #[error("primary volume descriptor not found")]
+    PrimaryVolumeDescriptorNotFound,
+    #[error("invalid directory record")]
+    InvalidDirectoryRecord,
+    #[error("entry not found")]
+    NotFound,
+    #[error("entry is not a regular file")]
+    NotAFile,
+    #[error("entry is not a symbolic link")]
+    NotASymLink,
+}
+
+/// Builds a timestamp from date and time fields and a GMT offset in 15 minute intervals.
+fn timestamp(fields: [i64; 6], gmt_offset: i8) -> Option<SystemTime> {
+    let [year, month, day, hour, minute, second] = fields;
+    // An all-zero date means the time is not recorded
+    if !(1..=12).contains(&month) || day == 0 {
+        return None;
+    }
+    let secs = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second
+        - gmt_offset as i64 * 15 * 60;
+    if secs >= 0 {
+        SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64))
+    } else {
+        SystemTime::UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs()))
+    }
+}
+
+/// Parses the 7 byte timestamp format of directory records.
+fn record_timestamp(buf: &[u8]) -> Option<SystemTime> {
+    let fields = [0, 1, 2, 3, 4, 5].map(|i| buf[i] as i64);
+    timestamp(
+        [
+            fields[0] + 1900,
+            fields[1],
+            fields[2],
+            fields[3],
+            fields[4],
+            fields[5],
+        ],
+        buf[6] as i8,
+    )
+}
+
+/// Parses the 17 byte ASCII timestamp format of volume descriptors, also allowed in Rock Ridge.
+fn long_timestamp(buf: &[u8]) -> Option<SystemTime> {
+    let digits = |range: std::ops::Range<usize>| -> Option<i64> {
+        std::str::from_utf8(&buf[range]).ok()?.parse().ok()
+    };
+    timestamp(
+        [
+            digits(0..4)?,
+            digits(4..6)?,
+            digits(6..8)?,
+            digits(8..10)?,
+            digits(10..12)?,
+            digits(12..14)?,
+        ],
+        buf[16] as i8,
+    )
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Naming {
+    Iso9660,
+    Joliet,
+    RockRidge {
+        /// Number of bytes to skip at the start of each system use area
+        skip: usize,
+    },
+}
+
+struct Volume {
+    naming: Naming,
+    root: Entry,
+    path_table_start: u64,
+    path_table_len: usize,
+}
+
+#[derive(Clone)]
+struct Entry {
+    file_type: vfs::FileType,
+    /// Image offset and length of each extent, which are only several for huge files
+    extents: Vec<(u64, u64)>,
+    mode: Option<u32>,
+    mtime: Option<SystemTime>,
+    /// Rock Ridge symlink target
+    link_target: Option<Vec<u8>>,
+}
+
+impl Entry {
+    fn len(&self) -> u64 {
+        self.extents.iter().map(|&(_, len)| len).sum()
+    }
+}
+
+/// A directory record as stored on disk, before names are resolved.
+struct Record<'a> {
+    extent: u64,
+    len: u64,
+    flags: u8,
+    mtime: Option<SystemTime>,
+    identifier: &'a [u8],
+    system_use: &'a [u8],
+}
+
+fn parse_record(buf: &[u8]) -> Result<Record<'_>, Error> {
+    if buf.len() < 34 {
+        return Err(Error::InvalidDirectoryRecord);
+    }
+    let identifier_len = buf[32] as usize;
+    let system_use_start = 33 + identifier_len + (identifier_len + 1) % 2;
+    if 33 + identifier_len > buf.len() {
+        return Err(Error::InvalidDirectoryRecord);
+    }
+    Ok(Record {
+        extent: u32_at(buf, 2) as u64 * SECTOR_LEN,
+        len: u32_at(buf, 10) as u64,
+        flags: buf[25],
+        mtime: record_timestamp(&buf[18..25]),
+        identifier: &buf[33..33 + identifier_len],
+        system_use: buf.get(system_use_start..).unwrap_or_default(),
+    })
+}
+
+/// Strips the `;1` version suffix and the trailing dot of names without an extension.
+fn strip_version(name: &[u8]) -> &[u8] {
+    let name = match name.iter().rposition(|&b| b == b';') {
+        Some(i) => &name[..i],
+        None => name,
+    };
+    name.strip_suffix(b".").unwrap_or(name)
+}
+
+fn joliet_name(identifier: &[u8]) -> Vec<u8> {
+    let units: Vec<u16> = identifier
+        .chunks_exact(2)
+        .map(|c| u16::from_be_bytes([c[0], c[1]]))
+        .collect();
+    let name = String::from_utf16_lossy(&units);
+    strip_version(name.as_bytes()).to_vec()
+}
+
+impl Naming {
+    fn name_matches(self, name: &[u8], component: &[u8]) -> bool {
+        match self {
+            // Plain ISO 9660 names are upper case, but tools generally present them in lower case
+            Naming::Iso9660 => name.eq_ignore_ascii_case(component),
+            Naming::Joliet | Naming::RockRidge { .. } => name == component,
+        }
+    }
+}
+
+/// The entries of a directory as `(name, entry)` pairs.
+type Dir = Rc<Vec<(Vec<u8>, Entry)>>;
+
+struct PathTableEntry {
+    name: Vec<u8>,
+    extent: u64,
+    /// Number of the parent directory, which is its 1-based index in the path table
+    parent: u16,
+}
+
+struct Image<R: Read + Seek> {
+    reader: Rc<RefCell<R>>,
+    volume: Volume,
+    /// Directories by extent offset
+    dirs: HashMap<u64, Dir>,
+    /// Loaded on first use
+    path_table: Option<Vec<PathTableEntry>>,
+}
+
+impl<R: Read + Seek> Image<R> {
+    fn open(reader: Rc<RefCell<R>>) -> Result<Self, Error> {
+        let mut primary = None;
+        let mut joliet = None;
+        for sector in FIRST_DESCRIPTOR_SECTOR.. {
+            let descriptor = read_at(&reader, sector * SECTOR_LEN, SECTOR_LEN as usize)?;
+            if &descriptor[1..6] != STANDARD_IDENTIFIER {
+                break;
+            }
+            match descriptor[0] {
+                DESCRIPTOR_PRIMARY if primary.is_none() => primary = Some(descriptor),
+                DESCRIPTOR_SUPPLEMENTARY
+                    if JOLIET_ESCAPES.iter().any(|e| descriptor[88..91] == **e) =>
+                {
+                    joliet = Some(descriptor)
+                }
+                DESCRIPTOR_TERMINATOR => break,
+                _ => {}
+            }
+        }
+        let primary = primary.ok_or(Error::PrimaryVolumeDescriptorNotFound)?;
+        let mut image = Self {
+            reader,
+            volume: Self::volume(&primary, Naming::Iso9660)?,
+            dirs: HashMap::new(),
+            path_table: None,
+        };
+        // Rock Ridge is announced by an SP entry in the root's `.` record, and has richer
+        // metadata than Joliet
+        let root = image.volume.root.extents[0].0;
+        let dot = read_at(&image.reader, root, SECTOR_LEN as usize)?;
+        let dot = parse_record(&dot[..dot[0] as usize])?;
+        if let Some(skip) = susp::sp_skip(dot.system_use) {
+            image.volume.naming = Naming::RockRidge { skip };
+            image.volume.root = image.dot_entry(root)?;
+        } else if let Some(joliet) = joliet {
+            image.volume = Self::volume(&joliet, Naming::Joliet)?;
+        }
+        Ok(image)
+    }
+
+    fn volume(descriptor: &[u8], naming: Naming) -> Result<Volume, Error> {
+        let root = parse_record(&descriptor[156..190])?;
+        Ok(Volume {
+            naming,
+            root: Entry {
+                file_type: vfs::FileType::Dir,
+                extents: vec![(root.extent, root.len)],
+                mode: None,
+                mtime: root.mtime,
+                link_target: None,
+            },
+            path_table_start: u32_at(descriptor, 140) as u64 * SECTOR_LEN,
+            path_table_len: u32_at(descriptor, 132) as usize,
+        })
+    }
+
+    /// Reads the `.` record of the directory at `extent`, which describes the directory itself.
+    fn dot_entry(&mut self, extent: u64) -> Result<Entry, Error> {
+        let sector = read_at(&self.reader, extent, SECTOR_LEN as usize)?;
+        let record = parse_record(&sector[..sector[0] as usize])?;
+        let (_, entry) = self.entry(&record, false)?;
+        Ok(entry)
+    }
+
+    /// Resolves the name and metadata of a record. Directories that Rock Ridge relocated get an
+    /// empty name, so that they are skipped. Child links are followed only if
+    /// `follow_child_link`, as the `.` record they lead to can't itself be relocated.
+    fn entry(
+        &mut self,
+        record: &Record,
+        follow_child_link: bool,
+    ) -> Result<(Vec<u8>, Entry), Error> {
+        let mut entry = Entry {
+            file_type: if record.flags & FLAG_DIRECTORY != 0 {
+                vfs::FileType::Dir
+            } else {
+                vfs::FileType::File
+            },
+            extents: vec![(record.extent, record.len)],
+            mode: None,
+            mtime: record.mtime,
+            link_target: None,
+        };
+        let name = match self.volume.naming {
+            Naming::Iso9660 => strip_version(record.identifier).to_ascii_lowercase(),
+            Naming::Joliet => joliet_name(record.identifier),
+            Naming::RockRidge { skip } => {
+                let rr = susp::RockRidge::read(&self.reader, record.system_use, skip)?;
+                if let Some(mode) = rr.mode {
+                    entry.mode = Some(mode & 0o7777);
+                    entry.file_type = match mode & S_IFMT {
+                        S_IFLNK => vfs::FileType::SymLink,
+                        S_IFDIR => vfs::FileType::Dir,
+                        _ => entry.file_type,
+                    };
+                }
+                if let Some(target) = rr.symlink {
+                    entry.file_type = vfs::FileType::SymLink;
+                    entry.link_target = Some(target);
+                }
+                if let Some(mtime) = rr.mtime {
+                    entry.mtime = Some(mtime);
+                }
+                if let Some(child) = rr.child_link {
+                    if !follow_child_link {
+                        return Err(Error::InvalidDirectoryRecord);
+                    }
+                    // A deep directory relocated elsewhere, leaving a file in its place
+                    let relocated = self.dot_entry(child)?;
+                    entry.file_type = vfs::FileType::Dir;
+                    entry.extents = relocated.extents;
+                }
+                if rr.relocated {
+                    entry.file_type = vfs::FileType::File;
+                    entry.extents.clear();
+                    return Ok((Vec::new(), entry));
+                }
+                rr.name
+                    .unwrap_or_else(|| strip_version(record.identifier).to_ascii_lowercase())
+            }
+        };
+        Ok((name, entry))
+    }
+
+    fn dir(&mut self, extent: u64, len: u64) -> Result<Dir, Error> {
+        if let Some(dir) = self.dirs.get(&extent) {
+            return Ok(dir.clone());
+        }
+        let data = read_at(&self.reader, extent, len as usize)?;
+        let mut entries: Vec<(Vec<u8>, Entry)> = Vec::new();
+        let mut multi_extent = false;
+        // Whether the last record was kept, rather than skipped for having no name
+        let mut kept = false;
+        let mut i = 0;
+        while i < data.len() {
+            let record_len = data[i] as usize;
+            // Records don't cross sector boundaries, so the rest of a sector can be padding
+            if record_len == 0 {
+                i = (i as u64 / SECTOR_LEN + 1) as usize * SECTOR_LEN as usize;
+                continue;
+            }
+            let record = data
+                .get(i..i + record_len)
+                .ok_or(Error::InvalidDirectoryRecord)?;
+            i += record_len;
+            let record = parse_record(record)?;
+            // Skip the `.` and `..` records
+            if record.identifier == [0] || record.identifier == [1] {
+                continue;
+            }
+            if multi_extent {
+                // Further extents of a file larger than 4 GiB
+                let (_, entry) = entries
+                    .last_mut()
+                    .filter(|_| kept)
+                    .ok_or(Error::InvalidDirectoryRecord)?;
+                entry.extents.push((record.extent, record.len));
+            } else {
+                let (name, entry) = self.entry(&record, true)?;
+                kept = !name.is_empty();
+                if kept {
+                    entries.push((name, entry));
+                }
+            }
+            multi_extent = record.flags & FLAG_MULTI_EXTENT != 0;
+        }
+        let entries = Rc::new(entries);
+        self.dirs.insert(extent, entries.clone());
+        Ok(entries)
+    }
+
+    fn path_table(&mut self) -> Result<&[PathTableEntry], Error> {
+        if self.path_table.is_none() {
+            let data = read_at(
+                &self.reader,
+                self.volume.path_table_start,
+                self.volume.path_table_len,
+            )?;
+            let mut entries = Vec::new();
+            let mut i = 0;
+            while i + 8 <= data.len() {
+                let name_len = data[i] as usize;
+                let extent = u32_at(&data, i + 2) as u64 * SECTOR_LEN;
+                let parent = u16_at(&data, i + 6);
+                let name = data
+                    .get(i + 8..i + 8 + name_len)
+                    .ok_or(Error::InvalidDirectoryRecord)?;
+                let name = match self.volume.naming {
+                    Naming::Joliet => joliet_name(name),
+                    _ => name.to_vec(),
+                };
+                entries.push(PathTableEntry {
+                    name,
+                    extent,
+                    parent,
+                });
+                i += 8 + name_len + name_len % 2;
+            }
+            self.path_table = Some(entries);
+        }
+        Ok(self.path_table.as_ref().unwrap())
+    }
+
+    /// Finds the extent of the directory containing the last component of a path. The path
+    /// table is used when it holds the same names as the directory records, otherwise
+    /// directories are walked from the root.
+    fn parent_extent(&mut self, components: &[&[u8]]) -> Result<(u64, u64), Error> {
+        let naming = self.volume.naming;
+        if let Naming::RockRidge { .. } = naming {
+            let mut extent = self.volume.root.extents[0];
+            for component in components {
+                let dir = self.dir(extent.0, extent.1)?;
+                let (_, entry) = dir
+                    .iter()
+                    .find(|(name, entry)| {
+                        naming.name_matches(name, component)
+                            && entry.file_type == vfs::FileType::Dir
+                    })
+                    .ok_or(Error::NotFound)?;
+                extent = entry.extents[0];
+            }
+            return Ok(extent);
+        }
+        let mut number = 1;
+        let mut extent = self.volume.root.extents[0].0;
+        for component in components {
+            let table = self.path_table()?;
+            let index = table
+                .iter()
+                .position(|e| {
+                    e.parent as usize == number && naming.name_matches(&e.name, component)
+                })
+                .ok_or(Error::NotFound)?;
+            number = index + 1;
+            extent = table[index].extent;
+        }
+        let len = self.dot_entry(extent)?.extents[0].1;
+        Ok((extent, len))
+    }
+
+    fn lookup(&mut self, path: &[u8]) -> Result<Entry, Error> {
+        let components = vfs::path::components(path);
+        let Some((last, parents)) = components.split_last() else {
+            return Ok(self.volume.root.clone());
+        };
+        let (extent, len) = self.parent_extent(parents)?;
+        let naming = self.volume.naming;
+        let dir = self.dir(extent, len)?;
+        dir.iter()
+            .find(|(name, _)| naming.name_matches(name, last))
+            .map(|(_, entry)| entry.clone())
+            .ok_or(Error::NotFound)
+    }
+}
+
+/// Reads a file made of several extents, each up to 4 GiB.
+struct MultiExtentFile<R: Read + Seek> {
+    extents: Vec<WindowReadSeek<R>>,
+    len: u64,
+    offset: u64,
+}
+
+impl<R: Read + Seek> Read for MultiExtentFile<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut start = 0;
+        for extent in &mut self.extents {
+            if self.offset < start + extent.len() {
+                extent.seek(SeekFrom::Start(self.offset - start))?;
+                let n = extent.read(buf)?;
+                self.offset += n as u64;
+                return Ok(n);
+            }
+            start += extent.len();
+        }
+        Ok(0)
+    }
+}
+
+impl<R: Read + Seek> Seek for MultiExtentFile<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.offset = vfs::io::seek_position(pos, self.offset, self.len)?;
+        Ok(self.offset)
+    }
+}
+
+enum FileInner<R: Read + Seek> {
+    Contiguous(WindowReadSeek<R>),
+    MultiExtent(MultiExtentFile<R>),
+}
+
+pub struct File<R: Read + Seek>(FileInner<R>);
+
+impl<R: Read + Seek> Read for File<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match &mut self.0 {
+            FileInner::Contiguous(x) => x.read(buf),
+            FileInner::MultiExtent(x) => x.read(buf),
+        }
+    }
+}
+
+impl<R: Read + Seek> Seek for File<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match &mut self.0 {
+            FileInner::Contiguous(x) => x.seek(pos),
+            FileInner::MultiExtent(x) => x.seek(pos),
+        }
+    }
+}
+
+/// An ISO 9660 image, using Rock Ridge or Joliet names when present. Directories are looked up
+/// through the path table where possible and files are views directly into the backing IO.
+pub struct Iso9660Fs<R: Read + Seek> {
+    reader: Rc<RefCell<R>>,
+    image: Option<Image<R>>,
+}
+
+impl<R: Read + Seek> Iso9660Fs<R> {
+    fn entry(&mut self, path: &[u8]) -> Result<Entry, Error> {
+        if self.image.is_none() {
+            self.image = Some(Image::open(self.reader.clone())?);
+        }
+        self.image.as_mut().unwrap().lookup(path)
+    }
+
+    /// Returns the target of a Rock Ridge symbolic link.
+    pub fn read_link(&mut self, path: &[u8]) -> Result<Vec<u8>, Error> {
+        self.entry(path)?.link_target.ok_or(Error::NotASymLink)
+    }
+}
+
+impl<R: Read + Seek> vfs::IoBackedFs<R> for Iso9660Fs<R> {
+    type Password = ();
+
+    fn from_io(io: R, _password: Self::Password) -> Self {
+        Self {
+            reader: Rc::new(RefCell::new(io)),
+            image: None,
+        }
+    }
+}
+
+impl<R: Read + Seek> vfs::Fs for Iso9660Fs<R> {
+    type Path = [u8];
+    type Error = Error;
+    type File = File<R>;
+
+    fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Self::Error> {
+        let entry = self.entry(path)?;
+        Ok(vfs::Metadata {
+            file_type: entry.file_type,
+            len: match entry.file_type {
+                vfs::FileType::File => entry.len(),
+                _ => 0,
+            },
+            mode: entry.mode,
+            mtime: entry.mtime,
+        })
+    }
+
+    fn open(&mut self, path: &[u8]) -> Result<Self::File, Self::Error> {
+        let entry = self.entry(path)?;
+        if entry.file_type != vfs::FileType::File {
+            return Err(Error::NotAFile);
+        }
+        let len = entry.len();
+        let mut extents: Vec<_> = entry
+            .extents
+            .into_iter()
+            .map(|(start, len)| WindowReadSeek::new(self.reader.clone(), start, len))
+            .collect();
+        Ok(File(if extents.len() == 1 {
+            FileInner::Contiguous(extents.pop().unwrap())
+        } else {
+            FileInner::MultiExtent(MultiExtentFile {
+                extents,
+                len,
+                offset: 0,
+            })
+        }))
+    }
+}
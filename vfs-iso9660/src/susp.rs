@@ -0,0 +1,135 @@
+//! System Use Sharing Protocol entries, which carry the Rock Ridge extensions.
+
+use crate::{long_timestamp, read_at, record_timestamp, u32_at, Error, SECTOR_LEN};
+use std::{
+    cell::RefCell,
+    io::{Read, Seek},
+    time::SystemTime,
+};
+
+const NM_CURRENT: u8 = 1 << 1;
+const NM_PARENT: u8 = 1 << 2;
+const SL_CONTINUE: u8 = 1 << 0;
+const SL_CURRENT: u8 = 1 << 1;
+const SL_PARENT: u8 = 1 << 2;
+const SL_ROOT: u8 = 1 << 3;
+const TF_MODIFY: u8 = 1 << 1;
+const TF_LONG_FORM: u8 = 1 << 7;
+/// Upper bound on continuation areas per record, so that loops in corrupt images terminate
+const MAX_CONTINUATIONS: usize = 64;
+
+/// Calls `f` with the signature and the whole of each entry in a system use area.
+fn entries(area: &[u8], mut f: impl FnMut(&[u8; 2], &[u8])) {
+    let mut i = 0;
+    while i + 4 <= area.len() {
+        let len = area[i + 2] as usize;
+        let Some(entry) = area.get(i..i + len).filter(|_| len >= 4) else {
+            break;
+        };
+        let signature = [entry[0], entry[1]];
+        if &signature == b"ST" {
+            break;
+        }
+        f(&signature, entry);
+        i += len;
+    }
+}
+
+/// Returns the number of bytes to skip in each system use area if the area starts with an SP
+/// entry, which marks the use of SUSP on the volume.
+pub(crate) fn sp_skip(system_use: &[u8]) -> Option<usize> {
+    match system_use {
+        [b'S', b'P', 7, _, 0xbe, 0xef, skip, ..] => Some(*skip as usize),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct RockRidge {
+    pub(crate) name: Option<Vec<u8>>,
+    pub(crate) mode: Option<u32>,
+    pub(crate) mtime: Option<SystemTime>,
+    /// Target of a symbolic link
+    pub(crate) symlink: Option<Vec<u8>>,
+    /// Image offset of a directory that was relocated from this record's position
+    pub(crate) child_link: Option<u64>,
+    /// Whether this is a relocated directory, which should be hidden from its actual parent
+    pub(crate) relocated: bool,
+}
+
+impl RockRidge {
+    /// Collects the Rock Ridge entries of a record, following continuation areas.
+    pub(crate) fn read<R: Read + Seek>(
+        reader: &RefCell<R>,
+        system_use: &[u8],
+        skip: usize,
+    ) -> Result<Self, Error> {
+        let mut rr = Self::default();
+        let mut area = system_use.get(skip..).unwrap_or_default().to_vec();
+        // Whether the last symlink component continues in the next component record
+        let mut continued = false;
+        for _ in 0..MAX_CONTINUATIONS {
+            let mut continuation = None;
+            entries(&area, |signature, entry| match signature {
+                b"CE" if entry.len() >= 28 => {
+                    let start = u32_at(entry, 4) as u64 * SECTOR_LEN + u32_at(entry, 12) as u64;
+                    continuation = Some((start, u32_at(entry, 20) as usize));
+                }
+                b"PX" if entry.len() >= 12 => rr.mode = Some(u32_at(entry, 4)),
+                // Long names are split across several NM entries
+                b"NM" if entry.len() >= 5 && entry[4] & (NM_CURRENT | NM_PARENT) == 0 => {
+                    rr.name.get_or_insert_with(Vec::new).extend(&entry[5..]);
+                }
+                b"SL" if entry.len() >= 5 => {
+                    let target = rr.symlink.get_or_insert_with(Vec::new);
+                    let mut records = &entry[5..];
+                    while let [flags, len, rest @ ..] = records {
+                        let Some(content) = rest.get(..*len as usize) else {
+                            break;
+                        };
+                        records = &rest[*len as usize..];
+                        if !continued && !target.is_empty() && !target.ends_with(b"/") {
+                            target.push(b'/');
+                        }
+                        if flags & SL_ROOT != 0 {
+                            if !target.ends_with(b"/") {
+                                target.push(b'/');
+                            }
+                        } else if flags & SL_PARENT != 0 {
+                            target.extend(b"..");
+                        } else if flags & SL_CURRENT != 0 {
+                            target.push(b'.');
+                        } else {
+                            target.extend(content);
+                        }
+                        continued = flags & SL_CONTINUE != 0;
+                    }
+                }
+                b"TF" if entry.len() >= 5 && entry[4] & TF_MODIFY != 0 => {
+                    let flags = entry[4];
+                    let long_form = flags & TF_LONG_FORM != 0;
+                    let len = if long_form { 17 } else { 7 };
+                    // The creation time comes first if present
+                    let start = 5 + (flags & 1) as usize * len;
+                    if let Some(timestamp) = entry.get(start..start + len) {
+                        rr.mtime = if long_form {
+                            long_timestamp(timestamp)
+                        } else {
+                            record_timestamp(timestamp)
+                        };
+                    }
+                }
+                b"CL" if entry.len() >= 12 => {
+                    rr.child_link = Some(u32_at(entry, 4) as u64 * SECTOR_LEN)
+                }
+                b"RE" => rr.relocated = true,
+                _ => {}
+            });
+            let Some((start, len)) = continuation else {
+                break;
+            };
+            area = read_at(reader, start, len)?;
+        }
+        Ok(rr)
+    }
+}
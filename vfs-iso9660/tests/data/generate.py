@@ -0,0 +1,186 @@
+# Writes the images of the vfs-iso9660 tests next to this script.
+import os, struct
+
+S = 2048
+
+def both16(n): return struct.pack("<H", n) + struct.pack(">H", n)
+def both32(n): return struct.pack("<I", n) + struct.pack(">I", n)
+
+# 2021-03-04 05:06:07 at GMT+1, which is 04:06:07 UTC
+STAMP = bytes([121, 3, 4, 5, 6, 7, 4])
+
+def record(name, extent, length, flags=0, system_use=b""):
+    body = bytes([0]) + both32(extent) + both32(length) + STAMP + bytes([flags, 0, 0]) + both16(1)
+    body += bytes([len(name)]) + name + (b"\0" if len(name) % 2 == 0 else b"") + system_use
+    body = bytes([len(body) + 1]) + body
+    if len(body) % 2:
+        body += b"\0"
+    return bytes([len(body)]) + body[1:]
+
+def dir_data(records):
+    out = bytearray()
+    for r in records:
+        if len(out) % S + len(r) > S:
+            out += bytes(-len(out) % S)
+        out += r
+    return bytes(out)
+
+def path_table(entries, big):
+    fmt32, fmt16 = (">I", ">H") if big else ("<I", "<H")
+    out = bytearray()
+    for name, extent, parent in entries:
+        out += bytes([len(name), 0]) + struct.pack(fmt32, extent) + struct.pack(fmt16, parent) + name
+        if len(name) % 2:
+            out += b"\0"
+    return bytes(out)
+
+def descriptor(kind, root, sectors, pt_len, pt_l, pt_m, escape=b"", volume_id=b"TEST"):
+    d = bytearray(S)
+    d[0] = kind
+    d[1:6] = b"CD001"
+    d[6] = 1
+    d[40:72] = volume_id.ljust(32, b" ")[:32]
+    d[80:88] = both32(sectors)
+    d[88:88 + len(escape)] = escape
+    d[120:124] = both16(1)
+    d[124:128] = both16(1)
+    d[128:132] = both16(S)
+    d[132:140] = both32(pt_len)
+    d[140:144] = struct.pack("<I", pt_l)
+    d[148:152] = struct.pack(">I", pt_m)
+    d[156:190] = root
+    d[881] = 1
+    return bytes(d)
+
+def terminator():
+    d = bytearray(S)
+    d[0] = 255
+    d[1:6] = b"CD001"
+    d[6] = 1
+    return bytes(d)
+
+def image(sectors):
+    out = bytearray(max(sectors) * S + S)
+    for n, data in sectors.items():
+        out[n * S:n * S + len(data)] = data
+    # Trim trailing padding of the last sector's file data to a whole sector
+    return bytes(out)
+
+def ucs2(s): return s.encode("utf-16-be")
+
+README = b"Hello world! (readme)\n"
+NESTED = b"Hello world! (nested)\n"
+NOEXT = b"no extension\n"
+MULTI = bytes(i % 251 for i in range(S + 1000))
+
+def joliet_image():
+    # 16 PVD, 17 SVD, 18 terminator, 19-22 path tables, 23-24 ISO dirs, 25-26 Joliet dirs,
+    # 27+ file data
+    readme, nested, noext, multi = 27, 28, 29, 30
+    def dirs(joliet):
+        root, sub = (25, 26) if joliet else (23, 24)
+        name = (lambda s: ucs2(s)) if joliet else (lambda s: s.encode())
+        root_records = [
+            record(b"\0", root, S, 2),
+            record(b"\1", root, S, 2),
+            # Multi-extent files repeat the record for each extent
+            record(name("Multi Extent.bin;1" if joliet else "MULTI.BIN;1"), multi, S, 0x80),
+            record(name("Multi Extent.bin;1" if joliet else "MULTI.BIN;1"), multi + 1, 1000),
+            record(name("noext;1" if joliet else "NOEXT.;1"), noext, len(NOEXT)),
+            record(name("Read Me.txt;1" if joliet else "README.TXT;1"), readme, len(README)),
+            record(name("Sub Directory" if joliet else "SUBDIR"), sub, S, 2),
+        ]
+        sub_records = [
+            record(b"\0", sub, S, 2),
+            record(b"\1", root, S, 2),
+            record(name("nested file.txt;1" if joliet else "NESTED.TXT;1"), nested, len(NESTED)),
+        ]
+        table = [(b"\0", root, 1), (name("Sub Directory" if joliet else "SUBDIR"), sub, 1)]
+        return root, dir_data(root_records), sub, dir_data(sub_records), table
+    sectors = {}
+    for joliet, (pt_l, pt_m) in [(False, (19, 20)), (True, (21, 22))]:
+        root, root_data, sub, sub_data, table = dirs(joliet)
+        sectors[root], sectors[sub] = root_data, sub_data
+        sectors[pt_l], sectors[pt_m] = path_table(table, False), path_table(table, True)
+        desc = descriptor(
+            2 if joliet else 1, record(b"\0", root, S, 2), 32, len(sectors[pt_l]), pt_l, pt_m,
+            b"%/E" if joliet else b"",
+        )
+        sectors[17 if joliet else 16] = desc
+    sectors[18] = terminator()
+    sectors[readme], sectors[nested], sectors[noext] = README, NESTED, NOEXT
+    sectors[multi] = MULTI
+    sectors[31] = b""
+    return image(sectors)
+
+def su(sig, data, version=1):
+    return sig + bytes([len(data) + 4, version]) + data
+
+SERIALS = iter(range(1, 100))
+
+def px(mode, nlink=1):
+    return su(b"PX", both32(mode) + both32(nlink) + both32(0) + both32(0) + both32(next(SERIALS)))
+
+def nm(name, flags=0):
+    return su(b"NM", bytes([flags]) + name)
+
+def tf_short(stamp):
+    return su(b"TF", bytes([1 << 1]) + stamp)
+
+def tf_long(text, offset):
+    return su(b"TF", bytes([1 << 1 | 1 << 7]) + text + bytes([offset]))
+
+def sl(components, flags=0):
+    body = bytes([flags])
+    for cflags, content in components:
+        body += bytes([cflags, len(content)]) + content
+    return su(b"SL", body)
+
+def ce(sector, offset, length):
+    return su(b"CE", both32(sector) + both32(offset) + both32(length))
+
+TARGET = b"Hello world! (target)\n"
+MIXED = b"Hello world! (mixed case)\n"
+
+def rock_ridge_image():
+    # 16 PVD, 17 terminator, 18-19 path tables, 20 root, 21 dir, 22 continuation area, 23+ data
+    root, sub, cont, mixed, target = 20, 21, 22, 23, 24
+    sp = su(b"SP", b"\xbe\xef\0")
+    # 2020-01-02 03:04:05 UTC
+    mtime = bytes([120, 1, 2, 3, 4, 5, 0])
+    root_records = [
+        record(b"\0", root, S, 2, sp + px(0o40755, 3) + tf_short(mtime)),
+        record(b"\1", root, S, 2, px(0o40755, 3)),
+        record(b"ABS.;1", 0, 0, 0, px(0o120777) + nm(b"absolute")
+               + sl([(8, b""), (0, b"etc"), (0, b"hosts")])),
+        record(b"DIR", sub, S, 2, px(0o40750, 2) + nm(b"dir") + tf_short(mtime)),
+        record(b"LINK.;1", 0, 0, 0, px(0o120777) + nm(b"relative")
+               + sl([(4, b""), (0, b"dir"), (1, b"tar"), (0, b"get.txt")])),
+        record(b"MIXED.TXT;1", mixed, len(MIXED), 0,
+               px(0o100640) + nm(b"Mixed ", 1) + nm(b"Case.txt") + tf_short(mtime)),
+    ]
+    # The name and a long form timestamp of 2022-06-07 08:09:10 at GMT-2 are in a continuation
+    continuation = nm(b"target.txt") + tf_long(b"2022060708091000", 0xf8)
+    sub_records = [
+        record(b"\0", sub, S, 2, px(0o40750, 2)),
+        record(b"\1", root, S, 2, px(0o40755, 3)),
+        record(b"TARGET.TXT;1", target, len(TARGET), 0,
+               px(0o100444) + ce(cont, 0, len(continuation))),
+    ]
+    table = [(b"\0", root, 1), (b"DIR", sub, 1)]
+    sectors = {
+        16: descriptor(1, record(b"\0", root, S, 2), 25, len(path_table(table, False)), 18, 19),
+        17: terminator(),
+        18: path_table(table, False),
+        19: path_table(table, True),
+        root: dir_data(root_records),
+        sub: dir_data(sub_records),
+        cont: continuation,
+        mixed: MIXED,
+        target: TARGET,
+    }
+    return image(sectors)
+
+for name, build in [("joliet.iso", joliet_image), ("rock-ridge.iso", rock_ridge_image)]:
+    with open(os.path.join(os.path.dirname(os.path.abspath(__file__)), name), "wb") as f:
+        f.write(build())
@@ -0,0 +1,203 @@
+use std::{
+    io::{Read, Seek, SeekFrom},
+    time::{Duration, SystemTime},
+};
+use vfs::{Fs, IoBackedFs};
+
+fn at(secs: u64) -> Option<SystemTime> {
+    Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+fn read(fs: &mut vfs_iso9660::Iso9660Fs<std::io::Cursor<Vec<u8>>>, path: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    fs.open(path.as_bytes())
+        .unwrap()
+        .read_to_end(&mut data)
+        .unwrap();
+    data
+}
+
+#[test]
+fn test() {
+    // Images written by tests/data/generate.py. The first has ISO 9660 and Joliet directory
+    // trees with the same files, including one stored as two extents, all recorded at
+    // 2021-03-04 05:06:07 GMT+1
+    let joliet = std::fs::read("tests/data/joliet.iso").unwrap();
+    let multi: Vec<u8> = (0..3048).map(|i| (i % 251) as u8).collect();
+    let mut fs = vfs_iso9660::Iso9660Fs::from_io(std::io::Cursor::new(joliet.clone()), ());
+    assert_eq!(
+        fs.metadata(b"Read Me.txt").unwrap(),
+        vfs::Metadata {
+            file_type: vfs::FileType::File,
+            len: 22,
+            mode: None,
+            mtime: at(1_614_830_767),
+        }
+    );
+    assert_eq!(
+        fs.metadata(b"Sub Directory").unwrap().file_type,
+        vfs::FileType::Dir
+    );
+    assert!(matches!(
+        fs.metadata(b"README.TXT"),
+        Err(vfs_iso9660::Error::NotFound)
+    ));
+    assert_eq!(read(&mut fs, "Read Me.txt"), b"Hello world! (readme)\n");
+    assert_eq!(
+        read(&mut fs, "Sub Directory/nested file.txt"),
+        b"Hello world! (nested)\n"
+    );
+    assert_eq!(read(&mut fs, "noext"), b"no extension\n");
+    assert_eq!(fs.metadata(b"Multi Extent.bin").unwrap().len, 3048);
+    assert_eq!(read(&mut fs, "Multi Extent.bin"), multi);
+    let mut file = fs.open(b"Multi Extent.bin").unwrap();
+    let mut buf = [0; 16];
+    file.seek(SeekFrom::Start(2040)).unwrap();
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, multi[2040..2056]);
+    assert!(matches!(
+        fs.open(b"Sub Directory"),
+        Err(vfs_iso9660::Error::NotAFile)
+    ));
+
+    // Without the Joliet descriptor, the plain ISO 9660 names are used
+    let mut plain = joliet;
+    plain[17 * 2048] = 3;
+    let mut fs = vfs_iso9660::Iso9660Fs::from_io(std::io::Cursor::new(plain), ());
+    assert_eq!(read(&mut fs, "readme.txt"), b"Hello world! (readme)\n");
+    assert_eq!(read(&mut fs, "README.TXT"), b"Hello world! (readme)\n");
+    assert_eq!(
+        read(&mut fs, "subdir/nested.txt"),
+        b"Hello world! (nested)\n"
+    );
+    assert_eq!(read(&mut fs, "noext"), b"no extension\n");
+    assert_eq!(read(&mut fs, "multi.bin"), multi);
+    assert!(matches!(
+        fs.metadata(b"Read Me.txt"),
+        Err(vfs_iso9660::Error::NotFound)
+    ));
+
+    // The second uses Rock Ridge, with a name split over two NM entries, one entry's NM and TF
+    // in a continuation area and symlinks whose components span several records
+    let rock_ridge = std::fs::read("tests/data/rock-ridge.iso").unwrap();
+    let mut fs = vfs_iso9660::Iso9660Fs::from_io(std::io::Cursor::new(rock_ridge), ());
+    assert_eq!(
+        fs.metadata(b"").unwrap(),
+        vfs::Metadata {
+            file_type: vfs::FileType::Dir,
+            len: 0,
+            mode: Some(0o755),
+            mtime: at(1_577_934_245),
+        }
+    );
+    assert_eq!(
+        fs.metadata(b"Mixed Case.txt").unwrap(),
+        vfs::Metadata {
+            file_type: vfs::FileType::File,
+            len: 26,
+            mode: Some(0o640),
+            mtime: at(1_577_934_245),
+        }
+    );
+    assert_eq!(
+        fs.metadata(b"dir").unwrap(),
+        vfs::Metadata {
+            file_type: vfs::FileType::Dir,
+            len: 0,
+            mode: Some(0o750),
+            mtime: at(1_577_934_245),
+        }
+    );
+    assert_eq!(
+        fs.metadata(b"dir/target.txt").unwrap(),
+        vfs::Metadata {
+            file_type: vfs::FileType::File,
+            len: 22,
+            mode: Some(0o444),
+            mtime: at(1_654_596_550),
+        }
+    );
+    assert_eq!(
+        fs.metadata(b"relative").unwrap(),
+        vfs::Metadata {
+            file_type: vfs::FileType::SymLink,
+            len: 0,
+            mode: Some(0o777),
+            mtime: at(1_614_830_767),
+        }
+    );
+    assert_eq!(
+        read(&mut fs, "Mixed Case.txt"),
+        b"Hello world! (mixed case)\n"
+    );
+    assert_eq!(read(&mut fs, "dir/target.txt"), b"Hello world! (target)\n");
+    assert!(matches!(
+        fs.metadata(b"mixed.txt"),
+        Err(vfs_iso9660::Error::NotFound)
+    ));
+    assert_eq!(fs.read_link(b"relative").unwrap(), b"../dir/target.txt");
+    assert_eq!(fs.read_link(b"absolute").unwrap(), b"/etc/hosts");
+    assert!(matches!(
+        fs.read_link(b"dir"),
+        Err(vfs_iso9660::Error::NotASymLink)
+    ));
+    assert!(matches!(
+        fs.open(b"absolute"),
+        Err(vfs_iso9660::Error::NotAFile)
+    ));
+
+    // A record continuing a file whose first record was skipped for its empty name, in the plain
+    // ISO 9660 tree, where the first file is the one stored as two extents
+    let mut image = std::fs::read("tests/data/joliet.iso").unwrap();
+    image[17 * 2048] = 3;
+    let root = root_extent(&image);
+    let sector = &mut image[root..root + 2048];
+    // After the `.` and `..` records
+    let first = sector[0] as usize + sector[sector[0] as usize] as usize;
+    assert_ne!(sector[first + 25] & 0x80, 0);
+    sector[first + 32] = 0;
+    let mut fs = vfs_iso9660::Iso9660Fs::from_io(std::io::Cursor::new(image), ());
+    assert!(matches!(
+        fs.metadata(b"noext"),
+        Err(vfs_iso9660::Error::InvalidDirectoryRecord)
+    ));
+
+    // Child links from `dir` to the root, whose `.` record links back to itself
+    let mut image = std::fs::read("tests/data/rock-ridge.iso").unwrap();
+    let root = root_extent(&image);
+    for identifier in [&b"\0"[..], b"DIR"] {
+        let record = find_record(&mut image[root..root + 2048], identifier);
+        let px = record.windows(2).position(|w| w == b"PX").unwrap();
+        // The 44-byte PX entry becomes a CL entry and a PD padding entry
+        let mut cl = vec![b'C', b'L', 12, 1];
+        let extent = (root / 2048) as u32;
+        cl.extend(extent.to_le_bytes());
+        cl.extend(extent.to_be_bytes());
+        cl.extend([b'P', b'D', 32, 1]);
+        record[px..px + 16].copy_from_slice(&cl);
+    }
+    let mut fs = vfs_iso9660::Iso9660Fs::from_io(std::io::Cursor::new(image), ());
+    assert!(matches!(
+        fs.metadata(b"dir"),
+        Err(vfs_iso9660::Error::InvalidDirectoryRecord)
+    ));
+}
+
+/// Returns the image offset of the root directory named by the primary volume descriptor.
+fn root_extent(image: &[u8]) -> usize {
+    let root = &image[16 * 2048 + 156..];
+    u32::from_le_bytes(root[2..6].try_into().unwrap()) as usize * 2048
+}
+
+/// Returns the record whose identifier starts with `identifier` in a directory sector.
+fn find_record<'a>(sector: &'a mut [u8], identifier: &[u8]) -> &'a mut [u8] {
+    let mut i = 0;
+    loop {
+        let len = sector[i] as usize;
+        let record_identifier = &sector[i + 33..i + 33 + sector[i + 32] as usize];
+        if record_identifier.starts_with(identifier) {
+            return &mut sector[i..i + len];
+        }
+        i += len;
+    }
+}
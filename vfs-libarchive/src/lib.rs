@@ -1,15 +1,17 @@
 use cache_read_seek::CachedReadSeek;
 use libarchive_sys::{
     archive, archive_entry, archive_entry_filetype, archive_entry_pathname, archive_entry_size,
-    archive_errno, archive_error_string, archive_read_add_passphrase, archive_read_close,
-    archive_read_data_block, archive_read_free, archive_read_new, archive_read_next_header,
-    archive_read_open2, archive_read_set_seek_callback, archive_read_support_filter_all,
+    archive_errno, archive_error_string, archive_read_add_passphrase,
+    archive_read_append_callback_data, archive_read_close, archive_read_data_block,
+    archive_read_free, archive_read_new, archive_read_next_header, archive_read_open1,
+    archive_read_set_read_callback, archive_read_set_seek_callback, archive_read_set_skip_callback,
+    archive_read_set_switch_callback, archive_read_support_filter_all,
     archive_read_support_format_all, archive_set_error, ARCHIVE_EOF, ARCHIVE_OK, SEEK_CUR,
     SEEK_END, SEEK_SET,
 };
 use std::{
     cell::RefCell,
-    ffi::{c_void, CStr, CString},
+    ffi::{c_int, c_void, CStr, CString},
     io::{Read, Seek, SeekFrom},
     ptr::{null, null_mut},
     rc::Rc,
@@ -23,7 +25,8 @@ struct ClientData<R: Read + Seek> {
 
 struct InnerFs<R: Read + Seek> {
     a: *mut archive,
-    client_data: *mut ClientData<R>,
+    /// One per volume, in order
+    client_data: Vec<*mut ClientData<R>>,
     password: Option<CString>,
 }
 
@@ -56,8 +59,15 @@ unsafe extern "C" fn skip_callback<R: Read + Seek>(
     request: i64,
 ) -> i64 {
     let client_data = &mut *(client_data as *mut ClientData<R>);
-    client_data.reader.seek(SeekFrom::Current(request)).unwrap();
-    request
+    // Skipping stops at the end of the volume, so that libarchive reads on into the next one
+    let offset = client_data.reader.stream_position().unwrap();
+    let len = client_data.reader.seek(SeekFrom::End(0)).unwrap();
+    let skipped = request.min(len.saturating_sub(offset).try_into().unwrap());
+    client_data
+        .reader
+        .seek(SeekFrom::Start(offset + skipped as u64))
+        .unwrap();
+    skipped
 }
 
 unsafe extern "C" fn seek_callback<R: Read + Seek>(
@@ -76,6 +86,18 @@ unsafe extern "C" fn seek_callback<R: Read + Seek>(
     client_data.reader.seek(pos).unwrap().try_into().unwrap()
 }
 
+unsafe extern "C" fn switch_callback<R: Read + Seek>(
+    _: *mut archive,
+    _client_data1: *mut c_void,
+    client_data2: *mut c_void,
+) -> c_int {
+    // The next volume is read from its start
+    if let Some(client_data) = (client_data2 as *mut ClientData<R>).as_mut() {
+        client_data.reader.rewind().unwrap();
+    }
+    ARCHIVE_OK
+}
+
 impl<R: Read + Seek> InnerFs<R> {
     unsafe fn init_archive(&mut self) {
         self.a = archive_read_new();
@@ -87,21 +109,29 @@ impl<R: Read + Seek> InnerFs<R> {
         }
         assert_eq!(archive_read_support_format_all(self.a), ARCHIVE_OK);
         assert_eq!(archive_read_support_filter_all(self.a), ARCHIVE_OK);
+        assert_eq!(
+            archive_read_set_read_callback(self.a, Some(read_callback::<R>)),
+            ARCHIVE_OK
+        );
+        assert_eq!(
+            archive_read_set_skip_callback(self.a, Some(skip_callback::<R>)),
+            ARCHIVE_OK
+        );
         assert_eq!(
             archive_read_set_seek_callback(self.a, Some(seek_callback::<R>)),
             ARCHIVE_OK
         );
         assert_eq!(
-            archive_read_open2(
-                self.a,
-                self.client_data as *mut c_void,
-                None,
-                Some(read_callback::<R>),
-                Some(skip_callback::<R>),
-                None,
-            ),
+            archive_read_set_switch_callback(self.a, Some(switch_callback::<R>)),
             ARCHIVE_OK
         );
+        for &client_data in &self.client_data {
+            assert_eq!(
+                archive_read_append_callback_data(self.a, client_data as *mut c_void),
+                ARCHIVE_OK
+            );
+        }
+        assert_eq!(archive_read_open1(self.a), ARCHIVE_OK);
     }
 
     unsafe fn free_archive(&mut self) {
@@ -111,11 +141,13 @@ impl<R: Read + Seek> InnerFs<R> {
 
     unsafe fn rewind(&mut self) {
         self.free_archive();
-        (*self.client_data).reader.rewind().unwrap();
+        for &client_data in &self.client_data {
+            (*client_data).reader.rewind().unwrap();
+        }
         self.init_archive();
     }
 
-    unsafe fn find_entry(&mut self, path: &[u8]) -> *mut archive_entry {
+    unsafe fn find_entry(&mut self, path: &[u8]) -> Option<*mut archive_entry> {
         let path = UnixPath::new(path);
         unsafe {
             self.rewind();
@@ -129,10 +161,10 @@ impl<R: Read + Seek> InnerFs<R> {
                         let entry_path = typed_path::UnixPath::new(entry_path);
                         dbg!(entry_path);
                         if entry_path == path {
-                            return entry;
+                            return Some(entry);
                         }
                     }
-                    ARCHIVE_EOF => todo!("entry not found: {path}"),
+                    ARCHIVE_EOF => return None,
                     r => todo!(
                         "{r}: {} {:?}",
                         archive_errno(self.a),
@@ -148,7 +180,9 @@ impl<R: Read + Seek> Drop for InnerFs<R> {
     fn drop(&mut self) {
         unsafe {
             self.free_archive();
-            let _ = Box::from_raw(self.client_data as *mut ClientData<R>);
+            for &client_data in &self.client_data {
+                let _ = Box::from_raw(client_data);
+            }
         }
     }
 }
@@ -329,6 +363,30 @@ impl<R: Read + Seek> Seek for File<R> {
     }
 }
 
+impl<R: Read + Seek> LibArchiveFs<R> {
+    /// Opens an archive split into several volumes, such as the parts of a multi-volume RAR or a
+    /// split 7z, given in order.
+    pub fn from_volumes(volumes: Vec<R>, password: Option<CString>) -> Self {
+        let mut inner = InnerFs {
+            a: null_mut(),
+            client_data: volumes
+                .into_iter()
+                .map(|reader| {
+                    Box::into_raw(Box::new(ClientData {
+                        reader,
+                        buf: [0; 4096],
+                    }))
+                })
+                .collect(),
+            password,
+        };
+        unsafe { inner.init_archive() }
+        Self {
+            inner: Rc::new(RefCell::new(inner)),
+        }
+    }
+}
+
 // TODO: Try deriving this
 impl<R: Read + Seek> Clone for LibArchiveFs<R> {
     fn clone(&self) -> Self {
@@ -342,18 +400,7 @@ impl<R: Read + Seek> vfs::IoBackedFs<R> for LibArchiveFs<R> {
     type Password = Option<CString>;
 
     fn from_io(io: R, password: Self::Password) -> Self {
-        let mut inner = InnerFs {
-            a: null_mut(),
-            client_data: Box::into_raw(Box::new(ClientData {
-                reader: io,
-                buf: [0; 4096],
-            })),
-            password,
-        };
-        unsafe { inner.init_archive() }
-        Self {
-            inner: Rc::new(RefCell::new(inner)),
-        }
+        Self::from_volumes(vec![io], password)
     }
 }
 
@@ -364,7 +411,7 @@ impl<R: Read + Seek> vfs::Fs for LibArchiveFs<R> {
 
     fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Self::Error> {
         unsafe {
-            let entry = self.inner.borrow_mut().find_entry(path);
+            let entry = self.inner.borrow_mut().find_entry(path).ok_or(())?;
             Ok(vfs::Metadata {
                 file_type: match archive_entry_filetype(entry) {
                     0o100000 => vfs::FileType::File,
@@ -373,6 +420,8 @@ impl<R: Read + Seek> vfs::Fs for LibArchiveFs<R> {
                     _ => todo!(),
                 },
                 len: archive_entry_size(entry).try_into().unwrap(),
+                mode: None,
+                mtime: None,
             })
         }
     }
@@ -1,3 +1,4 @@
+use std::io::Read;
 use vfs::{Fs, IoBackedFs};
 
 #[test]
@@ -34,7 +35,9 @@ fn test() {
         fs.metadata(b"a").unwrap(),
         vfs::Metadata {
             file_type: vfs::FileType::Dir,
-            len: 0
+            len: 0,
+            mode: None,
+            mtime: None,
         }
     );
     assert_eq!(
@@ -42,24 +45,125 @@ fn test() {
         vfs::Metadata {
             file_type: vfs::FileType::File,
             len: b_data.len() as u64,
+            mode: None,
+            mtime: None,
         }
     );
     assert_eq!(
         fs.metadata(b"c").unwrap(),
         vfs::Metadata {
             file_type: vfs::FileType::SymLink,
-            len: 0
+            len: 0,
+            mode: None,
+            mtime: None,
         }
     );
     assert_eq!(
         fs.metadata(b"d").unwrap(),
         vfs::Metadata {
             file_type: vfs::FileType::File,
-            len: d_data.len() as u64
+            len: d_data.len() as u64,
+            mode: None,
+            mtime: None,
         }
     );
     let b = fs.open(b"b").unwrap();
     let d = fs.open(b"d").unwrap();
     assert_eq!(std::io::read_to_string(b).unwrap(), b_data);
     assert_eq!(std::io::read_to_string(d).unwrap(), d_data);
+
+    // The same archive split at odd offsets into volumes
+    let tar_data = std::fs::read(&tar).unwrap();
+    let volumes = tar_data
+        .chunks(1000)
+        .map(|chunk| std::io::Cursor::new(chunk.to_vec()))
+        .collect();
+    let mut fs = vfs_libarchive::LibArchiveFs::from_volumes(volumes, None);
+    assert_eq!(fs.metadata(b"d").unwrap().len, d_data.len() as u64);
+    let d = fs.open(b"d").unwrap();
+    assert_eq!(std::io::read_to_string(d).unwrap(), d_data);
+    assert!(fs.metadata(b"e").is_err());
+}
+
+/// Opens the files in `dir` whose names start with `prefix`, in the order of their names.
+fn volumes(dir: &std::path::Path, prefix: &str) -> Vec<std::fs::File> {
+    let mut names: Vec<_> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+    names.sort();
+    names
+        .iter()
+        .map(|name| std::fs::File::open(dir.join(name)).unwrap())
+        .collect()
+}
+
+#[test]
+fn multi_volume() {
+    let tmp = tempfile::tempdir().unwrap();
+    let tmp = tmp.path();
+    // Incompressible, so that each file spans several volumes
+    let mut x = 1u32;
+    let mut data = || {
+        (0..3000)
+            .map(|_| {
+                x = x.wrapping_mul(1103515245).wrapping_add(12345);
+                (x >> 16) as u8
+            })
+            .collect::<Vec<u8>>()
+    };
+    let b_data = data();
+    let d_data = data();
+    std::fs::write(tmp.join("b"), &b_data).unwrap();
+    std::fs::write(tmp.join("d"), &d_data).unwrap();
+    let run = |args: &[&str]| {
+        let status = std::process::Command::new(args[0])
+            .args(&args[1..])
+            .current_dir(tmp)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    // Reading files against their order in the archive goes back to earlier volumes, which the
+    // switch callback reads from their start
+    let check = |volumes: Vec<std::fs::File>| {
+        assert!(volumes.len() > 2);
+        let mut fs = vfs_libarchive::LibArchiveFs::from_volumes(volumes, None);
+        for (path, data) in [(b"d", &d_data), (b"b", &b_data), (b"d", &d_data)] {
+            assert_eq!(fs.metadata(path).unwrap().len, data.len() as u64);
+            let mut contents = Vec::new();
+            fs.open(path).unwrap().read_to_end(&mut contents).unwrap();
+            assert!(&contents == data);
+        }
+    };
+
+    // A RAR in parts named `a.part1.rar`, `a.part2.rar` and so on
+    run(&["rar", "a", "-m0", "-v1k", "-idq", "a.rar", "b", "d"]);
+    check(volumes(tmp, "a.part"));
+
+    // A 7z split into `c.7z.001`, `c.7z.002` and so on, whose header is in its last volume
+    run(&[
+        "bsdtar",
+        "--format",
+        "7zip",
+        "--options",
+        "7zip:compression=store",
+        "-cf",
+        "c.7z",
+        "b",
+        "d",
+    ]);
+    run(&[
+        "split",
+        "-b",
+        "1000",
+        "-d",
+        "-a",
+        "3",
+        "--numeric-suffixes=1",
+        "c.7z",
+        "c.7z.",
+    ]);
+    check(volumes(tmp, "c.7z."));
 }
@@ -26,6 +26,8 @@ impl vfs::Fs for LocalFs {
         Ok(vfs::Metadata {
             file_type,
             len: m.len(),
+            mode: Some(std::os::unix::fs::PermissionsExt::mode(&m.permissions()) & 0o7777),
+            mtime: m.modified().ok(),
         })
     }
 
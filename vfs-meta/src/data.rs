@@ -0,0 +1,121 @@
+//! RFC 2397 data URLs, such as `data:text/plain;base64,aGVsbG8=` or `data:,hello%20world`.
+
+use std::io::Cursor;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("data url has no comma before the data")]
+    MissingComma,
+    #[error("invalid percent-encoding")]
+    InvalidPercentEncoding,
+    #[error("invalid base64")]
+    InvalidBase64,
+}
+
+fn hex_digit(b: u8) -> Option<u8> {
+    (b as char).to_digit(16).map(|d| d as u8)
+}
+
+fn percent_decode(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+    while let Some(&b) = bytes.next() {
+        if b != b'%' {
+            out.push(b);
+            continue;
+        }
+        let high = bytes.next().and_then(|&b| hex_digit(b));
+        let low = bytes.next().and_then(|&b| hex_digit(b));
+        let (Some(high), Some(low)) = (high, low) else {
+            return Err(Error::InvalidPercentEncoding);
+        };
+        out.push(high << 4 | low);
+    }
+    Ok(out)
+}
+
+fn base64_value(b: u8) -> Option<u32> {
+    Some(match b {
+        b'A'..=b'Z' => b - b'A',
+        b'a'..=b'z' => b - b'a' + 26,
+        b'0'..=b'9' => b - b'0' + 52,
+        // The URL-safe alphabet is accepted as well
+        b'+' | b'-' => 62,
+        b'/' | b'_' => 63,
+        _ => return None,
+    } as u32)
+}
+
+/// Decodes base64, ignoring whitespace and with optional padding.
+fn base64_decode(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::with_capacity(data.len() / 4 * 3);
+    let mut bits = 0u32;
+    let mut n_bits = 0;
+    let data = data.iter().filter(|b| !b.is_ascii_whitespace());
+    let mut padding = 0;
+    for &b in data {
+        if b == b'=' {
+            padding += 1;
+            continue;
+        }
+        if padding > 0 {
+            return Err(Error::InvalidBase64);
+        }
+        bits = bits << 6 | base64_value(b).ok_or(Error::InvalidBase64)?;
+        n_bits += 6;
+        if n_bits >= 8 {
+            n_bits -= 8;
+            out.push((bits >> n_bits) as u8);
+        }
+    }
+    // A single leftover character can't hold a whole byte
+    if n_bits >= 6 || padding > 2 {
+        return Err(Error::InvalidBase64);
+    }
+    Ok(out)
+}
+
+/// Decodes the part of a data URL after `data:`.
+fn decode(url: &[u8]) -> Result<Vec<u8>, Error> {
+    let comma = url
+        .iter()
+        .position(|&b| b == b',')
+        .ok_or(Error::MissingComma)?;
+    let (header, data) = (&url[..comma], &url[comma + 1..]);
+    // The media type is irrelevant to a file. A bare `base64` header is accepted for brevity.
+    let base64 = header == b"base64" || header.ends_with(b";base64");
+    let data = percent_decode(data)?;
+    if base64 {
+        base64_decode(&data)
+    } else {
+        Ok(data)
+    }
+}
+
+/// A file whose contents are given inline by the path, decoded into memory.
+pub struct DataFs;
+
+impl vfs::StandaloneFs for DataFs {
+    fn new() -> Self {
+        Self
+    }
+}
+
+impl vfs::Fs for DataFs {
+    type Path = [u8];
+    type Error = Error;
+    type File = Cursor<Vec<u8>>;
+
+    fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Self::Error> {
+        Ok(vfs::Metadata {
+            file_type: vfs::FileType::File,
+            len: decode(path)?.len() as u64,
+            mode: None,
+            mtime: None,
+        })
+    }
+
+    fn open(&mut self, path: &[u8]) -> Result<Self::File, Self::Error> {
+        Ok(Cursor::new(decode(path)?))
+    }
+}
@@ -0,0 +1,143 @@
+//! Inherited file descriptors, such as `fd:3`, and `stdin:` for descriptor 0.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    os::{
+        fd::{BorrowedFd, RawFd},
+        unix::fs::{FileExt, FileTypeExt, MetadataExt},
+    },
+    sync::{Arc, Mutex},
+};
+
+const STDIN: RawFd = 0;
+
+/// Pipes and sockets spooled so far by device and inode, as they can only be read once
+static SPOOLED: Mutex<BTreeMap<(u64, u64), Arc<File>>> = Mutex::new(BTreeMap::new());
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed reading file descriptor")]
+    Io(#[from] std::io::Error),
+    #[error("invalid file descriptor number")]
+    InvalidFd,
+}
+
+/// Returns a file with the contents of `fd`. Pipes and sockets are spooled into an anonymous
+/// temporary file the first time, and anything else is read directly.
+fn file(fd: RawFd) -> Result<Arc<File>, Error> {
+    // SAFETY: The descriptor is only duplicated, which fails with `EBADF` if it isn't open
+    let mut file = File::from(unsafe { BorrowedFd::borrow_raw(fd) }.try_clone_to_owned()?);
+    let metadata = file.metadata()?;
+    let file_type = metadata.file_type();
+    if !file_type.is_fifo() && !file_type.is_socket() {
+        return Ok(Arc::new(file));
+    }
+    let mut spooled = SPOOLED.lock().unwrap();
+    let key = (metadata.dev(), metadata.ino());
+    if let Some(spool) = spooled.get(&key) {
+        return Ok(spool.clone());
+    }
+    let mut spool = tempfile::tempfile()?;
+    std::io::copy(&mut file, &mut spool)?;
+    let spool = Arc::new(spool);
+    spooled.insert(key, spool.clone());
+    Ok(spool)
+}
+
+fn parse_fd(path: &[u8]) -> Result<RawFd, Error> {
+    let path = path.strip_prefix(b"/").unwrap_or(path);
+    std::str::from_utf8(path)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|&fd| fd >= 0)
+        .ok_or(Error::InvalidFd)
+}
+
+/// A file read with positioned reads, so that it doesn't share an offset with other opens of the
+/// same descriptor.
+pub struct FdFile {
+    file: Arc<File>,
+    len: u64,
+    offset: u64,
+}
+
+impl Read for FdFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.file.read_at(buf, self.offset)?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for FdFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.offset = vfs::io::seek_position(pos, self.offset, self.len)?;
+        Ok(self.offset)
+    }
+}
+
+fn metadata(fd: RawFd) -> Result<vfs::Metadata, Error> {
+    Ok(vfs::Metadata {
+        file_type: vfs::FileType::File,
+        len: file(fd)?.metadata()?.len(),
+        mode: None,
+        mtime: None,
+    })
+}
+
+fn open(fd: RawFd) -> Result<FdFile, Error> {
+    let file = file(fd)?;
+    Ok(FdFile {
+        len: file.metadata()?.len(),
+        file,
+        offset: 0,
+    })
+}
+
+/// A file descriptor inherited by the process, with the number as path.
+pub struct FdFs;
+
+impl vfs::StandaloneFs for FdFs {
+    fn new() -> Self {
+        Self
+    }
+}
+
+impl vfs::Fs for FdFs {
+    type Path = [u8];
+    type Error = Error;
+    type File = FdFile;
+
+    fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Self::Error> {
+        metadata(parse_fd(path)?)
+    }
+
+    fn open(&mut self, path: &[u8]) -> Result<Self::File, Self::Error> {
+        open(parse_fd(path)?)
+    }
+}
+
+/// Standard input, as an alias of `fd:0` that takes no path.
+pub struct StdinFs;
+
+impl vfs::StandaloneFs for StdinFs {
+    fn new() -> Self {
+        Self
+    }
+}
+
+impl vfs::Fs for StdinFs {
+    type Path = [u8];
+    type Error = Error;
+    type File = FdFile;
+
+    fn metadata(&mut self, _path: &[u8]) -> Result<vfs::Metadata, Self::Error> {
+        metadata(STDIN)
+    }
+
+    fn open(&mut self, _path: &[u8]) -> Result<Self::File, Self::Error> {
+        open(STDIN)
+    }
+}
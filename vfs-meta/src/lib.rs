@@ -1,15 +1,76 @@
+// Without any layer created by `IoBackedFs::from_io`, the io-backed enums are empty and the
+// arguments of their methods go unused
+#![cfg_attr(
+    not(any(
+        feature = "vfs-libarchive",
+        feature = "vfs-zip",
+        feature = "vfs-tar",
+        feature = "vfs-compress",
+        feature = "vfs-squashfs",
+        feature = "vfs-iso9660",
+        feature = "vfs-ext4",
+        feature = "vfs-fat",
+        feature = "vfs-partitions",
+        feature = "vfs-oci",
+        feature = "vfs-package",
+        feature = "vfs-nar",
+        feature = "vfs-asar",
+        feature = "vfs-sqlar",
+        feature = "vfs-virtual"
+    )),
+    allow(unused_imports, unused_variables)
+)]
+
+mod data;
+mod fd;
 mod parser;
+mod volume;
 
+use data::DataFs;
+use fd::{FdFile, FdFs, StdinFs};
 use std::{
-    ffi::OsStr,
     io::{Read, Seek, SeekFrom},
     os::unix::prelude::OsStrExt,
-    path::{Path, PathBuf},
+    path::Path,
 };
 use vfs::{Fs, IoBackedFs, StandaloneFs};
+#[cfg(feature = "vfs-asar")]
+use vfs_asar::AsarFs;
+#[cfg(feature = "vfs-compress")]
+use vfs_compress::{Bzip2Fs, GzipFs, Lz4Fs, XzFs, ZstdFs};
+#[cfg(feature = "vfs-ext4")]
+use vfs_ext4::Ext4Fs;
+#[cfg(feature = "vfs-fat")]
+use vfs_fat::FatFs;
+#[cfg(feature = "vfs-http")]
 use vfs_http::{HttpFs, HttpsFs};
+#[cfg(feature = "vfs-iso9660")]
+use vfs_iso9660::Iso9660Fs;
+#[cfg(feature = "vfs-libarchive")]
 use vfs_libarchive::LibArchiveFs;
+#[cfg(feature = "vfs-local")]
 use vfs_local::LocalFs;
+#[cfg(feature = "vfs-nar")]
+use vfs_nar::NarFs;
+#[cfg(feature = "vfs-oci")]
+use vfs_oci::OciFs;
+#[cfg(feature = "vfs-package")]
+use vfs_package::{DebFs, RpmFs};
+#[cfg(feature = "vfs-partitions")]
+use vfs_partitions::PartitionsFs;
+#[cfg(feature = "vfs-sqlar")]
+use vfs_sqlar::SqlarFs;
+#[cfg(feature = "vfs-squashfs")]
+use vfs_squashfs::SquashFs;
+#[cfg(feature = "vfs-tar")]
+use vfs_tar::TarFs;
+#[cfg(feature = "vfs-vdisk")]
+use vfs_vdisk::{AndroidSparseFs, Qcow2Fs, VhdFs, VhdxFs, VmdkFs};
+#[cfg(feature = "vfs-virtual")]
+use vfs_virtual::{ConcatFs, SliceFs};
+#[cfg(feature = "vfs-zip")]
+use vfs_zip::ZipFs;
+pub use volume::next_volume_name;
 
 pub struct MetaFs;
 
@@ -17,6 +78,7 @@ pub trait ReadSeek: Read + Seek {}
 
 impl<R: Read + Seek> ReadSeek for R {}
 
+#[allow(clippy::large_enum_variant)]
 enum AnyFs {
     Standalone(AnyStandaloneFs),
     IoBacked(AnyIoBackedFs),
@@ -29,25 +91,115 @@ enum AnyStandaloneFs {
     Https(HttpsFs),
     #[cfg(feature = "vfs-http")]
     Http(HttpFs),
+    Data(DataFs),
+    Fd(FdFs),
+    Stdin(StdinFs),
 }
 
 enum AnyIoBackedFs {
     #[cfg(feature = "vfs-libarchive")]
     LibArchive(LibArchiveFs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-zip")]
+    Zip(ZipFs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-tar")]
+    Tar(TarFs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-compress")]
+    Gzip(GzipFs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-compress")]
+    Xz(XzFs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-compress")]
+    Zstd(ZstdFs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-compress")]
+    Bzip2(Bzip2Fs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-compress")]
+    Lz4(Lz4Fs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-squashfs")]
+    SquashFs(SquashFs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-iso9660")]
+    Iso9660(Iso9660Fs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-ext4")]
+    Ext4(Ext4Fs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-fat")]
+    Fat(FatFs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-partitions")]
+    Partitions(PartitionsFs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-vdisk")]
+    Qcow2(Qcow2Fs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-vdisk")]
+    Vhd(VhdFs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-vdisk")]
+    Vhdx(VhdxFs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-vdisk")]
+    Vmdk(VmdkFs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-vdisk")]
+    AndroidSparse(AndroidSparseFs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-oci")]
+    Oci(OciFs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-package")]
+    Deb(DebFs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-package")]
+    Rpm(RpmFs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-nar")]
+    Nar(NarFs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-asar")]
+    Asar(AsarFs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-sqlar")]
+    Sqlar(SqlarFs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-virtual")]
+    Concat(ConcatFs<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-virtual")]
+    Slice(SliceFs<Box<dyn ReadSeek>>),
 }
 
+#[allow(clippy::large_enum_variant)]
 pub enum AnyStandaloneFile {
     #[cfg(feature = "vfs-local")]
     Local(<LocalFs as Fs>::File),
     #[cfg(feature = "vfs-http")]
     Http(<HttpFs as Fs>::File),
+    Data(<DataFs as Fs>::File),
+    Fd(FdFile),
 }
 
+#[allow(clippy::large_enum_variant)]
 pub enum AnyIoBackedFile {
     #[cfg(feature = "vfs-libarchive")]
     LibArchive(<LibArchiveFs<Box<dyn ReadSeek>> as Fs>::File),
+    #[cfg(feature = "vfs-zip")]
+    Zip(<ZipFs<Box<dyn ReadSeek>> as Fs>::File),
+    #[cfg(feature = "vfs-tar")]
+    Tar(<TarFs<Box<dyn ReadSeek>> as Fs>::File),
+    #[cfg(feature = "vfs-compress")]
+    Compress(vfs_compress::File<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-squashfs")]
+    SquashFs(<SquashFs<Box<dyn ReadSeek>> as Fs>::File),
+    #[cfg(feature = "vfs-iso9660")]
+    Iso9660(<Iso9660Fs<Box<dyn ReadSeek>> as Fs>::File),
+    #[cfg(feature = "vfs-ext4")]
+    Ext4(<Ext4Fs<Box<dyn ReadSeek>> as Fs>::File),
+    #[cfg(feature = "vfs-fat")]
+    Fat(<FatFs<Box<dyn ReadSeek>> as Fs>::File),
+    #[cfg(feature = "vfs-partitions")]
+    Partitions(<PartitionsFs<Box<dyn ReadSeek>> as Fs>::File),
+    #[cfg(feature = "vfs-vdisk")]
+    Vdisk(vfs_vdisk::File),
+    #[cfg(feature = "vfs-oci")]
+    Oci(<OciFs<Box<dyn ReadSeek>> as Fs>::File),
+    #[cfg(feature = "vfs-package")]
+    Package(vfs_package::File<Box<dyn ReadSeek>>),
+    #[cfg(feature = "vfs-nar")]
+    Nar(<NarFs<Box<dyn ReadSeek>> as Fs>::File),
+    #[cfg(feature = "vfs-asar")]
+    Asar(<AsarFs<Box<dyn ReadSeek>> as Fs>::File),
+    #[cfg(feature = "vfs-sqlar")]
+    Sqlar(<SqlarFs<Box<dyn ReadSeek>> as Fs>::File),
+    #[cfg(feature = "vfs-virtual")]
+    Concat(<ConcatFs<Box<dyn ReadSeek>> as Fs>::File),
+    #[cfg(feature = "vfs-virtual")]
+    Slice(<SliceFs<Box<dyn ReadSeek>> as Fs>::File),
 }
 
+#[allow(clippy::large_enum_variant)]
 pub enum AnyFile {
     Standalone(AnyStandaloneFile),
     IoBacked(AnyIoBackedFile),
@@ -62,12 +214,71 @@ impl AnyStandaloneFs {
             b"https" => Some(Self::Https(HttpsFs::new())),
             #[cfg(feature = "vfs-http")]
             b"http" => Some(Self::Http(HttpFs::new())),
+            b"data" => Some(Self::Data(DataFs::new())),
+            b"fd" => Some(Self::Fd(FdFs::new())),
+            b"stdin" => Some(Self::Stdin(StdinFs::new())),
             _ => None,
         }
     }
 }
 
 impl AnyIoBackedFs {
+    /// Like `from_name_io`, but lets disk images and multi-volume archives open the files they
+    /// refer to, such as backing files and later volumes, relative to `path` in `fs`.
+    #[cfg_attr(
+        not(any(
+            feature = "vfs-vdisk",
+            feature = "vfs-libarchive",
+            feature = "vfs-virtual"
+        )),
+        allow(unused_variables)
+    )]
+    fn from_name_io_in(
+        name: &[u8],
+        io: impl ReadSeek + 'static,
+        fs: AnyFs,
+        path: &[u8],
+    ) -> Option<Self> {
+        match name {
+            #[cfg(feature = "vfs-vdisk")]
+            b"qcow2" => Some(Self::Qcow2(Qcow2Fs::with_sibling_opener(
+                Box::new(io),
+                sibling_opener(fs, path),
+            ))),
+            #[cfg(feature = "vfs-vdisk")]
+            b"vhd" => Some(Self::Vhd(VhdFs::with_sibling_opener(
+                Box::new(io),
+                sibling_opener(fs, path),
+            ))),
+            #[cfg(feature = "vfs-vdisk")]
+            b"vhdx" => Some(Self::Vhdx(VhdxFs::with_sibling_opener(
+                Box::new(io),
+                sibling_opener(fs, path),
+            ))),
+            #[cfg(feature = "vfs-vdisk")]
+            b"vmdk" => Some(Self::Vmdk(VmdkFs::with_sibling_opener(
+                Box::new(io),
+                sibling_opener(fs, path),
+            ))),
+            #[cfg(feature = "vfs-vdisk")]
+            b"sparse" => Some(Self::AndroidSparse(AndroidSparseFs::with_sibling_opener(
+                Box::new(io),
+                sibling_opener(fs, path),
+            ))),
+            #[cfg(feature = "vfs-libarchive")]
+            b"libarchive" if volume::is_first_volume(path) => Some(Self::LibArchive(
+                LibArchiveFs::from_volumes(volumes(Box::new(io), fs, path), Default::default()),
+            )),
+            // Other volumes are only looked for next to the first one, so that a path to a file
+            // that happens to end with a number isn't joined with its neighbours
+            #[cfg(feature = "vfs-virtual")]
+            b"concat" if volume::is_first_volume(path) => Some(Self::Concat(
+                ConcatFs::from_volumes(volumes(Box::new(io), fs, path)),
+            )),
+            _ => Self::from_name_io(name, io),
+        }
+    }
+
     fn from_name_io(name: &[u8], io: impl ReadSeek + 'static) -> Option<Self> {
         match name {
             #[cfg(feature = "vfs-libarchive")]
@@ -75,6 +286,46 @@ impl AnyIoBackedFs {
                 Box::new(io),
                 Default::default(),
             ))),
+            #[cfg(feature = "vfs-zip")]
+            b"zip" => Some(Self::Zip(ZipFs::from_io(Box::new(io), Default::default()))),
+            #[cfg(feature = "vfs-tar")]
+            b"tar" => Some(Self::Tar(TarFs::from_io(Box::new(io), ()))),
+            #[cfg(feature = "vfs-compress")]
+            b"gzip" => Some(Self::Gzip(GzipFs::from_io(Box::new(io), ()))),
+            #[cfg(feature = "vfs-compress")]
+            b"xz" => Some(Self::Xz(XzFs::from_io(Box::new(io), ()))),
+            #[cfg(feature = "vfs-compress")]
+            b"zstd" => Some(Self::Zstd(ZstdFs::from_io(Box::new(io), ()))),
+            #[cfg(feature = "vfs-compress")]
+            b"bzip2" => Some(Self::Bzip2(Bzip2Fs::from_io(Box::new(io), ()))),
+            #[cfg(feature = "vfs-compress")]
+            b"lz4" => Some(Self::Lz4(Lz4Fs::from_io(Box::new(io), ()))),
+            #[cfg(feature = "vfs-squashfs")]
+            b"squashfs" => Some(Self::SquashFs(SquashFs::from_io(Box::new(io), ()))),
+            #[cfg(feature = "vfs-iso9660")]
+            b"iso9660" => Some(Self::Iso9660(Iso9660Fs::from_io(Box::new(io), ()))),
+            #[cfg(feature = "vfs-ext4")]
+            b"ext4" => Some(Self::Ext4(Ext4Fs::from_io(Box::new(io), ()))),
+            #[cfg(feature = "vfs-fat")]
+            b"fat" => Some(Self::Fat(FatFs::from_io(Box::new(io), ()))),
+            #[cfg(feature = "vfs-partitions")]
+            b"partitions" => Some(Self::Partitions(PartitionsFs::from_io(Box::new(io), ()))),
+            #[cfg(feature = "vfs-oci")]
+            b"oci" => Some(Self::Oci(OciFs::from_io(Box::new(io), ()))),
+            #[cfg(feature = "vfs-package")]
+            b"deb" => Some(Self::Deb(DebFs::from_io(Box::new(io), ()))),
+            #[cfg(feature = "vfs-package")]
+            b"rpm" => Some(Self::Rpm(RpmFs::from_io(Box::new(io), ()))),
+            #[cfg(feature = "vfs-nar")]
+            b"nar" => Some(Self::Nar(NarFs::from_io(Box::new(io), ()))),
+            #[cfg(feature = "vfs-asar")]
+            b"asar" => Some(Self::Asar(AsarFs::from_io(Box::new(io), ()))),
+            #[cfg(feature = "vfs-sqlar")]
+            b"sqlar" => Some(Self::Sqlar(SqlarFs::from_io(Box::new(io), ()))),
+            #[cfg(feature = "vfs-virtual")]
+            b"concat" => Some(Self::Concat(ConcatFs::from_io(Box::new(io), ()))),
+            #[cfg(feature = "vfs-virtual")]
+            b"slice" => Some(Self::Slice(SliceFs::from_io(Box::new(io), ()))),
             _ => None,
         }
     }
@@ -87,6 +338,8 @@ impl Read for AnyStandaloneFile {
             AnyStandaloneFile::Local(x) => x.read(buf),
             #[cfg(feature = "vfs-http")]
             AnyStandaloneFile::Http(x) => x.read(buf),
+            AnyStandaloneFile::Data(x) => x.read(buf),
+            AnyStandaloneFile::Fd(x) => x.read(buf),
         }
     }
 }
@@ -98,24 +351,90 @@ impl Seek for AnyStandaloneFile {
             AnyStandaloneFile::Local(x) => x.seek(pos),
             #[cfg(feature = "vfs-http")]
             AnyStandaloneFile::Http(x) => x.seek(pos),
+            AnyStandaloneFile::Data(x) => x.seek(pos),
+            AnyStandaloneFile::Fd(x) => x.seek(pos),
         }
     }
 }
 
 impl Read for AnyIoBackedFile {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        match self {
+        match *self {
             #[cfg(feature = "vfs-libarchive")]
-            AnyIoBackedFile::LibArchive(x) => x.read(buf),
+            AnyIoBackedFile::LibArchive(ref mut x) => x.read(buf),
+            #[cfg(feature = "vfs-zip")]
+            AnyIoBackedFile::Zip(ref mut x) => x.read(buf),
+            #[cfg(feature = "vfs-tar")]
+            AnyIoBackedFile::Tar(ref mut x) => x.read(buf),
+            #[cfg(feature = "vfs-compress")]
+            AnyIoBackedFile::Compress(ref mut x) => x.read(buf),
+            #[cfg(feature = "vfs-squashfs")]
+            AnyIoBackedFile::SquashFs(ref mut x) => x.read(buf),
+            #[cfg(feature = "vfs-iso9660")]
+            AnyIoBackedFile::Iso9660(ref mut x) => x.read(buf),
+            #[cfg(feature = "vfs-ext4")]
+            AnyIoBackedFile::Ext4(ref mut x) => x.read(buf),
+            #[cfg(feature = "vfs-fat")]
+            AnyIoBackedFile::Fat(ref mut x) => x.read(buf),
+            #[cfg(feature = "vfs-partitions")]
+            AnyIoBackedFile::Partitions(ref mut x) => x.read(buf),
+            #[cfg(feature = "vfs-vdisk")]
+            AnyIoBackedFile::Vdisk(ref mut x) => x.read(buf),
+            #[cfg(feature = "vfs-oci")]
+            AnyIoBackedFile::Oci(ref mut x) => x.read(buf),
+            #[cfg(feature = "vfs-package")]
+            AnyIoBackedFile::Package(ref mut x) => x.read(buf),
+            #[cfg(feature = "vfs-nar")]
+            AnyIoBackedFile::Nar(ref mut x) => x.read(buf),
+            #[cfg(feature = "vfs-asar")]
+            AnyIoBackedFile::Asar(ref mut x) => x.read(buf),
+            #[cfg(feature = "vfs-sqlar")]
+            AnyIoBackedFile::Sqlar(ref mut x) => x.read(buf),
+            #[cfg(feature = "vfs-virtual")]
+            AnyIoBackedFile::Concat(ref mut x) => x.read(buf),
+            #[cfg(feature = "vfs-virtual")]
+            AnyIoBackedFile::Slice(ref mut x) => x.read(buf),
         }
     }
 }
 
 impl Seek for AnyIoBackedFile {
     fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
-        match self {
+        match *self {
             #[cfg(feature = "vfs-libarchive")]
-            AnyIoBackedFile::LibArchive(x) => x.seek(pos),
+            AnyIoBackedFile::LibArchive(ref mut x) => x.seek(pos),
+            #[cfg(feature = "vfs-zip")]
+            AnyIoBackedFile::Zip(ref mut x) => x.seek(pos),
+            #[cfg(feature = "vfs-tar")]
+            AnyIoBackedFile::Tar(ref mut x) => x.seek(pos),
+            #[cfg(feature = "vfs-compress")]
+            AnyIoBackedFile::Compress(ref mut x) => x.seek(pos),
+            #[cfg(feature = "vfs-squashfs")]
+            AnyIoBackedFile::SquashFs(ref mut x) => x.seek(pos),
+            #[cfg(feature = "vfs-iso9660")]
+            AnyIoBackedFile::Iso9660(ref mut x) => x.seek(pos),
+            #[cfg(feature = "vfs-ext4")]
+            AnyIoBackedFile::Ext4(ref mut x) => x.seek(pos),
+            #[cfg(feature = "vfs-fat")]
+            AnyIoBackedFile::Fat(ref mut x) => x.seek(pos),
+            #[cfg(feature = "vfs-partitions")]
+            AnyIoBackedFile::Partitions(ref mut x) => x.seek(pos),
+            #[cfg(feature = "vfs-vdisk")]
+            AnyIoBackedFile::Vdisk(ref mut x) => x.seek(pos),
+            #[cfg(feature = "vfs-oci")]
+            AnyIoBackedFile::Oci(ref mut x) => x.seek(pos),
+            #[cfg(feature = "vfs-package")]
+            AnyIoBackedFile::Package(ref mut x) => x.seek(pos),
+            #[cfg(feature = "vfs-nar")]
+            AnyIoBackedFile::Nar(ref mut x) => x.seek(pos),
+            #[cfg(feature = "vfs-asar")]
+            AnyIoBackedFile::Asar(ref mut x) => x.seek(pos),
+            #[cfg(feature = "vfs-sqlar")]
+            AnyIoBackedFile::Sqlar(ref mut x) => x.seek(pos),
+            #[cfg(feature = "vfs-virtual")]
+            AnyIoBackedFile::Concat(ref mut x) => x.seek(pos),
+            #[cfg(feature = "vfs-virtual")]
+            AnyIoBackedFile::Slice(ref mut x) => x.seek(pos),
         }
     }
 }
@@ -139,17 +458,18 @@ impl Seek for AnyFile {
 }
 
 impl Fs for AnyFs {
+    type Path = [u8];
     type Error = ();
     type File = AnyFile;
 
-    fn metadata(&mut self, path: impl AsRef<Path>) -> Result<vfs::Metadata, Self::Error> {
+    fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Self::Error> {
         match self {
             AnyFs::Standalone(fs) => fs.metadata(path),
             AnyFs::IoBacked(fs) => fs.metadata(path),
         }
     }
 
-    fn open(&mut self, path: impl AsRef<Path>) -> Result<Self::File, Self::Error> {
+    fn open(&mut self, path: &[u8]) -> Result<Self::File, Self::Error> {
         match self {
             AnyFs::Standalone(fs) => fs.open(path).map(AnyFile::Standalone),
             AnyFs::IoBacked(fs) => fs.open(path).map(AnyFile::IoBacked),
@@ -157,83 +477,284 @@ impl Fs for AnyFs {
     }
 }
 
+/// The path of a `local:` component, which is any byte string.
+#[cfg(feature = "vfs-local")]
+fn local_path(path: &[u8]) -> &Path {
+    Path::new(std::ffi::OsStr::from_bytes(path))
+}
+
+/// The path of an `http:` or `https:` component, which may be written as `http://host/path`.
+#[cfg(feature = "vfs-http")]
+fn http_path(path: &[u8]) -> Result<&str, ()> {
+    let path = path.strip_prefix(b"//").unwrap_or(path);
+    std::str::from_utf8(path).map_err(drop)
+}
+
 impl Fs for AnyStandaloneFs {
+    type Path = [u8];
     type Error = ();
     type File = AnyStandaloneFile;
 
-    fn metadata(&mut self, path: impl AsRef<Path>) -> Result<vfs::Metadata, Self::Error> {
-        Ok(match self {
+    fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Self::Error> {
+        match self {
             #[cfg(feature = "vfs-local")]
-            AnyStandaloneFs::Local(x) => x.metadata(path).unwrap(),
+            AnyStandaloneFs::Local(x) => x.metadata(local_path(path)).map_err(drop),
             #[cfg(feature = "vfs-http")]
-            AnyStandaloneFs::Https(x) => x.metadata(path).unwrap(),
+            AnyStandaloneFs::Https(x) => x.metadata(http_path(path)?).map_err(drop),
             #[cfg(feature = "vfs-http")]
-            AnyStandaloneFs::Http(x) => x.metadata(path).unwrap(),
-        })
+            AnyStandaloneFs::Http(x) => x.metadata(http_path(path)?).map_err(drop),
+            AnyStandaloneFs::Data(x) => x.metadata(path).map_err(drop),
+            AnyStandaloneFs::Fd(x) => x.metadata(path).map_err(drop),
+            AnyStandaloneFs::Stdin(x) => x.metadata(path).map_err(drop),
+        }
     }
 
-    fn open(&mut self, path: impl AsRef<Path>) -> Result<Self::File, Self::Error> {
-        Ok(match self {
+    fn open(&mut self, path: &[u8]) -> Result<Self::File, Self::Error> {
+        match self {
             #[cfg(feature = "vfs-local")]
-            AnyStandaloneFs::Local(x) => AnyStandaloneFile::Local(x.open(path).unwrap()),
+            AnyStandaloneFs::Local(x) => x
+                .open(local_path(path))
+                .map(AnyStandaloneFile::Local)
+                .map_err(drop),
             #[cfg(feature = "vfs-http")]
-            AnyStandaloneFs::Https(x) => AnyStandaloneFile::Http(x.open(path).unwrap()),
+            AnyStandaloneFs::Https(x) => x
+                .open(http_path(path)?)
+                .map(AnyStandaloneFile::Http)
+                .map_err(drop),
             #[cfg(feature = "vfs-http")]
-            AnyStandaloneFs::Http(x) => AnyStandaloneFile::Http(x.open(path).unwrap()),
-        })
+            AnyStandaloneFs::Http(x) => x
+                .open(http_path(path)?)
+                .map(AnyStandaloneFile::Http)
+                .map_err(drop),
+            AnyStandaloneFs::Data(x) => x.open(path).map(AnyStandaloneFile::Data).map_err(drop),
+            AnyStandaloneFs::Fd(x) => x.open(path).map(AnyStandaloneFile::Fd).map_err(drop),
+            AnyStandaloneFs::Stdin(x) => x.open(path).map(AnyStandaloneFile::Fd).map_err(drop),
+        }
     }
 }
 
 impl Fs for AnyIoBackedFs {
+    type Path = [u8];
     type Error = ();
     type File = AnyIoBackedFile;
 
-    fn metadata(&mut self, path: impl AsRef<Path>) -> Result<vfs::Metadata, Self::Error> {
-        match self {
+    fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Self::Error> {
+        match *self {
             #[cfg(feature = "vfs-libarchive")]
-            AnyIoBackedFs::LibArchive(x) => x.metadata(path),
+            AnyIoBackedFs::LibArchive(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-zip")]
+            AnyIoBackedFs::Zip(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-tar")]
+            AnyIoBackedFs::Tar(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-compress")]
+            AnyIoBackedFs::Gzip(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-compress")]
+            AnyIoBackedFs::Xz(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-compress")]
+            AnyIoBackedFs::Zstd(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-compress")]
+            AnyIoBackedFs::Bzip2(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-compress")]
+            AnyIoBackedFs::Lz4(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-squashfs")]
+            AnyIoBackedFs::SquashFs(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-iso9660")]
+            AnyIoBackedFs::Iso9660(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-ext4")]
+            AnyIoBackedFs::Ext4(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-fat")]
+            AnyIoBackedFs::Fat(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-partitions")]
+            AnyIoBackedFs::Partitions(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-vdisk")]
+            AnyIoBackedFs::Qcow2(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-vdisk")]
+            AnyIoBackedFs::Vhd(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-vdisk")]
+            AnyIoBackedFs::Vhdx(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-vdisk")]
+            AnyIoBackedFs::Vmdk(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-vdisk")]
+            AnyIoBackedFs::AndroidSparse(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-oci")]
+            AnyIoBackedFs::Oci(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-package")]
+            AnyIoBackedFs::Deb(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-package")]
+            AnyIoBackedFs::Rpm(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-nar")]
+            AnyIoBackedFs::Nar(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-asar")]
+            AnyIoBackedFs::Asar(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-sqlar")]
+            AnyIoBackedFs::Sqlar(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-virtual")]
+            AnyIoBackedFs::Concat(ref mut x) => x.metadata(path).map_err(drop),
+            #[cfg(feature = "vfs-virtual")]
+            AnyIoBackedFs::Slice(ref mut x) => x.metadata(path).map_err(drop),
         }
     }
 
-    fn open(&mut self, path: impl AsRef<Path>) -> Result<Self::File, Self::Error> {
-        match self {
+    fn open(&mut self, path: &[u8]) -> Result<Self::File, Self::Error> {
+        match *self {
             #[cfg(feature = "vfs-libarchive")]
-            AnyIoBackedFs::LibArchive(x) => x.open(path).map(AnyIoBackedFile::LibArchive),
+            AnyIoBackedFs::LibArchive(ref mut x) => {
+                x.open(path).map(AnyIoBackedFile::LibArchive).map_err(drop)
+            }
+            #[cfg(feature = "vfs-zip")]
+            AnyIoBackedFs::Zip(ref mut x) => x.open(path).map(AnyIoBackedFile::Zip).map_err(drop),
+            #[cfg(feature = "vfs-tar")]
+            AnyIoBackedFs::Tar(ref mut x) => x.open(path).map(AnyIoBackedFile::Tar).map_err(drop),
+            #[cfg(feature = "vfs-compress")]
+            AnyIoBackedFs::Gzip(ref mut x) => {
+                x.open(path).map(AnyIoBackedFile::Compress).map_err(drop)
+            }
+            #[cfg(feature = "vfs-compress")]
+            AnyIoBackedFs::Xz(ref mut x) => {
+                x.open(path).map(AnyIoBackedFile::Compress).map_err(drop)
+            }
+            #[cfg(feature = "vfs-compress")]
+            AnyIoBackedFs::Zstd(ref mut x) => {
+                x.open(path).map(AnyIoBackedFile::Compress).map_err(drop)
+            }
+            #[cfg(feature = "vfs-compress")]
+            AnyIoBackedFs::Bzip2(ref mut x) => {
+                x.open(path).map(AnyIoBackedFile::Compress).map_err(drop)
+            }
+            #[cfg(feature = "vfs-compress")]
+            AnyIoBackedFs::Lz4(ref mut x) => {
+                x.open(path).map(AnyIoBackedFile::Compress).map_err(drop)
+            }
+            #[cfg(feature = "vfs-squashfs")]
+            AnyIoBackedFs::SquashFs(ref mut x) => {
+                x.open(path).map(AnyIoBackedFile::SquashFs).map_err(drop)
+            }
+            #[cfg(feature = "vfs-iso9660")]
+            AnyIoBackedFs::Iso9660(ref mut x) => {
+                x.open(path).map(AnyIoBackedFile::Iso9660).map_err(drop)
+            }
+            #[cfg(feature = "vfs-ext4")]
+            AnyIoBackedFs::Ext4(ref mut x) => x.open(path).map(AnyIoBackedFile::Ext4).map_err(drop),
+            #[cfg(feature = "vfs-fat")]
+            AnyIoBackedFs::Fat(ref mut x) => x.open(path).map(AnyIoBackedFile::Fat).map_err(drop),
+            #[cfg(feature = "vfs-partitions")]
+            AnyIoBackedFs::Partitions(ref mut x) => {
+                x.open(path).map(AnyIoBackedFile::Partitions).map_err(drop)
+            }
+            #[cfg(feature = "vfs-vdisk")]
+            AnyIoBackedFs::Qcow2(ref mut x) => {
+                x.open(path).map(AnyIoBackedFile::Vdisk).map_err(drop)
+            }
+            #[cfg(feature = "vfs-vdisk")]
+            AnyIoBackedFs::Vhd(ref mut x) => x.open(path).map(AnyIoBackedFile::Vdisk).map_err(drop),
+            #[cfg(feature = "vfs-vdisk")]
+            AnyIoBackedFs::Vhdx(ref mut x) => {
+                x.open(path).map(AnyIoBackedFile::Vdisk).map_err(drop)
+            }
+            #[cfg(feature = "vfs-vdisk")]
+            AnyIoBackedFs::Vmdk(ref mut x) => {
+                x.open(path).map(AnyIoBackedFile::Vdisk).map_err(drop)
+            }
+            #[cfg(feature = "vfs-vdisk")]
+            AnyIoBackedFs::AndroidSparse(ref mut x) => {
+                x.open(path).map(AnyIoBackedFile::Vdisk).map_err(drop)
+            }
+            #[cfg(feature = "vfs-oci")]
+            AnyIoBackedFs::Oci(ref mut x) => x.open(path).map(AnyIoBackedFile::Oci).map_err(drop),
+            #[cfg(feature = "vfs-package")]
+            AnyIoBackedFs::Deb(ref mut x) => {
+                x.open(path).map(AnyIoBackedFile::Package).map_err(drop)
+            }
+            #[cfg(feature = "vfs-package")]
+            AnyIoBackedFs::Rpm(ref mut x) => {
+                x.open(path).map(AnyIoBackedFile::Package).map_err(drop)
+            }
+            #[cfg(feature = "vfs-nar")]
+            AnyIoBackedFs::Nar(ref mut x) => x.open(path).map(AnyIoBackedFile::Nar).map_err(drop),
+            #[cfg(feature = "vfs-asar")]
+            AnyIoBackedFs::Asar(ref mut x) => x.open(path).map(AnyIoBackedFile::Asar).map_err(drop),
+            #[cfg(feature = "vfs-sqlar")]
+            AnyIoBackedFs::Sqlar(ref mut x) => {
+                x.open(path).map(AnyIoBackedFile::Sqlar).map_err(drop)
+            }
+            #[cfg(feature = "vfs-virtual")]
+            AnyIoBackedFs::Concat(ref mut x) => {
+                x.open(path).map(AnyIoBackedFile::Concat).map_err(drop)
+            }
+            #[cfg(feature = "vfs-virtual")]
+            AnyIoBackedFs::Slice(ref mut x) => {
+                x.open(path).map(AnyIoBackedFile::Slice).map_err(drop)
+            }
         }
     }
 }
 
-fn last_fs_and_path(path: &Path) -> (AnyFs, PathBuf) {
+/// Opens files named by a disk image relative to the directory of `path` in `fs`.
+#[cfg(feature = "vfs-vdisk")]
+fn sibling_opener(mut fs: AnyFs, path: &[u8]) -> vfs_vdisk::OpenSibling {
+    let dir = path[..path.iter().rposition(|&b| b == b'/').map_or(0, |i| i + 1)].to_vec();
+    Box::new(move |name| {
+        let path = if name.starts_with(b"/") {
+            name.to_vec()
+        } else {
+            [&dir, name].concat()
+        };
+        let file = fs
+            .open(&path)
+            .map_err(|()| std::io::Error::from(std::io::ErrorKind::NotFound))?;
+        Ok(Box::new(file))
+    })
+}
+
+/// Opens `first` and the volumes numbered after it in the directory of `path` in `fs`, stopping at
+/// the first one that is missing, and then the `.zip` that ends a split zip set.
+#[cfg(any(feature = "vfs-libarchive", feature = "vfs-virtual"))]
+fn volumes(first: Box<dyn ReadSeek>, mut fs: AnyFs, path: &[u8]) -> Vec<Box<dyn ReadSeek>> {
+    let mut volumes = vec![first];
+    let mut next = next_volume_name(path);
+    while let Some(name) = next {
+        let Ok(file) = fs.open(&name) else {
+            break;
+        };
+        volumes.push(Box::new(file));
+        next = next_volume_name(&name);
+    }
+    if let Some(file) = volume::split_zip_last_volume(path).and_then(|name| fs.open(&name).ok()) {
+        volumes.push(Box::new(file));
+    }
+    volumes
+}
+
+/// Opens each component of a meta path in the filesystem of the one before, returning the last
+/// filesystem and the path in it.
+fn last_fs_and_path(path: &Path) -> Result<(AnyFs, Vec<u8>), ()> {
     let path = path.as_os_str().as_bytes();
-    let meta_components = parser::parse(path).unwrap().1;
-    let [(head_proto, head_path), ref tail @ ..] = meta_components.as_slice() else {
-        unreachable!()
-    };
-    let head_fs = AnyFs::Standalone(AnyStandaloneFs::from_name(head_proto).unwrap());
-    let (fs, path) = tail.into_iter().fold(
+    let (_, meta_components) = parser::parse(path).map_err(drop)?;
+    let mut components = meta_components.into_iter();
+    let (head_proto, head_path) = components.next().ok_or(())?;
+    let head_fs = AnyFs::Standalone(AnyStandaloneFs::from_name(head_proto).ok_or(())?);
+    components.try_fold(
         (head_fs, head_path),
         |(mut fs, path), (tail_proto, tail_path)| {
-            let path = OsStr::from_bytes(path);
-            let file = fs.open(path).unwrap();
-            let tail_fs = AnyFs::IoBacked(AnyIoBackedFs::from_name_io(tail_proto, file).unwrap());
-            (tail_fs, tail_path)
+            let file = fs.open(&path)?;
+            let tail_fs = AnyIoBackedFs::from_name_io_in(tail_proto, file, fs, &path).ok_or(())?;
+            Ok((AnyFs::IoBacked(tail_fs), tail_path))
         },
-    );
-    let path = Path::new(OsStr::from_bytes(path)).to_path_buf();
-    (fs, path)
+    )
 }
 
 impl Fs for MetaFs {
+    type Path = Path;
     type Error = ();
     type File = AnyFile;
 
-    fn metadata(&mut self, path: impl AsRef<Path>) -> Result<vfs::Metadata, Self::Error> {
-        let (mut last_fs, last_path) = last_fs_and_path(path.as_ref());
-        last_fs.metadata(last_path)
+    fn metadata(&mut self, path: &Path) -> Result<vfs::Metadata, Self::Error> {
+        let (mut last_fs, last_path) = last_fs_and_path(path)?;
+        last_fs.metadata(&last_path)
     }
 
-    fn open(&mut self, path: impl AsRef<Path>) -> Result<Self::File, Self::Error> {
-        let (mut last_fs, last_path) = last_fs_and_path(path.as_ref());
-        last_fs.open(last_path)
+    fn open(&mut self, path: &Path) -> Result<Self::File, Self::Error> {
+        let (mut last_fs, last_path) = last_fs_and_path(path)?;
+        last_fs.open(&last_path)
     }
 }
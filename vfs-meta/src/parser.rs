@@ -7,18 +7,21 @@ use nom::{
     IResult,
 };
 
+/// A protocol and the path in it.
+type MetaComponent<'a> = (&'a [u8], Vec<u8>);
+
 fn path(s: &[u8]) -> IResult<&[u8], Vec<u8>> {
     escaped_transform(is_not(b"|".as_slice()), '\\', is_a(b"\\|".as_slice()))(s)
 }
 
-fn meta_component(s: &[u8]) -> IResult<&[u8], (&[u8], Vec<u8>)> {
+fn meta_component(s: &[u8]) -> IResult<&[u8], MetaComponent<'_>> {
     separated_pair(take_until(b":".as_slice()), char(':'), path)(s)
 }
 
-fn meta_path(s: &[u8]) -> IResult<&[u8], Vec<(&[u8], Vec<u8>)>> {
+fn meta_path(s: &[u8]) -> IResult<&[u8], Vec<MetaComponent<'_>>> {
     separated_list1(char('|'), meta_component)(s)
 }
 
-pub(crate) fn parse(s: &[u8]) -> IResult<&[u8], Vec<(&[u8], Vec<u8>)>> {
+pub(crate) fn parse(s: &[u8]) -> IResult<&[u8], Vec<MetaComponent<'_>>> {
     terminated(meta_path, eof)(s)
 }
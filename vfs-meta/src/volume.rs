@@ -0,0 +1,81 @@
+//! Names of the volumes of multi-volume archives.
+
+/// Returns the name of the volume following `name` in a numbered set, by incrementing the last
+/// number in the file name while keeping its width, as in `a.zip.001` to `a.zip.002` or
+/// `a.part9.rar` to `a.part10.rar`.
+pub fn next_volume_name(name: &[u8]) -> Option<Vec<u8>> {
+    let file_name_start = name.iter().rposition(|&b| b == b'/').map_or(0, |i| i + 1);
+    let digits_end = file_name_start
+        + name[file_name_start..]
+            .iter()
+            .rposition(u8::is_ascii_digit)?
+        + 1;
+    let digits_start = name[..digits_end]
+        .iter()
+        .rposition(|b| !b.is_ascii_digit())
+        .map_or(0, |i| i + 1)
+        .max(file_name_start);
+    let mut number = name[digits_start..digits_end].to_vec();
+    // Increment with carry, growing the number when all digits are nines
+    let mut i = number.len();
+    loop {
+        if i == 0 {
+            number.insert(0, b'1');
+            break;
+        }
+        i -= 1;
+        if number[i] == b'9' {
+            number[i] = b'0';
+        } else {
+            number[i] += 1;
+            break;
+        }
+    }
+    Some([&name[..digits_start], &number, &name[digits_end..]].concat())
+}
+
+/// Whether `path` names the first volume of a multi-volume archive, as in `a.part1.rar`,
+/// `a.7z.001`, `a.zip.001` or `a.z01` of a split zip set. Plain numbers need at least two digits,
+/// so that names like `notes.1` aren't taken for volumes.
+#[cfg(any(feature = "vfs-libarchive", feature = "vfs-virtual"))]
+pub(crate) fn is_first_volume(path: &[u8]) -> bool {
+    let is_one = |digits: &[u8]| {
+        !digits.is_empty()
+            && digits.iter().all(u8::is_ascii_digit)
+            && digits.ends_with(b"1")
+            && digits[..digits.len() - 1].iter().all(|&b| b == b'0')
+    };
+    let mut extensions = file_name(path).rsplit(|&b| b == b'.');
+    match (extensions.next(), extensions.next()) {
+        (Some(b"rar"), Some(part)) => part.strip_prefix(b"part").is_some_and(is_one),
+        (Some(number), Some(_)) => {
+            let number = number
+                .strip_prefix(b"z")
+                .or(number.strip_prefix(b"Z"))
+                .unwrap_or(number);
+            number.len() >= 2 && is_one(number)
+        }
+        _ => false,
+    }
+}
+
+/// Returns the name of the last volume of the split zip set that `path` is a volume of, as in
+/// `a.zip` for `a.z01`. Its name isn't numbered like those before it.
+#[cfg(any(feature = "vfs-libarchive", feature = "vfs-virtual"))]
+pub(crate) fn split_zip_last_volume(path: &[u8]) -> Option<Vec<u8>> {
+    let dot = path.len() - file_name(path).iter().rev().position(|&b| b == b'.')? - 1;
+    let extension = &path[dot + 1..];
+    let zip: &[u8] = match extension.first()? {
+        b'z' => b"zip",
+        b'Z' => b"ZIP",
+        _ => return None,
+    };
+    let digits = &extension[1..];
+    (digits.len() >= 2 && digits.iter().all(u8::is_ascii_digit))
+        .then(|| [&path[..=dot], zip].concat())
+}
+
+#[cfg(any(feature = "vfs-libarchive", feature = "vfs-virtual"))]
+fn file_name(path: &[u8]) -> &[u8] {
+    &path[path.iter().rposition(|&b| b == b'/').map_or(0, |i| i + 1)..]
+}
@@ -1,5 +1,10 @@
+use std::{
+    io::{Read, Seek, SeekFrom, Write},
+    os::fd::AsRawFd,
+    path::Path,
+};
 use vfs::{FileType, Fs, Metadata};
-use vfs_meta::MetaFs;
+use vfs_meta::{next_volume_name, MetaFs};
 
 #[test]
 fn test() {
@@ -16,11 +21,118 @@ fn test() {
                     "libarchive:0.dll"
                 ]
                 .join("|")
+                .as_ref()
             )
             .unwrap(),
         Metadata {
             file_type: FileType::File,
-            len: 4294967295
+            len: 4294967295,
+            mode: None,
+            mtime: None,
         }
     );
 }
+
+#[test]
+fn data() {
+    // A gzip stream given inline
+    assert_eq!(
+        MetaFs
+            .metadata(Path::new(
+                "data:application/gzip;base64,H4sIAAAAAAAAA8tIzcnJBwCGphA2BQAAAA==|gzip:"
+            ))
+            .unwrap()
+            .len,
+        5
+    );
+    assert_eq!(
+        MetaFs
+            .metadata(Path::new("data:,hello%20world"))
+            .unwrap()
+            .len,
+        11
+    );
+    assert_eq!(
+        MetaFs
+            .metadata(Path::new("data:,hello%20world|slice:6+5"))
+            .unwrap()
+            .len,
+        5
+    );
+}
+
+#[test]
+fn concat() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path().to_str().unwrap();
+    std::fs::write(tmp.path().join("a.001"), b"hello ").unwrap();
+    std::fs::write(tmp.path().join("a.002"), b"world").unwrap();
+    let len = |path: String| MetaFs.metadata(Path::new(&path)).unwrap().len;
+    assert_eq!(len(format!("local:{dir}/a.001|concat:")), 11);
+    // Volumes after the first are read alone
+    assert_eq!(len(format!("local:{dir}/a.002|concat:")), 5);
+    // A split zip set ends with its `.zip`
+    std::fs::write(tmp.path().join("b.z01"), b"one ").unwrap();
+    std::fs::write(tmp.path().join("b.z02"), b"two ").unwrap();
+    std::fs::write(tmp.path().join("b.zip"), b"three").unwrap();
+    assert_eq!(len(format!("local:{dir}/b.z01|concat:")), 13);
+    // Single digits aren't taken for volume numbers
+    std::fs::write(tmp.path().join("notes.1"), b"first").unwrap();
+    std::fs::write(tmp.path().join("notes.2"), b"second").unwrap();
+    assert_eq!(len(format!("local:{dir}/notes.1|concat:")), 5);
+}
+
+/// Reads `path` from its start and again after seeking back.
+fn read_twice(path: &str) -> Vec<u8> {
+    let mut file = MetaFs.open(Path::new(path)).unwrap();
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents).unwrap();
+    file.seek(SeekFrom::Start(6)).unwrap();
+    let mut rest = Vec::new();
+    file.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, contents[6..]);
+    contents
+}
+
+#[test]
+fn fd() {
+    // A pipe can only be read once, so reopening it reads what was spooled the first time
+    let (reader, mut writer) = std::io::pipe().unwrap();
+    writer.write_all(b"hello from a pipe").unwrap();
+    drop(writer);
+    let path = format!("fd:{}", reader.as_raw_fd());
+    assert_eq!(MetaFs.metadata(Path::new(&path)).unwrap().len, 17);
+    assert_eq!(read_twice(&path), b"hello from a pipe");
+    assert_eq!(read_twice(&path), b"hello from a pipe");
+    // Another pipe, which usually reuses the descriptor number of the closed one
+    drop(reader);
+    let (reader, mut writer) = std::io::pipe().unwrap();
+    writer.write_all(b"hello from another pipe").unwrap();
+    drop(writer);
+    let path = format!("fd:{}", reader.as_raw_fd());
+    assert_eq!(read_twice(&path), b"hello from another pipe");
+
+    // A regular file is read directly, without sharing an offset between opens
+    let mut file = tempfile::tempfile().unwrap();
+    file.write_all(b"hello from a file").unwrap();
+    let path = format!("fd:{}", file.as_raw_fd());
+    let mut first = MetaFs.open(Path::new(&path)).unwrap();
+    let mut buf = [0; 5];
+    first.read_exact(&mut buf).unwrap();
+    assert_eq!(read_twice(&path), b"hello from a file");
+    first.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b" from");
+
+    assert!(MetaFs.metadata(Path::new("fd:-1")).is_err());
+    assert!(MetaFs.metadata(Path::new("fd:x")).is_err());
+}
+
+#[test]
+fn volume_names() {
+    let next =
+        |name: &str| next_volume_name(name.as_bytes()).map(|n| String::from_utf8(n).unwrap());
+    assert_eq!(next("a.zip.001").as_deref(), Some("a.zip.002"));
+    assert_eq!(next("x.part9.rar").as_deref(), Some("x.part10.rar"));
+    assert_eq!(next("v1/a.z09").as_deref(), Some("v1/a.z10"));
+    assert_eq!(next("v1/a.zip"), None);
+}
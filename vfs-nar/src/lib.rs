@@ -0,0 +1,218 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{BufReader, Read, Seek},
+    rc::Rc,
+};
+use window_read_seek::WindowReadSeek;
+
+const MAGIC: &[u8] = b"nix-archive-1";
+/// Upper bound on strings other than file contents, which are read into memory
+const MAX_TOKEN_LEN: u64 = 4096;
+/// Upper bound on directory nesting, so that malicious archives can't overflow the stack
+const MAX_DEPTH: usize = 256;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed reading from backing IO")]
+    Io(#[from] std::io::Error),
+    #[error("invalid nar archive")]
+    InvalidArchive,
+    #[error("entry not found")]
+    NotFound,
+    #[error("entry is not a regular file")]
+    NotAFile,
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    metadata: vfs::Metadata,
+    offset: u64,
+}
+
+type Index = HashMap<Vec<u8>, Entry>;
+
+/// Reads the tokens of a NAR, which are strings padded to 8 bytes after a 64-bit length.
+struct Tokens<R: Read> {
+    reader: BufReader<R>,
+    pos: u64,
+}
+
+impl<R: Read + Seek> Tokens<R> {
+    fn len(&mut self) -> Result<u64, Error> {
+        let mut len = [0; 8];
+        self.reader.read_exact(&mut len)?;
+        self.pos += 8;
+        Ok(u64::from_le_bytes(len))
+    }
+
+    /// Skips `len` bytes of data and their padding.
+    fn skip(&mut self, len: u64) -> Result<(), Error> {
+        let len = len
+            .checked_next_multiple_of(8)
+            .ok_or(Error::InvalidArchive)?;
+        self.reader
+            .seek_relative(i64::try_from(len).map_err(|_| Error::InvalidArchive)?)?;
+        self.pos += len;
+        Ok(())
+    }
+
+    fn string(&mut self) -> Result<Vec<u8>, Error> {
+        let len = self.len()?;
+        if len > MAX_TOKEN_LEN {
+            return Err(Error::InvalidArchive);
+        }
+        let mut string = vec![0; len.next_multiple_of(8) as usize];
+        self.reader.read_exact(&mut string)?;
+        self.pos += string.len() as u64;
+        string.truncate(len as usize);
+        Ok(string)
+    }
+
+    fn expect(&mut self, token: &[u8]) -> Result<(), Error> {
+        if self.string()? != token {
+            return Err(Error::InvalidArchive);
+        }
+        Ok(())
+    }
+
+    /// Reads a node, the part of an entry between its parentheses, and adds it to `index`.
+    fn node(&mut self, index: &mut Index, path: Vec<u8>, depth: usize) -> Result<(), Error> {
+        if depth > MAX_DEPTH {
+            return Err(Error::InvalidArchive);
+        }
+        self.expect(b"(")?;
+        self.expect(b"type")?;
+        let metadata = |file_type, len, mode| vfs::Metadata {
+            file_type,
+            len,
+            mode,
+            mtime: None,
+        };
+        match self.string()?.as_slice() {
+            b"regular" => {
+                let mut token = self.string()?;
+                // NARs record only the executable bit, and normalize the other permissions
+                let mut mode = 0o444;
+                if token == b"executable" {
+                    self.expect(b"")?;
+                    mode = 0o555;
+                    token = self.string()?;
+                }
+                if token != b"contents" {
+                    return Err(Error::InvalidArchive);
+                }
+                let len = self.len()?;
+                let entry = Entry {
+                    metadata: metadata(vfs::FileType::File, len, Some(mode)),
+                    offset: self.pos,
+                };
+                index.insert(path, entry);
+                self.skip(len)?;
+            }
+            b"symlink" => {
+                self.expect(b"target")?;
+                self.string()?;
+                let entry = Entry {
+                    metadata: metadata(vfs::FileType::SymLink, 0, None),
+                    offset: 0,
+                };
+                index.insert(path, entry);
+            }
+            b"directory" => {
+                let entry = Entry {
+                    metadata: metadata(vfs::FileType::Dir, 0, None),
+                    offset: 0,
+                };
+                index.insert(path.clone(), entry);
+                loop {
+                    match self.string()?.as_slice() {
+                        b")" => return Ok(()),
+                        b"entry" => {}
+                        _ => return Err(Error::InvalidArchive),
+                    }
+                    self.expect(b"(")?;
+                    self.expect(b"name")?;
+                    let name = self.string()?;
+                    if name.is_empty() || name == b"." || name == b".." || name.contains(&b'/') {
+                        return Err(Error::InvalidArchive);
+                    }
+                    self.expect(b"node")?;
+                    let child = if path.is_empty() {
+                        name
+                    } else {
+                        [path.as_slice(), b"/", &name].concat()
+                    };
+                    self.node(index, child, depth + 1)?;
+                    self.expect(b")")?;
+                }
+            }
+            _ => return Err(Error::InvalidArchive),
+        }
+        self.expect(b")")
+    }
+}
+
+fn read_index<R: Read + Seek>(reader: &mut R) -> Result<Index, Error> {
+    reader.rewind()?;
+    let mut tokens = Tokens {
+        reader: BufReader::new(reader),
+        pos: 0,
+    };
+    tokens.expect(MAGIC)?;
+    let mut index = Index::new();
+    tokens.node(&mut index, Vec::new(), 0)?;
+    Ok(index)
+}
+
+/// A Nix archive, as stored in binary caches. The root may be a directory or a single file,
+/// which is then found at the empty path. Since NARs have no index, the whole archive is scanned
+/// on first access; file contents are skipped by seeking.
+pub struct NarFs<R: Read + Seek> {
+    reader: Rc<RefCell<R>>,
+    index: Option<Index>,
+}
+
+impl<R: Read + Seek> NarFs<R> {
+    fn entry(&mut self, path: &[u8]) -> Result<Entry, Error> {
+        if self.index.is_none() {
+            self.index = Some(read_index(&mut *self.reader.borrow_mut())?);
+        }
+        let index = self.index.as_ref().unwrap();
+        let path = vfs::path::normalize(path);
+        index.get(&path).cloned().ok_or(Error::NotFound)
+    }
+}
+
+impl<R: Read + Seek> vfs::IoBackedFs<R> for NarFs<R> {
+    type Password = ();
+
+    fn from_io(io: R, _password: Self::Password) -> Self {
+        Self {
+            reader: Rc::new(RefCell::new(io)),
+            index: None,
+        }
+    }
+}
+
+impl<R: Read + Seek> vfs::Fs for NarFs<R> {
+    type Path = [u8];
+    type Error = Error;
+    type File = WindowReadSeek<R>;
+
+    fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Self::Error> {
+        Ok(self.entry(path)?.metadata)
+    }
+
+    fn open(&mut self, path: &[u8]) -> Result<Self::File, Self::Error> {
+        let entry = self.entry(path)?;
+        if entry.metadata.file_type != vfs::FileType::File {
+            return Err(Error::NotAFile);
+        }
+        Ok(WindowReadSeek::new(
+            self.reader.clone(),
+            entry.offset,
+            entry.metadata.len,
+        ))
+    }
+}
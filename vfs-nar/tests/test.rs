@@ -0,0 +1,94 @@
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use vfs::{Fs, IoBackedFs};
+
+fn token(nar: &mut Vec<u8>, s: &[u8]) {
+    nar.extend_from_slice(&(s.len() as u64).to_le_bytes());
+    nar.extend_from_slice(s);
+    nar.resize(nar.len().next_multiple_of(8), 0);
+}
+
+fn tokens(nar: &mut Vec<u8>, tokens: &[&[u8]]) {
+    for s in tokens {
+        token(nar, s);
+    }
+}
+
+fn regular(nar: &mut Vec<u8>, name: &[u8], contents: &[u8], executable: bool) {
+    tokens(
+        nar,
+        &[
+            b"entry", b"(", b"name", name, b"node", b"(", b"type", b"regular",
+        ],
+    );
+    if executable {
+        tokens(nar, &[b"executable", b""]);
+    }
+    tokens(nar, &[b"contents", contents, b")", b")"]);
+}
+
+#[test]
+fn test() {
+    let mut nar = Vec::new();
+    tokens(&mut nar, &[b"nix-archive-1", b"(", b"type", b"directory"]);
+    tokens(&mut nar, &[b"entry", b"(", b"name", b"bin", b"node"]);
+    tokens(&mut nar, &[b"(", b"type", b"directory"]);
+    regular(&mut nar, b"foo", b"#!/bin/sh\necho foo\n", true);
+    tokens(&mut nar, &[b")", b")"]);
+    tokens(&mut nar, &[b"entry", b"(", b"name", b"lib", b"node"]);
+    tokens(
+        &mut nar,
+        &[b"(", b"type", b"symlink", b"target", b"bin", b")", b")"],
+    );
+    let large = vec![b'x'; 100_000];
+    regular(&mut nar, b"large", &large, false);
+    regular(&mut nar, b"empty", b"", false);
+    tokens(&mut nar, &[b")"]);
+
+    let mut fs = vfs_nar::NarFs::from_io(Cursor::new(nar.clone()), ());
+    let mut contents = String::new();
+    let mut file = fs.open(b"/bin/foo").unwrap();
+    file.read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "#!/bin/sh\necho foo\n");
+    let metadata = fs.metadata(b"bin/foo").unwrap();
+    assert_eq!(metadata.mode, Some(0o555));
+    assert_eq!(fs.metadata(b"").unwrap().file_type, vfs::FileType::Dir);
+    assert_eq!(fs.metadata(b"bin").unwrap().file_type, vfs::FileType::Dir);
+    assert_eq!(
+        fs.metadata(b"lib").unwrap().file_type,
+        vfs::FileType::SymLink
+    );
+    let mut file = fs.open(b"large").unwrap();
+    file.seek(SeekFrom::Start(99_990)).unwrap();
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail).unwrap();
+    assert_eq!(tail, [b'x'; 10]);
+    assert_eq!(fs.metadata(b"empty").unwrap().len, 0);
+    assert_eq!(fs.metadata(b"large").unwrap().mode, Some(0o444));
+    assert!(matches!(
+        fs.metadata(b"missing"),
+        Err(vfs_nar::Error::NotFound)
+    ));
+    assert!(matches!(fs.open(b"bin"), Err(vfs_nar::Error::NotAFile)));
+
+    // A NAR of a single file
+    let mut nar = Vec::new();
+    tokens(&mut nar, &[b"nix-archive-1", b"(", b"type", b"regular"]);
+    tokens(&mut nar, &[b"contents", b"single", b")"]);
+    let mut fs = vfs_nar::NarFs::from_io(Cursor::new(nar), ());
+    let mut contents = String::new();
+    fs.open(b"").unwrap().read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "single");
+
+    // Truncated and malformed archives
+    let mut fs = vfs_nar::NarFs::from_io(Cursor::new(b"nix-archive-1".to_vec()), ());
+    assert!(fs.metadata(b"").is_err());
+    let mut nar = Vec::new();
+    tokens(&mut nar, &[b"nix-archive-1", b"(", b"type", b"directory"]);
+    regular(&mut nar, b"..", b"", false);
+    tokens(&mut nar, &[b")"]);
+    let mut fs = vfs_nar::NarFs::from_io(Cursor::new(nar), ());
+    assert!(matches!(
+        fs.metadata(b""),
+        Err(vfs_nar::Error::InvalidArchive)
+    ));
+}
@@ -0,0 +1,314 @@
+use mini_json::Value;
+use std::{
+    collections::BTreeMap,
+    io::{Read, Seek, SeekFrom},
+};
+use vfs::{Fs, IoBackedFs};
+use vfs_compress::{GzipFs, ZstdFs};
+use vfs_tar::TarFs;
+
+const WHITEOUT_PREFIX: &[u8] = b".wh.";
+/// Marks a directory whose contents in lower layers are hidden
+const OPAQUE_WHITEOUT: &[u8] = b".wh..wh..opq";
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+const ZSTD_MAGIC: &[u8] = b"\x28\xb5\x2f\xfd";
+/// Upper bound on the nesting of image indexes, so that cycles terminate
+const MAX_INDEX_DEPTH: usize = 8;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed reading from backing IO")]
+    Io(#[from] std::io::Error),
+    #[error("failed reading tar archive")]
+    Tar(#[from] vfs_tar::Error),
+    #[error("failed decompressing layer")]
+    Compress(#[from] vfs_compress::Error),
+    #[error("neither index.json nor manifest.json found")]
+    NoManifest,
+    #[error("invalid {0}")]
+    InvalidJson(&'static str),
+    #[error("entry not found")]
+    NotFound,
+    #[error("entry is not a regular file")]
+    NotAFile,
+}
+
+/// A layer blob, decompressed if needed.
+enum Blob<R: Read + Seek> {
+    Plain(vfs_tar::File<R>),
+    Compressed(Box<vfs_compress::File<vfs_tar::File<R>>>),
+}
+
+impl<R: Read + Seek> Read for Blob<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Blob::Plain(x) => x.read(buf),
+            Blob::Compressed(x) => x.read(buf),
+        }
+    }
+}
+
+impl<R: Read + Seek> Seek for Blob<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            Blob::Plain(x) => x.seek(pos),
+            Blob::Compressed(x) => x.seek(pos),
+        }
+    }
+}
+
+/// Paths of the config and layer blobs of an image in the archive, bottom layer first.
+struct Manifest {
+    config: Vec<u8>,
+    layers: Vec<Vec<u8>>,
+}
+
+fn read_json<R: Read + Seek>(
+    archive: &mut TarFs<R>,
+    path: &[u8],
+    what: &'static str,
+) -> Result<Value, Error> {
+    let mut data = Vec::new();
+    archive.open(path)?.read_to_end(&mut data)?;
+    Value::parse(&data).ok_or(Error::InvalidJson(what))
+}
+
+/// Returns the path of the blob that a descriptor refers to by digest.
+fn blob_path(descriptor: &Value) -> Option<Vec<u8>> {
+    let digest = descriptor.get("digest")?.as_str()?;
+    let (algorithm, hex) = digest.split_once(':')?;
+    if algorithm.contains('/') || hex.contains('/') {
+        return None;
+    }
+    Some(format!("blobs/{algorithm}/{hex}").into_bytes())
+}
+
+/// Follows an OCI image index down to the manifest of the first image.
+fn oci_manifest<R: Read + Seek>(archive: &mut TarFs<R>, index: Value) -> Result<Manifest, Error> {
+    let mut index = index;
+    for _ in 0..MAX_INDEX_DEPTH {
+        let invalid = || Error::InvalidJson("image index");
+        // Skip attestation manifests, which are marked with an unknown platform
+        let descriptor = index
+            .get("manifests")
+            .and_then(Value::as_array)
+            .and_then(|manifests| {
+                manifests.iter().find(|m| {
+                    let os = m.get("platform").and_then(|p| p.get("os"));
+                    os.and_then(Value::as_str) != Some("unknown")
+                })
+            })
+            .ok_or_else(invalid)?;
+        let path = blob_path(descriptor).ok_or_else(invalid)?;
+        let manifest = read_json(archive, &path, "image manifest")?;
+        // Multi-platform images nest another index
+        if manifest.get("manifests").is_some() {
+            index = manifest;
+            continue;
+        }
+        let invalid = || Error::InvalidJson("image manifest");
+        let config = manifest
+            .get("config")
+            .and_then(blob_path)
+            .ok_or_else(invalid)?;
+        let layers = manifest
+            .get("layers")
+            .and_then(Value::as_array)
+            .and_then(|layers| layers.iter().map(blob_path).collect::<Option<_>>())
+            .ok_or_else(invalid)?;
+        return Ok(Manifest { config, layers });
+    }
+    Err(Error::InvalidJson("image index"))
+}
+
+/// Reads the first image of a `docker save` manifest, which lists paths in the archive.
+fn docker_manifest(manifest: &Value) -> Result<Manifest, Error> {
+    let invalid = || Error::InvalidJson("manifest.json");
+    let path = |v: &Value| v.as_str().map(|s| s.as_bytes().to_vec());
+    let image = manifest
+        .as_array()
+        .and_then(<[_]>::first)
+        .ok_or_else(invalid)?;
+    let config = image.get("Config").and_then(path).ok_or_else(invalid)?;
+    let layers = image
+        .get("Layers")
+        .and_then(Value::as_array)
+        .and_then(|layers| layers.iter().map(path).collect::<Option<_>>())
+        .ok_or_else(invalid)?;
+    Ok(Manifest { config, layers })
+}
+
+fn open_layer<R: Read + Seek>(
+    archive: &mut TarFs<R>,
+    path: &[u8],
+) -> Result<TarFs<Blob<R>>, Error> {
+    let mut file = archive.open(path)?;
+    let mut magic = Vec::new();
+    (&mut file).take(4).read_to_end(&mut magic)?;
+    file.seek(SeekFrom::Start(0))?;
+    // Media types are missing from `docker save` manifests, so detect the compression instead
+    let blob = if magic.starts_with(GZIP_MAGIC) {
+        Blob::Compressed(Box::new(GzipFs::from_io(file, ()).open(b"")?))
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        Blob::Compressed(Box::new(ZstdFs::from_io(file, ()).open(b"")?))
+    } else {
+        Blob::Plain(file)
+    };
+    Ok(TarFs::from_io(blob, ()))
+}
+
+type Paths = BTreeMap<Vec<u8>, usize>;
+
+/// Removes everything below the directory `dir` from `paths`.
+fn remove_children(paths: &mut Paths, dir: &[u8]) {
+    if dir.is_empty() {
+        paths.clear();
+        return;
+    }
+    // Paths below `dir` sort between `dir/` and `dir0`, as `0` follows `/`
+    let mut below = paths.split_off([dir, b"/"].concat().as_slice());
+    let mut after = below.split_off([dir, b"0"].concat().as_slice());
+    paths.append(&mut after);
+}
+
+struct Image<R: Read + Seek> {
+    layers: Vec<TarFs<Blob<R>>>,
+    /// The index of the topmost layer containing each path
+    paths: Paths,
+    config: Vec<u8>,
+}
+
+impl<R: Read + Seek> Image<R> {
+    fn open(archive: &mut TarFs<R>) -> Result<Self, Error> {
+        let manifest = match read_json(archive, b"index.json", "index.json") {
+            Ok(index) => oci_manifest(archive, index)?,
+            Err(Error::Tar(vfs_tar::Error::NotFound)) => {
+                match read_json(archive, b"manifest.json", "manifest.json") {
+                    Ok(manifest) => docker_manifest(&manifest)?,
+                    Err(Error::Tar(vfs_tar::Error::NotFound)) => return Err(Error::NoManifest),
+                    Err(err) => return Err(err),
+                }
+            }
+            Err(err) => return Err(err),
+        };
+        let mut config = Vec::new();
+        archive.open(&manifest.config)?.read_to_end(&mut config)?;
+
+        let mut layers = Vec::new();
+        let mut paths = Paths::new();
+        for (i, path) in manifest.layers.iter().enumerate() {
+            let mut layer = open_layer(archive, path)?;
+            // Whiteouts only hide entries of lower layers, so apply them before adding entries
+            let mut entries = Vec::new();
+            for path in layer.paths()? {
+                let (dir, name) = match path.iter().rposition(|&b| b == b'/') {
+                    Some(slash) => (&path[..slash], &path[slash + 1..]),
+                    None => (&b""[..], path),
+                };
+                if name == OPAQUE_WHITEOUT {
+                    remove_children(&mut paths, dir);
+                } else if let Some(name) = name.strip_prefix(WHITEOUT_PREFIX) {
+                    let path = vfs::path::normalize(&[dir, b"/", name].concat());
+                    paths.remove(&path);
+                    remove_children(&mut paths, &path);
+                } else {
+                    entries.push(path.to_vec());
+                }
+            }
+            for path in entries {
+                // Anything but a directory replaces a lower directory along with its contents
+                if layer.metadata(&path)?.file_type != vfs::FileType::Dir {
+                    remove_children(&mut paths, &path);
+                }
+                paths.insert(path, i);
+            }
+            layers.push(layer);
+        }
+        Ok(Self {
+            layers,
+            paths,
+            config,
+        })
+    }
+
+    /// Returns the layer holding the topmost version of a normalized path.
+    fn layer(&mut self, path: &[u8]) -> Result<&mut TarFs<Blob<R>>, Error> {
+        let i = *self.paths.get(path).ok_or(Error::NotFound)?;
+        Ok(&mut self.layers[i])
+    }
+}
+
+pub struct File<R: Read + Seek>(vfs_tar::File<Blob<R>>);
+
+impl<R: Read + Seek> Read for File<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl<R: Read + Seek> Seek for File<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+/// The root filesystem of a container image, flattened from its layers on first access. Reads
+/// OCI image layouts archived as tar (as written by `skopeo copy oci-archive:`) and `docker save`
+/// archives, with plain, gzip or zstd compressed layers.
+pub struct OciFs<R: Read + Seek> {
+    archive: TarFs<R>,
+    image: Option<Image<R>>,
+}
+
+impl<R: Read + Seek> OciFs<R> {
+    fn image(&mut self) -> Result<&mut Image<R>, Error> {
+        if self.image.is_none() {
+            self.image = Some(Image::open(&mut self.archive)?);
+        }
+        Ok(self.image.as_mut().unwrap())
+    }
+
+    /// Returns the image configuration JSON, which holds e.g. the environment and entrypoint.
+    pub fn config(&mut self) -> Result<&[u8], Error> {
+        Ok(&self.image()?.config)
+    }
+}
+
+impl<R: Read + Seek> vfs::IoBackedFs<R> for OciFs<R> {
+    type Password = ();
+
+    fn from_io(io: R, _password: Self::Password) -> Self {
+        Self {
+            archive: TarFs::from_io(io, ()),
+            image: None,
+        }
+    }
+}
+
+impl<R: Read + Seek> vfs::Fs for OciFs<R> {
+    type Path = [u8];
+    type Error = Error;
+    type File = File<R>;
+
+    fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Self::Error> {
+        let image = self.image()?;
+        let path = vfs::path::normalize(path);
+        if path.is_empty() {
+            return Ok(vfs::Metadata {
+                file_type: vfs::FileType::Dir,
+                len: 0,
+                mode: None,
+                mtime: None,
+            });
+        }
+        Ok(image.layer(&path)?.metadata(&path)?)
+    }
+
+    fn open(&mut self, path: &[u8]) -> Result<Self::File, Self::Error> {
+        if self.metadata(path)?.file_type != vfs::FileType::File {
+            return Err(Error::NotAFile);
+        }
+        let path = vfs::path::normalize(path);
+        Ok(File(self.image()?.layer(&path)?.open(&path)?))
+    }
+}
@@ -0,0 +1,135 @@
+use std::{io::Read, path::Path, process::Command};
+use vfs::{Fs, IoBackedFs};
+
+fn write(path: &Path, contents: &str) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, contents).unwrap();
+}
+
+fn tar(archive: &Path, dir: &Path) {
+    assert!(Command::new("tar")
+        .arg("cf")
+        .arg(archive)
+        .arg("-C")
+        .arg(dir)
+        .arg(".")
+        .status()
+        .unwrap()
+        .success());
+}
+
+fn check(archive: &Path) {
+    let mut fs = vfs_oci::OciFs::from_io(std::fs::File::open(archive).unwrap(), ());
+    let read = |fs: &mut vfs_oci::OciFs<_>, path: &str| {
+        let mut contents = String::new();
+        let mut file = fs.open(path.as_bytes()).unwrap();
+        file.read_to_string(&mut contents).unwrap();
+        contents
+    };
+    assert_eq!(read(&mut fs, "etc/hostname"), "upper");
+    assert_eq!(read(&mut fs, "/etc/passwd"), "root");
+    assert_eq!(read(&mut fs, "var/cache/new"), "new");
+    assert_eq!(read(&mut fs, "opt/replaced"), "file");
+    assert_eq!(fs.metadata(b"").unwrap().file_type, vfs::FileType::Dir);
+    assert_eq!(
+        fs.metadata(b"usr/bin").unwrap().file_type,
+        vfs::FileType::Dir
+    );
+    assert_eq!(
+        fs.metadata(b"var/cache").unwrap().file_type,
+        vfs::FileType::Dir
+    );
+    assert_eq!(
+        fs.metadata(b"link").unwrap().file_type,
+        vfs::FileType::SymLink
+    );
+    for hidden in [
+        "usr/bin/tool",
+        "usr/bin/.wh.tool",
+        "var/cache/old",
+        "var/cache/.wh..wh..opq",
+        "opt/replaced/inner",
+    ] {
+        assert!(fs.metadata(hidden.as_bytes()).is_err(), "{hidden}");
+    }
+    assert!(fs.open(b"etc").is_err());
+    assert_eq!(fs.config().unwrap(), br#"{"config":{"Env":["A=1"]}}"#);
+}
+
+#[test]
+fn test() {
+    let tmp = tempfile::tempdir().unwrap();
+    let tmp = tmp.path();
+
+    // A plain lower layer and a gzip compressed upper layer with whiteouts
+    let lower = tmp.join("lower");
+    write(&lower.join("etc/hostname"), "lower");
+    write(&lower.join("etc/passwd"), "root");
+    write(&lower.join("usr/bin/tool"), "tool");
+    write(&lower.join("var/cache/old"), "old");
+    write(&lower.join("opt/replaced/inner"), "inner");
+    let upper = tmp.join("upper");
+    write(&upper.join("etc/hostname"), "upper");
+    write(&upper.join("usr/bin/.wh.tool"), "");
+    write(&upper.join("var/cache/.wh..wh..opq"), "");
+    write(&upper.join("var/cache/new"), "new");
+    write(&upper.join("opt/replaced"), "file");
+    std::os::unix::fs::symlink("etc/hostname", upper.join("link")).unwrap();
+    tar(&tmp.join("lower.tar"), &lower);
+    tar(&tmp.join("upper.tar"), &upper);
+    assert!(Command::new("gzip")
+        .arg(tmp.join("upper.tar"))
+        .status()
+        .unwrap()
+        .success());
+    let lower_tar = std::fs::read(tmp.join("lower.tar")).unwrap();
+    let upper_tar = std::fs::read(tmp.join("upper.tar.gz")).unwrap();
+    let config = r#"{"config":{"Env":["A=1"]}}"#;
+
+    // An OCI layout with a multi-platform index listing an attestation manifest first
+    let oci = tmp.join("oci");
+    let blobs = oci.join("blobs/sha256");
+    std::fs::create_dir_all(&blobs).unwrap();
+    std::fs::write(blobs.join("11"), &lower_tar).unwrap();
+    std::fs::write(blobs.join("22"), &upper_tar).unwrap();
+    write(&blobs.join("33"), config);
+    write(
+        &blobs.join("44"),
+        r#"{"schemaVersion":2,"config":{"digest":"sha256:33"},"layers":[
+            {"mediaType":"application/vnd.oci.image.layer.v1.tar","digest":"sha256:11"},
+            {"mediaType":"application/vnd.oci.image.layer.v1.tar+gzip","digest":"sha256:22"}]}"#,
+    );
+    write(
+        &blobs.join("55"),
+        r#"{"manifests":[
+            {"digest":"sha256:66","platform":{"architecture":"unknown","os":"unknown"}},
+            {"digest":"sha256:44","platform":{"architecture":"amd64","os":"linux"}}]}"#,
+    );
+    write(
+        &oci.join("index.json"),
+        r#"{"schemaVersion":2,"manifests":[{"digest":"sha256:55","annotations":{"a":"é😀"}}]}"#,
+    );
+    tar(&tmp.join("oci.tar"), &oci);
+    check(&tmp.join("oci.tar"));
+
+    // A `docker save` archive listing layers by path
+    let docker = tmp.join("docker");
+    std::fs::create_dir_all(docker.join("a")).unwrap();
+    std::fs::create_dir_all(docker.join("b")).unwrap();
+    std::fs::write(docker.join("a/layer.tar"), &lower_tar).unwrap();
+    std::fs::write(docker.join("b/layer.tar"), &upper_tar).unwrap();
+    write(&docker.join("config.json"), config);
+    write(
+        &docker.join("manifest.json"),
+        r#"[{"Config":"config.json","RepoTags":["x:latest"],"Layers":["a/layer.tar","b/layer.tar"]}]"#,
+    );
+    tar(&tmp.join("docker.tar"), &docker);
+    check(&tmp.join("docker.tar"));
+
+    // Neither kind of manifest
+    let mut fs = vfs_oci::OciFs::from_io(std::fs::File::open(tmp.join("lower.tar")).unwrap(), ());
+    assert!(matches!(
+        fs.metadata(b"etc"),
+        Err(vfs_oci::Error::NoManifest)
+    ));
+}
@@ -0,0 +1,176 @@
+//! The "newc" cpio format, which RPM uses for payloads.
+
+use crate::Error;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom},
+    rc::Rc,
+    time::{Duration, SystemTime},
+};
+use window_read_seek::WindowReadSeek;
+
+const HEADER_LEN: usize = 110;
+const MAGIC: &[u8] = b"070701";
+/// Like `MAGIC`, with a checksum of the data in the last field
+const CRC_MAGIC: &[u8] = b"070702";
+const TRAILER: &[u8] = b"TRAILER!!!";
+const MODE_TYPE_MASK: u32 = 0o170000;
+const MODE_DIR: u32 = 0o040000;
+const MODE_FILE: u32 = 0o100000;
+const MODE_SYMLINK: u32 = 0o120000;
+
+#[derive(Clone, Debug)]
+struct Entry {
+    metadata: vfs::Metadata,
+    offset: u64,
+}
+
+impl Entry {
+    fn dir() -> Self {
+        Self {
+            metadata: vfs::Metadata {
+                file_type: vfs::FileType::Dir,
+                len: 0,
+                mode: None,
+                mtime: None,
+            },
+            offset: 0,
+        }
+    }
+}
+
+type Index = HashMap<Vec<u8>, Entry>;
+
+fn align4(n: u64) -> u64 {
+    n.next_multiple_of(4)
+}
+
+fn read_index<R: Read + Seek>(reader: &mut R) -> Result<Index, Error> {
+    let invalid = || Error::InvalidArchive("cpio archive");
+    let mut index = Index::new();
+    // Hard links share an inode, and only the last one of them has the data
+    let mut links: HashMap<u32, Vec<Vec<u8>>> = HashMap::new();
+    let mut offset = 0;
+    loop {
+        let mut header = [0; HEADER_LEN];
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(&mut header)?;
+        if &header[..6] != MAGIC && &header[..6] != CRC_MAGIC {
+            return Err(invalid());
+        }
+        let field = |i: usize| -> Result<u32, Error> {
+            std::str::from_utf8(&header[6 + i * 8..][..8])
+                .ok()
+                .and_then(|s| u32::from_str_radix(s, 16).ok())
+                .ok_or_else(invalid)
+        };
+        let inode = field(0)?;
+        let mode = field(1)?;
+        let links_count = field(4)?;
+        let mtime = field(5)?;
+        let len = field(6)? as u64;
+        let name_len = field(11)? as usize;
+        let mut name = vec![0; name_len];
+        reader.read_exact(&mut name)?;
+        let name = name.split(|&b| b == 0).next().unwrap_or_default();
+        let data_offset = align4(offset + (HEADER_LEN + name_len) as u64);
+        offset = align4(data_offset + len);
+        if name == TRAILER {
+            break;
+        }
+        let path = vfs::path::normalize(name);
+        if path.is_empty() {
+            continue;
+        }
+        let file_type = match mode & MODE_TYPE_MASK {
+            MODE_DIR => vfs::FileType::Dir,
+            MODE_FILE => vfs::FileType::File,
+            MODE_SYMLINK => vfs::FileType::SymLink,
+            // Device nodes and FIFOs have no representation in `vfs::FileType`
+            _ => continue,
+        };
+        if file_type == vfs::FileType::File && links_count > 1 {
+            links.entry(inode).or_default().push(path.clone());
+        }
+        let mut components = vfs::path::components(&path);
+        while components.pop().is_some() && !components.is_empty() {
+            index
+                .entry(components.join(b"/".as_slice()))
+                .or_insert_with(Entry::dir);
+        }
+        index.insert(
+            path,
+            Entry {
+                metadata: vfs::Metadata {
+                    file_type,
+                    len: if file_type == vfs::FileType::File {
+                        len
+                    } else {
+                        0
+                    },
+                    mode: Some(mode & !MODE_TYPE_MASK),
+                    mtime: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(mtime as u64)),
+                },
+                offset: data_offset,
+            },
+        );
+    }
+    for paths in links.into_values() {
+        let data = paths
+            .iter()
+            .filter_map(|path| index.get(path))
+            .find(|entry| entry.metadata.len > 0)
+            .cloned();
+        if let Some(data) = data {
+            for path in paths {
+                index.insert(path, data.clone());
+            }
+        }
+    }
+    Ok(index)
+}
+
+/// A cpio archive, indexed by its headers on first access. Files are views directly into the
+/// backing IO.
+pub(crate) struct CpioFs<R: Read + Seek> {
+    reader: Rc<RefCell<R>>,
+    index: Option<Index>,
+}
+
+impl<R: Read + Seek> CpioFs<R> {
+    pub(crate) fn new(io: R) -> Self {
+        Self {
+            reader: Rc::new(RefCell::new(io)),
+            index: None,
+        }
+    }
+
+    fn entry(&mut self, path: &[u8]) -> Result<Entry, Error> {
+        if self.index.is_none() {
+            self.index = Some(read_index(&mut *self.reader.borrow_mut())?);
+        }
+        let path = vfs::path::normalize(path);
+        if path.is_empty() {
+            return Ok(Entry::dir());
+        }
+        let index = self.index.as_ref().unwrap();
+        index.get(&path).cloned().ok_or(Error::NotFound)
+    }
+
+    pub(crate) fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Error> {
+        Ok(self.entry(path)?.metadata)
+    }
+
+    pub(crate) fn open(&mut self, path: &[u8]) -> Result<WindowReadSeek<R>, Error> {
+        let entry = self.entry(path)?;
+        if entry.metadata.file_type != vfs::FileType::File {
+            return Err(Error::NotAFile);
+        }
+        Ok(WindowReadSeek::new(
+            self.reader.clone(),
+            entry.offset,
+            entry.metadata.len,
+        ))
+    }
+}
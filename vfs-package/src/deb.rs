@@ -0,0 +1,122 @@
+//! Debian binary packages, which are ar archives of a control and a data tar archive.
+
+use crate::{decompress, Error, Package, Payload};
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    io::{Read, Seek, SeekFrom},
+    rc::Rc,
+};
+use vfs::{Fs, IoBackedFs};
+use vfs_tar::TarFs;
+use window_read_seek::WindowReadSeek;
+
+const AR_MAGIC: &[u8] = b"!<arch>\n";
+const AR_HEADER_LEN: u64 = 60;
+const AR_HEADER_END: &[u8] = b"`\n";
+
+/// A member of an ar archive, with the offset and length of its data.
+struct Member {
+    name: Vec<u8>,
+    offset: u64,
+    len: u64,
+}
+
+fn ar_members<R: Read + Seek>(reader: &RefCell<R>) -> Result<Vec<Member>, Error> {
+    let invalid = || Error::InvalidArchive("ar archive");
+    let mut reader = reader.borrow_mut();
+    let len = reader.seek(SeekFrom::End(0))?;
+    let mut magic = [0; AR_MAGIC.len()];
+    reader.seek(SeekFrom::Start(0))?;
+    reader.read_exact(&mut magic)?;
+    if magic != AR_MAGIC {
+        return Err(invalid());
+    }
+    let mut members = Vec::new();
+    let mut offset = AR_MAGIC.len() as u64;
+    while offset + AR_HEADER_LEN <= len {
+        let mut header = [0; AR_HEADER_LEN as usize];
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(&mut header)?;
+        if &header[58..] != AR_HEADER_END {
+            return Err(invalid());
+        }
+        // GNU ar terminates names with a slash
+        let name = header[..16].trim_ascii_end();
+        let name = name.strip_suffix(b"/").unwrap_or(name);
+        let member_len = std::str::from_utf8(&header[48..58])
+            .ok()
+            .and_then(|s| s.trim().parse::<u64>().ok())
+            .ok_or_else(invalid)?;
+        members.push(Member {
+            name: name.to_vec(),
+            offset: offset + AR_HEADER_LEN,
+            len: member_len,
+        });
+        // Members are aligned to 2 bytes
+        offset = (offset + AR_HEADER_LEN + member_len).next_multiple_of(2);
+    }
+    Ok(members)
+}
+
+/// A field name and its value.
+type Field = (Vec<u8>, Vec<u8>);
+
+/// Parses the fields of a control file, each value followed by a newline. Continuation lines of
+/// multiline fields are kept as they are, including their leading space.
+fn control_fields(data: &[u8]) -> Result<Vec<Field>, Error> {
+    let invalid = || Error::InvalidArchive("control file");
+    let mut fields: Vec<Field> = Vec::new();
+    for line in data.split(|&b| b == b'\n') {
+        if line.trim_ascii().is_empty() {
+            continue;
+        }
+        if line.starts_with(b" ") || line.starts_with(b"\t") {
+            let (_, value) = fields.last_mut().ok_or_else(invalid)?;
+            value.extend_from_slice(line);
+            value.push(b'\n');
+            continue;
+        }
+        let colon = line.iter().position(|&b| b == b':').ok_or_else(invalid)?;
+        let mut value = line[colon + 1..].trim_ascii().to_vec();
+        value.push(b'\n');
+        fields.push((line[..colon].trim_ascii().to_vec(), value));
+    }
+    Ok(fields)
+}
+
+pub(crate) fn open<R: Read + Seek>(reader: &Rc<RefCell<R>>) -> Result<Package<R>, Error> {
+    let members = ar_members(reader)?;
+    // Members are named e.g. `control.tar.xz` or `data.tar.zst`
+    let member = |prefix: &[u8], what| {
+        members
+            .iter()
+            .find(|m| m.name.starts_with(prefix))
+            .map(|m| WindowReadSeek::new(reader.clone(), m.offset, m.len))
+            .ok_or(Error::MissingMember(what))
+    };
+
+    let mut control = TarFs::from_io(decompress(member(b"control.tar", "control archive")?)?, ());
+    let mut meta = BTreeMap::new();
+    let paths: Vec<Vec<u8>> = control.paths()?.map(<[u8]>::to_vec).collect();
+    for path in paths {
+        if path.contains(&b'/') || control.metadata(&path)?.file_type != vfs::FileType::File {
+            continue;
+        }
+        let mut data = Vec::new();
+        control.open(&path)?.read_to_end(&mut data)?;
+        if path == b"control" {
+            meta.extend(control_fields(&data)?);
+        }
+        meta.insert(path, data);
+    }
+    if !meta.contains_key(b"control".as_slice()) {
+        return Err(Error::MissingMember("control file"));
+    }
+
+    let data = TarFs::from_io(decompress(member(b"data.tar", "data archive")?)?, ());
+    Ok(Package {
+        payload: Payload::Tar(data),
+        meta,
+    })
+}
@@ -0,0 +1,247 @@
+mod cpio;
+mod deb;
+mod rpm;
+
+use cpio::CpioFs;
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    io::{Cursor, Read, Seek, SeekFrom},
+    rc::Rc,
+};
+use vfs::{Fs, IoBackedFs};
+use vfs_compress::{Bzip2Fs, GzipFs, XzFs, ZstdFs};
+use vfs_tar::TarFs;
+use window_read_seek::WindowReadSeek;
+
+/// Directory holding the package metadata, next to the payload
+const META_DIR: &[u8] = b".meta";
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+const XZ_MAGIC: &[u8] = b"\xfd7zXZ\0";
+const ZSTD_MAGIC: &[u8] = b"\x28\xb5\x2f\xfd";
+const BZIP2_MAGIC: &[u8] = b"BZh";
+/// Legacy `.lzma` streams, which have no real magic but start with these properties
+const LZMA_MAGIC: &[u8] = b"\x5d\0\0";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed reading from backing IO")]
+    Io(#[from] std::io::Error),
+    #[error("failed reading tar archive")]
+    Tar(#[from] vfs_tar::Error),
+    #[error("failed decompressing package member")]
+    Compress(#[from] vfs_compress::Error),
+    #[error("invalid {0}")]
+    InvalidArchive(&'static str),
+    #[error("package has no {0}")]
+    MissingMember(&'static str),
+    #[error("unsupported compression format")]
+    UnsupportedCompression,
+    #[error("entry not found")]
+    NotFound,
+    #[error("entry is not a regular file")]
+    NotAFile,
+}
+
+/// A member of a package, decompressed if needed.
+enum Stream<R: Read + Seek> {
+    Plain(WindowReadSeek<R>),
+    Compressed(Box<vfs_compress::File<WindowReadSeek<R>>>),
+}
+
+impl<R: Read + Seek> Read for Stream<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Plain(x) => x.read(buf),
+            Stream::Compressed(x) => x.read(buf),
+        }
+    }
+}
+
+impl<R: Read + Seek> Seek for Stream<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            Stream::Plain(x) => x.seek(pos),
+            Stream::Compressed(x) => x.seek(pos),
+        }
+    }
+}
+
+/// Detects the compression of a member by its magic, as neither format reliably names it.
+fn decompress<R: Read + Seek>(mut data: WindowReadSeek<R>) -> Result<Stream<R>, Error> {
+    let mut magic = Vec::new();
+    (&mut data).take(6).read_to_end(&mut magic)?;
+    data.seek(SeekFrom::Start(0))?;
+    let compressed = if magic.starts_with(GZIP_MAGIC) {
+        GzipFs::from_io(data, ()).open(b"")?
+    } else if magic.starts_with(XZ_MAGIC) {
+        XzFs::from_io(data, ()).open(b"")?
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        ZstdFs::from_io(data, ()).open(b"")?
+    } else if magic.starts_with(BZIP2_MAGIC) {
+        Bzip2Fs::from_io(data, ()).open(b"")?
+    } else if magic.starts_with(LZMA_MAGIC) {
+        return Err(Error::UnsupportedCompression);
+    } else {
+        return Ok(Stream::Plain(data));
+    };
+    Ok(Stream::Compressed(Box::new(compressed)))
+}
+
+enum Payload<R: Read + Seek> {
+    Tar(TarFs<Stream<R>>),
+    Cpio(CpioFs<Stream<R>>),
+}
+
+struct Package<R: Read + Seek> {
+    payload: Payload<R>,
+    /// Contents of the files in the metadata directory by name
+    meta: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+type OpenPackage<R> = fn(&Rc<RefCell<R>>) -> Result<Package<R>, Error>;
+
+enum FileInner<R: Read + Seek> {
+    Tar(vfs_tar::File<Stream<R>>),
+    Cpio(WindowReadSeek<Stream<R>>),
+    Meta(Cursor<Vec<u8>>),
+}
+
+pub struct File<R: Read + Seek>(FileInner<R>);
+
+impl<R: Read + Seek> Read for File<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match &mut self.0 {
+            FileInner::Tar(x) => x.read(buf),
+            FileInner::Cpio(x) => x.read(buf),
+            FileInner::Meta(x) => x.read(buf),
+        }
+    }
+}
+
+impl<R: Read + Seek> Seek for File<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match &mut self.0 {
+            FileInner::Tar(x) => x.seek(pos),
+            FileInner::Cpio(x) => x.seek(pos),
+            FileInner::Meta(x) => x.seek(pos),
+        }
+    }
+}
+
+/// Where a path of the package filesystem leads.
+enum Target<'a> {
+    MetaDir,
+    Meta(&'a [u8]),
+    Payload,
+}
+
+fn target(path: &[u8]) -> Target<'_> {
+    match vfs::path::components(path).as_slice() {
+        [dir] if *dir == META_DIR => Target::MetaDir,
+        [dir, name] if *dir == META_DIR => Target::Meta(name),
+        _ => Target::Payload,
+    }
+}
+
+struct PackageFs<R: Read + Seek> {
+    reader: Rc<RefCell<R>>,
+    package: Option<Package<R>>,
+}
+
+impl<R: Read + Seek> PackageFs<R> {
+    fn new(io: R) -> Self {
+        Self {
+            reader: Rc::new(RefCell::new(io)),
+            package: None,
+        }
+    }
+
+    fn package(&mut self, open: OpenPackage<R>) -> Result<&mut Package<R>, Error> {
+        if self.package.is_none() {
+            self.package = Some(open(&self.reader)?);
+        }
+        Ok(self.package.as_mut().unwrap())
+    }
+
+    fn metadata(&mut self, path: &[u8], open: OpenPackage<R>) -> Result<vfs::Metadata, Error> {
+        let package = self.package(open)?;
+        let (file_type, len) = match target(path) {
+            Target::MetaDir => (vfs::FileType::Dir, 0),
+            Target::Meta(name) => {
+                let data = package.meta.get(name).ok_or(Error::NotFound)?;
+                (vfs::FileType::File, data.len() as u64)
+            }
+            Target::Payload => {
+                return match &mut package.payload {
+                    Payload::Tar(fs) => Ok(fs.metadata(path)?),
+                    Payload::Cpio(fs) => fs.metadata(path),
+                }
+            }
+        };
+        Ok(vfs::Metadata {
+            file_type,
+            len,
+            mode: None,
+            mtime: None,
+        })
+    }
+
+    fn open(&mut self, path: &[u8], open: OpenPackage<R>) -> Result<File<R>, Error> {
+        let package = self.package(open)?;
+        Ok(File(match target(path) {
+            Target::MetaDir => return Err(Error::NotAFile),
+            Target::Meta(name) => {
+                let data = package.meta.get(name).ok_or(Error::NotFound)?;
+                FileInner::Meta(Cursor::new(data.clone()))
+            }
+            Target::Payload => match &mut package.payload {
+                Payload::Tar(fs) => FileInner::Tar(fs.open(path)?),
+                Payload::Cpio(fs) => FileInner::Cpio(fs.open(path)?),
+            },
+        }))
+    }
+}
+
+macro_rules! impl_fs {
+    ($(#[$attr:meta])* $T:ident, $open:path) => {
+        $(#[$attr])*
+        pub struct $T<R: Read + Seek>(PackageFs<R>);
+
+        impl<R: Read + Seek> vfs::IoBackedFs<R> for $T<R> {
+            type Password = ();
+
+            fn from_io(io: R, _password: Self::Password) -> Self {
+                Self(PackageFs::new(io))
+            }
+        }
+
+        impl<R: Read + Seek> vfs::Fs for $T<R> {
+            type Path = [u8];
+            type Error = Error;
+            type File = File<R>;
+
+            fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Error> {
+                self.0.metadata(path, $open)
+            }
+
+            fn open(&mut self, path: &[u8]) -> Result<Self::File, Error> {
+                self.0.open(path, $open)
+            }
+        }
+    };
+}
+
+impl_fs!(
+    /// A Debian binary package. The root is the data archive, and `/.meta` holds the members of
+    /// the control archive (`control`, `md5sums`, maintainer scripts, ...) as well as one file
+    /// per field of `control`, such as `/.meta/Version`.
+    DebFs,
+    deb::open
+);
+impl_fs!(
+    /// An RPM package. The root is the cpio payload, and `/.meta` holds one file per header tag
+    /// in `rpm --queryformat` naming, such as `/.meta/Version` or `/.meta/Requires`.
+    RpmFs,
+    rpm::open
+);
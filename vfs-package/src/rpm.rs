@@ -0,0 +1,165 @@
+//! RPM packages, which are a lead, a signature header and a main header followed by a
+//! compressed cpio payload.
+
+use crate::{cpio::CpioFs, decompress, Error, Package, Payload};
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    io::{Read, Seek, SeekFrom},
+    rc::Rc,
+};
+use vfs::bytes::be_u32_at;
+use window_read_seek::WindowReadSeek;
+
+const LEAD_MAGIC: &[u8] = b"\xed\xab\xee\xdb";
+const LEAD_LEN: u64 = 96;
+const HEADER_MAGIC: &[u8] = b"\x8e\xad\xe8\x01";
+const HEADER_INTRO_LEN: u64 = 16;
+const INDEX_ENTRY_LEN: usize = 16;
+/// Upper bound on the size of a header, which is read into memory
+const MAX_HEADER_LEN: u64 = 64 << 20;
+
+const TYPE_CHAR: u32 = 1;
+const TYPE_INT8: u32 = 2;
+const TYPE_INT16: u32 = 3;
+const TYPE_INT32: u32 = 4;
+const TYPE_INT64: u32 = 5;
+const TYPE_STRING: u32 = 6;
+const TYPE_STRING_ARRAY: u32 = 8;
+/// Translated strings, of which the first is the untranslated one
+const TYPE_I18NSTRING: u32 = 9;
+
+/// Header tags exposed in `/.meta`, by their `rpm --queryformat` names
+const TAGS: &[(u32, &str)] = &[
+    (1000, "Name"),
+    (1001, "Version"),
+    (1002, "Release"),
+    (1003, "Epoch"),
+    (1004, "Summary"),
+    (1005, "Description"),
+    (1006, "BuildTime"),
+    (1007, "BuildHost"),
+    (1009, "Size"),
+    (1011, "Vendor"),
+    (1014, "License"),
+    (1015, "Packager"),
+    (1016, "Group"),
+    (1020, "URL"),
+    (1021, "OS"),
+    (1022, "Arch"),
+    (1023, "PreIn"),
+    (1024, "PostIn"),
+    (1025, "PreUn"),
+    (1026, "PostUn"),
+    (1044, "SourceRPM"),
+    (1047, "Provides"),
+    (1049, "Requires"),
+    (1054, "Conflicts"),
+    (1090, "Obsoletes"),
+    (1124, "PayloadFormat"),
+    (1125, "PayloadCompressor"),
+];
+
+/// A header structure, split into its index entries and its data store.
+struct Header {
+    index: Vec<u8>,
+    store: Vec<u8>,
+    /// Length of the header in the package
+    len: u64,
+}
+
+fn read_header<R: Read + Seek>(reader: &mut R, offset: u64) -> Result<Header, Error> {
+    let invalid = || Error::InvalidArchive("rpm header");
+    let mut intro = [0; HEADER_INTRO_LEN as usize];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut intro)?;
+    if &intro[..4] != HEADER_MAGIC {
+        return Err(invalid());
+    }
+    let entries = be_u32_at(&intro, 8) as u64;
+    let store_len = be_u32_at(&intro, 12) as u64;
+    let index_len = entries * INDEX_ENTRY_LEN as u64;
+    if index_len + store_len > MAX_HEADER_LEN {
+        return Err(invalid());
+    }
+    let mut index = vec![0; index_len as usize];
+    reader.read_exact(&mut index)?;
+    let mut store = vec![0; store_len as usize];
+    reader.read_exact(&mut store)?;
+    Ok(Header {
+        index,
+        store,
+        len: HEADER_INTRO_LEN + index_len + store_len,
+    })
+}
+
+/// Formats a header entry as text, one element per line. Returns `None` for binary entries and
+/// entries that point outside the data store.
+fn entry_value(store: &[u8], kind: u32, offset: usize, count: usize) -> Option<Vec<u8>> {
+    let data = store.get(offset..)?;
+    let numbers = |size: usize, to_u64: fn(&[u8]) -> u64| {
+        let data = data.get(..count.checked_mul(size)?)?;
+        let numbers = data.chunks(size).map(|x| to_u64(x).to_string());
+        Some(numbers.collect::<Vec<_>>().join("\n").into_bytes())
+    };
+    let mut value = match kind {
+        TYPE_CHAR | TYPE_INT8 => data.get(..count)?.to_vec(),
+        TYPE_INT16 => numbers(2, |x| u16::from_be_bytes(x.try_into().unwrap()) as u64)?,
+        TYPE_INT32 => numbers(4, |x| u32::from_be_bytes(x.try_into().unwrap()) as u64)?,
+        TYPE_INT64 => numbers(8, |x| u64::from_be_bytes(x.try_into().unwrap()))?,
+        TYPE_STRING | TYPE_STRING_ARRAY | TYPE_I18NSTRING => {
+            let count = if kind == TYPE_STRING_ARRAY { count } else { 1 };
+            let strings: Vec<&[u8]> = data.split(|&b| b == 0).take(count).collect();
+            // The last string must be terminated within the store
+            let end = strings.iter().map(|s| s.len() + 1).sum::<usize>();
+            if strings.len() < count || end > data.len() {
+                return None;
+            }
+            strings.join(b"\n".as_slice())
+        }
+        _ => return None,
+    };
+    value.push(b'\n');
+    Some(value)
+}
+
+pub(crate) fn open<R: Read + Seek>(reader: &Rc<RefCell<R>>) -> Result<Package<R>, Error> {
+    let (meta, payload_offset, len) = {
+        let mut reader = reader.borrow_mut();
+        let len = reader.seek(SeekFrom::End(0))?;
+        let mut lead = [0; LEAD_LEN as usize];
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_exact(&mut lead)?;
+        if &lead[..4] != LEAD_MAGIC {
+            return Err(Error::InvalidArchive("rpm lead"));
+        }
+        // The signature header is padded to 8 bytes, unlike the main header
+        let signature = read_header(&mut *reader, LEAD_LEN)?;
+        let header_offset = (LEAD_LEN + signature.len).next_multiple_of(8);
+        let header = read_header(&mut *reader, header_offset)?;
+
+        let mut meta = BTreeMap::new();
+        for entry in header.index.chunks(INDEX_ENTRY_LEN) {
+            let tag = be_u32_at(entry, 0);
+            let Some(&(_, name)) = TAGS.iter().find(|(t, _)| *t == tag) else {
+                continue;
+            };
+            let kind = be_u32_at(entry, 4);
+            let offset = be_u32_at(entry, 8) as usize;
+            let count = be_u32_at(entry, 12) as usize;
+            if let Some(value) = entry_value(&header.store, kind, offset, count) {
+                meta.insert(name.as_bytes().to_vec(), value);
+            }
+        }
+        (meta, header_offset + header.len, len)
+    };
+    if payload_offset > len {
+        return Err(Error::MissingMember("payload"));
+    }
+
+    let payload = WindowReadSeek::new(reader.clone(), payload_offset, len - payload_offset);
+    Ok(Package {
+        payload: Payload::Cpio(CpioFs::new(decompress(payload)?)),
+        meta,
+    })
+}
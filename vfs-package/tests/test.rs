@@ -0,0 +1,163 @@
+use std::{io::Read, path::Path, process::Command};
+use vfs::{Fs, IoBackedFs};
+
+fn write(path: &Path, contents: &str) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, contents).unwrap();
+}
+
+fn read<F: Fs<Path = [u8]>>(fs: &mut F, path: &str) -> String
+where
+    F::Error: std::fmt::Debug,
+{
+    let mut contents = String::new();
+    let mut file = fs.open(path.as_bytes()).unwrap();
+    file.read_to_string(&mut contents).unwrap();
+    contents
+}
+
+fn cpio_entry(archive: &mut Vec<u8>, ino: u32, mode: u32, nlink: u32, name: &str, data: &[u8]) {
+    let fields = [ino, mode, 0, 0, nlink, 1700000000, data.len() as u32];
+    archive.extend_from_slice(b"070701");
+    for field in fields
+        .into_iter()
+        .chain([0, 0, 0, 0, name.len() as u32 + 1, 0])
+    {
+        archive.extend_from_slice(format!("{field:08x}").as_bytes());
+    }
+    archive.extend_from_slice(name.as_bytes());
+    archive.push(0);
+    archive.resize(archive.len().next_multiple_of(4), 0);
+    archive.extend_from_slice(data);
+    archive.resize(archive.len().next_multiple_of(4), 0);
+}
+
+/// Builds a header structure from tag, type, count and data of its entries.
+fn rpm_header(entries: &[(u32, u32, u32, &[u8])]) -> Vec<u8> {
+    let mut index = Vec::new();
+    let mut store = Vec::new();
+    for &(tag, kind, count, data) in entries {
+        for field in [tag, kind, store.len() as u32, count] {
+            index.extend_from_slice(&field.to_be_bytes());
+        }
+        store.extend_from_slice(data);
+    }
+    let mut header = b"\x8e\xad\xe8\x01\0\0\0\0".to_vec();
+    header.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+    header.extend_from_slice(&(store.len() as u32).to_be_bytes());
+    header.extend_from_slice(&index);
+    header.extend_from_slice(&store);
+    header
+}
+
+fn test_deb(tmp: &Path) {
+    let root = tmp.join("deb");
+    write(&root.join("usr/bin/hello"), "#!/bin/sh\necho hello\n");
+    write(&root.join("usr/share/doc/hello/copyright"), "public domain");
+    write(
+        &root.join("DEBIAN/control"),
+        "Package: hello\nVersion: 1.0-1\nArchitecture: all\nMaintainer: Someone <someone@example.com>\n\
+         Description: greeting\n long description\n .\n more\n",
+    );
+    write(&root.join("DEBIAN/postinst"), "#!/bin/sh\nexit 0\n");
+    let executable = std::os::unix::fs::PermissionsExt::from_mode(0o755);
+    std::fs::set_permissions(root.join("DEBIAN/postinst"), executable).unwrap();
+    assert!(Command::new("dpkg-deb")
+        .args(["--root-owner-group", "--build"])
+        .arg(&root)
+        .arg(tmp.join("hello.deb"))
+        .output()
+        .unwrap()
+        .status
+        .success());
+
+    let mut fs =
+        vfs_package::DebFs::from_io(std::fs::File::open(tmp.join("hello.deb")).unwrap(), ());
+    assert_eq!(read(&mut fs, "usr/bin/hello"), "#!/bin/sh\necho hello\n");
+    assert_eq!(
+        read(&mut fs, "/usr/share/doc/hello/copyright"),
+        "public domain"
+    );
+    assert_eq!(fs.metadata(b"usr").unwrap().file_type, vfs::FileType::Dir);
+    assert_eq!(fs.metadata(b".meta").unwrap().file_type, vfs::FileType::Dir);
+    assert_eq!(read(&mut fs, ".meta/Package"), "hello\n");
+    assert_eq!(read(&mut fs, "/.meta/Version"), "1.0-1\n");
+    assert_eq!(
+        read(&mut fs, ".meta/Description"),
+        "greeting\n long description\n .\n more\n"
+    );
+    assert!(read(&mut fs, ".meta/control").starts_with("Package: hello\n"));
+    assert_eq!(read(&mut fs, ".meta/postinst"), "#!/bin/sh\nexit 0\n");
+    assert_eq!(fs.metadata(b".meta/Version").unwrap().len, 6);
+    assert!(fs.metadata(b".meta/Missing").is_err());
+    assert!(fs.metadata(b"DEBIAN/control").is_err());
+    assert!(fs.open(b".meta").is_err());
+}
+
+fn test_rpm(tmp: &Path) {
+    let mut cpio = Vec::new();
+    cpio_entry(&mut cpio, 1, 0o040755, 2, "./usr/bin", b"");
+    cpio_entry(&mut cpio, 2, 0o100755, 1, "./usr/bin/hello", b"hello");
+    // A hard link pair, with the data on the last link
+    cpio_entry(&mut cpio, 3, 0o100644, 2, "./etc/a", b"");
+    cpio_entry(&mut cpio, 3, 0o100644, 2, "./etc/b", b"linked");
+    cpio_entry(&mut cpio, 4, 0o120777, 1, "./etc/link", b"a");
+    cpio_entry(&mut cpio, 0, 0, 1, "TRAILER!!!", b"");
+    std::fs::write(tmp.join("payload.cpio"), &cpio).unwrap();
+    assert!(Command::new("gzip")
+        .arg(tmp.join("payload.cpio"))
+        .status()
+        .unwrap()
+        .success());
+
+    let mut rpm = b"\xed\xab\xee\xdb\x03\0".to_vec();
+    rpm.resize(96, 0);
+    rpm.extend_from_slice(&rpm_header(&[(1000, 7, 3, b"sig")]));
+    rpm.resize(rpm.len().next_multiple_of(8), 0);
+    rpm.extend_from_slice(&rpm_header(&[
+        (1000, 6, 1, b"hello\0"),
+        (1001, 6, 1, b"2.1\0"),
+        (1004, 9, 2, b"Says hello\0Sagt hallo\0"),
+        (1009, 4, 1, &11u32.to_be_bytes()),
+        (1049, 8, 2, b"libc.so.6\0/bin/sh\0"),
+        // Unknown tags and entries outside the store are ignored
+        (5000, 6, 1, b"x\0"),
+        (1022, 6, 1, b"noarch"),
+    ]));
+    rpm.extend_from_slice(&std::fs::read(tmp.join("payload.cpio.gz")).unwrap());
+    std::fs::write(tmp.join("hello.rpm"), &rpm).unwrap();
+
+    let mut fs =
+        vfs_package::RpmFs::from_io(std::fs::File::open(tmp.join("hello.rpm")).unwrap(), ());
+    assert_eq!(read(&mut fs, "usr/bin/hello"), "hello");
+    assert_eq!(read(&mut fs, "etc/a"), "linked");
+    assert_eq!(read(&mut fs, "/etc/b"), "linked");
+    let metadata = fs.metadata(b"usr/bin/hello").unwrap();
+    assert_eq!(metadata.mode, Some(0o755));
+    assert_eq!(fs.metadata(b"usr").unwrap().file_type, vfs::FileType::Dir);
+    assert_eq!(
+        fs.metadata(b"etc/link").unwrap().file_type,
+        vfs::FileType::SymLink
+    );
+    assert_eq!(read(&mut fs, ".meta/Name"), "hello\n");
+    assert_eq!(read(&mut fs, ".meta/Version"), "2.1\n");
+    assert_eq!(read(&mut fs, ".meta/Summary"), "Says hello\n");
+    assert_eq!(read(&mut fs, ".meta/Size"), "11\n");
+    assert_eq!(read(&mut fs, ".meta/Requires"), "libc.so.6\n/bin/sh\n");
+    assert!(fs.metadata(b".meta/Arch").is_err());
+    assert!(fs.metadata(b"etc/missing").is_err());
+
+    // Not an RPM at all
+    let mut fs = vfs_package::RpmFs::from_io(std::io::Cursor::new(vec![0; 200]), ());
+    assert!(matches!(
+        fs.metadata(b""),
+        Err(vfs_package::Error::InvalidArchive(_))
+    ));
+}
+
+#[test]
+fn test() {
+    let tmp = tempfile::tempdir().unwrap();
+    test_deb(tmp.path());
+    test_rpm(tmp.path());
+}
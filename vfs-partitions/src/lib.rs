@@ -0,0 +1,268 @@
+use std::{
+    cell::RefCell,
+    io::{Read, Seek, SeekFrom},
+    rc::Rc,
+};
+use vfs::{
+    bytes::{u32_at, u64_at},
+    io::read_at,
+};
+use window_read_seek::WindowReadSeek;
+
+const BOOT_SIGNATURE: [u8; 2] = [0x55, 0xaa];
+const MBR_ENTRIES_START: usize = 446;
+const MBR_ENTRY_LEN: usize = 16;
+const MBR_TYPE_EMPTY: u8 = 0x00;
+const MBR_TYPES_EXTENDED: [u8; 3] = [0x05, 0x0f, 0x85];
+const MBR_TYPE_GPT_PROTECTIVE: u8 = 0xee;
+/// Logical partitions are numbered after the four primary slots, as Linux does
+const FIRST_LOGICAL_NUMBER: usize = 5;
+/// Upper bound on extended boot records, so that loops in corrupt images terminate
+const MAX_LOGICAL_PARTITIONS: usize = 256;
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+/// GPT disks use the logical sector size of the device, which is one of these in practice
+const GPT_SECTOR_LENS: [u64; 2] = [512, 4096];
+const GPT_MAX_ENTRIES: u32 = 1024;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed reading from backing IO")]
+    Io(#[from] std::io::Error),
+    #[error("no MBR or GPT partition table found")]
+    NoPartitionTable,
+    #[error("invalid partition table")]
+    InvalidPartitionTable,
+    #[error("entry not found")]
+    NotFound,
+    #[error("entry is not a regular file")]
+    NotAFile,
+}
+
+/// Converts a sector number or count to bytes, rejecting values that overflow.
+fn sectors_to_bytes(sectors: u64, sector_len: u64) -> Result<u64, Error> {
+    sectors
+        .checked_mul(sector_len)
+        .ok_or(Error::InvalidPartitionTable)
+}
+
+struct Partition {
+    /// The partition number, which names it as `p<number>`
+    number: usize,
+    /// The GPT partition name, if any
+    label: Option<String>,
+    start: u64,
+    len: u64,
+}
+
+/// Parses the four entries of an MBR or extended boot record as `(type, start, len)` in
+/// sectors, including empty ones.
+fn mbr_entries(sector: &[u8]) -> Result<[(u8, u64, u64); 4], Error> {
+    if sector[510..512] != BOOT_SIGNATURE {
+        return Err(Error::InvalidPartitionTable);
+    }
+    Ok([0, 1, 2, 3].map(|i| {
+        let entry = &sector[MBR_ENTRIES_START + i * MBR_ENTRY_LEN..][..MBR_ENTRY_LEN];
+        (entry[4], u32_at(entry, 8) as u64, u32_at(entry, 12) as u64)
+    }))
+}
+
+/// Walks the chain of extended boot records of an extended partition, each of which describes
+/// one logical partition and links to the next record.
+fn logical_partitions<R: Read + Seek>(
+    reader: &RefCell<R>,
+    extended_start: u64,
+    out: &mut Vec<Partition>,
+) -> Result<(), Error> {
+    let mut ebr = extended_start;
+    for number in FIRST_LOGICAL_NUMBER..FIRST_LOGICAL_NUMBER + MAX_LOGICAL_PARTITIONS {
+        let entries = mbr_entries(&read_at(reader, sectors_to_bytes(ebr, 512)?, 512)?)?;
+        let (kind, start, len) = entries[0];
+        if kind != MBR_TYPE_EMPTY && len != 0 {
+            // Logical partitions are relative to their record
+            out.push(Partition {
+                number,
+                label: None,
+                start: sectors_to_bytes(ebr + start, 512)?,
+                len: sectors_to_bytes(len, 512)?,
+            });
+        }
+        let (kind, next, _) = entries[1];
+        if kind == MBR_TYPE_EMPTY || next == 0 {
+            return Ok(());
+        }
+        // Links are relative to the extended partition
+        ebr = extended_start + next;
+    }
+    Err(Error::InvalidPartitionTable)
+}
+
+/// Reads the GPT header of a disk with the given sector size, falling back to the backup
+/// header in the last sector if the primary one is damaged.
+fn gpt_header<R: Read + Seek>(
+    reader: &RefCell<R>,
+    sector_len: u64,
+) -> Result<Option<Vec<u8>>, Error> {
+    let primary = read_at(reader, sector_len, sector_len as usize)?;
+    if &primary[..8] == GPT_SIGNATURE {
+        return Ok(Some(primary));
+    }
+    let disk_len = reader.borrow_mut().seek(SeekFrom::End(0))?;
+    let Some(last) = (disk_len / sector_len).checked_sub(1) else {
+        return Ok(None);
+    };
+    let backup = read_at(reader, last * sector_len, sector_len as usize)?;
+    Ok((&backup[..8] == GPT_SIGNATURE).then_some(backup))
+}
+
+fn gpt_partitions<R: Read + Seek>(reader: &RefCell<R>) -> Result<Vec<Partition>, Error> {
+    let mut header = None;
+    for sector_len in GPT_SECTOR_LENS {
+        if let Some(h) = gpt_header(reader, sector_len)? {
+            header = Some((h, sector_len));
+            break;
+        }
+    }
+    let (header, sector_len) = header.ok_or(Error::InvalidPartitionTable)?;
+    let entries_start = sectors_to_bytes(u64_at(&header, 72), sector_len)?;
+    let entry_count = u32_at(&header, 80);
+    let entry_len = u32_at(&header, 84) as usize;
+    if entry_count > GPT_MAX_ENTRIES || !(128..=4096).contains(&entry_len) {
+        return Err(Error::InvalidPartitionTable);
+    }
+    let entries = read_at(reader, entries_start, entry_count as usize * entry_len)?;
+    let mut partitions = Vec::new();
+    for (i, entry) in entries.chunks_exact(entry_len).enumerate() {
+        // Unused entries have a zero type GUID
+        if entry[..16].iter().all(|&b| b == 0) {
+            continue;
+        }
+        let (first, last) = (u64_at(entry, 32), u64_at(entry, 40));
+        if last < first {
+            return Err(Error::InvalidPartitionTable);
+        }
+        let units: Vec<u16> = entry[56..128]
+            .chunks_exact(2)
+            .map(|u| u16::from_le_bytes([u[0], u[1]]))
+            .take_while(|&u| u != 0)
+            .collect();
+        let label = String::from_utf16_lossy(&units);
+        partitions.push(Partition {
+            number: i + 1,
+            label: Some(label).filter(|l| !l.is_empty() && !l.contains('/')),
+            start: sectors_to_bytes(first, sector_len)?,
+            len: sectors_to_bytes(
+                (last - first)
+                    .checked_add(1)
+                    .ok_or(Error::InvalidPartitionTable)?,
+                sector_len,
+            )?,
+        });
+    }
+    Ok(partitions)
+}
+
+fn partitions<R: Read + Seek>(reader: &RefCell<R>) -> Result<Vec<Partition>, Error> {
+    let mbr = read_at(reader, 0, 512)?;
+    let entries = mbr_entries(&mbr).map_err(|_| Error::NoPartitionTable)?;
+    if entries
+        .iter()
+        .any(|&(kind, ..)| kind == MBR_TYPE_GPT_PROTECTIVE)
+    {
+        return gpt_partitions(reader);
+    }
+    let mut partitions = Vec::new();
+    for (i, &(kind, start, len)) in entries.iter().enumerate() {
+        if kind == MBR_TYPE_EMPTY || len == 0 {
+            continue;
+        }
+        if MBR_TYPES_EXTENDED.contains(&kind) {
+            logical_partitions(reader, start, &mut partitions)?;
+        } else {
+            partitions.push(Partition {
+                number: i + 1,
+                label: None,
+                start: sectors_to_bytes(start, 512)?,
+                len: sectors_to_bytes(len, 512)?,
+            });
+        }
+    }
+    if partitions.is_empty() {
+        // Filesystems such as FAT also end their first sector with the boot signature
+        return Err(Error::NoPartitionTable);
+    }
+    partitions.sort_by_key(|p| p.number);
+    Ok(partitions)
+}
+
+/// The partitions of a disk image with an MBR or GPT partition table, as files named `p1`,
+/// `p2` and so on, or by their GPT partition names. Logical partitions inside an MBR extended
+/// partition are numbered from `p5`, as Linux does.
+pub struct PartitionsFs<R: Read + Seek> {
+    reader: Rc<RefCell<R>>,
+    /// Parsed on first use
+    partitions: Option<Vec<Partition>>,
+}
+
+impl<R: Read + Seek> PartitionsFs<R> {
+    /// Finds a partition by its number or GPT name. Numbers take precedence over names.
+    fn partition(&mut self, name: &[u8]) -> Result<&Partition, Error> {
+        if self.partitions.is_none() {
+            self.partitions = Some(partitions(&self.reader)?);
+        }
+        let partitions = self.partitions.as_ref().unwrap();
+        let number = name
+            .strip_prefix(b"p")
+            .and_then(|n| std::str::from_utf8(n).ok())
+            .and_then(|n| n.parse::<usize>().ok());
+        partitions
+            .iter()
+            .find(|p| Some(p.number) == number)
+            .or_else(|| {
+                partitions
+                    .iter()
+                    .find(|p| p.label.as_ref().is_some_and(|l| l.as_bytes() == name))
+            })
+            .ok_or(Error::NotFound)
+    }
+}
+
+impl<R: Read + Seek> vfs::IoBackedFs<R> for PartitionsFs<R> {
+    type Password = ();
+
+    fn from_io(io: R, _password: Self::Password) -> Self {
+        Self {
+            reader: Rc::new(RefCell::new(io)),
+            partitions: None,
+        }
+    }
+}
+
+impl<R: Read + Seek> vfs::Fs for PartitionsFs<R> {
+    type Path = [u8];
+    type Error = Error;
+    type File = WindowReadSeek<R>;
+
+    fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Self::Error> {
+        let (file_type, len) = match vfs::path::components(path)[..] {
+            [] => (vfs::FileType::Dir, 0),
+            [name] => (vfs::FileType::File, self.partition(name)?.len),
+            _ => return Err(Error::NotFound),
+        };
+        Ok(vfs::Metadata {
+            file_type,
+            len,
+            mode: None,
+            mtime: None,
+        })
+    }
+
+    fn open(&mut self, path: &[u8]) -> Result<Self::File, Self::Error> {
+        let partition = match vfs::path::components(path)[..] {
+            [] => return Err(Error::NotAFile),
+            [name] => self.partition(name)?,
+            _ => return Err(Error::NotFound),
+        };
+        let (start, len) = (partition.start, partition.len);
+        Ok(WindowReadSeek::new(self.reader.clone(), start, len))
+    }
+}
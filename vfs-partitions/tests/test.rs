@@ -0,0 +1,104 @@
+use std::io::{Read, Seek, SeekFrom};
+use vfs::{Fs, IoBackedFs};
+
+/// Writes an MBR or extended boot record with entries given as `(index, type, start, len)`.
+fn write_mbr(sector: &mut [u8], entries: &[(usize, u8, u32, u32)]) {
+    sector[446..510].fill(0);
+    for &(index, kind, start, len) in entries {
+        let entry = &mut sector[446 + index * 16..][..16];
+        entry[4] = kind;
+        entry[8..12].copy_from_slice(&start.to_le_bytes());
+        entry[12..16].copy_from_slice(&len.to_le_bytes());
+    }
+    sector[510..512].copy_from_slice(&[0x55, 0xaa]);
+}
+
+/// A disk of `sectors` sectors with contents that differ between sectors.
+fn patterned_disk(sectors: usize) -> Vec<u8> {
+    (0..sectors * 512).map(|i| (i / 512 + i) as u8).collect()
+}
+
+/// Checks partitions given as names and sector ranges.
+fn check(disk: Vec<u8>, partitions: &[(&str, u64, u64)]) {
+    let mut fs = vfs_partitions::PartitionsFs::from_io(std::io::Cursor::new(disk.clone()), ());
+    assert_eq!(fs.metadata(b"").unwrap().file_type, vfs::FileType::Dir);
+    for &(name, start, len) in partitions {
+        let metadata = fs.metadata(name.as_bytes()).unwrap();
+        assert_eq!(metadata.file_type, vfs::FileType::File);
+        assert_eq!(metadata.len, len * 512);
+        let mut file = fs.open(name.as_bytes()).unwrap();
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).unwrap();
+        assert!(contents == disk[start as usize * 512..][..len as usize * 512]);
+        file.seek(SeekFrom::End(-1)).unwrap();
+        assert_eq!(file.read(&mut [0; 10]).unwrap(), 1);
+    }
+    assert!(fs.metadata(b"p9").is_err());
+    assert!(fs.metadata(b"p1/x").is_err());
+    assert!(fs.open(b"").is_err());
+}
+
+#[test]
+fn test() {
+    // Two primary partitions and an extended partition with two logical ones
+    let mut disk = patterned_disk(200);
+    write_mbr(
+        &mut disk,
+        &[(0, 0x83, 10, 20), (1, 0x0c, 30, 10), (3, 0x05, 100, 100)],
+    );
+    write_mbr(
+        &mut disk[100 * 512..],
+        &[(0, 0x83, 2, 30), (1, 0x05, 50, 50)],
+    );
+    write_mbr(&mut disk[150 * 512..], &[(0, 0x83, 2, 40)]);
+    let partitions = [
+        ("p1", 10, 20),
+        ("p2", 30, 10),
+        ("p5", 102, 30),
+        ("p6", 152, 40),
+    ];
+    check(disk, &partitions);
+
+    // A GPT disk with a protective MBR and named partitions, the second entry left unused
+    let mut disk = patterned_disk(300);
+    write_mbr(&mut disk, &[(0, 0xee, 1, 299)]);
+    disk[1024..1024 + 128 * 128].fill(0);
+    let header = &mut disk[512..1024];
+    header[..8].copy_from_slice(b"EFI PART");
+    header[72..80].copy_from_slice(&2u64.to_le_bytes());
+    header[80..84].copy_from_slice(&128u32.to_le_bytes());
+    header[84..88].copy_from_slice(&128u32.to_le_bytes());
+    for (index, first, last, name) in [(0, 40, 99, "boot"), (2, 100, 249, "root fs")] {
+        let entry = &mut disk[1024 + index * 128..][..128];
+        entry[..16].fill(0xab);
+        entry[32..40].copy_from_slice(&(first as u64).to_le_bytes());
+        entry[40..48].copy_from_slice(&(last as u64).to_le_bytes());
+        for (i, unit) in name.encode_utf16().enumerate() {
+            entry[56 + i * 2..][..2].copy_from_slice(&unit.to_le_bytes());
+        }
+    }
+    let partitions = [
+        ("p1", 40, 60),
+        ("boot", 40, 60),
+        ("p3", 100, 150),
+        ("root fs", 100, 150),
+    ];
+    check(disk.clone(), &partitions);
+
+    // Sector numbers whose byte offsets overflow
+    let mut overflowing = disk.clone();
+    overflowing[1024 + 32..][..8].copy_from_slice(&(u64::MAX / 256).to_le_bytes());
+    overflowing[1024 + 40..][..8].copy_from_slice(&u64::MAX.to_le_bytes());
+    let mut fs = vfs_partitions::PartitionsFs::from_io(std::io::Cursor::new(overflowing), ());
+    assert!(matches!(
+        fs.metadata(b"p1"),
+        Err(vfs_partitions::Error::InvalidPartitionTable)
+    ));
+    let mut overflowing = disk;
+    overflowing[512 + 72..][..8].copy_from_slice(&u64::MAX.to_le_bytes());
+    let mut fs = vfs_partitions::PartitionsFs::from_io(std::io::Cursor::new(overflowing), ());
+    assert!(matches!(
+        fs.metadata(b"p1"),
+        Err(vfs_partitions::Error::InvalidPartitionTable)
+    ));
+}
@@ -0,0 +1,248 @@
+use rusqlite::{params, Connection, OptionalExtension, MAIN_DB};
+use std::{
+    io::{Cursor, Read, Seek, SeekFrom, Write},
+    time::{Duration, SystemTime},
+};
+
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFLNK: u32 = 0o120000;
+const S_IFREG: u32 = 0o100000;
+const NEW_FILE_MODE: u32 = S_IFREG | 0o644;
+const NEW_DIR_MODE: u32 = S_IFDIR | 0o755;
+/// The schema used by `sqlite3 -A`
+const CREATE_TABLE: &str = "CREATE TABLE IF NOT EXISTS sqlar(
+    name TEXT PRIMARY KEY, mode INT, mtime INT, sz INT, data BLOB)";
+/// Whether any entry lies below the directory `?1`, as these sort between `?1/` and `?10`
+const HAS_CHILDREN: &str =
+    "SELECT EXISTS(SELECT 1 FROM sqlar WHERE name > ?1 || '/' AND name < ?1 || '0')";
+const ZLIB_LEVEL: u8 = 6;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed accessing backing IO")]
+    Io(#[from] std::io::Error),
+    #[error("failed querying sqlite database")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("invalid compressed data")]
+    InvalidData,
+    #[error("invalid entry name")]
+    InvalidName,
+    #[error("entry not found")]
+    NotFound,
+    #[error("entry is not a regular file")]
+    NotAFile,
+    #[error("entry is not a directory")]
+    NotADirectory,
+}
+
+/// Returns the name of a path in the `sqlar` table, which has no leading slash.
+fn name(path: &[u8]) -> Option<String> {
+    String::from_utf8(vfs::path::normalize(path)).ok()
+}
+
+fn has_children(db: &Connection, name: &str) -> Result<bool, Error> {
+    Ok(db.query_row(HAS_CHILDREN, [name], |row| row.get(0))?)
+}
+
+fn mode(db: &Connection, name: &str) -> Result<Option<u32>, Error> {
+    let query = "SELECT mode FROM sqlar WHERE name = ?1";
+    Ok(db.query_row(query, [name], |row| row.get(0)).optional()?)
+}
+
+fn now() -> i64 {
+    let since_epoch = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH);
+    since_epoch.map_or(0, |d| d.as_secs() as i64)
+}
+
+/// Creates the directories leading up to the last slash in `name`.
+fn create_parents(db: &Connection, name: &str) -> Result<(), Error> {
+    for (i, _) in name.match_indices('/') {
+        let dir = &name[..i];
+        let insert = "INSERT OR IGNORE INTO sqlar VALUES (?1, ?2, ?3, 0, NULL)";
+        db.execute(insert, params![dir, NEW_DIR_MODE, now()])?;
+        if mode(db, dir)?.is_some_and(|mode| mode & S_IFMT != S_IFDIR) {
+            return Err(Error::NotADirectory);
+        }
+    }
+    Ok(())
+}
+
+/// An SQLite archive, as created by `sqlite3 -A`. The database is loaded into memory on first
+/// access. It can be modified through [`vfs::WritableFs`], and written back with
+/// [`SqlarFs::flush`].
+pub struct SqlarFs<R: Read + Seek> {
+    io: R,
+    db: Option<Connection>,
+}
+
+impl<R: Read + Seek> SqlarFs<R> {
+    fn db(&mut self) -> Result<&Connection, Error> {
+        if self.db.is_none() {
+            let len = self.io.seek(SeekFrom::End(0))?;
+            self.io.rewind()?;
+            let mut db = Connection::open_in_memory()?;
+            // An empty file is an empty database, which becomes an archive on the first write
+            if len > 0 {
+                db.deserialize_read_exact(MAIN_DB, &mut self.io, len as usize, false)?;
+            }
+            self.db = Some(db);
+        }
+        Ok(self.db.as_ref().unwrap())
+    }
+
+    /// Returns the database for modification, creating the table if needed.
+    fn writable_db(&mut self) -> Result<&Connection, Error> {
+        let db = self.db()?;
+        db.execute(CREATE_TABLE, [])?;
+        Ok(db)
+    }
+}
+
+impl<R: Read + Write + Seek> SqlarFs<R> {
+    /// Writes the database back to the backing IO. Databases don't shrink without a `VACUUM`, so
+    /// this overwrites the previous contents in place.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        if let Some(db) = &self.db {
+            let data = db.serialize(MAIN_DB)?;
+            self.io.rewind()?;
+            self.io.write_all(&data)?;
+            self.io.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> vfs::IoBackedFs<R> for SqlarFs<R> {
+    type Password = ();
+
+    fn from_io(io: R, _password: Self::Password) -> Self {
+        Self { io, db: None }
+    }
+}
+
+impl<R: Read + Seek> vfs::Fs for SqlarFs<R> {
+    type Path = [u8];
+    type Error = Error;
+    type File = Cursor<Vec<u8>>;
+
+    fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Self::Error> {
+        let name = name(path).ok_or(Error::NotFound)?;
+        let db = self.db()?;
+        let dir = vfs::Metadata {
+            file_type: vfs::FileType::Dir,
+            len: 0,
+            mode: None,
+            mtime: None,
+        };
+        if name.is_empty() {
+            return Ok(dir);
+        }
+        let query = "SELECT mode, mtime, sz FROM sqlar WHERE name = ?1";
+        let row = db
+            .query_row(query, [&name], |row| {
+                Ok((
+                    row.get::<_, u32>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, i64>(2)?,
+                ))
+            })
+            .optional()?;
+        let Some((mode, mtime, len)) = row else {
+            // Directories may be implied by their contents
+            return match has_children(db, &name)? {
+                true => Ok(dir),
+                false => Err(Error::NotFound),
+            };
+        };
+        let file_type = match mode & S_IFMT {
+            S_IFDIR => vfs::FileType::Dir,
+            S_IFLNK => vfs::FileType::SymLink,
+            _ => vfs::FileType::File,
+        };
+        Ok(vfs::Metadata {
+            file_type,
+            // Symlinks have a size of -1, with the target as data
+            len: if file_type == vfs::FileType::File {
+                len.max(0) as u64
+            } else {
+                0
+            },
+            mode: Some(mode & !S_IFMT),
+            mtime: u64::try_from(mtime)
+                .ok()
+                .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs)),
+        })
+    }
+
+    fn open(&mut self, path: &[u8]) -> Result<Self::File, Self::Error> {
+        if self.metadata(path)?.file_type != vfs::FileType::File {
+            return Err(Error::NotAFile);
+        }
+        let name = name(path).ok_or(Error::NotFound)?;
+        let db = self.db()?;
+        let query = "SELECT sz, data FROM sqlar WHERE name = ?1";
+        let (len, data) = db.query_row(query, [&name], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, Option<Vec<u8>>>(1)?))
+        })?;
+        let data = data.unwrap_or_default();
+        let len = usize::try_from(len).map_err(|_| Error::InvalidData)?;
+        // Data is stored compressed only if that makes it smaller
+        if data.len() == len {
+            return Ok(Cursor::new(data));
+        }
+        let data = miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(&data, len)
+            .map_err(|_| Error::InvalidData)?;
+        if data.len() != len {
+            return Err(Error::InvalidData);
+        }
+        Ok(Cursor::new(data))
+    }
+}
+
+impl<R: Read + Seek> vfs::WritableFs for SqlarFs<R> {
+    fn write(&mut self, path: &[u8], data: &[u8]) -> Result<(), Self::Error> {
+        let name = name(path)
+            .filter(|name| !name.is_empty())
+            .ok_or(Error::InvalidName)?;
+        let db = self.writable_db()?;
+        create_parents(db, &name)?;
+        // Replacing a file keeps its permissions
+        let mode = match mode(db, &name)? {
+            Some(mode) if mode & S_IFMT == S_IFREG => mode,
+            Some(_) => return Err(Error::NotAFile),
+            None if has_children(db, &name)? => return Err(Error::NotAFile),
+            None => NEW_FILE_MODE,
+        };
+        let compressed = miniz_oxide::deflate::compress_to_vec_zlib(data, ZLIB_LEVEL);
+        let blob = if compressed.len() < data.len() {
+            &compressed
+        } else {
+            data
+        };
+        let insert = "INSERT OR REPLACE INTO sqlar VALUES (?1, ?2, ?3, ?4, ?5)";
+        db.execute(insert, params![name, mode, now(), data.len() as i64, blob])?;
+        Ok(())
+    }
+
+    fn create_dir(&mut self, path: &[u8]) -> Result<(), Self::Error> {
+        let name = name(path)
+            .filter(|name| !name.is_empty())
+            .ok_or(Error::InvalidName)?;
+        let db = self.writable_db()?;
+        // With a trailing slash, the directory itself counts as a parent
+        create_parents(db, &format!("{name}/"))
+    }
+
+    fn remove(&mut self, path: &[u8]) -> Result<(), Self::Error> {
+        let name = name(path)
+            .filter(|name| !name.is_empty())
+            .ok_or(Error::InvalidName)?;
+        let db = self.writable_db()?;
+        let delete = "DELETE FROM sqlar WHERE name = ?1 OR (name > ?1 || '/' AND name < ?1 || '0')";
+        if db.execute(delete, [&name])? == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+}
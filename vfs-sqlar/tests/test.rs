@@ -0,0 +1,109 @@
+use std::{io::Read, process::Command};
+use vfs::{Fs, IoBackedFs, WritableFs};
+
+fn read<R: Read + std::io::Seek>(fs: &mut vfs_sqlar::SqlarFs<R>, path: &str) -> String {
+    let mut contents = String::new();
+    let mut file = fs.open(path.as_bytes()).unwrap();
+    file.read_to_string(&mut contents).unwrap();
+    contents
+}
+
+#[test]
+fn test() {
+    let tmp = tempfile::tempdir().unwrap();
+    let tmp = tmp.path();
+    let dir = tmp.join("d");
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("a"), "hello").unwrap();
+    std::fs::write(dir.join("sub/big"), "x".repeat(1000)).unwrap();
+    std::os::unix::fs::symlink("a", dir.join("link")).unwrap();
+    let archive = tmp.join("t.sqlar");
+    assert!(Command::new("sqlite3")
+        .arg(&archive)
+        .arg("-Ac")
+        .arg("d")
+        .current_dir(tmp)
+        .status()
+        .unwrap()
+        .success());
+
+    // Reading an archive written by `sqlite3 -A`, with stored and compressed entries
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&archive)
+        .unwrap();
+    let mut fs = vfs_sqlar::SqlarFs::from_io(file, ());
+    assert_eq!(read(&mut fs, "d/a"), "hello");
+    assert_eq!(read(&mut fs, "/d/sub/big"), "x".repeat(1000));
+    let metadata = fs.metadata(b"d/sub/big").unwrap();
+    assert_eq!(metadata.len, 1000);
+    assert_eq!(metadata.mode, Some(0o644));
+    assert!(metadata.mtime.is_some());
+    assert_eq!(fs.metadata(b"").unwrap().file_type, vfs::FileType::Dir);
+    assert_eq!(fs.metadata(b"d/sub").unwrap().file_type, vfs::FileType::Dir);
+    assert_eq!(
+        fs.metadata(b"d/link").unwrap().file_type,
+        vfs::FileType::SymLink
+    );
+    assert!(matches!(
+        fs.open(b"d/link"),
+        Err(vfs_sqlar::Error::NotAFile)
+    ));
+    assert!(matches!(
+        fs.metadata(b"d/missing"),
+        Err(vfs_sqlar::Error::NotFound)
+    ));
+
+    // Modifying it and writing it back
+    fs.write(b"d/a", b"replaced").unwrap();
+    fs.write(b"new/deep/file", "y".repeat(500).as_bytes())
+        .unwrap();
+    fs.create_dir(b"empty").unwrap();
+    fs.remove(b"d/sub").unwrap();
+    assert!(matches!(
+        fs.write(b"d/a/below", b""),
+        Err(vfs_sqlar::Error::NotADirectory)
+    ));
+    assert!(matches!(
+        fs.write(b"new", b""),
+        Err(vfs_sqlar::Error::NotAFile)
+    ));
+    assert!(matches!(
+        fs.remove(b"d/sub"),
+        Err(vfs_sqlar::Error::NotFound)
+    ));
+    fs.flush().unwrap();
+    drop(fs);
+
+    let mut fs = vfs_sqlar::SqlarFs::from_io(std::fs::File::open(&archive).unwrap(), ());
+    assert_eq!(read(&mut fs, "d/a"), "replaced");
+    assert_eq!(read(&mut fs, "new/deep/file"), "y".repeat(500));
+    assert_eq!(
+        fs.metadata(b"new/deep").unwrap().file_type,
+        vfs::FileType::Dir
+    );
+    assert_eq!(fs.metadata(b"empty").unwrap().file_type, vfs::FileType::Dir);
+    assert!(fs.metadata(b"d/sub/big").is_err());
+
+    // `sqlite3 -A` reads back what was written
+    let out = tmp.join("out");
+    std::fs::create_dir(&out).unwrap();
+    assert!(Command::new("sqlite3")
+        .arg(&archive)
+        .arg("-Ax")
+        .current_dir(&out)
+        .status()
+        .unwrap()
+        .success());
+    assert_eq!(std::fs::read(out.join("d/a")).unwrap(), b"replaced");
+    assert_eq!(
+        std::fs::read(out.join("new/deep/file")).unwrap(),
+        "y".repeat(500).as_bytes()
+    );
+
+    // Creating an archive from an empty file
+    let mut fs = vfs_sqlar::SqlarFs::from_io(std::io::Cursor::new(Vec::new()), ());
+    fs.write(b"f", b"data").unwrap();
+    assert_eq!(read(&mut fs, "f"), "data");
+}
@@ -0,0 +1,59 @@
+use crate::Error;
+use std::io::Read;
+
+#[derive(Clone, Copy)]
+pub(crate) enum Compression {
+    Gzip,
+    Lzma,
+    Xz,
+    Lz4,
+    Zstd,
+}
+
+impl Compression {
+    pub(crate) fn from_id(id: u16) -> Result<Self, Error> {
+        match id {
+            1 => Ok(Self::Gzip),
+            2 => Ok(Self::Lzma),
+            4 => Ok(Self::Xz),
+            5 => Ok(Self::Lz4),
+            6 => Ok(Self::Zstd),
+            // LZO has no pure-Rust decoder
+            id => Err(Error::UnsupportedCompression(id)),
+        }
+    }
+
+    /// Decompresses a whole metadata or data block, which is at most `max_len` bytes long once
+    /// decompressed.
+    pub(crate) fn decompress(self, data: &[u8], max_len: usize) -> std::io::Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(max_len);
+        match self {
+            Self::Gzip => {
+                flate2::read::ZlibDecoder::new(data)
+                    .take(max_len as u64)
+                    .read_to_end(&mut out)?;
+            }
+            Self::Lzma => {
+                let stream = xz2::stream::Stream::new_lzma_decoder(u64::MAX)?;
+                xz2::read::XzDecoder::new_stream(data, stream)
+                    .take(max_len as u64)
+                    .read_to_end(&mut out)?;
+            }
+            Self::Xz => {
+                xz2::read::XzDecoder::new(data)
+                    .take(max_len as u64)
+                    .read_to_end(&mut out)?;
+            }
+            Self::Lz4 => {
+                out = lz4_flex::block::decompress(data, max_len).map_err(std::io::Error::other)?;
+            }
+            Self::Zstd => {
+                ruzstd::decoding::StreamingDecoder::new(data)
+                    .map_err(std::io::Error::other)?
+                    .take(max_len as u64)
+                    .read_to_end(&mut out)?;
+            }
+        }
+        Ok(out)
+    }
+}
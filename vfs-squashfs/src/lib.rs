@@ -0,0 +1,622 @@
+mod compression;
+
+use compression::Compression;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom},
+    rc::Rc,
+    time::{Duration, SystemTime},
+};
+use vfs::{
+    bytes::{u16_at, u32_at, u64_at},
+    io::read_at,
+};
+
+const MAGIC: u32 = 0x73717368;
+const SUPERBLOCK_LEN: usize = 96;
+const METADATA_BLOCK_LEN: usize = 8192;
+const METADATA_UNCOMPRESSED: u16 = 1 << 15;
+const DATA_UNCOMPRESSED: u32 = 1 << 24;
+const NO_FRAGMENT: u32 = 0xffffffff;
+const NO_XATTR: u32 = 0xffffffff;
+const XATTR_VALUE_OOL: u16 = 0x100;
+/// Number of decompressed metadata blocks to keep before starting over
+const METADATA_CACHE_LEN: usize = 1024;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed reading from backing IO")]
+    Io(#[from] std::io::Error),
+    #[error("invalid squashfs superblock")]
+    InvalidSuperblock,
+    #[error("unsupported squashfs version {0}.{1}")]
+    UnsupportedVersion(u16, u16),
+    #[error("unsupported compression {0}")]
+    UnsupportedCompression(u16),
+    #[error("invalid inode type {0}")]
+    InvalidInodeType(u16),
+    #[error("invalid metadata reference {0:#x}")]
+    InvalidMetadataRef(u64),
+    #[error("block list of a file of length {0} runs past the image")]
+    InvalidBlockList(u64),
+    #[error("invalid fragment index {0}")]
+    InvalidFragment(u32),
+    #[error("invalid xattr type {0}")]
+    InvalidXattrType(u16),
+    #[error("entry not found")]
+    NotFound,
+    #[error("entry is not a regular file")]
+    NotAFile,
+}
+
+struct Superblock {
+    block_size: u32,
+    fragment_count: u32,
+    compression: Compression,
+    /// Length of the image, which may be followed by padding
+    bytes_used: u64,
+    root_inode: u64,
+    xattr_id_table_start: u64,
+    inode_table_start: u64,
+    directory_table_start: u64,
+    fragment_table_start: u64,
+}
+
+impl Superblock {
+    fn parse(buf: &[u8]) -> Result<Self, Error> {
+        if u32_at(buf, 0) != MAGIC {
+            return Err(Error::InvalidSuperblock);
+        }
+        let (major, minor) = (u16_at(buf, 28), u16_at(buf, 30));
+        if major != 4 {
+            return Err(Error::UnsupportedVersion(major, minor));
+        }
+        let block_size = u32_at(buf, 12);
+        if !block_size.is_power_of_two() || block_size > 1 << 20 {
+            return Err(Error::InvalidSuperblock);
+        }
+        Ok(Self {
+            block_size,
+            fragment_count: u32_at(buf, 16),
+            compression: Compression::from_id(u16_at(buf, 20))?,
+            root_inode: u64_at(buf, 32),
+            bytes_used: u64_at(buf, 40),
+            xattr_id_table_start: u64_at(buf, 56),
+            inode_table_start: u64_at(buf, 64),
+            directory_table_start: u64_at(buf, 72),
+            fragment_table_start: u64_at(buf, 80),
+        })
+    }
+}
+
+/// A position in a metadata table: the image offset of a metadata block and an offset into its
+/// decompressed contents.
+#[derive(Clone, Copy)]
+struct MetadataCursor {
+    block: u64,
+    offset: usize,
+}
+
+impl MetadataCursor {
+    /// Resolves a reference stored in the image, which packs the block position relative to
+    /// `table_start` above a 16-bit offset.
+    fn from_ref(table_start: u64, reference: u64) -> Result<Self, Error> {
+        Ok(Self {
+            block: table_start
+                .checked_add(reference >> 16)
+                .ok_or(Error::InvalidMetadataRef(reference))?,
+            offset: (reference & 0xffff) as usize,
+        })
+    }
+}
+
+/// A data or fragment block, as described by its position and on-disk size word.
+#[derive(Clone, Copy)]
+struct BlockRef {
+    start: u64,
+    size: u32,
+}
+
+impl BlockRef {
+    fn is_sparse(self) -> bool {
+        self.size & !DATA_UNCOMPRESSED == 0
+    }
+}
+
+enum InodeKind {
+    Dir {
+        listing: MetadataCursor,
+        listing_len: u32,
+    },
+    File {
+        len: u64,
+        blocks: Vec<BlockRef>,
+        /// Fragment index and offset into the fragment block holding the tail of the file
+        fragment: Option<(u32, u32)>,
+    },
+    SymLink,
+    /// Device nodes, FIFOs and sockets, which have no representation in `vfs::FileType`
+    Other,
+}
+
+struct Inode {
+    kind: InodeKind,
+    xattr: Option<u32>,
+    mode: u32,
+    mtime: SystemTime,
+}
+
+/// An extended attribute of an entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Xattr {
+    /// The full name, including the namespace prefix (e.g. `user.foo`)
+    pub name: Vec<u8>,
+    pub value: Vec<u8>,
+}
+
+struct Image<R: Read + Seek> {
+    reader: Rc<RefCell<R>>,
+    superblock: Superblock,
+    /// Decompressed metadata blocks by image offset, along with the offset of the next block
+    metadata_cache: HashMap<u64, (Rc<[u8]>, u64)>,
+}
+
+impl<R: Read + Seek> Image<R> {
+    fn open(reader: Rc<RefCell<R>>) -> Result<Self, Error> {
+        let superblock = Superblock::parse(&read_at(&reader, 0, SUPERBLOCK_LEN)?)?;
+        Ok(Self {
+            reader,
+            superblock,
+            metadata_cache: HashMap::new(),
+        })
+    }
+
+    fn metadata_block(&mut self, pos: u64) -> Result<(Rc<[u8]>, u64), Error> {
+        if let Some(block) = self.metadata_cache.get(&pos) {
+            return Ok(block.clone());
+        }
+        let header = u16_at(&read_at(&self.reader, pos, 2)?, 0);
+        let len = (header & !METADATA_UNCOMPRESSED) as usize;
+        let data = read_at(&self.reader, pos + 2, len)?;
+        let data = if header & METADATA_UNCOMPRESSED != 0 {
+            data
+        } else {
+            self.superblock
+                .compression
+                .decompress(&data, METADATA_BLOCK_LEN)?
+        };
+        if self.metadata_cache.len() >= METADATA_CACHE_LEN {
+            self.metadata_cache.clear();
+        }
+        let block = (Rc::from(data), pos + 2 + len as u64);
+        self.metadata_cache.insert(pos, block.clone());
+        Ok(block)
+    }
+
+    /// Reads `len` bytes of a metadata table, which may span several metadata blocks.
+    fn read_metadata(&mut self, cursor: &mut MetadataCursor, len: usize) -> Result<Vec<u8>, Error> {
+        let mut buf = Vec::with_capacity(len);
+        while buf.len() < len {
+            let (block, next) = self.metadata_block(cursor.block)?;
+            if cursor.offset >= block.len() {
+                if block.is_empty() {
+                    return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+                }
+                cursor.offset -= block.len();
+                cursor.block = next;
+                continue;
+            }
+            let n = (len - buf.len()).min(block.len() - cursor.offset);
+            buf.extend_from_slice(&block[cursor.offset..cursor.offset + n]);
+            cursor.offset += n;
+        }
+        Ok(buf)
+    }
+
+    fn read_u32(&mut self, cursor: &mut MetadataCursor) -> Result<u32, Error> {
+        Ok(u32_at(&self.read_metadata(cursor, 4)?, 0))
+    }
+
+    /// Reads an entry of a lookup table such as the fragment table, which is stored as an array
+    /// of metadata block positions at `table_start`.
+    fn read_table_entry(
+        &mut self,
+        table_start: u64,
+        index: u32,
+        entry_len: usize,
+    ) -> Result<Vec<u8>, Error> {
+        let offset = index as usize * entry_len;
+        let pointer_pos = table_start + (offset / METADATA_BLOCK_LEN) as u64 * 8;
+        let block = u64_at(&read_at(&self.reader, pointer_pos, 8)?, 0);
+        let mut cursor = MetadataCursor {
+            block,
+            offset: offset % METADATA_BLOCK_LEN,
+        };
+        self.read_metadata(&mut cursor, entry_len)
+    }
+
+    fn fragment(&mut self, index: u32) -> Result<BlockRef, Error> {
+        if index >= self.superblock.fragment_count {
+            return Err(Error::InvalidFragment(index));
+        }
+        let entry = self.read_table_entry(self.superblock.fragment_table_start, index, 16)?;
+        Ok(BlockRef {
+            start: u64_at(&entry, 0),
+            size: u32_at(&entry, 8),
+        })
+    }
+
+    fn inode(&mut self, reference: u64) -> Result<Inode, Error> {
+        let mut cursor = MetadataCursor::from_ref(self.superblock.inode_table_start, reference)?;
+        let header = self.read_metadata(&mut cursor, 16)?;
+        let inode_type = u16_at(&header, 0);
+        let (kind, xattr) = match inode_type {
+            1 => {
+                let buf = self.read_metadata(&mut cursor, 16)?;
+                let listing = MetadataCursor {
+                    block: self.superblock.directory_table_start + u32_at(&buf, 0) as u64,
+                    offset: u16_at(&buf, 10) as usize,
+                };
+                let listing_len = u16_at(&buf, 8) as u32;
+                (
+                    InodeKind::Dir {
+                        listing,
+                        listing_len,
+                    },
+                    NO_XATTR,
+                )
+            }
+            8 => {
+                // The directory index that follows is only an optimization for huge directories
+                let buf = self.read_metadata(&mut cursor, 24)?;
+                let listing = MetadataCursor {
+                    block: self.superblock.directory_table_start + u32_at(&buf, 8) as u64,
+                    offset: u16_at(&buf, 18) as usize,
+                };
+                let listing_len = u32_at(&buf, 4);
+                (
+                    InodeKind::Dir {
+                        listing,
+                        listing_len,
+                    },
+                    u32_at(&buf, 20),
+                )
+            }
+            2 | 9 => {
+                let (blocks_start, fragment, fragment_offset, len, xattr) = if inode_type == 2 {
+                    let buf = self.read_metadata(&mut cursor, 16)?;
+                    let (start, frag, frag_offset) =
+                        (u32_at(&buf, 0) as u64, u32_at(&buf, 4), u32_at(&buf, 8));
+                    (start, frag, frag_offset, u32_at(&buf, 12) as u64, NO_XATTR)
+                } else {
+                    let buf = self.read_metadata(&mut cursor, 40)?;
+                    let (start, len) = (u64_at(&buf, 0), u64_at(&buf, 8));
+                    (
+                        start,
+                        u32_at(&buf, 28),
+                        u32_at(&buf, 32),
+                        len,
+                        u32_at(&buf, 36),
+                    )
+                };
+                let block_size = self.superblock.block_size as u64;
+                let fragment = (fragment != NO_FRAGMENT).then_some((fragment, fragment_offset));
+                let block_count = if fragment.is_some() {
+                    len / block_size
+                } else {
+                    len.div_ceil(block_size)
+                };
+                // Each block takes a size word in the image, even if it is sparse
+                if block_count * 4 > self.superblock.bytes_used {
+                    return Err(Error::InvalidBlockList(len));
+                }
+                let sizes = self.read_metadata(&mut cursor, block_count as usize * 4)?;
+                let mut start = blocks_start;
+                let blocks = sizes
+                    .chunks_exact(4)
+                    .map(|size| {
+                        let block = BlockRef {
+                            start,
+                            size: u32_at(size, 0),
+                        };
+                        start = start
+                            .checked_add((block.size & !DATA_UNCOMPRESSED) as u64)
+                            .ok_or(Error::InvalidBlockList(len))?;
+                        Ok(block)
+                    })
+                    .collect::<Result<_, Error>>()?;
+                (
+                    InodeKind::File {
+                        len,
+                        blocks,
+                        fragment,
+                    },
+                    xattr,
+                )
+            }
+            3 | 10 => {
+                let buf = self.read_metadata(&mut cursor, 8)?;
+                let target_len = u32_at(&buf, 4) as usize;
+                let xattr = if inode_type == 10 {
+                    self.read_metadata(&mut cursor, target_len)?;
+                    self.read_u32(&mut cursor)?
+                } else {
+                    NO_XATTR
+                };
+                (InodeKind::SymLink, xattr)
+            }
+            4..=7 => (InodeKind::Other, NO_XATTR),
+            11 | 12 => {
+                let buf = self.read_metadata(&mut cursor, 12)?;
+                (InodeKind::Other, u32_at(&buf, 8))
+            }
+            13 | 14 => {
+                let buf = self.read_metadata(&mut cursor, 8)?;
+                (InodeKind::Other, u32_at(&buf, 4))
+            }
+            inode_type => return Err(Error::InvalidInodeType(inode_type)),
+        };
+        Ok(Inode {
+            kind,
+            xattr: (xattr != NO_XATTR).then_some(xattr),
+            mode: u16_at(&header, 2) as u32 & 0o7777,
+            mtime: SystemTime::UNIX_EPOCH + Duration::from_secs(u32_at(&header, 8) as u64),
+        })
+    }
+
+    /// Scans a directory listing for `name`, returning the inode reference of the entry.
+    fn lookup_in_dir(
+        &mut self,
+        mut cursor: MetadataCursor,
+        listing_len: u32,
+        name: &[u8],
+    ) -> Result<Option<u64>, Error> {
+        // The listing length counts the implicit `.` and `..` entries as three bytes
+        let mut remaining = listing_len.saturating_sub(3) as usize;
+        while remaining > 0 {
+            let header = self.read_metadata(&mut cursor, 12)?;
+            let count = u32_at(&header, 0) as usize + 1;
+            let inode_block = u32_at(&header, 4) as u64;
+            remaining = remaining.saturating_sub(12);
+            for _ in 0..count {
+                let entry = self.read_metadata(&mut cursor, 8)?;
+                let inode_offset = u16_at(&entry, 0) as u64;
+                let name_len = u16_at(&entry, 6) as usize + 1;
+                let entry_name = self.read_metadata(&mut cursor, name_len)?;
+                remaining = remaining.saturating_sub(8 + name_len);
+                if entry_name == name {
+                    return Ok(Some(inode_block << 16 | inode_offset));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn lookup(&mut self, path: &[u8]) -> Result<Inode, Error> {
+        let mut inode = self.inode(self.superblock.root_inode)?;
+        for component in vfs::path::components(path) {
+            let InodeKind::Dir {
+                listing,
+                listing_len,
+            } = inode.kind
+            else {
+                return Err(Error::NotFound);
+            };
+            let reference = self
+                .lookup_in_dir(listing, listing_len, component)?
+                .ok_or(Error::NotFound)?;
+            inode = self.inode(reference)?;
+        }
+        Ok(inode)
+    }
+
+    fn xattrs(&mut self, index: u32) -> Result<Vec<Xattr>, Error> {
+        let header = read_at(&self.reader, self.superblock.xattr_id_table_start, 16)?;
+        let kv_start = u64_at(&header, 0);
+        let id_table_start = self.superblock.xattr_id_table_start + 16;
+        let entry = self.read_table_entry(id_table_start, index, 16)?;
+        let mut cursor = MetadataCursor::from_ref(kv_start, u64_at(&entry, 0))?;
+        let count = u32_at(&entry, 8);
+        let mut xattrs = Vec::new();
+        for _ in 0..count {
+            let key = self.read_metadata(&mut cursor, 4)?;
+            let key_type = u16_at(&key, 0);
+            let prefix: &[u8] = match key_type & !XATTR_VALUE_OOL {
+                0 => b"user.",
+                1 => b"trusted.",
+                2 => b"security.",
+                _ => return Err(Error::InvalidXattrType(key_type)),
+            };
+            let name = self.read_metadata(&mut cursor, u16_at(&key, 2) as usize)?;
+            let value_len = self.read_u32(&mut cursor)? as usize;
+            let mut value = self.read_metadata(&mut cursor, value_len)?;
+            if key_type & XATTR_VALUE_OOL != 0 {
+                // The value is stored once elsewhere and referenced by every inode that has it
+                let mut value_cursor = MetadataCursor::from_ref(kv_start, u64_at(&value, 0))?;
+                let value_len = self.read_u32(&mut value_cursor)? as usize;
+                value = self.read_metadata(&mut value_cursor, value_len)?;
+            }
+            xattrs.push(Xattr {
+                name: [prefix, &name].concat(),
+                value,
+            });
+        }
+        Ok(xattrs)
+    }
+}
+
+/// The tail end of a file that is packed into a fragment block with the tails of other files.
+struct Fragment {
+    block: BlockRef,
+    offset: u32,
+}
+
+pub struct File<R: Read + Seek> {
+    reader: Rc<RefCell<R>>,
+    compression: Compression,
+    block_size: u32,
+    len: u64,
+    blocks: Vec<BlockRef>,
+    fragment: Option<Fragment>,
+    /// The most recently decompressed block and its index, which sequential reads mostly hit
+    cached: Option<(usize, Vec<u8>)>,
+    offset: u64,
+}
+
+impl<R: Read + Seek> File<R> {
+    fn decompress_block(&self, block: BlockRef) -> std::io::Result<Vec<u8>> {
+        let len = (block.size & !DATA_UNCOMPRESSED) as usize;
+        let data = read_at(&self.reader, block.start, len)?;
+        if block.size & DATA_UNCOMPRESSED != 0 {
+            Ok(data)
+        } else {
+            self.compression.decompress(&data, self.block_size as usize)
+        }
+    }
+
+    /// Returns the contents of the `index`th block of the file, including the fragment tail.
+    fn load_block(&self, index: usize) -> std::io::Result<Vec<u8>> {
+        let block_start = index as u64 * self.block_size as u64;
+        let len = (self.len - block_start).min(self.block_size as u64) as usize;
+        let data = match (self.blocks.get(index), &self.fragment) {
+            (Some(block), _) if block.is_sparse() => vec![0; len],
+            (Some(&block), _) => self.decompress_block(block)?,
+            (None, Some(fragment)) => {
+                let data = self.decompress_block(fragment.block)?;
+                let start = fragment.offset as usize;
+                data.get(start..start + len)
+                    .ok_or_else(|| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            "fragment is shorter than the file tail",
+                        )
+                    })?
+                    .to_vec()
+            }
+            (None, None) => unreachable!("block past the end of the file"),
+        };
+        if data.len() < len {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "block is shorter than expected",
+            ));
+        }
+        Ok(data)
+    }
+}
+
+impl<R: Read + Seek> Read for File<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.offset >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+        let index = (self.offset / self.block_size as u64) as usize;
+        if self.cached.as_ref().is_none_or(|(i, _)| *i != index) {
+            self.cached = Some((index, self.load_block(index)?));
+        }
+        let (_, data) = self.cached.as_ref().unwrap();
+        let start = (self.offset % self.block_size as u64) as usize;
+        let end = data.len().min((self.len - self.offset) as usize + start);
+        let n = buf.len().min(end - start);
+        buf[..n].copy_from_slice(&data[start..start + n]);
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for File<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.offset = vfs::io::seek_position(pos, self.offset, self.len)?;
+        Ok(self.offset)
+    }
+}
+
+/// A SquashFS 4.0 image. Lookups walk the directory table from the root and only the metadata
+/// and data blocks that are needed are read, so it works well over ranged HTTP reads.
+pub struct SquashFs<R: Read + Seek> {
+    reader: Rc<RefCell<R>>,
+    image: Option<Image<R>>,
+}
+
+impl<R: Read + Seek> SquashFs<R> {
+    fn image(&mut self) -> Result<&mut Image<R>, Error> {
+        if self.image.is_none() {
+            self.image = Some(Image::open(self.reader.clone())?);
+        }
+        Ok(self.image.as_mut().unwrap())
+    }
+
+    /// Returns the extended attributes of an entry.
+    pub fn xattrs(&mut self, path: &[u8]) -> Result<Vec<Xattr>, Error> {
+        let image = self.image()?;
+        match image.lookup(path)?.xattr {
+            Some(index) => image.xattrs(index),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+impl<R: Read + Seek> vfs::IoBackedFs<R> for SquashFs<R> {
+    type Password = ();
+
+    fn from_io(io: R, _password: Self::Password) -> Self {
+        Self {
+            reader: Rc::new(RefCell::new(io)),
+            image: None,
+        }
+    }
+}
+
+impl<R: Read + Seek> vfs::Fs for SquashFs<R> {
+    type Path = [u8];
+    type Error = Error;
+    type File = File<R>;
+
+    fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Self::Error> {
+        let inode = self.image()?.lookup(path)?;
+        let (file_type, len) = match inode.kind {
+            InodeKind::Dir { .. } => (vfs::FileType::Dir, 0),
+            InodeKind::File { len, .. } => (vfs::FileType::File, len),
+            InodeKind::SymLink => (vfs::FileType::SymLink, 0),
+            InodeKind::Other => return Err(Error::NotFound),
+        };
+        Ok(vfs::Metadata {
+            file_type,
+            len,
+            mode: Some(inode.mode),
+            mtime: Some(inode.mtime),
+        })
+    }
+
+    fn open(&mut self, path: &[u8]) -> Result<Self::File, Self::Error> {
+        let reader = self.reader.clone();
+        let image = self.image()?;
+        let (len, blocks, fragment) = match image.lookup(path)?.kind {
+            InodeKind::File {
+                len,
+                blocks,
+                fragment,
+            } => (len, blocks, fragment),
+            InodeKind::Other => return Err(Error::NotFound),
+            _ => return Err(Error::NotAFile),
+        };
+        let fragment = match fragment {
+            Some((index, offset)) => Some(Fragment {
+                block: image.fragment(index)?,
+                offset,
+            }),
+            None => None,
+        };
+        Ok(File {
+            reader,
+            compression: image.superblock.compression,
+            block_size: image.superblock.block_size,
+            len,
+            blocks,
+            fragment,
+            cached: None,
+            offset: 0,
+        })
+    }
+}
@@ -0,0 +1,224 @@
+# Writes the SquashFS 4.0 images of the vfs-squashfs tests, one per compressor, next to this
+# script. Needs the zstd command line tool.
+import lzma, os, struct, subprocess, zlib
+
+BLOCK = 4096
+META = 8192
+MTIME = 1_700_000_000
+NONE = 0xffffffffffffffff
+
+def zstd(data):
+    return subprocess.run(["zstd", "-q", "-c", "-19"], input=data, capture_output=True, check=True).stdout
+
+def lz4(data):
+    """Compresses an LZ4 block with greedy matching of 4 byte sequences."""
+    out, last, i, literals = bytearray(), {}, 0, 0
+
+    def length(n):
+        while n >= 255:
+            out.append(255)
+            n -= 255
+        out.append(n)
+
+    def sequence(literal_end, match_len, offset):
+        literal_len = literal_end - literals
+        token = min(literal_len, 15) << 4 | (min(match_len - 4, 15) if match_len else 0)
+        out.append(token)
+        if literal_len >= 15:
+            length(literal_len - 15)
+        out.extend(data[literals:literal_end])
+        if match_len:
+            out.extend(struct.pack("<H", offset))
+            if match_len - 4 >= 15:
+                length(match_len - 4 - 15)
+
+    # The last match must start at least 12 bytes before the end, and end 5 bytes before it
+    while i + 12 <= len(data):
+        key = data[i:i + 4]
+        candidate = last.get(key)
+        last[key] = i
+        if candidate is None or i - candidate > 0xffff:
+            i += 1
+            continue
+        match_len = 4
+        while i + match_len < len(data) - 5 and data[candidate + match_len] == data[i + match_len]:
+            match_len += 1
+        sequence(i, match_len, i - candidate)
+        i += match_len
+        literals = i
+    sequence(len(data), 0, 0)
+    return bytes(out)
+
+COMPRESSORS = {
+    "gzip": (1, lambda d: zlib.compress(d, 9)),
+    "xz": (4, lambda d: lzma.compress(d, format=lzma.FORMAT_XZ, check=lzma.CHECK_CRC32)),
+    "lz4": (5, lz4),
+    "zstd": (6, zstd),
+}
+
+def lcg_bytes(n, seed):
+    out, x = bytearray(), seed
+    for _ in range(n):
+        x = (x * 1103515245 + 12345) & 0xffffffff
+        out.append(x >> 24)
+    return bytes(out)
+
+SMALL = b"Hello world! (small)\n" * 5
+BIG = (b"Hello world! (big)\n" * 216)[:BLOCK] + bytes(BLOCK) + lcg_bytes(BLOCK, 1) + b"tail " * 100
+MANY = [b"entry-%04d-with-a-fairly-long-name" % i for i in range(300)]
+
+def build(name):
+    comp_id, compress = COMPRESSORS[name]
+
+    def metadata_blocks(stream):
+        out, starts = bytearray(), []
+        for i in range(0, max(len(stream), 1), META):
+            chunk = bytes(stream[i:i + META])
+            starts.append(len(out))
+            c = compress(chunk)
+            if len(c) < len(chunk):
+                out += struct.pack("<H", len(c)) + c
+            else:
+                out += struct.pack("<H", len(chunk) | 0x8000) + chunk
+        return bytes(out), starts
+
+    def data_block(block):
+        c = compress(block)
+        if len(c) < len(block):
+            return c, len(c)
+        return block, len(block) | 1 << 24
+
+    data = bytearray()
+    small_start = big_start = 96
+    big_sizes = []
+    for i in range(3):
+        block = BIG[i * BLOCK:(i + 1) * BLOCK]
+        if block == bytes(BLOCK):
+            big_sizes.append(0)
+            continue
+        stored, size = data_block(block)
+        data += stored
+        big_sizes.append(size)
+    fragment_start = 96 + len(data)
+    stored, fragment_size = data_block(SMALL + BIG[3 * BLOCK:])
+    data += stored
+
+    # Inode numbers: small.txt 1, big.bin 2, link 3, many 4, root 5
+    def header(kind, mode, number):
+        return struct.pack("<HHHHII", kind, mode, 0, 0, MTIME + number, number)
+
+    fixed = [
+        ("small", header(2, 0o644, 1) + struct.pack("<IIII", small_start, 0, 0, len(SMALL))),
+        ("big", header(9, 0o600, 2)
+            + struct.pack("<QQQIIII", big_start, len(BIG), BLOCK, 1, 0, len(SMALL), 0)
+            + struct.pack("<3I", *big_sizes)),
+        ("link", header(3, 0o777, 3) + struct.pack("<II", 1, 9) + b"small.txt"),
+    ]
+
+    # Directory positions depend on inode positions and vice versa, so iterate to a fixed point
+    layout = {"many": (0, 0, 0, []), "root": (0, 0, 0)}
+    for _ in range(10):
+        inodes, offsets = bytearray(), {}
+        for key, inode in fixed:
+            offsets[key] = len(inodes)
+            inodes += inode
+        block, offset, size, index = layout["many"]
+        offsets["many"] = len(inodes)
+        inodes += header(8, 0o755, 4) + struct.pack(
+            "<IIIIHHI", 302, size, block, 5, len(index), offset, 0xffffffff
+        )
+        for listing_offset, start, entry_name in index:
+            inodes += struct.pack("<III", listing_offset, start, len(entry_name) - 1) + entry_name
+        block, offset, size = layout["root"]
+        offsets["root"] = len(inodes)
+        inodes += header(1, 0o755, 5) + struct.pack("<IIHHI", block, 3, size, offset, 6)
+        inode_table, inode_starts = metadata_blocks(inodes)
+        ref = lambda key: (inode_starts[offsets[key] // META], offsets[key] % META)
+
+        dirs = bytearray()
+        def listing(entries):
+            """Appends a listing of (name, inode key, number, type), returning its start and
+            the (offset, table position) of each header that starts a new metadata block"""
+            start, headers, i = len(dirs), [], 0
+            while i < len(entries):
+                block = ref(entries[i][1])[0]
+                run = []
+                header_pos = len(dirs)
+                headers.append((header_pos - start, header_pos, entries[i][0]))
+                while i < len(entries) and len(run) < 256 and ref(entries[i][1])[0] == block:
+                    if run and (header_pos // META != (len(dirs) + 12 + sum(8 + len(e[0]) for e in run)) // META):
+                        break
+                    run.append(entries[i])
+                    i += 1
+                dirs.extend(struct.pack("<III", len(run) - 1, block, run[0][2]))
+                for entry_name, key, number, kind in run:
+                    dirs.extend(struct.pack("<HhHH", ref(key)[1], number - run[0][2], kind, len(entry_name) - 1) + entry_name)
+            return start, headers
+        many_start, many_headers = listing([(n, "small", 1, 2) for n in MANY])
+        many_len = len(dirs) - many_start
+        root_start, _ = listing([
+            (b"big.bin", "big", 2, 2),
+            (b"link", "link", 3, 3),
+            (b"many", "many", 4, 1),
+            (b"small.txt", "small", 1, 2),
+        ])
+        root_len = len(dirs) - root_start
+        dir_table, dir_starts = metadata_blocks(dirs)
+        many_index = [
+            (offset, dir_starts[pos // META], entry_name)
+            for offset, pos, entry_name in many_headers[1:]
+            if pos // META != many_headers[0][1] // META
+        ]
+        new_layout = {
+            "many": (dir_starts[many_start // META], many_start % META, many_len + 3, many_index),
+            "root": (dir_starts[root_start // META], root_start % META, root_len + 3),
+        }
+        if new_layout == layout:
+            break
+        layout = new_layout
+    else:
+        raise Exception("directory layout did not converge")
+
+    image = bytearray(96) + data
+    inode_table_start = len(image)
+    image += inode_table
+    directory_table_start = len(image)
+    image += dir_table
+
+    fragments, _ = metadata_blocks(struct.pack("<QII", fragment_start, fragment_size, 0))
+    fragment_blocks = len(image)
+    image += fragments
+    fragment_table_start = len(image)
+    image += struct.pack("<Q", fragment_blocks)
+
+    ids, _ = metadata_blocks(struct.pack("<I", 0))
+    id_blocks = len(image)
+    image += ids
+    id_table_start = len(image)
+    image += struct.pack("<Q", id_blocks)
+
+    # One xattr on big.bin, stored inline in the key/value table
+    kv, _ = metadata_blocks(struct.pack("<HH", 0, 7) + b"comment" + struct.pack("<I", 5) + b"hello")
+    kv_start = len(image)
+    image += kv
+    xattr_ids, _ = metadata_blocks(struct.pack("<QII", 0, 1, 4 + 7 + 4 + 5))
+    xattr_id_blocks = len(image)
+    image += xattr_ids
+    xattr_id_table_start = len(image)
+    image += struct.pack("<QII", kv_start, 1, 0) + struct.pack("<Q", xattr_id_blocks)
+
+    root_ref = ref("root")
+    struct.pack_into(
+        "<IIIIIHHHHHHQQQQQQQQ", image, 0,
+        0x73717368, 5, MTIME, BLOCK, 1, comp_id, 12, 0, 1, 4, 0,
+        root_ref[0] << 16 | root_ref[1], len(image), id_table_start, xattr_id_table_start,
+        inode_table_start, directory_table_start, fragment_table_start, NONE,
+    )
+    # Pad to a whole 4 KiB, as mksquashfs does
+    image += bytes(-len(image) % 4096)
+    return bytes(image)
+
+for name in COMPRESSORS:
+    path = os.path.join(os.path.dirname(os.path.abspath(__file__)), f"{name}.squashfs")
+    with open(path, "wb") as f:
+        f.write(build(name))
@@ -0,0 +1,121 @@
+use std::{
+    io::{Read, Seek, SeekFrom},
+    time::{Duration, SystemTime},
+};
+use vfs::{Fs, IoBackedFs};
+
+const BLOCK: usize = 4096;
+
+fn lcg_bytes(len: usize, mut seed: u32) -> Vec<u8> {
+    (0..len)
+        .map(|_| {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 24) as u8
+        })
+        .collect()
+}
+
+#[test]
+fn test() {
+    let small = "Hello world! (small)\n".repeat(5);
+    // A compressed block, a sparse block, an incompressible block stored raw and a fragment tail
+    let mut big = "Hello world! (big)\n".repeat(216).into_bytes();
+    big.truncate(BLOCK);
+    big.resize(BLOCK * 2, 0);
+    big.extend(lcg_bytes(BLOCK, 1));
+    big.extend("tail ".repeat(100).bytes());
+    let mtime = |number: u64| SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000 + number);
+
+    // Images with the same tree, written by tests/data/generate.py: `many` is an extended
+    // directory whose listing spans two metadata blocks, so it carries a directory index
+    for compression in ["gzip", "xz", "lz4", "zstd"] {
+        let image = std::fs::File::open(format!("tests/data/{compression}.squashfs")).unwrap();
+        let mut fs = vfs_squashfs::SquashFs::from_io(image, ());
+        assert_eq!(
+            fs.metadata(b"").unwrap(),
+            vfs::Metadata {
+                file_type: vfs::FileType::Dir,
+                len: 0,
+                mode: Some(0o755),
+                mtime: Some(mtime(5)),
+            }
+        );
+        assert_eq!(
+            fs.metadata(b"small.txt").unwrap(),
+            vfs::Metadata {
+                file_type: vfs::FileType::File,
+                len: small.len() as u64,
+                mode: Some(0o644),
+                mtime: Some(mtime(1)),
+            }
+        );
+        assert_eq!(
+            fs.metadata(b"big.bin").unwrap(),
+            vfs::Metadata {
+                file_type: vfs::FileType::File,
+                len: big.len() as u64,
+                mode: Some(0o600),
+                mtime: Some(mtime(2)),
+            }
+        );
+        assert_eq!(
+            fs.metadata(b"link").unwrap(),
+            vfs::Metadata {
+                file_type: vfs::FileType::SymLink,
+                len: 0,
+                mode: Some(0o777),
+                mtime: Some(mtime(3)),
+            }
+        );
+        assert!(matches!(
+            fs.metadata(b"missing"),
+            Err(vfs_squashfs::Error::NotFound)
+        ));
+        assert!(matches!(
+            fs.open(b"many"),
+            Err(vfs_squashfs::Error::NotAFile)
+        ));
+
+        let file = fs.open(b"small.txt").unwrap();
+        assert_eq!(std::io::read_to_string(file).unwrap(), small);
+        let mut file = fs.open(b"big.bin").unwrap();
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).unwrap();
+        assert_eq!(contents, big);
+        let mut buf = [0xff; 16];
+        file.seek(SeekFrom::Start(BLOCK as u64 * 2 - 8)).unwrap();
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, big[BLOCK * 2 - 8..BLOCK * 2 + 8]);
+        file.seek(SeekFrom::End(-10)).unwrap();
+        assert_eq!(std::io::read_to_string(file).unwrap(), "tail tail ");
+
+        for i in [0, 150, 299] {
+            let path = format!("many/entry-{i:04}-with-a-fairly-long-name");
+            let file = fs.open(path.as_bytes()).unwrap();
+            assert_eq!(std::io::read_to_string(file).unwrap(), small);
+        }
+        assert!(matches!(
+            fs.metadata(b"many/entry-0300-with-a-fairly-long-name"),
+            Err(vfs_squashfs::Error::NotFound)
+        ));
+
+        assert_eq!(
+            fs.xattrs(b"big.bin").unwrap(),
+            [vfs_squashfs::Xattr {
+                name: b"user.comment".to_vec(),
+                value: b"hello".to_vec(),
+            }]
+        );
+        assert_eq!(fs.xattrs(b"small.txt").unwrap(), []);
+    }
+
+    // An image too short for the block list of a file, as told by its superblock
+    let mut image = std::fs::read("tests/data/gzip.squashfs").unwrap();
+    image[40..48].copy_from_slice(&8u64.to_le_bytes());
+    let mut fs = vfs_squashfs::SquashFs::from_io(std::io::Cursor::new(image), ());
+    assert_eq!(fs.metadata(b"small.txt").unwrap().len, small.len() as u64);
+    assert!(matches!(
+        fs.metadata(b"big.bin"),
+        Err(vfs_squashfs::Error::InvalidBlockList(len)) if len == big.len() as u64
+    ));
+}
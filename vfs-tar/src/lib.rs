@@ -0,0 +1,657 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom},
+    rc::Rc,
+    time::{Duration, SystemTime},
+};
+use window_read_seek::WindowReadSeek;
+
+const BLOCK_LEN: u64 = 512;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed reading from backing IO")]
+    Io(#[from] std::io::Error),
+    #[error("header checksum mismatch at offset {0}")]
+    Checksum(u64),
+    #[error("invalid numeric header field")]
+    InvalidNumber,
+    #[error("invalid PAX extended header")]
+    InvalidPaxHeader,
+    #[error("invalid sparse map")]
+    InvalidSparseMap,
+    #[error("entry length at offset {0} overflows")]
+    LengthOverflow(u64),
+    #[error("entry not found")]
+    NotFound,
+    #[error("entry is not a regular file")]
+    NotAFile,
+}
+
+/// A region of a sparse file that has data stored in the archive. Everything else reads as
+/// zeros.
+#[derive(Clone, Copy, Debug)]
+struct SparseRegion {
+    offset: u64,
+    len: u64,
+}
+
+#[derive(Clone, Debug)]
+enum Data {
+    Contiguous {
+        offset: u64,
+    },
+    /// The stored regions are concatenated in the archive starting at `offset`, each paired with
+    /// its offset from there
+    Sparse {
+        offset: u64,
+        map: Vec<(SparseRegion, u64)>,
+    },
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    file_type: vfs::FileType,
+    len: u64,
+    data: Data,
+    mode: Option<u32>,
+    mtime: Option<SystemTime>,
+}
+
+impl Entry {
+    fn dir() -> Self {
+        Self {
+            file_type: vfs::FileType::Dir,
+            len: 0,
+            data: Data::Contiguous { offset: 0 },
+            mode: None,
+            mtime: None,
+        }
+    }
+}
+
+type Index = HashMap<Vec<u8>, Entry>;
+
+/// Parses a numeric header field, which is either NUL or space terminated octal, or a GNU
+/// base-256 big-endian number if the high bit of the first byte is set.
+fn parse_number(field: &[u8]) -> Result<u64, Error> {
+    if let Some((&first, rest)) = field.split_first() {
+        if first & 0x80 != 0 {
+            return rest
+                .iter()
+                .try_fold((first & 0x7f) as u64, |n, &b| {
+                    n.checked_mul(256).map(|n| n + b as u64)
+                })
+                .ok_or(Error::InvalidNumber);
+        }
+    }
+    let field = field
+        .split(|&b| b == 0)
+        .next()
+        .unwrap_or_default()
+        .trim_ascii();
+    if field.is_empty() {
+        return Ok(0);
+    }
+    std::str::from_utf8(field)
+        .ok()
+        .and_then(|s| u64::from_str_radix(s, 8).ok())
+        .ok_or(Error::InvalidNumber)
+}
+
+fn parse_decimal(s: &[u8]) -> Option<u64> {
+    std::str::from_utf8(s).ok()?.trim().parse().ok()
+}
+
+/// Parses a PAX timestamp, which is decimal seconds with an optional fraction.
+fn parse_pax_time(s: &[u8]) -> Option<SystemTime> {
+    let s = std::str::from_utf8(s).ok()?;
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let (secs, fraction) = s.split_once('.').unwrap_or((s, ""));
+    let nanos = format!("{fraction:0<9}");
+    let time = Duration::new(secs.parse().ok()?, nanos.get(..9)?.parse().ok()?);
+    if negative {
+        SystemTime::UNIX_EPOCH.checked_sub(time)
+    } else {
+        SystemTime::UNIX_EPOCH.checked_add(time)
+    }
+}
+
+fn parse_string(field: &[u8]) -> &[u8] {
+    field.split(|&b| b == 0).next().unwrap_or_default()
+}
+
+fn checksum_matches(header: &[u8; BLOCK_LEN as usize]) -> Result<bool, Error> {
+    let expected = parse_number(&header[148..156])?;
+    let unsigned: u64 = header
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| if (148..156).contains(&i) { b' ' } else { b } as u64)
+        .sum();
+    // Some historic implementations summed signed bytes
+    let signed: i64 = header
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| if (148..156).contains(&i) { b' ' as i8 } else { b as i8 } as i64)
+        .sum();
+    Ok(expected == unsigned || expected as i64 == signed)
+}
+
+fn round_up(len: u64) -> Option<u64> {
+    len.div_ceil(BLOCK_LEN).checked_mul(BLOCK_LEN)
+}
+
+fn read_data<R: Read>(reader: &mut R, len: u64) -> Result<Vec<u8>, Error> {
+    let mut data = Vec::new();
+    reader.take(len).read_to_end(&mut data)?;
+    if (data.len() as u64) < len {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(data)
+}
+
+/// Overrides for the next entry, collected from GNU and PAX extension headers.
+#[derive(Default)]
+struct Extensions {
+    path: Option<Vec<u8>>,
+    link_path: Option<Vec<u8>>,
+    len: Option<u64>,
+    mtime: Option<SystemTime>,
+    sparse_len: Option<u64>,
+    sparse_map: Option<Vec<SparseRegion>>,
+    /// PAX sparse format 1.0 stores the map at the start of the entry data
+    sparse_map_in_data: bool,
+}
+
+fn parse_sparse_pairs(
+    numbers: impl IntoIterator<Item = Option<u64>>,
+) -> Result<Vec<SparseRegion>, Error> {
+    let numbers = numbers
+        .into_iter()
+        .collect::<Option<Vec<_>>>()
+        .ok_or(Error::InvalidSparseMap)?;
+    if numbers.len() % 2 != 0 {
+        return Err(Error::InvalidSparseMap);
+    }
+    Ok(numbers
+        .chunks_exact(2)
+        .map(|x| SparseRegion {
+            offset: x[0],
+            len: x[1],
+        })
+        .collect())
+}
+
+/// Pairs the regions of a sparse map with their offsets into the stored data, checking that
+/// they are in order and that neither offsets nor lengths overflow.
+fn sparse_data_map(map: Vec<SparseRegion>) -> Result<Vec<(SparseRegion, u64)>, Error> {
+    let mut end = 0;
+    let mut data_offset = 0u64;
+    map.into_iter()
+        .map(|region| {
+            if region.offset < end {
+                return Err(Error::InvalidSparseMap);
+            }
+            end = region
+                .offset
+                .checked_add(region.len)
+                .ok_or(Error::InvalidSparseMap)?;
+            let entry = (region, data_offset);
+            data_offset = data_offset
+                .checked_add(region.len)
+                .ok_or(Error::InvalidSparseMap)?;
+            Ok(entry)
+        })
+        .collect()
+}
+
+fn parse_pax(data: &[u8], extensions: &mut Extensions) -> Result<(), Error> {
+    let mut rest = data;
+    // GNU sparse format 0.0 repeats the offset and size keywords for each region
+    let mut sparse_offsets = Vec::new();
+    while !rest.is_empty() {
+        let space = rest
+            .iter()
+            .position(|&b| b == b' ')
+            .ok_or(Error::InvalidPaxHeader)?;
+        let len = parse_decimal(&rest[..space]).ok_or(Error::InvalidPaxHeader)? as usize;
+        if len <= space || len > rest.len() || rest[len - 1] != b'\n' {
+            return Err(Error::InvalidPaxHeader);
+        }
+        let record = &rest[space + 1..len - 1];
+        rest = &rest[len..];
+        let equals = record
+            .iter()
+            .position(|&b| b == b'=')
+            .ok_or(Error::InvalidPaxHeader)?;
+        let (key, value) = (&record[..equals], &record[equals + 1..]);
+        match key {
+            b"path" | b"GNU.sparse.name" => extensions.path = Some(value.to_vec()),
+            b"linkpath" => extensions.link_path = Some(value.to_vec()),
+            b"size" => extensions.len = parse_decimal(value),
+            b"mtime" => extensions.mtime = parse_pax_time(value),
+            b"GNU.sparse.size" | b"GNU.sparse.realsize" => {
+                extensions.sparse_len = parse_decimal(value)
+            }
+            b"GNU.sparse.map" => {
+                extensions.sparse_map = Some(parse_sparse_pairs(
+                    value.split(|&b| b == b',').map(parse_decimal),
+                )?)
+            }
+            b"GNU.sparse.offset" => sparse_offsets.push(parse_decimal(value)),
+            b"GNU.sparse.numbytes" => {
+                let offset = sparse_offsets.pop().ok_or(Error::InvalidSparseMap)?;
+                extensions
+                    .sparse_map
+                    .get_or_insert_with(Vec::new)
+                    .extend(parse_sparse_pairs([offset, parse_decimal(value)])?);
+            }
+            b"GNU.sparse.major" if value == b"1" => extensions.sparse_map_in_data = true,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Parses the old GNU sparse map stored in the header, followed by extension blocks.
+fn parse_gnu_sparse<R: Read>(
+    reader: &mut R,
+    header: &[u8; BLOCK_LEN as usize],
+) -> Result<(Vec<SparseRegion>, u64), Error> {
+    fn parse_regions(entries: &[u8], map: &mut Vec<SparseRegion>) -> Result<(), Error> {
+        for entry in entries.chunks_exact(24) {
+            if entry[0] == 0 {
+                break;
+            }
+            map.push(SparseRegion {
+                offset: parse_number(&entry[..12])?,
+                len: parse_number(&entry[12..])?,
+            });
+        }
+        Ok(())
+    }
+
+    let mut map = Vec::new();
+    parse_regions(&header[386..482], &mut map)?;
+    let mut is_extended = header[482] != 0;
+    let mut blocks_read = 0;
+    while is_extended {
+        let mut block = [0; BLOCK_LEN as usize];
+        reader.read_exact(&mut block)?;
+        blocks_read += 1;
+        parse_regions(&block[..504], &mut map)?;
+        is_extended = block[504] != 0;
+    }
+    Ok((map, blocks_read * BLOCK_LEN))
+}
+
+/// Parses the PAX sparse format 1.0 map at the start of the entry data, returning the map and
+/// its padded length.
+fn parse_data_sparse_map<R: Read>(reader: &mut R) -> Result<(Vec<SparseRegion>, u64), Error> {
+    let mut buf = Vec::new();
+    let mut numbers = Vec::new();
+    // The number of region offsets and lengths that follow the region count
+    let mut count = None;
+    let mut consumed = 0;
+    while count.is_none_or(|count| numbers.len() < count) {
+        let mut block = [0; BLOCK_LEN as usize];
+        reader.read_exact(&mut block)?;
+        consumed += BLOCK_LEN;
+        buf.extend_from_slice(&block);
+        while let Some(newline) = buf.iter().position(|&b| b == b'\n') {
+            let number = parse_decimal(&buf[..newline]);
+            buf.drain(..=newline);
+            match count {
+                None => {
+                    let regions = number.ok_or(Error::InvalidSparseMap)?;
+                    count = Some(
+                        usize::try_from(regions)
+                            .ok()
+                            .and_then(|regions| regions.checked_mul(2))
+                            .ok_or(Error::InvalidSparseMap)?,
+                    );
+                }
+                Some(count) if numbers.len() < count => numbers.push(number),
+                Some(_) => break,
+            }
+        }
+    }
+    Ok((parse_sparse_pairs(numbers)?, consumed))
+}
+
+fn insert_parents(index: &mut Index, path: &[u8]) {
+    let mut components = vfs::path::components(path);
+    while components.pop().is_some() && !components.is_empty() {
+        index
+            .entry(components.join(b"/".as_slice()))
+            .or_insert_with(Entry::dir);
+    }
+}
+
+fn read_index<R: Read + Seek>(reader: &mut R) -> Result<Index, Error> {
+    let mut index = Index::new();
+    let mut hard_links = Vec::new();
+    let mut extensions = Extensions::default();
+    let mut offset = 0;
+    loop {
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut header = [0; BLOCK_LEN as usize];
+        match reader.read_exact(&mut header) {
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            r => r?,
+        }
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        if !checksum_matches(&header)? {
+            return Err(Error::Checksum(offset));
+        }
+        let type_flag = header[156];
+        let mut len = parse_number(&header[124..136])?;
+        if let Some(pax_len) = extensions.len {
+            len = pax_len;
+        }
+        let overflow = move || Error::LengthOverflow(offset);
+        let mut data_offset = offset.checked_add(BLOCK_LEN).ok_or_else(overflow)?;
+        let mut next = round_up(len)
+            .and_then(|len| data_offset.checked_add(len))
+            .ok_or_else(overflow)?;
+
+        match type_flag {
+            b'L' => {
+                let data = read_data(reader, len)?;
+                extensions.path = Some(parse_string(&data).to_vec());
+                offset = next;
+                continue;
+            }
+            b'K' => {
+                let data = read_data(reader, len)?;
+                extensions.link_path = Some(parse_string(&data).to_vec());
+                offset = next;
+                continue;
+            }
+            b'x' => {
+                parse_pax(&read_data(reader, len)?, &mut extensions)?;
+                offset = next;
+                continue;
+            }
+            b'g' | b'V' => {
+                offset = next;
+                continue;
+            }
+            _ => {}
+        }
+
+        let is_ustar = &header[257..262] == b"ustar";
+        let is_gnu = &header[257..265] == b"ustar  \0";
+        let path = match extensions.path.take() {
+            Some(path) => path,
+            None => {
+                let name = parse_string(&header[..100]);
+                let prefix = parse_string(&header[345..500]);
+                if is_ustar && !is_gnu && !prefix.is_empty() {
+                    [prefix, b"/", name].concat()
+                } else {
+                    name.to_vec()
+                }
+            }
+        };
+        let link_path = extensions
+            .link_path
+            .take()
+            .unwrap_or_else(|| parse_string(&header[157..257]).to_vec());
+
+        let mode = parse_number(&header[100..108])? as u32 & 0o7777;
+        let mtime = match extensions.mtime.take() {
+            Some(mtime) => mtime,
+            None => SystemTime::UNIX_EPOCH
+                .checked_add(Duration::from_secs(parse_number(&header[136..148])?))
+                .ok_or(Error::InvalidNumber)?,
+        };
+
+        let mut sparse_map = extensions.sparse_map.take();
+        let mut file_len = extensions.sparse_len.take().unwrap_or(len);
+        if type_flag == b'S' {
+            let (map, extension_len) = parse_gnu_sparse(reader, &header)?;
+            sparse_map = Some(map);
+            file_len = parse_number(&header[483..495])?;
+            data_offset += extension_len;
+            next = next.checked_add(extension_len).ok_or_else(overflow)?;
+        }
+        if std::mem::take(&mut extensions.sparse_map_in_data) {
+            reader.seek(SeekFrom::Start(data_offset))?;
+            let (map, map_len) = parse_data_sparse_map(reader)?;
+            sparse_map = Some(map);
+            data_offset += map_len;
+        }
+        extensions = Extensions::default();
+
+        let path = vfs::path::normalize(&path);
+        offset = next;
+        if path.is_empty() {
+            continue;
+        }
+        let (file_type, len, data) = match type_flag {
+            b'0' | b'\0' | b'7' | b'S' => (
+                vfs::FileType::File,
+                file_len,
+                match sparse_map {
+                    Some(map) => Data::Sparse {
+                        offset: data_offset,
+                        map: sparse_data_map(map)?,
+                    },
+                    None => Data::Contiguous {
+                        offset: data_offset,
+                    },
+                },
+            ),
+            b'1' => {
+                hard_links.push((path.clone(), vfs::path::normalize(&link_path)));
+                (
+                    vfs::FileType::File,
+                    0,
+                    Data::Contiguous {
+                        offset: data_offset,
+                    },
+                )
+            }
+            b'2' => (
+                vfs::FileType::SymLink,
+                0,
+                Data::Contiguous {
+                    offset: data_offset,
+                },
+            ),
+            b'5' | b'D' => (vfs::FileType::Dir, 0, Data::Contiguous { offset: 0 }),
+            // Device nodes and FIFOs have no representation in `vfs::FileType`
+            _ => continue,
+        };
+        insert_parents(&mut index, &path);
+        index.insert(
+            path,
+            Entry {
+                file_type,
+                len,
+                data,
+                mode: Some(mode),
+                mtime: Some(mtime),
+            },
+        );
+    }
+
+    for (path, target) in hard_links {
+        if let Some(target) = index.get(&target).cloned() {
+            index.insert(path, target);
+        }
+    }
+    Ok(index)
+}
+
+/// Reads a sparse file, filling the holes between stored regions with zeros.
+struct SparseFile<R: Read + Seek> {
+    data: WindowReadSeek<R>,
+    /// Stored regions paired with their offsets into `data`
+    map: Vec<(SparseRegion, u64)>,
+    len: u64,
+    offset: u64,
+}
+
+impl<R: Read + Seek> SparseFile<R> {
+    fn new(reader: Rc<RefCell<R>>, offset: u64, map: Vec<(SparseRegion, u64)>, len: u64) -> Self {
+        // `sparse_data_map` checked that this doesn't overflow
+        let data_len = map
+            .last()
+            .map_or(0, |(region, data_offset)| data_offset + region.len);
+        Self {
+            data: WindowReadSeek::new(reader, offset, data_len),
+            map,
+            len,
+            offset: 0,
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for SparseFile<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.offset);
+        let max = (buf.len() as u64).min(remaining) as usize;
+        if max == 0 {
+            return Ok(0);
+        }
+        let offset = self.offset;
+        // The first region not wholly before `offset`, whose end `sparse_data_map` checked
+        let region = self
+            .map
+            .iter()
+            .find(|(region, _)| offset < region.offset + region.len);
+        let n = match region {
+            Some(&(region, data_offset)) if region.offset <= offset => {
+                let skip = offset - region.offset;
+                let n = (max as u64).min(region.len - skip) as usize;
+                self.data.seek(SeekFrom::Start(data_offset + skip))?;
+                self.data.read(&mut buf[..n])?
+            }
+            Some(&(region, _)) => {
+                let n = (max as u64).min(region.offset - offset) as usize;
+                buf[..n].fill(0);
+                n
+            }
+            None => {
+                buf[..max].fill(0);
+                max
+            }
+        };
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for SparseFile<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.offset = vfs::io::seek_position(pos, self.offset, self.len)?;
+        Ok(self.offset)
+    }
+}
+
+enum FileInner<R: Read + Seek> {
+    Contiguous(WindowReadSeek<R>),
+    Sparse(SparseFile<R>),
+}
+
+pub struct File<R: Read + Seek>(FileInner<R>);
+
+impl<R: Read + Seek> Read for File<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match &mut self.0 {
+            FileInner::Contiguous(x) => x.read(buf),
+            FileInner::Sparse(x) => x.read(buf),
+        }
+    }
+}
+
+impl<R: Read + Seek> Seek for File<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match &mut self.0 {
+            FileInner::Contiguous(x) => x.seek(pos),
+            FileInner::Sparse(x) => x.seek(pos),
+        }
+    }
+}
+
+/// An uncompressed tar archive, indexed by its headers on first access. Files are views directly
+/// into the backing IO.
+pub struct TarFs<R: Read + Seek> {
+    reader: Rc<RefCell<R>>,
+    index: Option<Index>,
+}
+
+impl<R: Read + Seek> TarFs<R> {
+    fn index(&mut self) -> Result<&Index, Error> {
+        if self.index.is_none() {
+            self.index = Some(read_index(&mut *self.reader.borrow_mut())?);
+        }
+        Ok(self.index.as_ref().unwrap())
+    }
+
+    /// Returns the normalized paths of all entries in no particular order, including parent
+    /// directories that have no entry of their own.
+    pub fn paths(&mut self) -> Result<impl Iterator<Item = &[u8]>, Error> {
+        Ok(self.index()?.keys().map(Vec::as_slice))
+    }
+
+    fn entry(&mut self, path: &[u8]) -> Result<Entry, Error> {
+        let index = self.index()?;
+        let path = vfs::path::normalize(path);
+        if path.is_empty() {
+            return Ok(Entry::dir());
+        }
+        index.get(&path).cloned().ok_or(Error::NotFound)
+    }
+}
+
+impl<R: Read + Seek> vfs::IoBackedFs<R> for TarFs<R> {
+    type Password = ();
+
+    fn from_io(io: R, _password: Self::Password) -> Self {
+        Self {
+            reader: Rc::new(RefCell::new(io)),
+            index: None,
+        }
+    }
+}
+
+impl<R: Read + Seek> vfs::Fs for TarFs<R> {
+    type Path = [u8];
+    type Error = Error;
+    type File = File<R>;
+
+    fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Self::Error> {
+        let entry = self.entry(path)?;
+        Ok(vfs::Metadata {
+            file_type: entry.file_type,
+            len: entry.len,
+            mode: entry.mode,
+            mtime: entry.mtime,
+        })
+    }
+
+    fn open(&mut self, path: &[u8]) -> Result<Self::File, Self::Error> {
+        let entry = self.entry(path)?;
+        if entry.file_type != vfs::FileType::File {
+            return Err(Error::NotAFile);
+        }
+        let reader = self.reader.clone();
+        Ok(File(match entry.data {
+            Data::Contiguous { offset } => {
+                FileInner::Contiguous(WindowReadSeek::new(reader, offset, entry.len))
+            }
+            Data::Sparse { offset, map } => {
+                FileInner::Sparse(SparseFile::new(reader, offset, map, entry.len))
+            }
+        }))
+    }
+}
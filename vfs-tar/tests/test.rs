@@ -0,0 +1,154 @@
+use std::{
+    io::{Read, Seek, SeekFrom},
+    os::unix::fs::PermissionsExt,
+    time::{Duration, SystemTime},
+};
+use vfs::{Fs, IoBackedFs};
+
+#[test]
+fn test() {
+    let tmp = tempfile::tempdir().unwrap();
+    let tmp = tmp.path();
+    let long_dir = "a/".to_owned() + &"long_directory_name/".repeat(8);
+    let b_data = "Hello world! (B)";
+    let sparse_data = "Hello world! (sparse)";
+    let sparse_offset = 1 << 20;
+    std::fs::create_dir_all(tmp.join(&long_dir)).unwrap();
+    std::fs::write(tmp.join(&long_dir).join("b"), b_data).unwrap();
+    std::os::unix::fs::symlink("/symlink/target/path", tmp.join("c")).unwrap();
+    std::fs::hard_link(tmp.join(&long_dir).join("b"), tmp.join("d")).unwrap();
+    let sparse = std::fs::File::create(tmp.join("sparse")).unwrap();
+    sparse.set_len(sparse_offset * 4).unwrap();
+    std::os::unix::fs::FileExt::write_at(&sparse, sparse_data.as_bytes(), sparse_offset).unwrap();
+    let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let b_path = long_dir.clone() + "b";
+    for (path, mode) in [
+        (long_dir.as_str(), 0o750),
+        (&b_path, 0o640),
+        ("sparse", 0o600),
+    ] {
+        let path = tmp.join(path);
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        std::fs::File::open(&path)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+    }
+
+    for format in [
+        ["--format=gnu"].as_slice(),
+        &["--format=pax", "--sparse-version=0.0"],
+        &["--format=pax", "--sparse-version=0.1"],
+        &["--format=pax", "--sparse-version=1.0"],
+    ] {
+        let tar = tmp.join("test.tar");
+        assert!(std::process::Command::new("tar")
+            .arg("cSf")
+            .arg(&tar)
+            .args(format)
+            .arg("-C")
+            .arg(tmp)
+            .args(["a", "c", "d", "sparse"])
+            .status()
+            .unwrap()
+            .success());
+        let mut fs = vfs_tar::TarFs::from_io(std::fs::File::open(&tar).unwrap(), ());
+        assert_eq!(
+            fs.metadata(long_dir.as_bytes()).unwrap(),
+            vfs::Metadata {
+                file_type: vfs::FileType::Dir,
+                len: 0,
+                mode: Some(0o750),
+                mtime: Some(mtime),
+            }
+        );
+        assert_eq!(
+            fs.metadata(b_path.as_bytes()).unwrap(),
+            vfs::Metadata {
+                file_type: vfs::FileType::File,
+                len: b_data.len() as u64,
+                mode: Some(0o640),
+                mtime: Some(mtime),
+            }
+        );
+        assert!(matches!(
+            fs.metadata(b"c").unwrap(),
+            vfs::Metadata {
+                file_type: vfs::FileType::SymLink,
+                len: 0,
+                mode: Some(0o777),
+                mtime: Some(_),
+            }
+        ));
+        assert_eq!(
+            fs.metadata(b"./sparse").unwrap(),
+            vfs::Metadata {
+                file_type: vfs::FileType::File,
+                len: sparse_offset * 4,
+                mode: Some(0o600),
+                mtime: Some(mtime),
+            }
+        );
+        let b = fs.open(b_path.as_bytes()).unwrap();
+        assert_eq!(std::io::read_to_string(b).unwrap(), b_data);
+        let d = fs.open(b"d").unwrap();
+        assert_eq!(std::io::read_to_string(d).unwrap(), b_data);
+        let mut sparse = fs.open(b"sparse").unwrap();
+        let mut buf = vec![0xff; sparse_data.len() + 2];
+        sparse.seek(SeekFrom::Start(sparse_offset - 1)).unwrap();
+        sparse.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [b"\0", sparse_data.as_bytes(), b"\0"].concat());
+        sparse.rewind().unwrap();
+        let mut contents = Vec::new();
+        sparse.read_to_end(&mut contents).unwrap();
+        assert_eq!(contents.len() as u64, sparse_offset * 4);
+        assert!(matches!(fs.open(b"c"), Err(vfs_tar::Error::NotAFile)));
+    }
+
+    // A base-256 size so large that skipping the entry data would overflow the offset
+    let mut huge = header(b"huge", 0, b'0');
+    huge[124..128].copy_from_slice(&[0x80, 0, 0, 0]);
+    huge[128..136].fill(0xff);
+    let mut fs = vfs_tar::TarFs::from_io(std::io::Cursor::new(with_checksum(huge)), ());
+    assert!(matches!(
+        fs.metadata(b"huge"),
+        Err(vfs_tar::Error::LengthOverflow(0))
+    ));
+
+    // PAX sparse maps whose regions overflow or are out of order
+    for map in [
+        "18446744073709551615,2",
+        "0,18446744073709551615,1,1",
+        "8,1,4,1",
+    ] {
+        let record = format!("GNU.sparse.map={map}\n");
+        let record = format!("{} {record}", record.len() + 3);
+        let mut archive = with_checksum(header(b"pax", record.len() as u64, b'x')).to_vec();
+        archive.extend(record.as_bytes());
+        archive.resize(1024, 0);
+        archive.extend(with_checksum(header(b"sparse", 0, b'0')));
+        let mut fs = vfs_tar::TarFs::from_io(std::io::Cursor::new(archive), ());
+        assert!(matches!(
+            fs.metadata(b"sparse"),
+            Err(vfs_tar::Error::InvalidSparseMap)
+        ));
+    }
+}
+
+/// Returns a ustar header without its checksum.
+fn header(name: &[u8], len: u64, type_flag: u8) -> [u8; 512] {
+    let mut header = [0; 512];
+    header[..name.len()].copy_from_slice(name);
+    header[100..108].copy_from_slice(b"0000644\0");
+    header[124..136].copy_from_slice(format!("{len:011o}\0").as_bytes());
+    header[136..148].copy_from_slice(b"00000000000\0");
+    header[156] = type_flag;
+    header
+}
+
+fn with_checksum(mut header: [u8; 512]) -> [u8; 512] {
+    header[148..156].fill(b' ');
+    let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+    header[148..155].copy_from_slice(format!("{checksum:06o}\0").as_bytes());
+    header
+}
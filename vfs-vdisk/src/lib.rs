@@ -0,0 +1,419 @@
+mod qcow2;
+mod sparse;
+mod vhd;
+mod vhdx;
+mod vmdk;
+
+use std::{
+    cell::RefCell,
+    io::{Read, Seek, SeekFrom},
+    rc::Rc,
+};
+use vfs::{
+    bytes::{be_u32_at, be_u64_at, u16_at, u32_at, u64_at},
+    io::read_at,
+};
+
+/// Upper bound on the length of backing file chains, so that loops terminate
+const MAX_BACKING_DEPTH: usize = 16;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed reading from backing IO")]
+    Io(#[from] std::io::Error),
+    #[error("invalid {0} image")]
+    InvalidImage(&'static str),
+    #[error("unsupported {0} feature: {1}")]
+    UnsupportedFeature(&'static str, &'static str),
+    #[error("{0:?} is referenced by the image, but there is no way to open other files")]
+    NoSiblingOpener(String),
+    #[error("backing file chain is too long")]
+    BackingChainTooLong,
+    #[error("entry not found")]
+    NotFound,
+}
+
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek + ?Sized> ReadSeek for T {}
+
+/// Opens a file that an image refers to by name, such as the backing file of a differencing
+/// image or an extent of a VMDK descriptor. Names are relative to the directory of the
+/// outermost image, unless absolute.
+pub type OpenSibling = Box<dyn FnMut(&[u8]) -> std::io::Result<Box<dyn ReadSeek>>>;
+
+type Shared = Rc<RefCell<dyn ReadSeek>>;
+
+/// Opens files named by an image relative to the image's own directory.
+struct Siblings<'a> {
+    open: &'a mut OpenSibling,
+    /// Directory of the image, with a trailing slash unless empty
+    dir: Vec<u8>,
+}
+
+impl Siblings<'_> {
+    fn path(&self, name: &[u8]) -> Vec<u8> {
+        if name.starts_with(b"/") {
+            name.to_vec()
+        } else {
+            [&self.dir, name].concat()
+        }
+    }
+
+    fn open(&mut self, name: &[u8]) -> Result<Box<dyn ReadSeek>, Error> {
+        let path = self.path(name);
+        Ok((self.open)(&path)?)
+    }
+
+    /// Returns the siblings of the file `name`, which resolve names relative to its directory.
+    fn of(&mut self, name: &[u8]) -> Siblings<'_> {
+        let mut dir = self.path(name);
+        dir.truncate(dir.iter().rposition(|&b| b == b'/').map_or(0, |i| i + 1));
+        Siblings {
+            open: self.open,
+            dir,
+        }
+    }
+}
+
+/// Opens a sibling file, failing if there is no way to open siblings.
+fn open_sibling(siblings: Option<&mut Siblings>, name: &[u8]) -> Result<Box<dyn ReadSeek>, Error> {
+    siblings
+        .ok_or_else(|| Error::NoSiblingOpener(String::from_utf8_lossy(name).into_owned()))?
+        .open(name)
+}
+
+#[derive(Clone, Copy)]
+enum Codec {
+    /// Raw deflate, as used by qcow2
+    Deflate,
+    /// Deflate with a zlib header, as used by VMDK
+    Zlib,
+    Zstd,
+}
+
+/// A compressed cluster, which is decompressed as a whole.
+struct Compressed {
+    reader: Shared,
+    offset: u64,
+    /// Upper bound on the compressed length, which may extend past the end of the image
+    len: usize,
+    codec: Codec,
+    /// Guest offset and length of the cluster
+    guest_start: u64,
+    guest_len: usize,
+}
+
+impl Compressed {
+    fn decompress(&self) -> std::io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        {
+            let mut reader = self.reader.borrow_mut();
+            reader.seek(SeekFrom::Start(self.offset))?;
+            (&mut *reader)
+                .take(self.len as u64)
+                .read_to_end(&mut data)?;
+        }
+        let invalid = |_| std::io::Error::from(std::io::ErrorKind::InvalidData);
+        let mut out = match self.codec {
+            Codec::Deflate => {
+                miniz_oxide::inflate::decompress_to_vec_with_limit(&data, self.guest_len)
+                    .map_err(invalid)?
+            }
+            Codec::Zlib => {
+                miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(&data, self.guest_len)
+                    .map_err(invalid)?
+            }
+            Codec::Zstd => {
+                let mut out = Vec::new();
+                ruzstd::decoding::StreamingDecoder::new(data.as_slice())
+                    .map_err(std::io::Error::other)?
+                    .take(self.guest_len as u64)
+                    .read_to_end(&mut out)?;
+                out
+            }
+        };
+        // Trailing zeros of a cluster needn't be stored
+        out.resize(self.guest_len, 0);
+        Ok(out)
+    }
+}
+
+/// How a range of guest bytes is stored.
+enum Mapping {
+    Zero,
+    /// Not allocated in this image, so read from the backing file, or as zeros without one
+    Backing,
+    /// A repeated 4 byte pattern, aligned to the guest offset
+    Fill([u8; 4]),
+    /// Stored uncompressed, starting at this offset of a file
+    Data(Shared, u64),
+    Compressed(Compressed),
+}
+
+/// A virtual disk format, which maps guest offsets to where their data is stored.
+trait Format {
+    fn len(&self) -> u64;
+
+    /// Maps the guest byte at `offset`, returning how it is stored and the guest offset that the
+    /// mapping extends to.
+    fn map(&mut self, offset: u64) -> Result<(Mapping, u64), Error>;
+
+    /// Name of the backing file that unallocated ranges are read from.
+    fn backing_file(&self) -> Option<&[u8]>;
+}
+
+type OpenFormat = fn(Shared, Option<&mut Siblings>) -> Result<Box<dyn Format>, Error>;
+
+/// Detects the format of a backing file by its magic, returning `None` for raw images.
+fn probe(io: &mut dyn ReadSeek) -> Result<Option<OpenFormat>, Error> {
+    let mut magic = [0; 21];
+    io.seek(SeekFrom::Start(0))?;
+    let n = io.read(&mut magic)?;
+    let magic = &magic[..n];
+    let open: OpenFormat = if magic.starts_with(qcow2::MAGIC) {
+        qcow2::open
+    } else if magic.starts_with(vhdx::MAGIC) {
+        vhdx::open
+    } else if magic.starts_with(vmdk::SPARSE_MAGIC) || magic.starts_with(vmdk::DESCRIPTOR_MAGIC) {
+        vmdk::open
+    } else if magic.starts_with(sparse::MAGIC) {
+        sparse::open
+    } else if vhd::probe(io)? {
+        vhd::open
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(open))
+}
+
+/// Opens an image and its chain of backing files.
+fn open_disk(
+    reader: Shared,
+    open: OpenFormat,
+    mut siblings: Option<&mut Siblings>,
+    depth: usize,
+) -> Result<File, Error> {
+    if depth > MAX_BACKING_DEPTH {
+        return Err(Error::BackingChainTooLong);
+    }
+    let format = open(reader, siblings.as_deref_mut())?;
+    let backing = match format.backing_file() {
+        Some(name) => {
+            let mut io = open_sibling(siblings.as_deref_mut(), name)?;
+            let siblings = siblings.unwrap();
+            Some(match probe(&mut *io)? {
+                Some(open) => {
+                    let mut siblings = siblings.of(name);
+                    let reader = Rc::new(RefCell::new(io));
+                    Box::new(open_disk(reader, open, Some(&mut siblings), depth + 1)?)
+                }
+                None => io,
+            })
+        }
+        None => None,
+    };
+    Ok(File {
+        format,
+        backing,
+        cluster: None,
+        offset: 0,
+    })
+}
+
+/// A decompressed cluster, kept for reads of the rest of it.
+struct CachedCluster {
+    guest_start: u64,
+    data: Vec<u8>,
+}
+
+/// The guest contents of a virtual disk.
+pub struct File {
+    format: Box<dyn Format>,
+    backing: Option<Box<dyn ReadSeek>>,
+    cluster: Option<CachedCluster>,
+    offset: u64,
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.format.len().saturating_sub(self.offset);
+        let max = (buf.len() as u64).min(remaining) as usize;
+        if max == 0 {
+            return Ok(0);
+        }
+        let offset = self.offset;
+        let (mapping, end) = self.format.map(offset).map_err(std::io::Error::other)?;
+        let buf = &mut buf[..(max as u64).min(end - offset) as usize];
+        let n = match mapping {
+            Mapping::Zero => {
+                buf.fill(0);
+                buf.len()
+            }
+            Mapping::Backing => match &mut self.backing {
+                Some(backing) => {
+                    backing.seek(SeekFrom::Start(offset))?;
+                    match backing.read(buf)? {
+                        // Backing files may be shorter than the image
+                        0 => {
+                            buf.fill(0);
+                            buf.len()
+                        }
+                        n => n,
+                    }
+                }
+                None => {
+                    buf.fill(0);
+                    buf.len()
+                }
+            },
+            Mapping::Fill(pattern) => {
+                for (i, b) in buf.iter_mut().enumerate() {
+                    *b = pattern[(offset as usize + i) % 4];
+                }
+                buf.len()
+            }
+            Mapping::Data(reader, pos) => {
+                let mut reader = reader.borrow_mut();
+                reader.seek(SeekFrom::Start(pos))?;
+                match reader.read(buf)? {
+                    0 => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+                    n => n,
+                }
+            }
+            Mapping::Compressed(compressed) => {
+                let cached = self
+                    .cluster
+                    .as_ref()
+                    .is_some_and(|c| c.guest_start == compressed.guest_start);
+                if !cached {
+                    self.cluster = Some(CachedCluster {
+                        guest_start: compressed.guest_start,
+                        data: compressed.decompress()?,
+                    });
+                }
+                let cluster = self.cluster.as_ref().unwrap();
+                let start = (offset - cluster.guest_start) as usize;
+                buf.copy_from_slice(&cluster.data[start..start + buf.len()]);
+                buf.len()
+            }
+        };
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.offset = vfs::io::seek_position(pos, self.offset, self.format.len())?;
+        Ok(self.offset)
+    }
+}
+
+struct DiskFs<R: Read + Seek> {
+    reader: Rc<RefCell<R>>,
+    open_sibling: Option<OpenSibling>,
+    /// Guest length, known after the first access
+    len: Option<u64>,
+}
+
+impl<R: Read + Seek + 'static> DiskFs<R> {
+    fn new(io: R, open_sibling: Option<OpenSibling>) -> Self {
+        Self {
+            reader: Rc::new(RefCell::new(io)),
+            open_sibling,
+            len: None,
+        }
+    }
+
+    /// Opens the disk, which is the only file, at the root path.
+    fn open(&mut self, path: &[u8], open: OpenFormat) -> Result<File, Error> {
+        if !vfs::path::normalize(path).is_empty() {
+            return Err(Error::NotFound);
+        }
+        let mut siblings = self.open_sibling.as_mut().map(|open| Siblings {
+            open,
+            dir: Vec::new(),
+        });
+        let file = open_disk(self.reader.clone(), open, siblings.as_mut(), 0)?;
+        self.len = Some(file.format.len());
+        Ok(file)
+    }
+
+    fn metadata(&mut self, path: &[u8], open: OpenFormat) -> Result<vfs::Metadata, Error> {
+        let len = match self.len {
+            Some(len) if vfs::path::normalize(path).is_empty() => len,
+            _ => self.open(path, open)?.format.len(),
+        };
+        Ok(vfs::Metadata {
+            file_type: vfs::FileType::File,
+            len,
+            mode: None,
+            mtime: None,
+        })
+    }
+}
+
+macro_rules! impl_fs {
+    ($(#[$attr:meta])* $T:ident, $open:path) => {
+        $(#[$attr])*
+        pub struct $T<R: Read + Seek>(DiskFs<R>);
+
+        impl<R: Read + Seek + 'static> $T<R> {
+            /// Creates the disk with a way to open the files it refers to by name, such as its
+            /// backing file.
+            pub fn with_sibling_opener(io: R, open_sibling: OpenSibling) -> Self {
+                Self(DiskFs::new(io, Some(open_sibling)))
+            }
+        }
+
+        impl<R: Read + Seek + 'static> vfs::IoBackedFs<R> for $T<R> {
+            type Password = ();
+
+            fn from_io(io: R, _password: Self::Password) -> Self {
+                Self(DiskFs::new(io, None))
+            }
+        }
+
+        impl<R: Read + Seek + 'static> vfs::Fs for $T<R> {
+            type Path = [u8];
+            type Error = Error;
+            type File = File;
+
+            fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Error> {
+                self.0.metadata(path, $open)
+            }
+
+            fn open(&mut self, path: &[u8]) -> Result<Self::File, Error> {
+                self.0.open(path, $open)
+            }
+        }
+    };
+}
+
+impl_fs!(
+    /// A qcow2 image, including compressed clusters and a backing file of any supported format.
+    Qcow2Fs,
+    qcow2::open
+);
+impl_fs!(
+    /// A fixed, dynamic or differencing VHD image.
+    VhdFs,
+    vhd::open
+);
+impl_fs!(
+    /// A VHDX image, which may be differencing.
+    VhdxFs,
+    vhdx::open
+);
+impl_fs!(
+    /// A VMDK image, either a single sparse extent (including stream-optimized ones) or a
+    /// descriptor naming flat and sparse extent files.
+    VmdkFs,
+    vmdk::open
+);
+impl_fs!(
+    /// An Android sparse image, as produced by `img2simg`.
+    AndroidSparseFs,
+    sparse::open
+);
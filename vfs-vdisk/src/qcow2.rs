@@ -0,0 +1,165 @@
+//! QEMU copy-on-write images, version 2 and 3.
+
+use crate::{
+    be_u32_at, be_u64_at, read_at, Codec, Compressed, Error, Format, Mapping, Shared, Siblings,
+};
+use std::{collections::HashMap, io::SeekFrom, rc::Rc};
+
+pub(crate) const MAGIC: &[u8; 4] = b"QFI\xfb";
+const NAME: &str = "qcow2";
+const HEADER_LEN: usize = 72;
+const V3_HEADER_LEN: usize = 104;
+const INCOMPAT_DIRTY: u64 = 1 << 0;
+const INCOMPAT_CORRUPT: u64 = 1 << 1;
+const INCOMPAT_EXTERNAL_DATA: u64 = 1 << 2;
+const INCOMPAT_COMPRESSION_TYPE: u64 = 1 << 3;
+const INCOMPAT_EXTENDED_L2: u64 = 1 << 4;
+const COMPRESSION_ZSTD: u8 = 1;
+const OFFSET_MASK: u64 = 0x00ff_ffff_ffff_fe00;
+const L2_COMPRESSED: u64 = 1 << 62;
+const L2_ZERO: u64 = 1 << 0;
+/// Number of L2 tables kept in memory
+const L2_CACHE_LEN: usize = 64;
+
+struct Qcow2 {
+    reader: Shared,
+    cluster_bits: u32,
+    len: u64,
+    l1: Vec<u64>,
+    l2_cache: HashMap<u64, Rc<Vec<u64>>>,
+    codec: Codec,
+    backing_file: Option<Vec<u8>>,
+}
+
+pub(crate) fn open(
+    reader: Shared,
+    _siblings: Option<&mut Siblings>,
+) -> Result<Box<dyn Format>, Error> {
+    let header = read_at(&reader, 0, HEADER_LEN)?;
+    if &header[..4] != MAGIC {
+        return Err(Error::InvalidImage(NAME));
+    }
+    let version = be_u32_at(&header, 4);
+    let cluster_bits = be_u32_at(&header, 20);
+    if !(2..=3).contains(&version) || !(9..=21).contains(&cluster_bits) {
+        return Err(Error::InvalidImage(NAME));
+    }
+    if be_u32_at(&header, 32) != 0 {
+        return Err(Error::UnsupportedFeature(NAME, "encryption"));
+    }
+    let mut codec = Codec::Deflate;
+    if version == 3 {
+        let v3 = read_at(&reader, HEADER_LEN as u64, V3_HEADER_LEN - HEADER_LEN)?;
+        let incompatible = be_u64_at(&v3, 0);
+        let header_len = be_u32_at(&v3, 28) as usize;
+        if incompatible & INCOMPAT_CORRUPT != 0 {
+            return Err(Error::InvalidImage(NAME));
+        }
+        if incompatible & INCOMPAT_EXTERNAL_DATA != 0 {
+            return Err(Error::UnsupportedFeature(NAME, "external data files"));
+        }
+        if incompatible & INCOMPAT_EXTENDED_L2 != 0 {
+            return Err(Error::UnsupportedFeature(NAME, "extended L2 entries"));
+        }
+        if incompatible & !(INCOMPAT_DIRTY | INCOMPAT_COMPRESSION_TYPE) != 0 {
+            return Err(Error::UnsupportedFeature(
+                NAME,
+                "unknown incompatible features",
+            ));
+        }
+        if incompatible & INCOMPAT_COMPRESSION_TYPE != 0
+            && header_len > V3_HEADER_LEN
+            && read_at(&reader, V3_HEADER_LEN as u64, 1)?[0] == COMPRESSION_ZSTD
+        {
+            codec = Codec::Zstd;
+        }
+    }
+    let backing_offset = be_u64_at(&header, 8);
+    let backing_file = match backing_offset {
+        0 => None,
+        _ => Some(read_at(
+            &reader,
+            backing_offset,
+            be_u32_at(&header, 16) as usize,
+        )?),
+    };
+    let l1_len = be_u32_at(&header, 36) as u64;
+    if l1_len * 8 > reader.borrow_mut().seek(SeekFrom::End(0))? {
+        return Err(Error::InvalidImage(NAME));
+    }
+    let l1 = read_at(&reader, be_u64_at(&header, 40), l1_len as usize * 8)?;
+    Ok(Box::new(Qcow2 {
+        cluster_bits,
+        len: be_u64_at(&header, 24),
+        l1: l1.chunks_exact(8).map(|e| be_u64_at(e, 0)).collect(),
+        l2_cache: HashMap::new(),
+        codec,
+        backing_file,
+        reader,
+    }))
+}
+
+impl Qcow2 {
+    fn l2_table(&mut self, offset: u64) -> Result<Rc<Vec<u64>>, Error> {
+        if let Some(table) = self.l2_cache.get(&offset) {
+            return Ok(table.clone());
+        }
+        if self.l2_cache.len() >= L2_CACHE_LEN {
+            self.l2_cache.clear();
+        }
+        let data = read_at(&self.reader, offset, 1 << self.cluster_bits)?;
+        let table = Rc::new(data.chunks_exact(8).map(|e| be_u64_at(e, 0)).collect());
+        self.l2_cache.insert(offset, Rc::clone(&table));
+        Ok(table)
+    }
+}
+
+impl Format for Qcow2 {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn map(&mut self, offset: u64) -> Result<(Mapping, u64), Error> {
+        let cluster_len = 1 << self.cluster_bits;
+        let l2_len = cluster_len / 8;
+        let cluster = offset >> self.cluster_bits;
+        let cluster_start = cluster << self.cluster_bits;
+        let end = cluster_start + cluster_len;
+        let l2_offset = self
+            .l1
+            .get((cluster / l2_len) as usize)
+            .map_or(0, |e| e & OFFSET_MASK);
+        if l2_offset == 0 {
+            return Ok((Mapping::Backing, end));
+        }
+        let entry = self.l2_table(l2_offset)?[(cluster % l2_len) as usize];
+        if entry & L2_COMPRESSED != 0 {
+            // The offset and the number of additional 512 byte sectors share the entry
+            let offset_bits = 62 - (self.cluster_bits - 8);
+            let host_offset = entry & ((1 << offset_bits) - 1);
+            let sectors = ((entry >> offset_bits) & ((1 << (self.cluster_bits - 8)) - 1)) + 1;
+            let compressed = Compressed {
+                reader: self.reader.clone(),
+                offset: host_offset,
+                len: (sectors * 512 - (host_offset & 511)) as usize,
+                codec: self.codec,
+                guest_start: cluster_start,
+                guest_len: cluster_len as usize,
+            };
+            return Ok((Mapping::Compressed(compressed), end));
+        }
+        let host_offset = entry & OFFSET_MASK;
+        let mapping = if entry & L2_ZERO != 0 {
+            Mapping::Zero
+        } else if host_offset == 0 {
+            Mapping::Backing
+        } else {
+            Mapping::Data(self.reader.clone(), host_offset + (offset - cluster_start))
+        };
+        Ok((mapping, end))
+    }
+
+    fn backing_file(&self) -> Option<&[u8]> {
+        self.backing_file.as_deref()
+    }
+}
@@ -0,0 +1,106 @@
+//! Android sparse images, which store a disk as a sequence of raw, fill and skipped chunks.
+
+use crate::{read_at, u16_at, u32_at, Error, Format, Mapping, Shared, Siblings};
+
+pub(crate) const MAGIC: &[u8; 4] = &0xed26ff3au32.to_le_bytes();
+const NAME: &str = "Android sparse";
+const HEADER_LEN: usize = 28;
+const CHUNK_HEADER_LEN: usize = 12;
+const CHUNK_RAW: u16 = 0xcac1;
+const CHUNK_FILL: u16 = 0xcac2;
+const CHUNK_DONT_CARE: u16 = 0xcac3;
+const CHUNK_CRC32: u16 = 0xcac4;
+
+enum Chunk {
+    /// Stored at this offset of the image
+    Raw(u64),
+    Fill([u8; 4]),
+    DontCare,
+}
+
+struct Sparse {
+    reader: Shared,
+    len: u64,
+    /// Chunks by their guest offset, in order
+    chunks: Vec<(u64, Chunk)>,
+}
+
+pub(crate) fn open(
+    reader: Shared,
+    _siblings: Option<&mut Siblings>,
+) -> Result<Box<dyn Format>, Error> {
+    let header = read_at(&reader, 0, HEADER_LEN)?;
+    if &header[..4] != MAGIC || u16_at(&header, 4) != 1 {
+        return Err(Error::InvalidImage(NAME));
+    }
+    let header_len = u16_at(&header, 8) as u64;
+    let chunk_header_len = u16_at(&header, 10) as u64;
+    let block_len = u32_at(&header, 12) as u64;
+    let blocks = u32_at(&header, 16) as u64;
+    let chunk_count = u32_at(&header, 20);
+    if block_len == 0 || !block_len.is_multiple_of(4) {
+        return Err(Error::InvalidImage(NAME));
+    }
+    if chunk_header_len < CHUNK_HEADER_LEN as u64 {
+        return Err(Error::InvalidImage(NAME));
+    }
+    let mut chunks = Vec::new();
+    let mut pos = header_len;
+    let mut guest = 0;
+    for _ in 0..chunk_count {
+        let header = read_at(&reader, pos, CHUNK_HEADER_LEN)?;
+        let len = u32_at(&header, 4) as u64 * block_len;
+        let total_len = u32_at(&header, 8) as u64;
+        let data = pos + chunk_header_len;
+        let chunk = match u16_at(&header, 0) {
+            CHUNK_RAW => Chunk::Raw(data),
+            CHUNK_FILL => Chunk::Fill(read_at(&reader, data, 4)?.try_into().unwrap()),
+            CHUNK_DONT_CARE => Chunk::DontCare,
+            CHUNK_CRC32 => {
+                pos += total_len;
+                continue;
+            }
+            _ => return Err(Error::InvalidImage(NAME)),
+        };
+        if len > 0 {
+            chunks.push((guest, chunk));
+        }
+        guest += len;
+        pos += total_len;
+    }
+    // Blocks past the last chunk read as zeros
+    let len = blocks * block_len;
+    if guest < len {
+        chunks.push((guest, Chunk::DontCare));
+    }
+    Ok(Box::new(Sparse {
+        reader,
+        len,
+        chunks,
+    }))
+}
+
+impl Format for Sparse {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn map(&mut self, offset: u64) -> Result<(Mapping, u64), Error> {
+        let i = self.chunks.partition_point(|(start, _)| *start <= offset);
+        let end = self.chunks.get(i).map_or(self.len, |(start, _)| *start);
+        let Some((start, chunk)) = i.checked_sub(1).map(|i| &self.chunks[i]) else {
+            return Ok((Mapping::Zero, end));
+        };
+        let mapping = match chunk {
+            Chunk::Raw(pos) => Mapping::Data(self.reader.clone(), pos + (offset - start)),
+            // Fill chunks start at block boundaries, so the pattern is aligned to the guest offset
+            Chunk::Fill(pattern) => Mapping::Fill(*pattern),
+            Chunk::DontCare => Mapping::Zero,
+        };
+        Ok((mapping, end))
+    }
+
+    fn backing_file(&self) -> Option<&[u8]> {
+        None
+    }
+}
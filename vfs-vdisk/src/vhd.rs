@@ -0,0 +1,203 @@
+//! Virtual PC and Hyper-V VHD images, which are fixed, dynamic or differencing.
+
+use crate::{be_u32_at, be_u64_at, read_at, Error, Format, Mapping, ReadSeek, Shared, Siblings};
+use std::{
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom},
+};
+
+const FOOTER_MAGIC: &[u8; 8] = b"conectix";
+const DYNAMIC_HEADER_MAGIC: &[u8; 8] = b"cxsparse";
+const NAME: &str = "VHD";
+const FOOTER_LEN: u64 = 512;
+const DYNAMIC_HEADER_LEN: usize = 1024;
+const SECTOR_LEN: u64 = 512;
+const TYPE_FIXED: u32 = 2;
+const TYPE_DYNAMIC: u32 = 3;
+const TYPE_DIFFERENCING: u32 = 4;
+const UNALLOCATED: u32 = 0xffffffff;
+/// Parent locator platform code of a relative Windows path in UTF-16LE
+const PLATFORM_RELATIVE: &[u8; 4] = b"W2ru";
+/// Number of block bitmaps of differencing images kept in memory
+const BITMAP_CACHE_LEN: usize = 64;
+
+/// Checks for the footer at the end of the image, which is also copied to the start of dynamic
+/// images.
+pub(crate) fn probe(io: &mut dyn ReadSeek) -> Result<bool, Error> {
+    let len = io.seek(SeekFrom::End(0))?;
+    let Some(start) = len.checked_sub(FOOTER_LEN) else {
+        return Ok(false);
+    };
+    let mut magic = [0; 8];
+    io.seek(SeekFrom::Start(start))?;
+    io.read_exact(&mut magic)?;
+    Ok(&magic == FOOTER_MAGIC)
+}
+
+enum Kind {
+    Fixed,
+    Dynamic {
+        bat: Vec<u32>,
+        block_len: u64,
+        /// Length of the sector bitmap before the data of each block
+        bitmap_len: u64,
+        /// Block bitmaps by block number, which are only needed to tell which sectors of a
+        /// differencing image are read from the parent
+        bitmaps: Option<HashMap<u64, Vec<u8>>>,
+    },
+}
+
+struct Vhd {
+    reader: Shared,
+    len: u64,
+    kind: Kind,
+    parent: Option<Vec<u8>>,
+}
+
+/// Converts a parent path stored by Windows to a sibling name.
+pub(crate) fn parent_name(path: &str) -> Vec<u8> {
+    let path = path.replace('\\', "/");
+    path.strip_prefix("./").unwrap_or(&path).as_bytes().to_vec()
+}
+
+/// Finds the parent of a differencing image, preferring the relative path of the parent
+/// locators over the bare name in the header.
+fn parent<R: Read + Seek + ?Sized>(
+    reader: &std::cell::RefCell<R>,
+    header: &[u8],
+) -> Result<Vec<u8>, Error> {
+    for locator in header[576..768].chunks_exact(24) {
+        if &locator[..4] == PLATFORM_RELATIVE {
+            let data = read_at(
+                reader,
+                be_u64_at(locator, 16),
+                be_u32_at(locator, 8) as usize,
+            )?;
+            let units: Vec<u16> = data
+                .chunks_exact(2)
+                .map(|u| u16::from_le_bytes([u[0], u[1]]))
+                .collect();
+            return Ok(parent_name(&String::from_utf16_lossy(&units)));
+        }
+    }
+    let units: Vec<u16> = header[64..576]
+        .chunks_exact(2)
+        .map(|u| u16::from_be_bytes([u[0], u[1]]))
+        .take_while(|&u| u != 0)
+        .collect();
+    Ok(parent_name(&String::from_utf16_lossy(&units)))
+}
+
+pub(crate) fn open(
+    reader: Shared,
+    _siblings: Option<&mut Siblings>,
+) -> Result<Box<dyn Format>, Error> {
+    let image_len = reader.borrow_mut().seek(SeekFrom::End(0))?;
+    let footer_start = image_len
+        .checked_sub(FOOTER_LEN)
+        .ok_or(Error::InvalidImage(NAME))?;
+    let mut footer = read_at(&reader, footer_start, FOOTER_LEN as usize)?;
+    if &footer[..8] != FOOTER_MAGIC {
+        // The copy at the start of dynamic images survives truncation
+        footer = read_at(&reader, 0, FOOTER_LEN as usize)?;
+        if &footer[..8] != FOOTER_MAGIC {
+            return Err(Error::InvalidImage(NAME));
+        }
+    }
+    let len = be_u64_at(&footer, 48);
+    let disk_type = be_u32_at(&footer, 60);
+    if disk_type == TYPE_FIXED {
+        return Ok(Box::new(Vhd {
+            reader,
+            len,
+            kind: Kind::Fixed,
+            parent: None,
+        }));
+    }
+    if disk_type != TYPE_DYNAMIC && disk_type != TYPE_DIFFERENCING {
+        return Err(Error::InvalidImage(NAME));
+    }
+    let header = read_at(&reader, be_u64_at(&footer, 16), DYNAMIC_HEADER_LEN)?;
+    if &header[..8] != DYNAMIC_HEADER_MAGIC {
+        return Err(Error::InvalidImage(NAME));
+    }
+    let block_len = be_u32_at(&header, 32) as u64;
+    if block_len == 0 || !block_len.is_multiple_of(SECTOR_LEN) {
+        return Err(Error::InvalidImage(NAME));
+    }
+    let entries = be_u32_at(&header, 28) as usize;
+    let bat = read_at(&reader, be_u64_at(&header, 16), entries * 4)?;
+    let differencing = disk_type == TYPE_DIFFERENCING;
+    Ok(Box::new(Vhd {
+        len,
+        kind: Kind::Dynamic {
+            bat: bat.chunks_exact(4).map(|e| be_u32_at(e, 0)).collect(),
+            block_len,
+            bitmap_len: (block_len / SECTOR_LEN)
+                .div_ceil(8)
+                .next_multiple_of(SECTOR_LEN),
+            bitmaps: differencing.then(HashMap::new),
+        },
+        parent: if differencing {
+            Some(parent(&reader, &header)?)
+        } else {
+            None
+        },
+        reader,
+    }))
+}
+
+impl Format for Vhd {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn map(&mut self, offset: u64) -> Result<(Mapping, u64), Error> {
+        let Kind::Dynamic {
+            bat,
+            block_len,
+            bitmap_len,
+            bitmaps,
+        } = &mut self.kind
+        else {
+            return Ok((Mapping::Data(self.reader.clone(), offset), self.len));
+        };
+        let block = offset / *block_len;
+        let block_start = block * *block_len;
+        let end = block_start + *block_len;
+        let entry = bat.get(block as usize).copied().unwrap_or(UNALLOCATED);
+        if entry == UNALLOCATED {
+            return Ok((Mapping::Backing, end));
+        }
+        let bitmap_start = entry as u64 * SECTOR_LEN;
+        let data = Mapping::Data(
+            self.reader.clone(),
+            bitmap_start + *bitmap_len + (offset - block_start),
+        );
+        let Some(bitmaps) = bitmaps else {
+            return Ok((data, end));
+        };
+        if !bitmaps.contains_key(&block) {
+            if bitmaps.len() >= BITMAP_CACHE_LEN {
+                bitmaps.clear();
+            }
+            let bitmap = read_at(&self.reader, bitmap_start, *bitmap_len as usize)?;
+            bitmaps.insert(block, bitmap);
+        }
+        let bitmap = &bitmaps[&block];
+        // Sectors with a clear bit are read from the parent
+        let present = |sector: u64| bitmap[(sector / 8) as usize] & (0x80 >> (sector % 8)) != 0;
+        let sector = (offset - block_start) / SECTOR_LEN;
+        let sectors = *block_len / SECTOR_LEN;
+        let is_present = present(sector);
+        let run_end = (sector + 1..sectors)
+            .find(|&s| present(s) != is_present)
+            .unwrap_or(sectors);
+        let end = block_start + run_end * SECTOR_LEN;
+        Ok((if is_present { data } else { Mapping::Backing }, end))
+    }
+
+    fn backing_file(&self) -> Option<&[u8]> {
+        self.parent.as_deref()
+    }
+}
@@ -0,0 +1,274 @@
+//! Hyper-V VHDX images, which may be differencing.
+
+use crate::{read_at, u16_at, u32_at, u64_at, vhd, Error, Format, Mapping, Shared, Siblings};
+use std::collections::HashMap;
+
+pub(crate) const MAGIC: &[u8; 8] = b"vhdxfile";
+const NAME: &str = "VHDX";
+const HEADER_OFFSETS: [u64; 2] = [64 << 10, 128 << 10];
+const HEADER_LEN: usize = 4 << 10;
+const REGION_TABLE_OFFSET: u64 = 192 << 10;
+const REGION_TABLE_LEN: usize = 64 << 10;
+const METADATA_TABLE_LEN: usize = 64 << 10;
+const MIB: u64 = 1 << 20;
+const BAT_REGION: [u8; 16] = guid(
+    0x2dc27766,
+    0xf623,
+    0x4200,
+    *b"\x9d\x64\x11\x5e\x9b\xfd\x4a\x08",
+);
+const METADATA_REGION: [u8; 16] = guid(
+    0x8b7ca206,
+    0x4790,
+    0x4b9a,
+    *b"\xb8\xfe\x57\x5f\x05\x0f\x88\x6e",
+);
+const FILE_PARAMETERS: [u8; 16] = guid(
+    0xcaa16737,
+    0xfa36,
+    0x4d43,
+    *b"\xb3\xb6\x33\xf0\xaa\x44\xe7\x6b",
+);
+const DISK_SIZE: [u8; 16] = guid(
+    0x2fa54224,
+    0xcd1b,
+    0x4876,
+    *b"\xb2\x11\x5d\xbe\xd8\x3b\xf4\xb8",
+);
+const LOGICAL_SECTOR_SIZE: [u8; 16] = guid(
+    0x8141bf1d,
+    0xa96f,
+    0x4709,
+    *b"\xba\x47\xf2\x33\xa8\xfa\xab\x5f",
+);
+const PARENT_LOCATOR: [u8; 16] = guid(
+    0xa8d35f2d,
+    0xb30b,
+    0x454d,
+    *b"\xab\xf7\xd3\xd8\x48\x34\xab\x0c",
+);
+const HAS_PARENT: u32 = 1 << 1;
+const BLOCK_NOT_PRESENT: u64 = 0;
+const BLOCK_FULLY_PRESENT: u64 = 6;
+const BLOCK_PARTIALLY_PRESENT: u64 = 7;
+/// Number of sector bitmaps of differencing images kept in memory
+const BITMAP_CACHE_LEN: usize = 16;
+
+/// Encodes a GUID the way Windows stores it, with the first three fields little-endian.
+const fn guid(a: u32, b: u16, c: u16, d: [u8; 8]) -> [u8; 16] {
+    let a = a.to_le_bytes();
+    let b = b.to_le_bytes();
+    let c = c.to_le_bytes();
+    [
+        a[0], a[1], a[2], a[3], b[0], b[1], c[0], c[1], d[0], d[1], d[2], d[3], d[4], d[5], d[6],
+        d[7],
+    ]
+}
+
+/// CRC-32C, which checksums the headers.
+fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0x82f63b78 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+struct Vhdx {
+    reader: Shared,
+    len: u64,
+    block_len: u64,
+    sector_len: u64,
+    /// Number of payload blocks covered by each sector bitmap block
+    chunk_ratio: u64,
+    bat: Vec<u64>,
+    /// Sector bitmaps by chunk, only used by differencing images
+    bitmaps: HashMap<u64, Vec<u8>>,
+    parent: Option<Vec<u8>>,
+}
+
+/// Finds the relative path of the parent in a parent locator.
+fn parent(locator: &[u8]) -> Result<Vec<u8>, Error> {
+    let invalid = || Error::InvalidImage(NAME);
+    let utf16 = |offset: u32, len: u16| -> Result<String, Error> {
+        let data = locator
+            .get(offset as usize..offset as usize + len as usize)
+            .ok_or_else(invalid)?;
+        let units: Vec<u16> = data
+            .chunks_exact(2)
+            .map(|u| u16::from_le_bytes([u[0], u[1]]))
+            .collect();
+        Ok(String::from_utf16_lossy(&units))
+    };
+    let entries = locator.get(20..).ok_or_else(invalid)?;
+    for entry in entries.chunks_exact(12).take(u16_at(locator, 18) as usize) {
+        let key = utf16(u32_at(entry, 0), u16_at(entry, 8))?;
+        if key == "relative_path" {
+            let value = utf16(u32_at(entry, 4), u16_at(entry, 10))?;
+            return Ok(vhd::parent_name(&value));
+        }
+    }
+    Err(invalid())
+}
+
+pub(crate) fn open(
+    reader: Shared,
+    _siblings: Option<&mut Siblings>,
+) -> Result<Box<dyn Format>, Error> {
+    let invalid = || Error::InvalidImage(NAME);
+    if &read_at(&reader, 0, 8)?[..] != MAGIC {
+        return Err(invalid());
+    }
+    // Of the two headers, the valid one with the higher sequence number is current
+    let mut current: Option<Vec<u8>> = None;
+    for offset in HEADER_OFFSETS {
+        let mut header = read_at(&reader, offset, HEADER_LEN)?;
+        let checksum = u32_at(&header, 4);
+        header[4..8].fill(0);
+        if &header[..4] != b"head" || crc32c(&header) != checksum {
+            continue;
+        }
+        if current
+            .as_ref()
+            .is_none_or(|c| u64_at(c, 8) < u64_at(&header, 8))
+        {
+            current = Some(header);
+        }
+    }
+    let header = current.ok_or_else(invalid)?;
+    if header[48..64].iter().any(|&b| b != 0) {
+        return Err(Error::UnsupportedFeature(NAME, "log replay"));
+    }
+
+    let regions = read_at(&reader, REGION_TABLE_OFFSET, REGION_TABLE_LEN)?;
+    if &regions[..4] != b"regi" {
+        return Err(invalid());
+    }
+    let mut bat_region = None;
+    let mut metadata_region = None;
+    for entry in regions[16..]
+        .chunks_exact(32)
+        .take(u32_at(&regions, 8) as usize)
+    {
+        let region = Some((u64_at(entry, 16), u32_at(entry, 24) as usize));
+        if entry[..16] == BAT_REGION {
+            bat_region = region;
+        } else if entry[..16] == METADATA_REGION {
+            metadata_region = region;
+        }
+    }
+    let (bat_offset, bat_len) = bat_region.ok_or_else(invalid)?;
+    let (metadata_offset, _) = metadata_region.ok_or_else(invalid)?;
+
+    let table = read_at(&reader, metadata_offset, METADATA_TABLE_LEN)?;
+    if &table[..8] != b"metadata" {
+        return Err(invalid());
+    }
+    let mut items = HashMap::new();
+    for entry in table[32..]
+        .chunks_exact(32)
+        .take(u16_at(&table, 10) as usize)
+    {
+        let item: [u8; 16] = entry[..16].try_into().unwrap();
+        items.insert(item, (u32_at(entry, 16) as u64, u32_at(entry, 20) as usize));
+    }
+    let mut item = |id: &[u8; 16]| -> Result<Option<Vec<u8>>, Error> {
+        match items.remove(id) {
+            Some((offset, len)) => Ok(Some(read_at(&reader, metadata_offset + offset, len)?)),
+            None => Ok(None),
+        }
+    };
+    let parameters = item(&FILE_PARAMETERS)?.ok_or_else(invalid)?;
+    let len = item(&DISK_SIZE)?.ok_or_else(invalid)?;
+    let sector_len = item(&LOGICAL_SECTOR_SIZE)?.ok_or_else(invalid)?;
+    let parent = match u32_at(&parameters, 4) & HAS_PARENT {
+        0 => None,
+        _ => Some(parent(&item(&PARENT_LOCATOR)?.ok_or_else(invalid)?)?),
+    };
+    let block_len = u32_at(&parameters, 0) as u64;
+    let sector_len = u32_at(&sector_len, 0) as u64;
+    if !block_len.is_power_of_two() || !(MIB..=256 * MIB).contains(&block_len) {
+        return Err(invalid());
+    }
+    if sector_len != 512 && sector_len != 4096 {
+        return Err(invalid());
+    }
+
+    let bat = read_at(&reader, bat_offset, bat_len)?;
+    Ok(Box::new(Vhdx {
+        len: u64_at(&len, 0),
+        block_len,
+        sector_len,
+        chunk_ratio: (1 << 23) * sector_len / block_len,
+        bat: bat.chunks_exact(8).map(|e| u64_at(e, 0)).collect(),
+        bitmaps: HashMap::new(),
+        parent,
+        reader,
+    }))
+}
+
+impl Vhdx {
+    /// Tells whether a sector of a partially present block is stored in this image.
+    fn sector_present(&mut self, sector: u64) -> Result<bool, Error> {
+        let sectors_per_chunk = self.chunk_ratio * self.block_len / self.sector_len;
+        let chunk = sector / sectors_per_chunk;
+        if !self.bitmaps.contains_key(&chunk) {
+            let index = chunk * (self.chunk_ratio + 1) + self.chunk_ratio;
+            let entry = self.bat.get(index as usize).copied().unwrap_or(0);
+            if entry & 7 != BLOCK_FULLY_PRESENT {
+                return Err(Error::InvalidImage(NAME));
+            }
+            if self.bitmaps.len() >= BITMAP_CACHE_LEN {
+                self.bitmaps.clear();
+            }
+            let bitmap = read_at(&self.reader, entry >> 20 << 20, MIB as usize)?;
+            self.bitmaps.insert(chunk, bitmap);
+        }
+        let bit = sector % sectors_per_chunk;
+        Ok(self.bitmaps[&chunk][(bit / 8) as usize] & (1 << (bit % 8)) != 0)
+    }
+}
+
+impl Format for Vhdx {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn map(&mut self, offset: u64) -> Result<(Mapping, u64), Error> {
+        let block = offset / self.block_len;
+        let block_start = block * self.block_len;
+        let end = block_start + self.block_len;
+        // A sector bitmap entry follows the payload entries of each chunk
+        let index = block + block / self.chunk_ratio;
+        let entry = self.bat.get(index as usize).copied().unwrap_or(0);
+        let data = Mapping::Data(
+            self.reader.clone(),
+            (entry >> 20 << 20) + (offset - block_start),
+        );
+        let mapping = match entry & 7 {
+            BLOCK_NOT_PRESENT => Mapping::Backing,
+            BLOCK_FULLY_PRESENT => data,
+            BLOCK_PARTIALLY_PRESENT => {
+                let sector = offset / self.sector_len;
+                let is_present = self.sector_present(sector)?;
+                let sectors_end = end / self.sector_len;
+                let mut run_end = sector + 1;
+                while run_end < sectors_end && self.sector_present(run_end)? == is_present {
+                    run_end += 1;
+                }
+                let end = run_end * self.sector_len;
+                return Ok((if is_present { data } else { Mapping::Backing }, end));
+            }
+            // Undefined, zero and unmapped blocks
+            _ => Mapping::Zero,
+        };
+        Ok((mapping, end))
+    }
+
+    fn backing_file(&self) -> Option<&[u8]> {
+        self.parent.as_deref()
+    }
+}
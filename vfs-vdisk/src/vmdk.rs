@@ -0,0 +1,322 @@
+//! VMware VMDK images: hosted sparse extents, including stream-optimized ones, and descriptor
+//! files naming flat and sparse extent files.
+
+use crate::{
+    open_sibling, read_at, u32_at, u64_at, Codec, Compressed, Error, Format, Mapping, ReadSeek,
+    Shared, Siblings,
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{Read, SeekFrom},
+    rc::Rc,
+};
+
+pub(crate) const SPARSE_MAGIC: &[u8; 4] = b"KDMV";
+pub(crate) const DESCRIPTOR_MAGIC: &[u8; 21] = b"# Disk DescriptorFile";
+const NAME: &str = "VMDK";
+const SECTOR_LEN: u64 = 512;
+const HEADER_LEN: usize = 512;
+/// Upper bound on the length of descriptor files, which are small text files
+const MAX_DESCRIPTOR_LEN: u64 = 1 << 20;
+/// Grain directory offset of stream-optimized extents, whose footer holds the real one
+const GD_AT_END: u64 = u64::MAX;
+const FLAG_ZEROED_GTE: u32 = 1 << 2;
+const FLAG_COMPRESSED: u32 = 1 << 16;
+const COMPRESSION_DEFLATE: u16 = 1;
+/// Grain table entry of grains that read as zeros
+const GTE_ZERO: u32 = 1;
+/// Length of the LBA and size that precede compressed grains
+const GRAIN_MARKER_LEN: u64 = 12;
+/// Number of grain tables kept in memory
+const GT_CACHE_LEN: usize = 64;
+/// Parent content ID of images without a parent
+const NO_PARENT: &str = "ffffffff";
+
+struct SparseExtent {
+    reader: Shared,
+    grain_len: u64,
+    gtes_per_gt: u64,
+    /// Sector offsets of grain tables
+    gd: Vec<u32>,
+    gt_cache: HashMap<u32, Rc<Vec<u32>>>,
+    zeroed_gte: bool,
+    compressed: bool,
+}
+
+enum ExtentKind {
+    Sparse(Box<SparseExtent>),
+    /// Stored uncompressed, starting at this offset of a file
+    Flat(Shared, u64),
+    Zero,
+}
+
+struct Extent {
+    /// Guest offset and length
+    start: u64,
+    len: u64,
+    kind: ExtentKind,
+}
+
+struct Vmdk {
+    len: u64,
+    /// Extents in guest order
+    extents: Vec<Extent>,
+    parent: Option<Vec<u8>>,
+}
+
+/// Converts a count of sectors to bytes.
+fn sectors_to_bytes(sectors: u64) -> Result<u64, Error> {
+    sectors
+        .checked_mul(SECTOR_LEN)
+        .ok_or(Error::InvalidImage(NAME))
+}
+
+/// Reads the header of a sparse extent and its embedded descriptor, if any.
+fn sparse_extent(reader: Shared) -> Result<(SparseExtent, u64, Option<String>), Error> {
+    let mut header = read_at(&reader, 0, HEADER_LEN)?;
+    if &header[..4] != SPARSE_MAGIC {
+        return Err(Error::InvalidImage(NAME));
+    }
+    let descriptor = match (u64_at(&header, 28), u64_at(&header, 36)) {
+        (0, _) | (_, 0) => None,
+        (offset, len) => {
+            let data = read_at(
+                &reader,
+                sectors_to_bytes(offset)?,
+                sectors_to_bytes(len)?.min(MAX_DESCRIPTOR_LEN) as usize,
+            )?;
+            let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+            Some(String::from_utf8_lossy(&data[..end]).into_owned())
+        }
+    };
+    let image_len = reader.borrow_mut().seek(SeekFrom::End(0))?;
+    if u64_at(&header, 56) == GD_AT_END {
+        // The footer is followed by an end-of-stream marker
+        let footer = image_len
+            .checked_sub(2 * SECTOR_LEN)
+            .ok_or(Error::InvalidImage(NAME))?;
+        header = read_at(&reader, footer, HEADER_LEN)?;
+        if &header[..4] != SPARSE_MAGIC {
+            return Err(Error::InvalidImage(NAME));
+        }
+    }
+    let flags = u32_at(&header, 8);
+    let capacity = sectors_to_bytes(u64_at(&header, 12))?;
+    let grain_len = sectors_to_bytes(u64_at(&header, 20))?;
+    let gtes_per_gt = u32_at(&header, 44) as u64;
+    let compressed = flags & FLAG_COMPRESSED != 0;
+    if grain_len == 0 || gtes_per_gt == 0 {
+        return Err(Error::InvalidImage(NAME));
+    }
+    if compressed && u16::from_le_bytes([header[77], header[78]]) != COMPRESSION_DEFLATE {
+        return Err(Error::UnsupportedFeature(NAME, "compression algorithm"));
+    }
+    let gt_coverage = grain_len
+        .checked_mul(gtes_per_gt)
+        .ok_or(Error::InvalidImage(NAME))?;
+    // Both the grain directory and each grain table are stored in the extent
+    let gd_len = capacity.div_ceil(gt_coverage);
+    if gd_len * 4 > image_len || gtes_per_gt * 4 > image_len {
+        return Err(Error::InvalidImage(NAME));
+    }
+    let gd = read_at(
+        &reader,
+        sectors_to_bytes(u64_at(&header, 56))?,
+        gd_len as usize * 4,
+    )?;
+    let extent = SparseExtent {
+        reader,
+        grain_len,
+        gtes_per_gt,
+        gd: gd.chunks_exact(4).map(|e| u32_at(e, 0)).collect(),
+        gt_cache: HashMap::new(),
+        zeroed_gte: flags & FLAG_ZEROED_GTE != 0,
+        compressed,
+    };
+    Ok((extent, capacity, descriptor))
+}
+
+/// Returns the value of a `key="value"` line of a descriptor.
+fn descriptor_value<'a>(descriptor: &'a str, key: &str) -> Option<&'a str> {
+    descriptor.lines().find_map(|line| {
+        let (k, v) = line.split_once('=')?;
+        (k.trim() == key).then(|| v.trim().trim_matches('"'))
+    })
+}
+
+/// Returns the parent named by a descriptor, if it has one.
+fn descriptor_parent(descriptor: &str) -> Option<Vec<u8>> {
+    let cid = descriptor_value(descriptor, "parentCID")?;
+    if cid.eq_ignore_ascii_case(NO_PARENT) {
+        return None;
+    }
+    let name = descriptor_value(descriptor, "parentFileNameHint")?;
+    Some(crate::vhd::parent_name(name))
+}
+
+/// Parses an extent line such as `RW 2048 FLAT "disk-flat.vmdk" 0`, returning its length in
+/// sectors, type, file name and sector offset.
+fn extent_line(line: &str) -> Option<(u64, &str, Option<&str>, u64)> {
+    let mut words = line.trim().splitn(3, char::is_whitespace);
+    if !matches!(words.next()?, "RW" | "RDONLY" | "NOACCESS") {
+        return None;
+    }
+    let sectors = words.next()?.parse().ok()?;
+    let rest = words.next()?.trim_start();
+    let (kind, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let rest = rest.trim_start();
+    let Some(rest) = rest.strip_prefix('"') else {
+        return Some((sectors, kind, None, 0));
+    };
+    let (name, rest) = rest.split_once('"')?;
+    let offset = match rest.trim() {
+        "" => 0,
+        offset => offset.parse().ok()?,
+    };
+    Some((sectors, kind, Some(name), offset))
+}
+
+pub(crate) fn open(
+    reader: Shared,
+    mut siblings: Option<&mut Siblings>,
+) -> Result<Box<dyn Format>, Error> {
+    if read_at(&reader, 0, 4)? == SPARSE_MAGIC {
+        let (extent, len, descriptor) = sparse_extent(reader)?;
+        return Ok(Box::new(Vmdk {
+            len,
+            extents: vec![Extent {
+                start: 0,
+                len,
+                kind: ExtentKind::Sparse(Box::new(extent)),
+            }],
+            parent: descriptor.as_deref().and_then(descriptor_parent),
+        }));
+    }
+    let mut data = Vec::new();
+    {
+        let mut reader = reader.borrow_mut();
+        reader.seek(SeekFrom::Start(0))?;
+        (&mut *reader)
+            .take(MAX_DESCRIPTOR_LEN)
+            .read_to_end(&mut data)?;
+    }
+    if !data.starts_with(DESCRIPTOR_MAGIC) {
+        return Err(Error::InvalidImage(NAME));
+    }
+    let descriptor = String::from_utf8_lossy(&data);
+    let mut extents = Vec::new();
+    let mut start = 0;
+    for (sectors, kind, name, offset) in descriptor.lines().filter_map(extent_line) {
+        let len = sectors_to_bytes(sectors)?;
+        let mut open_extent = || -> Result<Shared, Error> {
+            let name = name.ok_or(Error::InvalidImage(NAME))?;
+            let io: Box<dyn ReadSeek> = open_sibling(siblings.as_deref_mut(), name.as_bytes())?;
+            Ok(Rc::new(RefCell::new(io)))
+        };
+        let kind = match kind {
+            "SPARSE" => ExtentKind::Sparse(Box::new(sparse_extent(open_extent()?)?.0)),
+            "FLAT" | "VMFS" => ExtentKind::Flat(open_extent()?, sectors_to_bytes(offset)?),
+            "ZERO" => ExtentKind::Zero,
+            _ => return Err(Error::UnsupportedFeature(NAME, "extent type")),
+        };
+        extents.push(Extent { start, len, kind });
+        start = start.checked_add(len).ok_or(Error::InvalidImage(NAME))?;
+    }
+    Ok(Box::new(Vmdk {
+        len: start,
+        extents,
+        parent: descriptor_parent(&descriptor),
+    }))
+}
+
+impl SparseExtent {
+    fn grain_table(&mut self, sector: u32) -> Result<Rc<Vec<u32>>, Error> {
+        if let Some(table) = self.gt_cache.get(&sector) {
+            return Ok(table.clone());
+        }
+        if self.gt_cache.len() >= GT_CACHE_LEN {
+            self.gt_cache.clear();
+        }
+        let data = read_at(
+            &self.reader,
+            sector as u64 * SECTOR_LEN,
+            self.gtes_per_gt as usize * 4,
+        )?;
+        let table = Rc::new(data.chunks_exact(4).map(|e| u32_at(e, 0)).collect());
+        self.gt_cache.insert(sector, Rc::clone(&table));
+        Ok(table)
+    }
+
+    /// Maps an offset relative to the start of the extent, which starts at `guest_start`.
+    fn map(&mut self, offset: u64, guest_start: u64) -> Result<(Mapping, u64), Error> {
+        let grain = offset / self.grain_len;
+        let grain_start = grain * self.grain_len;
+        let end = grain_start + self.grain_len;
+        let gt = self
+            .gd
+            .get((grain / self.gtes_per_gt) as usize)
+            .copied()
+            .unwrap_or(0);
+        if gt == 0 {
+            return Ok((Mapping::Backing, end));
+        }
+        let gte = self.grain_table(gt)?[(grain % self.gtes_per_gt) as usize];
+        let mapping = if gte == 0 {
+            Mapping::Backing
+        } else if gte == GTE_ZERO && self.zeroed_gte {
+            Mapping::Zero
+        } else if self.compressed {
+            let pos = gte as u64 * SECTOR_LEN;
+            let marker = read_at(&self.reader, pos, GRAIN_MARKER_LEN as usize)?;
+            Mapping::Compressed(Compressed {
+                reader: self.reader.clone(),
+                offset: pos + GRAIN_MARKER_LEN,
+                len: u32_at(&marker, 8) as usize,
+                codec: Codec::Zlib,
+                guest_start: guest_start + grain_start,
+                guest_len: self.grain_len as usize,
+            })
+        } else {
+            Mapping::Data(
+                self.reader.clone(),
+                gte as u64 * SECTOR_LEN + (offset - grain_start),
+            )
+        };
+        Ok((mapping, end))
+    }
+}
+
+impl Format for Vmdk {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn map(&mut self, offset: u64) -> Result<(Mapping, u64), Error> {
+        let i = self.extents.partition_point(|e| e.start <= offset);
+        let Some(extent) = i
+            .checked_sub(1)
+            .map(|i| &mut self.extents[i])
+            .filter(|e| offset < e.start + e.len)
+        else {
+            return Ok((Mapping::Zero, self.len));
+        };
+        let extent_end = extent.start + extent.len;
+        let (mapping, end) = match &mut extent.kind {
+            ExtentKind::Sparse(sparse) => {
+                let (mapping, end) = sparse.map(offset - extent.start, extent.start)?;
+                (mapping, extent.start + end)
+            }
+            ExtentKind::Flat(reader, pos) => (
+                Mapping::Data(reader.clone(), *pos + (offset - extent.start)),
+                extent_end,
+            ),
+            ExtentKind::Zero => (Mapping::Zero, extent_end),
+        };
+        Ok((mapping, end.min(extent_end)))
+    }
+
+    fn backing_file(&self) -> Option<&[u8]> {
+        self.parent.as_deref()
+    }
+}
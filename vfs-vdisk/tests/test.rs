@@ -0,0 +1,368 @@
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use vfs::{Fs, IoBackedFs};
+use vfs_vdisk::{AndroidSparseFs, OpenSibling, Qcow2Fs, ReadSeek, VhdFs, VhdxFs, VmdkFs};
+
+const CLUSTER: usize = 64 << 10;
+const MIB: usize = 1 << 20;
+
+fn patterned(len: usize, seed: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7 + seed + i / 512) as u8).collect()
+}
+
+/// Reads the whole disk, then reads back a few ranges after seeking.
+fn check(
+    mut fs: impl Fs<Path = [u8], File = vfs_vdisk::File, Error = vfs_vdisk::Error>,
+    expected: &[u8],
+) {
+    assert_eq!(fs.metadata(b"").unwrap().len, expected.len() as u64);
+    let mut file = fs.open(b"").unwrap();
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents).unwrap();
+    assert!(contents == expected);
+    let len = expected.len() as u64;
+    for pos in [0, 100, len / 3 - 10, len - 1] {
+        file.seek(SeekFrom::Start(pos)).unwrap();
+        let mut buf = [0; 20];
+        let n = file.read(&mut buf).unwrap();
+        assert!(n > 0 && buf[..n] == expected[pos as usize..][..n]);
+    }
+}
+
+/// A qcow2 image with 64 KiB clusters, a data cluster, a compressed cluster and a zero cluster
+/// over a raw backing file, returning the image and its expected contents.
+fn qcow2(backing: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let len = backing.len() + CLUSTER;
+    let mut image = vec![0; 6 * CLUSTER];
+    let header = &mut image[..104];
+    header[..4].copy_from_slice(b"QFI\xfb");
+    header[4..8].copy_from_slice(&3u32.to_be_bytes());
+    header[8..16].copy_from_slice(&200u64.to_be_bytes());
+    header[16..20].copy_from_slice(&8u32.to_be_bytes());
+    header[20..24].copy_from_slice(&16u32.to_be_bytes());
+    header[24..32].copy_from_slice(&(len as u64).to_be_bytes());
+    header[36..40].copy_from_slice(&1u32.to_be_bytes());
+    header[40..48].copy_from_slice(&(CLUSTER as u64).to_be_bytes());
+    header[100..104].copy_from_slice(&104u32.to_be_bytes());
+    image[200..208].copy_from_slice(b"base.raw");
+    image[CLUSTER..][..8].copy_from_slice(&(2 * CLUSTER as u64).to_be_bytes());
+
+    let mut expected = backing.to_vec();
+    expected.resize(len, 0);
+    let mut l2 = Vec::new();
+    let data = patterned(CLUSTER, 5);
+    image[3 * CLUSTER..4 * CLUSTER].copy_from_slice(&data);
+    expected[..CLUSTER].copy_from_slice(&data);
+    l2.push(3 * CLUSTER as u64);
+    let data: Vec<u8> = (0..CLUSTER).map(|i| (i / 100) as u8).collect();
+    let compressed = miniz_oxide::deflate::compress_to_vec(&data, 6);
+    let host = 4 * CLUSTER + 100;
+    image[host..][..compressed.len()].copy_from_slice(&compressed);
+    let sectors = ((host + compressed.len() - 1) >> 9) - (host >> 9);
+    l2.push(1 << 62 | (sectors as u64) << 54 | host as u64);
+    expected[CLUSTER..2 * CLUSTER].copy_from_slice(&data);
+    l2.push(1);
+    expected[2 * CLUSTER..3 * CLUSTER].fill(0);
+    for (i, entry) in l2.iter().enumerate() {
+        image[2 * CLUSTER + i * 8..][..8].copy_from_slice(&entry.to_be_bytes());
+    }
+    (image, expected)
+}
+
+/// A sibling opener that only opens `base.raw`.
+fn base_raw(backing: &[u8]) -> OpenSibling {
+    let backing = backing.to_vec();
+    Box::new(move |name| {
+        assert_eq!(name, b"base.raw");
+        Ok(Box::new(Cursor::new(backing.clone())) as Box<dyn ReadSeek>)
+    })
+}
+
+/// A sparse VMDK extent with 4 KiB grains and 4 entries per grain table, whose embedded
+/// descriptor names a parent. Of its 12 grains, three are allocated, one is zeroed by its grain
+/// table entry and the rest are unallocated, one grain table being missing altogether. Returns the
+/// image and its expected contents over `backing`.
+fn vmdk(backing: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    const GRAIN: usize = 4096;
+    let mut image = vec![0; 32 * 512];
+    let header = &mut image[..512];
+    header[..4].copy_from_slice(b"KDMV");
+    header[4..8].copy_from_slice(&1u32.to_le_bytes());
+    header[8..12].copy_from_slice(&(1u32 << 2).to_le_bytes());
+    header[12..20].copy_from_slice(&96u64.to_le_bytes());
+    header[20..28].copy_from_slice(&8u64.to_le_bytes());
+    header[28..36].copy_from_slice(&1u64.to_le_bytes());
+    header[36..44].copy_from_slice(&1u64.to_le_bytes());
+    header[44..48].copy_from_slice(&4u32.to_le_bytes());
+    header[56..64].copy_from_slice(&2u64.to_le_bytes());
+    let descriptor =
+        b"# Disk DescriptorFile\nparentCID=12345678\nparentFileNameHint=\"base.raw\"\n";
+    image[512..][..descriptor.len()].copy_from_slice(descriptor);
+    // The grain directory, then the first and last grain tables
+    for (sector, entries) in [
+        (2, &[3u32, 0, 4][..]),
+        (3, &[8, 0, 16, 1]),
+        (4, &[0, 0, 24, 0]),
+    ] {
+        for (i, entry) in entries.iter().enumerate() {
+            image[sector * 512 + i * 4..][..4].copy_from_slice(&entry.to_le_bytes());
+        }
+    }
+
+    let mut expected = backing.to_vec();
+    for (grain, sector, seed) in [(0, 8, 2), (2, 16, 3), (10, 24, 4)] {
+        let data = patterned(GRAIN, seed);
+        image[sector * 512..][..GRAIN].copy_from_slice(&data);
+        expected[grain * GRAIN..][..GRAIN].copy_from_slice(&data);
+    }
+    expected[3 * GRAIN..4 * GRAIN].fill(0);
+    (image, expected)
+}
+
+/// A dynamic or differencing VHD with 4 KiB blocks, of which the first and third are allocated.
+/// Only the first half of the first block is present in the differencing image. Returns the
+/// image and its expected contents over `backing`, or over zeros for dynamic images.
+fn vhd(backing: &[u8], differencing: bool) -> (Vec<u8>, Vec<u8>) {
+    const BLOCK: usize = 4096;
+    let mut image = vec![0; 24 * 512];
+    let mut footer = [0; 512];
+    footer[..8].copy_from_slice(b"conectix");
+    footer[16..24].copy_from_slice(&512u64.to_be_bytes());
+    footer[48..56].copy_from_slice(&(4 * BLOCK as u64).to_be_bytes());
+    footer[60..64].copy_from_slice(&if differencing { 4u32 } else { 3 }.to_be_bytes());
+    image[..512].copy_from_slice(&footer);
+    image[23 * 512..].copy_from_slice(&footer);
+    let header = &mut image[512..1536];
+    header[..8].copy_from_slice(b"cxsparse");
+    header[16..24].copy_from_slice(&1536u64.to_be_bytes());
+    header[28..32].copy_from_slice(&4u32.to_be_bytes());
+    header[32..36].copy_from_slice(&(BLOCK as u32).to_be_bytes());
+    let parent: Vec<u8> = ".\\base.raw"
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    let locator = &mut header[576..600];
+    locator[..4].copy_from_slice(b"W2ru");
+    locator[8..12].copy_from_slice(&(parent.len() as u32).to_be_bytes());
+    locator[16..24].copy_from_slice(&2048u64.to_be_bytes());
+    image[2048..][..parent.len()].copy_from_slice(&parent);
+    for (i, entry) in [5u32, 0xffffffff, 14, 0xffffffff].iter().enumerate() {
+        image[1536 + i * 4..][..4].copy_from_slice(&entry.to_be_bytes());
+    }
+
+    let mut expected = if differencing {
+        backing.to_vec()
+    } else {
+        vec![0; 4 * BLOCK]
+    };
+    for (block, sector, bitmap) in [(0, 5, 0xf0), (2, 14, 0xff)] {
+        let data = patterned(BLOCK, block);
+        image[sector * 512] = bitmap;
+        image[(sector + 1) * 512..][..BLOCK].copy_from_slice(&data);
+        let present = if differencing && bitmap == 0xf0 {
+            BLOCK / 2
+        } else {
+            BLOCK
+        };
+        expected[block * BLOCK..][..present].copy_from_slice(&data[..present]);
+    }
+    (image, expected)
+}
+
+/// CRC-32C, which checksums VHDX headers.
+fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0x82f63b78 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// Encodes a GUID the way Windows stores it.
+fn guid(a: u32, b: u16, c: u16, d: [u8; 8]) -> Vec<u8> {
+    [&a.to_le_bytes()[..], &b.to_le_bytes(), &c.to_le_bytes(), &d].concat()
+}
+
+/// A VHDX with 1 MiB blocks whose BAT marks the first and last of four blocks present, the
+/// second not present and the third zero, returning the image and its expected contents.
+fn vhdx() -> (Vec<u8>, Vec<u8>) {
+    let mut image = vec![0; 3 * MIB];
+    image[..8].copy_from_slice(b"vhdxfile");
+    // Only the second header is valid
+    let header = &mut image[128 << 10..][..4096];
+    header[..4].copy_from_slice(b"head");
+    header[8..16].copy_from_slice(&1u64.to_le_bytes());
+    let checksum = crc32c(header);
+    header[4..8].copy_from_slice(&checksum.to_le_bytes());
+
+    let bat_region = guid(
+        0x2dc27766,
+        0xf623,
+        0x4200,
+        *b"\x9d\x64\x11\x5e\x9b\xfd\x4a\x08",
+    );
+    let metadata_region = guid(
+        0x8b7ca206,
+        0x4790,
+        0x4b9a,
+        *b"\xb8\xfe\x57\x5f\x05\x0f\x88\x6e",
+    );
+    let regions = &mut image[192 << 10..];
+    regions[..4].copy_from_slice(b"regi");
+    regions[8..12].copy_from_slice(&2u32.to_le_bytes());
+    for (i, (id, offset, len)) in [
+        (metadata_region, 256u64 << 10, 128u32 << 10),
+        (bat_region, 384 << 10, 4096),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let entry = &mut regions[16 + i * 32..][..32];
+        entry[..16].copy_from_slice(&id);
+        entry[16..24].copy_from_slice(&offset.to_le_bytes());
+        entry[24..28].copy_from_slice(&len.to_le_bytes());
+    }
+
+    let items = [
+        (
+            guid(
+                0xcaa16737,
+                0xfa36,
+                0x4d43,
+                *b"\xb3\xb6\x33\xf0\xaa\x44\xe7\x6b",
+            ),
+            [(MIB as u64).to_le_bytes(), [0; 8]].concat(),
+        ),
+        (
+            guid(
+                0x2fa54224,
+                0xcd1b,
+                0x4876,
+                *b"\xb2\x11\x5d\xbe\xd8\x3b\xf4\xb8",
+            ),
+            (4 * MIB as u64).to_le_bytes().to_vec(),
+        ),
+        (
+            guid(
+                0x8141bf1d,
+                0xa96f,
+                0x4709,
+                *b"\xba\x47\xf2\x33\xa8\xfa\xab\x5f",
+            ),
+            512u32.to_le_bytes().to_vec(),
+        ),
+    ];
+    let metadata = &mut image[256 << 10..];
+    metadata[..8].copy_from_slice(b"metadata");
+    metadata[10..12].copy_from_slice(&(items.len() as u16).to_le_bytes());
+    for (i, (id, value)) in items.iter().enumerate() {
+        let offset = (64 << 10) + i * 8;
+        let entry = &mut metadata[32 + i * 32..][..32];
+        entry[..16].copy_from_slice(id);
+        entry[16..20].copy_from_slice(&(offset as u32).to_le_bytes());
+        entry[20..24].copy_from_slice(&(value.len() as u32).to_le_bytes());
+        metadata[offset..][..value.len()].copy_from_slice(value);
+    }
+
+    let mut expected = vec![0; 4 * MIB];
+    for (i, entry) in [MIB as u64 | 6, 0, 2, (2 * MIB as u64) | 6]
+        .iter()
+        .enumerate()
+    {
+        image[(384 << 10) + i * 8..][..8].copy_from_slice(&entry.to_le_bytes());
+    }
+    for (block, offset) in [(0, MIB), (3, 2 * MIB)] {
+        let data = patterned(MIB, block);
+        image[offset..][..MIB].copy_from_slice(&data);
+        expected[block * MIB..][..MIB].copy_from_slice(&data);
+    }
+    (image, expected)
+}
+
+#[test]
+fn test() {
+    let backing = patterned(16 * CLUSTER, 1);
+    let (image, expected) = qcow2(&backing);
+    check(
+        Qcow2Fs::with_sibling_opener(Cursor::new(image.clone()), base_raw(&backing)),
+        &expected,
+    );
+    // The backing file can't be opened without a way to open siblings
+    let mut fs = Qcow2Fs::from_io(Cursor::new(image), ());
+    assert!(fs.open(b"").is_err());
+    assert!(fs.metadata(b"disk").is_err());
+    // An L1 table longer than the image
+    let (mut image, _) = qcow2(&backing);
+    image[36..40].copy_from_slice(&u32::MAX.to_be_bytes());
+    let mut fs = Qcow2Fs::from_io(Cursor::new(image), ());
+    assert!(matches!(
+        fs.metadata(b""),
+        Err(vfs_vdisk::Error::InvalidImage(_))
+    ));
+
+    // Raw, fill, skipped and CRC chunks of 4 KiB blocks, with a trailing block not covered by
+    // any chunk
+    let raw = patterned(2 * 4096, 9);
+    let mut image = Vec::new();
+    for value in [0xed26ff3a, 0x00000001, 0x000c001c, 4096, 7, 4, 0] {
+        image.extend_from_slice(&u32::to_le_bytes(value));
+    }
+    let chunk = |kind: u32, blocks: u32, data: &[u8]| {
+        [
+            &kind.to_le_bytes()[..],
+            &blocks.to_le_bytes(),
+            &(12 + data.len() as u32).to_le_bytes(),
+            data,
+        ]
+        .concat()
+    };
+    image.extend(chunk(0xcac1, 2, &raw));
+    image.extend(chunk(0xcac2, 3, &[1, 2, 3, 4]));
+    image.extend(chunk(0xcac4, 0, &[0; 4]));
+    image.extend(chunk(0xcac3, 1, &[]));
+    let mut expected = raw;
+    expected.extend([1, 2, 3, 4].repeat(3 * 1024));
+    expected.resize(7 * 4096, 0);
+    check(AndroidSparseFs::from_io(Cursor::new(image), ()), &expected);
+
+    // Unallocated grains are read from the parent named by the embedded descriptor, or as zeros
+    // without one
+    let backing = patterned(12 * 4096, 6);
+    let (mut image, expected) = vmdk(&backing);
+    check(
+        VmdkFs::with_sibling_opener(Cursor::new(image.clone()), base_raw(&backing)),
+        &expected,
+    );
+    image[28..44].fill(0);
+    let mut expected = expected;
+    for grain in [1, 4, 5, 6, 7, 8, 9, 11] {
+        expected[grain * 4096..][..4096].fill(0);
+    }
+    check(VmdkFs::from_io(Cursor::new(image.clone()), ()), &expected);
+    // Capacities whose length in bytes overflows, or whose grain directory is longer than the
+    // image
+    for capacity in [u64::MAX, 1 << 40] {
+        let mut image = image.clone();
+        image[12..20].copy_from_slice(&capacity.to_le_bytes());
+        let mut fs = VmdkFs::from_io(Cursor::new(image), ());
+        assert!(matches!(
+            fs.metadata(b""),
+            Err(vfs_vdisk::Error::InvalidImage(_))
+        ));
+    }
+
+    // Dynamic and differencing VHDs
+    let backing = patterned(4 * 4096, 8);
+    let (image, expected) = vhd(&backing, false);
+    check(VhdFs::from_io(Cursor::new(image), ()), &expected);
+    let (image, expected) = vhd(&backing, true);
+    check(
+        VhdFs::with_sibling_opener(Cursor::new(image), base_raw(&backing)),
+        &expected,
+    );
+
+    let (image, expected) = vhdx();
+    check(VhdxFs::from_io(Cursor::new(image), ()), &expected);
+}
@@ -0,0 +1,201 @@
+use std::{
+    cell::RefCell,
+    io::{Read, Seek, SeekFrom},
+    rc::Rc,
+};
+use window_read_seek::WindowReadSeek;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed reading from backing IO")]
+    Io(#[from] std::io::Error),
+    #[error("invalid slice, expected `offset+len` or `offset`")]
+    InvalidSlice,
+    #[error("slice extends past the end of the file")]
+    SliceOutOfRange,
+    #[error("entry not found")]
+    NotFound,
+}
+
+/// Several readers joined into one stream.
+pub struct Concat<R: Read + Seek> {
+    parts: Vec<R>,
+    /// Offset at which each part ends, measured on first access
+    ends: Option<Vec<u64>>,
+    offset: u64,
+}
+
+impl<R: Read + Seek> Concat<R> {
+    pub fn new(parts: Vec<R>) -> Self {
+        Self {
+            parts,
+            ends: None,
+            offset: 0,
+        }
+    }
+
+    fn ends(&mut self) -> std::io::Result<&[u64]> {
+        if self.ends.is_none() {
+            let mut ends = Vec::with_capacity(self.parts.len());
+            let mut end = 0u64;
+            for part in &mut self.parts {
+                end = end
+                    .checked_add(part.seek(SeekFrom::End(0))?)
+                    .ok_or_else(vfs::io::invalid_seek)?;
+                ends.push(end);
+            }
+            self.ends = Some(ends);
+        }
+        Ok(self.ends.as_ref().unwrap())
+    }
+
+    fn len(&mut self) -> std::io::Result<u64> {
+        Ok(self.ends()?.last().copied().unwrap_or(0))
+    }
+}
+
+impl<R: Read + Seek> Read for Concat<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let offset = self.offset;
+        let ends = self.ends()?;
+        // The first part ending after the offset, which skips empty parts
+        let i = ends.partition_point(|&end| end <= offset);
+        if i == ends.len() || buf.is_empty() {
+            return Ok(0);
+        }
+        let start = if i == 0 { 0 } else { ends[i - 1] };
+        let n = (buf.len() as u64).min(ends[i] - offset) as usize;
+        let part = &mut self.parts[i];
+        part.seek(SeekFrom::Start(offset - start))?;
+        let n = part.read(&mut buf[..n])?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for Concat<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let offset = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len()?.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.offset.checked_add_signed(offset),
+        };
+        self.offset = offset.ok_or_else(vfs::io::invalid_seek)?;
+        Ok(self.offset)
+    }
+}
+
+/// A file made of several volumes joined in order, found at the empty path. Split archives are
+/// read by putting an archive layer on top.
+pub struct ConcatFs<R: Read + Seek> {
+    concat: Rc<RefCell<Concat<R>>>,
+}
+
+impl<R: Read + Seek> ConcatFs<R> {
+    pub fn from_volumes(volumes: Vec<R>) -> Self {
+        Self {
+            concat: Rc::new(RefCell::new(Concat::new(volumes))),
+        }
+    }
+
+    fn len(&mut self, path: &[u8]) -> Result<u64, Error> {
+        if !vfs::path::normalize(path).is_empty() {
+            return Err(Error::NotFound);
+        }
+        Ok(self.concat.borrow_mut().len()?)
+    }
+}
+
+impl<R: Read + Seek> vfs::IoBackedFs<R> for ConcatFs<R> {
+    type Password = ();
+
+    fn from_io(io: R, _password: Self::Password) -> Self {
+        Self::from_volumes(vec![io])
+    }
+}
+
+impl<R: Read + Seek> vfs::Fs for ConcatFs<R> {
+    type Path = [u8];
+    type Error = Error;
+    type File = WindowReadSeek<Concat<R>>;
+
+    fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Self::Error> {
+        Ok(vfs::Metadata {
+            file_type: vfs::FileType::File,
+            len: self.len(path)?,
+            mode: None,
+            mtime: None,
+        })
+    }
+
+    fn open(&mut self, path: &[u8]) -> Result<Self::File, Self::Error> {
+        let len = self.len(path)?;
+        Ok(WindowReadSeek::new(self.concat.clone(), 0, len))
+    }
+}
+
+fn parse_number(s: &[u8]) -> Option<u64> {
+    let s = std::str::from_utf8(s).ok()?;
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// A window of a file, with the path `offset+len` selecting it, or just `offset` to select the
+/// rest of the file. Numbers are decimal, or hexadecimal with a `0x` prefix.
+pub struct SliceFs<R: Read + Seek> {
+    reader: Rc<RefCell<R>>,
+}
+
+impl<R: Read + Seek> SliceFs<R> {
+    /// Returns the offset and length of the window selected by `path`.
+    fn window(&mut self, path: &[u8]) -> Result<(u64, u64), Error> {
+        let path = vfs::path::normalize(path);
+        let (offset, len) = match path.iter().position(|&b| b == b'+') {
+            Some(plus) => (&path[..plus], Some(&path[plus + 1..])),
+            None => (path.as_slice(), None),
+        };
+        let offset = parse_number(offset).ok_or(Error::InvalidSlice)?;
+        let len = len
+            .map(|len| parse_number(len).ok_or(Error::InvalidSlice))
+            .transpose()?;
+        let file_len = self.reader.borrow_mut().seek(SeekFrom::End(0))?;
+        let available = file_len.checked_sub(offset).ok_or(Error::SliceOutOfRange)?;
+        match len {
+            Some(len) if len > available => Err(Error::SliceOutOfRange),
+            Some(len) => Ok((offset, len)),
+            None => Ok((offset, available)),
+        }
+    }
+}
+
+impl<R: Read + Seek> vfs::IoBackedFs<R> for SliceFs<R> {
+    type Password = ();
+
+    fn from_io(io: R, _password: Self::Password) -> Self {
+        Self {
+            reader: Rc::new(RefCell::new(io)),
+        }
+    }
+}
+
+impl<R: Read + Seek> vfs::Fs for SliceFs<R> {
+    type Path = [u8];
+    type Error = Error;
+    type File = WindowReadSeek<R>;
+
+    fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Self::Error> {
+        Ok(vfs::Metadata {
+            file_type: vfs::FileType::File,
+            len: self.window(path)?.1,
+            mode: None,
+            mtime: None,
+        })
+    }
+
+    fn open(&mut self, path: &[u8]) -> Result<Self::File, Self::Error> {
+        let (offset, len) = self.window(path)?;
+        Ok(WindowReadSeek::new(self.reader.clone(), offset, len))
+    }
+}
@@ -0,0 +1,33 @@
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use vfs::{Fs, IoBackedFs};
+use vfs_virtual::{ConcatFs, Error, SliceFs};
+
+fn read_all(mut file: impl Read) -> Vec<u8> {
+    let mut data = Vec::new();
+    file.read_to_end(&mut data).unwrap();
+    data
+}
+
+#[test]
+fn test() {
+    let volumes = [&b"hello"[..], b"", b" ", b"world"];
+    let mut fs = ConcatFs::from_volumes(volumes.map(Cursor::new).to_vec());
+    assert_eq!(fs.metadata(b"").unwrap().len, 11);
+    assert!(matches!(fs.metadata(b"a"), Err(Error::NotFound)));
+    assert_eq!(read_all(fs.open(b"").unwrap()), b"hello world");
+    let mut file = fs.open(b"/").unwrap();
+    file.seek(SeekFrom::Start(4)).unwrap();
+    let mut buf = [0; 3];
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"o w");
+    file.seek(SeekFrom::End(-2)).unwrap();
+    assert_eq!(read_all(file), b"ld");
+
+    let mut fs = SliceFs::from_io(Cursor::new(b"0123456789"), ());
+    assert_eq!(read_all(fs.open(b"2+3").unwrap()), b"234");
+    assert_eq!(read_all(fs.open(b"0x8").unwrap()), b"89");
+    assert_eq!(fs.metadata(b"10+0").unwrap().len, 0);
+    assert!(matches!(fs.open(b"8+3"), Err(Error::SliceOutOfRange)));
+    assert!(matches!(fs.open(b"11"), Err(Error::SliceOutOfRange)));
+    assert!(matches!(fs.open(b"2+"), Err(Error::InvalidSlice)));
+}
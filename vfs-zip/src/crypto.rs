@@ -0,0 +1,235 @@
+use crate::Error;
+use aes::{Aes128, Aes192, Aes256};
+use ctr::{
+    cipher::{KeyIvInit, StreamCipher, StreamCipherSeek},
+    Ctr128LE,
+};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use std::io::{Read, Seek, SeekFrom};
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                0xedb88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc32_update(crc: u32, b: u8) -> u32 {
+    CRC32_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8)
+}
+
+/// Key state of the traditional PKWARE stream cipher.
+struct ZipCryptoKeys([u32; 3]);
+
+impl ZipCryptoKeys {
+    fn new(password: &[u8]) -> Self {
+        let mut keys = Self([0x12345678, 0x23456789, 0x34567890]);
+        for &b in password {
+            keys.update(b);
+        }
+        keys
+    }
+
+    fn update(&mut self, b: u8) {
+        let [k0, k1, k2] = &mut self.0;
+        *k0 = crc32_update(*k0, b);
+        *k1 = k1
+            .wrapping_add(*k0 & 0xff)
+            .wrapping_mul(134775813)
+            .wrapping_add(1);
+        *k2 = crc32_update(*k2, (*k1 >> 24) as u8);
+    }
+
+    fn decrypt(&mut self, b: u8) -> u8 {
+        let temp = (self.0[2] | 2) as u16;
+        let b = b ^ (temp.wrapping_mul(temp ^ 1) >> 8) as u8;
+        self.update(b);
+        b
+    }
+}
+
+/// Decrypts a ZipCrypto ("traditional PKWARE") encrypted entry. The cipher is stateful, so the
+/// stream can only be read sequentially.
+pub(crate) struct ZipCryptoReader<R: Read> {
+    reader: R,
+    keys: ZipCryptoKeys,
+}
+
+impl<R: Read> ZipCryptoReader<R> {
+    pub(crate) fn new(mut reader: R, password: &[u8], check_byte: u8) -> Result<Self, Error> {
+        let mut keys = ZipCryptoKeys::new(password);
+        let mut header = [0; 12];
+        reader.read_exact(&mut header)?;
+        let header = header.map(|b| keys.decrypt(b));
+        if header[11] != check_byte {
+            return Err(Error::IncorrectPassword);
+        }
+        Ok(Self { reader, keys })
+    }
+}
+
+impl<R: Read> Read for ZipCryptoReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        for b in &mut buf[..n] {
+            *b = self.keys.decrypt(*b);
+        }
+        Ok(n)
+    }
+}
+
+enum AesCipher {
+    Aes128(Ctr128LE<Aes128>),
+    Aes192(Ctr128LE<Aes192>),
+    Aes256(Ctr128LE<Aes256>),
+}
+
+impl AesCipher {
+    fn new(key: &[u8]) -> Self {
+        // WinZip AES uses a little-endian block counter starting at 1
+        let mut iv = [0; 16];
+        iv[0] = 1;
+        let iv = iv.as_slice().into();
+        match key.len() {
+            16 => Self::Aes128(Ctr128LE::new(key.into(), iv)),
+            24 => Self::Aes192(Ctr128LE::new(key.into(), iv)),
+            32 => Self::Aes256(Ctr128LE::new(key.into(), iv)),
+            _ => unreachable!(),
+        }
+    }
+
+    fn apply_keystream_at(&mut self, offset: u64, buf: &mut [u8]) {
+        match self {
+            Self::Aes128(c) => {
+                c.seek(offset);
+                c.apply_keystream(buf)
+            }
+            Self::Aes192(c) => {
+                c.seek(offset);
+                c.apply_keystream(buf)
+            }
+            Self::Aes256(c) => {
+                c.seek(offset);
+                c.apply_keystream(buf)
+            }
+        }
+    }
+}
+
+const AES_AUTH_CODE_LEN: u64 = 10;
+
+/// Decrypts a WinZip AES encrypted entry. Since AES is used in CTR mode, the decrypted stream
+/// supports seeking. The authentication code is verified when the entry is read sequentially
+/// to the end.
+pub(crate) struct AesReader<R: Read + Seek> {
+    reader: R,
+    cipher: AesCipher,
+    data_start: u64,
+    data_len: u64,
+    offset: u64,
+    mac: Option<(Hmac<Sha1>, u64)>,
+}
+
+impl<R: Read + Seek> AesReader<R> {
+    /// Reads the salt and password verifier at the start of `reader`, which must span the whole
+    /// encrypted entry.
+    pub(crate) fn new(mut reader: R, password: &[u8], strength: u8) -> Result<Self, Error> {
+        let key_len = match strength {
+            1 => 16,
+            2 => 24,
+            3 => 32,
+            _ => return Err(Error::UnsupportedAesStrength(strength)),
+        };
+        let salt_len = key_len / 2;
+        let len = reader.seek(SeekFrom::End(0))?;
+        reader.rewind()?;
+        let mut header = vec![0; salt_len + 2];
+        reader.read_exact(&mut header)?;
+        let (salt, verifier) = header.split_at(salt_len);
+        let mut derived = vec![0; key_len * 2 + 2];
+        pbkdf2::pbkdf2_hmac::<Sha1>(password, salt, 1000, &mut derived);
+        let (keys, derived_verifier) = derived.split_at(key_len * 2);
+        if verifier != derived_verifier {
+            return Err(Error::IncorrectPassword);
+        }
+        let (key, mac_key) = keys.split_at(key_len);
+        let data_start = header.len() as u64;
+        Ok(Self {
+            reader,
+            cipher: AesCipher::new(key),
+            data_start,
+            data_len: len
+                .checked_sub(data_start + AES_AUTH_CODE_LEN)
+                .ok_or(Error::Truncated)?,
+            offset: 0,
+            mac: Some((
+                Hmac::new_from_slice(mac_key).expect("HMAC accepts any key length"),
+                0,
+            )),
+        })
+    }
+
+    fn verify_auth_code(&mut self, mac: Hmac<Sha1>) -> std::io::Result<()> {
+        let mut auth_code = [0; AES_AUTH_CODE_LEN as usize];
+        self.reader
+            .seek(SeekFrom::Start(self.data_start + self.data_len))?;
+        self.reader.read_exact(&mut auth_code)?;
+        if mac.finalize().into_bytes()[..auth_code.len()] != auth_code {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "AES authentication code mismatch",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for AesReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.data_len.saturating_sub(self.offset);
+        let n = (buf.len() as u64).min(remaining) as usize;
+        if n == 0 {
+            return Ok(0);
+        }
+        self.reader
+            .seek(SeekFrom::Start(self.data_start + self.offset))?;
+        let n = self.reader.read(&mut buf[..n])?;
+        let buf = &mut buf[..n];
+        // The MAC covers the ciphertext, so it can only be tracked while reading sequentially
+        self.mac = match self.mac.take() {
+            Some((mut mac, mac_offset)) if mac_offset == self.offset => {
+                mac.update(buf);
+                Some((mac, mac_offset + n as u64))
+            }
+            _ => None,
+        };
+        self.cipher.apply_keystream_at(self.offset, buf);
+        self.offset += n as u64;
+        if matches!(self.mac, Some((_, mac_offset)) if mac_offset == self.data_len) {
+            let (mac, _) = self.mac.take().unwrap();
+            self.verify_auth_code(mac)?;
+        }
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for AesReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.offset = vfs::io::seek_position(pos, self.offset, self.data_len)?;
+        Ok(self.offset)
+    }
+}
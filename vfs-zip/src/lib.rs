@@ -0,0 +1,599 @@
+mod crypto;
+
+use cache_read_seek::CachedReadSeek;
+use crypto::{AesReader, ZipCryptoReader};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom},
+    rc::Rc,
+    time::{Duration, SystemTime},
+};
+use vfs::{
+    bytes::{u16_at, u32_at, u64_at},
+    time::days_from_civil,
+};
+use window_read_seek::WindowReadSeek;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const EOCD_SIGNATURE: u32 = 0x06054b50;
+const ZIP64_EOCD_SIGNATURE: u32 = 0x06064b50;
+const ZIP64_EOCD_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+
+const EOCD_LEN: usize = 22;
+const ZIP64_EOCD_LEN: usize = 56;
+const ZIP64_EOCD_LOCATOR_LEN: usize = 20;
+const LOCAL_HEADER_LEN: usize = 30;
+const CENTRAL_HEADER_LEN: usize = 46;
+
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+const AES_EXTRA_ID: u16 = 0x9901;
+const EXTENDED_TIMESTAMP_EXTRA_ID: u16 = 0x5455;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATE: u16 = 8;
+const METHOD_BZIP2: u16 = 12;
+const METHOD_ZSTD_DEPRECATED: u16 = 20;
+const METHOD_ZSTD: u16 = 93;
+const METHOD_AES: u16 = 99;
+
+const FLAG_ENCRYPTED: u16 = 1 << 0;
+const FLAG_DATA_DESCRIPTOR: u16 = 1 << 3;
+
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFLNK: u32 = 0o120000;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed reading from backing IO")]
+    Io(#[from] std::io::Error),
+    #[error("end of central directory record not found")]
+    EndOfCentralDirectoryNotFound,
+    #[error("invalid {0} signature")]
+    InvalidSignature(&'static str),
+    #[error("archive is truncated")]
+    Truncated,
+    #[error("invalid archive")]
+    InvalidArchive,
+    #[error("entry not found")]
+    NotFound,
+    #[error("entry is not a regular file")]
+    NotAFile,
+    #[error("unsupported compression method {0}")]
+    UnsupportedMethod(u16),
+    #[error("unsupported AES strength {0}")]
+    UnsupportedAesStrength(u8),
+    #[error("entry is encrypted but no password was given")]
+    PasswordRequired,
+    #[error("incorrect password")]
+    IncorrectPassword,
+}
+
+/// Parses an MS-DOS date and time, which has no zone and is taken as UTC.
+fn dos_timestamp(date: u16, time: u16) -> Option<SystemTime> {
+    let (year, month, day) = (
+        1980 + (date >> 9) as i64,
+        (date >> 5 & 0xf) as i64,
+        date & 0x1f,
+    );
+    if !(1..=12).contains(&month) || day == 0 {
+        return None;
+    }
+    let secs = days_from_civil(year, month, day as i64) * 86400
+        + (time >> 11) as i64 * 3600
+        + (time >> 5 & 0x3f) as i64 * 60
+        + (time & 0x1f) as i64 * 2;
+    SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs.try_into().ok()?))
+}
+
+#[derive(Clone, Copy)]
+struct AesInfo {
+    strength: u8,
+    method: u16,
+    /// AE-2 entries store no CRC, relying on the authentication code instead
+    has_crc: bool,
+}
+
+#[derive(Clone)]
+struct Entry {
+    file_type: vfs::FileType,
+    len: u64,
+    compressed_len: u64,
+    method: u16,
+    flags: u16,
+    crc32: u32,
+    dos_time: u16,
+    local_header_offset: u64,
+    aes: Option<AesInfo>,
+    mode: Option<u32>,
+    mtime: Option<SystemTime>,
+}
+
+impl Entry {
+    fn dir() -> Self {
+        Self {
+            file_type: vfs::FileType::Dir,
+            len: 0,
+            compressed_len: 0,
+            method: METHOD_STORED,
+            flags: 0,
+            crc32: 0,
+            dos_time: 0,
+            local_header_offset: 0,
+            aes: None,
+            mode: None,
+            mtime: None,
+        }
+    }
+}
+
+type Index = HashMap<Vec<u8>, Entry>;
+
+/// Location of the central directory, with `base` being the number of bytes prepended to the
+/// archive (e.g. a self-extractor stub) that all recorded offsets must be shifted by.
+struct CentralDirectory {
+    offset: u64,
+    len: u64,
+    base: u64,
+}
+
+fn find_central_directory<R: Read + Seek>(reader: &mut R) -> Result<CentralDirectory, Error> {
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    let tail_len = file_len.min((EOCD_LEN + u16::MAX as usize) as u64);
+    let tail_start = file_len - tail_len;
+    reader.seek(SeekFrom::Start(tail_start))?;
+    let mut tail = vec![0; tail_len as usize];
+    reader.read_exact(&mut tail)?;
+    let eocd_pos = (0..=tail.len().saturating_sub(EOCD_LEN))
+        .rev()
+        .find(|&i| {
+            i + EOCD_LEN <= tail.len()
+                && u32_at(&tail, i) == EOCD_SIGNATURE
+                && i + EOCD_LEN + u16_at(&tail, i + 20) as usize <= tail.len()
+        })
+        .ok_or(Error::EndOfCentralDirectoryNotFound)?;
+    let eocd = &tail[eocd_pos..];
+    let mut len = u32_at(eocd, 12) as u64;
+    let mut offset = u32_at(eocd, 16) as u64;
+    let mut end = tail_start + eocd_pos as u64;
+
+    if let Some(locator_pos) = eocd_pos.checked_sub(ZIP64_EOCD_LOCATOR_LEN) {
+        let locator = &tail[locator_pos..];
+        if u32_at(locator, 0) == ZIP64_EOCD_LOCATOR_SIGNATURE {
+            let recorded = u64_at(locator, 8);
+            let expected = (tail_start + locator_pos as u64)
+                .checked_sub(ZIP64_EOCD_LEN as u64)
+                .ok_or(Error::Truncated)?;
+            let mut record = [0; ZIP64_EOCD_LEN];
+            let mut record_pos = recorded;
+            reader.seek(SeekFrom::Start(record_pos))?;
+            if reader.read_exact(&mut record).is_err() || u32_at(&record, 0) != ZIP64_EOCD_SIGNATURE
+            {
+                record_pos = expected;
+                reader.seek(SeekFrom::Start(record_pos))?;
+                reader.read_exact(&mut record)?;
+            }
+            if u32_at(&record, 0) != ZIP64_EOCD_SIGNATURE {
+                return Err(Error::InvalidSignature("ZIP64 end of central directory"));
+            }
+            len = u64_at(&record, 40);
+            offset = u64_at(&record, 48);
+            end = record_pos;
+        }
+    }
+
+    let base = end
+        .checked_sub(offset.checked_add(len).ok_or(Error::InvalidArchive)?)
+        .ok_or(Error::Truncated)?;
+    Ok(CentralDirectory { offset, len, base })
+}
+
+fn parse_extra(extra: &[u8], mut f: impl FnMut(u16, &[u8])) {
+    let mut i = 0;
+    while i + 4 <= extra.len() {
+        let id = u16_at(extra, i);
+        let len = u16_at(extra, i + 2) as usize;
+        let Some(data) = extra.get(i + 4..i + 4 + len) else {
+            break;
+        };
+        f(id, data);
+        i += 4 + len;
+    }
+}
+
+fn read_index<R: Read + Seek>(reader: &mut R) -> Result<Index, Error> {
+    let cd = find_central_directory(reader)?;
+    // Validate the recorded length before allocating, since it comes straight from the archive
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    let start = cd.base + cd.offset;
+    if start.checked_add(cd.len).is_none_or(|end| end > file_len) {
+        return Err(Error::InvalidArchive);
+    }
+    reader.seek(SeekFrom::Start(start))?;
+    let mut buf = vec![0; cd.len.try_into().map_err(|_| Error::InvalidArchive)?];
+    reader.read_exact(&mut buf)?;
+
+    let mut index = Index::new();
+    let mut i = 0;
+    while i + CENTRAL_HEADER_LEN <= buf.len() {
+        let header = &buf[i..];
+        if u32_at(header, 0) != CENTRAL_HEADER_SIGNATURE {
+            return Err(Error::InvalidSignature("central directory header"));
+        }
+        let version_made_by = u16_at(header, 4);
+        let flags = u16_at(header, 8);
+        let mut method = u16_at(header, 10);
+        let dos_time = u16_at(header, 12);
+        let dos_date = u16_at(header, 14);
+        let crc32 = u32_at(header, 16);
+        let mut compressed_len = u32_at(header, 20) as u64;
+        let mut len = u32_at(header, 24) as u64;
+        let name_len = u16_at(header, 28) as usize;
+        let extra_len = u16_at(header, 30) as usize;
+        let comment_len = u16_at(header, 32) as usize;
+        let external_attributes = u32_at(header, 38);
+        let mut local_header_offset = u32_at(header, 42) as u64;
+        let name_end = CENTRAL_HEADER_LEN + name_len;
+        let extra_end = name_end + extra_len;
+        let name = header
+            .get(CENTRAL_HEADER_LEN..name_end)
+            .ok_or(Error::Truncated)?;
+        let extra = header.get(name_end..extra_end).ok_or(Error::Truncated)?;
+
+        let mut aes = None;
+        let mut mtime = None;
+        parse_extra(extra, |id, data| match id {
+            ZIP64_EXTRA_ID => {
+                // Only the fields saturated in the fixed header are present, in this order
+                let mut fields = data.chunks_exact(8).map(|x| u64_at(x, 0));
+                for field in [&mut len, &mut compressed_len, &mut local_header_offset] {
+                    if *field == u32::MAX as u64 {
+                        match fields.next() {
+                            Some(value) => *field = value,
+                            None => break,
+                        }
+                    }
+                }
+            }
+            AES_EXTRA_ID if data.len() >= 7 => {
+                aes = Some(AesInfo {
+                    strength: data[4],
+                    method: u16_at(data, 5),
+                    has_crc: u16_at(data, 0) == 1,
+                })
+            }
+            // Flags for which times follow, of which the central header only keeps mtime
+            EXTENDED_TIMESTAMP_EXTRA_ID if data.len() >= 5 && data[0] & 1 != 0 => {
+                let secs = u32_at(data, 1) as i32 as i64;
+                mtime = Some(match u64::try_from(secs) {
+                    Ok(secs) => SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+                    Err(_) => SystemTime::UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs()),
+                });
+            }
+            _ => {}
+        });
+        if method == METHOD_AES {
+            if let Some(aes) = aes {
+                method = aes.method;
+            }
+        } else {
+            aes = None;
+        }
+
+        // Unix hosts store the mode in the high half of the external attributes
+        let mode = (version_made_by >> 8 == 3).then_some(external_attributes >> 16);
+        let file_type = match mode.map(|mode| mode & S_IFMT) {
+            Some(S_IFLNK) => vfs::FileType::SymLink,
+            Some(S_IFDIR) => vfs::FileType::Dir,
+            _ if name.ends_with(b"/") || external_attributes & 0x10 != 0 => vfs::FileType::Dir,
+            _ => vfs::FileType::File,
+        };
+
+        let path = vfs::path::normalize(name);
+        let mut components = vfs::path::components(&path);
+        while components.pop().is_some() && !components.is_empty() {
+            index
+                .entry(components.join(b"/".as_slice()))
+                .or_insert_with(Entry::dir);
+        }
+        index.insert(
+            path,
+            Entry {
+                file_type,
+                len,
+                compressed_len,
+                method,
+                flags,
+                crc32,
+                dos_time,
+                local_header_offset: cd.base + local_header_offset,
+                aes,
+                mode: mode.map(|mode| mode & 0o7777),
+                mtime: mtime.or_else(|| dos_timestamp(dos_date, dos_time)),
+            },
+        );
+
+        i += extra_end + comment_len;
+    }
+    Ok(index)
+}
+
+/// Source of an entry's decrypted (but still compressed) data.
+enum Decrypted<R: Read + Seek> {
+    Plain(WindowReadSeek<R>),
+    ZipCrypto(ZipCryptoReader<WindowReadSeek<R>>),
+    Aes(Box<AesReader<WindowReadSeek<R>>>),
+}
+
+impl<R: Read + Seek> Read for Decrypted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Decrypted::Plain(x) => x.read(buf),
+            Decrypted::ZipCrypto(x) => x.read(buf),
+            Decrypted::Aes(x) => x.read(buf),
+        }
+    }
+}
+
+enum Decoder<R: Read + Seek> {
+    Stored(Decrypted<R>),
+    Deflate(Box<flate2::read::DeflateDecoder<Decrypted<R>>>),
+    Bzip2(bzip2::read::BzDecoder<Decrypted<R>>),
+    Zstd(Box<ruzstd::decoding::StreamingDecoder<Decrypted<R>, ruzstd::decoding::FrameDecoder>>),
+}
+
+impl<R: Read + Seek> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Decoder::Stored(x) => x.read(buf),
+            Decoder::Deflate(x) => x.read(buf),
+            Decoder::Bzip2(x) => x.read(buf),
+            Decoder::Zstd(x) => x.read(buf),
+        }
+    }
+}
+
+/// Everything needed to (re)start decoding an entry from its beginning.
+struct EntrySource<R: Read + Seek> {
+    data: WindowReadSeek<R>,
+    entry: Entry,
+    password: Option<Vec<u8>>,
+}
+
+impl<R: Read + Seek> EntrySource<R> {
+    fn decrypted(&self) -> Result<Decrypted<R>, Error> {
+        let mut data = self.data.clone();
+        data.rewind()?;
+        if self.entry.flags & FLAG_ENCRYPTED == 0 {
+            return Ok(Decrypted::Plain(data));
+        }
+        let password = self.password.as_deref().ok_or(Error::PasswordRequired)?;
+        Ok(match self.entry.aes {
+            Some(aes) => Decrypted::Aes(Box::new(AesReader::new(data, password, aes.strength)?)),
+            None => {
+                let check_byte = if self.entry.flags & FLAG_DATA_DESCRIPTOR != 0 {
+                    (self.entry.dos_time >> 8) as u8
+                } else {
+                    (self.entry.crc32 >> 24) as u8
+                };
+                Decrypted::ZipCrypto(ZipCryptoReader::new(data, password, check_byte)?)
+            }
+        })
+    }
+
+    fn decoder(&self) -> Result<Decoder<R>, Error> {
+        let decrypted = self.decrypted()?;
+        Ok(match self.entry.method {
+            METHOD_STORED => Decoder::Stored(decrypted),
+            METHOD_DEFLATE => {
+                Decoder::Deflate(Box::new(flate2::read::DeflateDecoder::new(decrypted)))
+            }
+            METHOD_BZIP2 => Decoder::Bzip2(bzip2::read::BzDecoder::new(decrypted)),
+            METHOD_ZSTD | METHOD_ZSTD_DEPRECATED => Decoder::Zstd(Box::new(
+                ruzstd::decoding::StreamingDecoder::new(decrypted).map_err(|err| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string())
+                })?,
+            )),
+            method => return Err(Error::UnsupportedMethod(method)),
+        })
+    }
+
+    fn expected_crc32(&self) -> Option<u32> {
+        match self.entry.aes {
+            Some(aes) if !aes.has_crc => None,
+            _ => Some(self.entry.crc32),
+        }
+    }
+}
+
+/// A compressed or ZipCrypto encrypted entry. Seeking backwards restarts decoding from the start
+/// of the entry, and seeking forwards decodes and discards the skipped data.
+struct CachelessFile<R: Read + Seek> {
+    source: EntrySource<R>,
+    decoder: Option<(Decoder<R>, crc32fast::Hasher)>,
+    decoder_offset: u64,
+    offset: u64,
+}
+
+impl<R: Read + Seek> CachelessFile<R> {
+    fn decode(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let (decoder, hasher) = self.decoder.as_mut().unwrap();
+        let n = decoder.read(buf)?;
+        hasher.update(&buf[..n]);
+        self.decoder_offset += n as u64;
+        if n == 0 && self.decoder_offset < self.source.entry.len {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        if n != 0 && self.decoder_offset == self.source.entry.len {
+            if let Some(expected) = self.source.expected_crc32() {
+                if hasher.clone().finalize() != expected {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "CRC-32 mismatch",
+                    ));
+                }
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Read for CachelessFile<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.source.entry.len;
+        if self.offset >= len {
+            return Ok(0);
+        }
+        if self.decoder.is_none() || self.offset < self.decoder_offset {
+            let decoder = self.source.decoder().map_err(std::io::Error::other)?;
+            self.decoder = Some((decoder, crc32fast::Hasher::new()));
+            self.decoder_offset = 0;
+        }
+        let mut skip_buf = [0; 4096];
+        while self.decoder_offset < self.offset {
+            let n = (self.offset - self.decoder_offset).min(skip_buf.len() as u64) as usize;
+            self.decode(&mut skip_buf[..n])?;
+        }
+        let n = (buf.len() as u64).min(len - self.offset) as usize;
+        let n = self.decode(&mut buf[..n])?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for CachelessFile<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.offset = vfs::io::seek_position(pos, self.offset, self.source.entry.len)?;
+        Ok(self.offset)
+    }
+}
+
+enum FileInner<R: Read + Seek> {
+    /// Stored entries are read directly from the backing IO
+    Stored(WindowReadSeek<R>),
+    /// AES is a seekable stream cipher, so stored AES entries need no restarts either
+    AesStored(Box<AesReader<WindowReadSeek<R>>>),
+    Decoded(Box<CachedReadSeek<CachelessFile<R>>>),
+}
+
+pub struct File<R: Read + Seek>(FileInner<R>);
+
+impl<R: Read + Seek> Read for File<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match &mut self.0 {
+            FileInner::Stored(x) => x.read(buf),
+            FileInner::AesStored(x) => x.read(buf),
+            FileInner::Decoded(x) => x.read(buf),
+        }
+    }
+}
+
+impl<R: Read + Seek> Seek for File<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match &mut self.0 {
+            FileInner::Stored(x) => x.seek(pos),
+            FileInner::AesStored(x) => x.seek(pos),
+            FileInner::Decoded(x) => x.seek(pos),
+        }
+    }
+}
+
+/// A ZIP archive, indexed by its central directory on first access.
+pub struct ZipFs<R: Read + Seek> {
+    reader: Rc<RefCell<R>>,
+    password: Option<Vec<u8>>,
+    index: Option<Index>,
+}
+
+impl<R: Read + Seek> ZipFs<R> {
+    fn entry(&mut self, path: &[u8]) -> Result<Entry, Error> {
+        if self.index.is_none() {
+            self.index = Some(read_index(&mut *self.reader.borrow_mut())?);
+        }
+        let index = self.index.as_ref().unwrap();
+        let path = vfs::path::normalize(path);
+        if path.is_empty() {
+            return Ok(Entry::dir());
+        }
+        index.get(&path).cloned().ok_or(Error::NotFound)
+    }
+
+    fn data(&self, entry: &Entry) -> Result<WindowReadSeek<R>, Error> {
+        let mut reader = self.reader.borrow_mut();
+        let mut header = [0; LOCAL_HEADER_LEN];
+        reader.seek(SeekFrom::Start(entry.local_header_offset))?;
+        reader.read_exact(&mut header)?;
+        if u32_at(&header, 0) != LOCAL_HEADER_SIGNATURE {
+            return Err(Error::InvalidSignature("local file header"));
+        }
+        let name_len = u16_at(&header, 26) as u64;
+        let extra_len = u16_at(&header, 28) as u64;
+        let start = entry.local_header_offset + LOCAL_HEADER_LEN as u64 + name_len + extra_len;
+        Ok(WindowReadSeek::new(
+            self.reader.clone(),
+            start,
+            entry.compressed_len,
+        ))
+    }
+}
+
+impl<R: Read + Seek> vfs::IoBackedFs<R> for ZipFs<R> {
+    type Password = Option<Vec<u8>>;
+
+    fn from_io(io: R, password: Self::Password) -> Self {
+        Self {
+            reader: Rc::new(RefCell::new(io)),
+            password,
+            index: None,
+        }
+    }
+}
+
+impl<R: Read + Seek> vfs::Fs for ZipFs<R> {
+    type Path = [u8];
+    type Error = Error;
+    type File = File<R>;
+
+    fn metadata(&mut self, path: &[u8]) -> Result<vfs::Metadata, Self::Error> {
+        let entry = self.entry(path)?;
+        Ok(vfs::Metadata {
+            file_type: entry.file_type,
+            len: entry.len,
+            mode: entry.mode,
+            mtime: entry.mtime,
+        })
+    }
+
+    fn open(&mut self, path: &[u8]) -> Result<Self::File, Self::Error> {
+        let entry = self.entry(path)?;
+        if entry.file_type != vfs::FileType::File {
+            return Err(Error::NotAFile);
+        }
+        let data = self.data(&entry)?;
+        let source = EntrySource {
+            data,
+            entry,
+            password: self.password.clone(),
+        };
+        let inner = match source.decrypted()? {
+            Decrypted::Plain(data) if source.entry.method == METHOD_STORED => {
+                FileInner::Stored(data)
+            }
+            Decrypted::Aes(data) if source.entry.method == METHOD_STORED => {
+                FileInner::AesStored(data)
+            }
+            _ => {
+                let decoder = source.decoder()?;
+                FileInner::Decoded(Box::new(CachedReadSeek::new(CachelessFile {
+                    source,
+                    decoder: Some((decoder, crc32fast::Hasher::new())),
+                    decoder_offset: 0,
+                    offset: 0,
+                })))
+            }
+        };
+        Ok(File(inner))
+    }
+}
@@ -0,0 +1,58 @@
+# Writes the AES and zstd archive of the vfs-zip tests next to this script. Needs the zstd command
+# line tool and the cryptography package.
+import hashlib, hmac, os, struct, subprocess, zlib
+from cryptography.hazmat.primitives.ciphers import Cipher, algorithms, modes
+
+def zstd(data):
+    return subprocess.run(["zstd", "-q", "-c", "-19"], input=data, capture_output=True, check=True).stdout
+
+def deflate(data):
+    c = zlib.compressobj(9, zlib.DEFLATED, -15)
+    return c.compress(data) + c.flush()
+
+def aes_encrypt(data, password, strength, salt):
+    keylen = {1: 16, 2: 24, 3: 32}[strength]
+    dk = hashlib.pbkdf2_hmac("sha1", password, salt, 1000, 2 * keylen + 2)
+    key, mac_key, verifier = dk[:keylen], dk[keylen:2 * keylen], dk[2 * keylen:]
+    ecb = Cipher(algorithms.AES(key), modes.ECB()).encryptor()
+    out = bytearray()
+    for i in range(0, len(data), 16):
+        counter = (i // 16 + 1).to_bytes(16, "little")
+        stream = ecb.update(counter)
+        out += bytes(a ^ b for a, b in zip(data[i:i + 16], stream))
+    mac = hmac.new(mac_key, bytes(out), hashlib.sha1).digest()[:10]
+    return salt + verifier + bytes(out) + mac
+
+DOS_TIME = (12 << 11) | (34 << 5) | (56 // 2)
+DOS_DATE = ((2024 - 1980) << 9) | (5 << 5) | 17
+
+entries = []
+def add(name, data, method, aes=None):
+    crc = zlib.crc32(data)
+    payload = {0: data, 8: deflate(data), 93: zstd(data)}[method]
+    flags, stored_method, extra = 0, method, b""
+    if aes:
+        strength, version, salt = aes
+        payload = aes_encrypt(payload, b"secret", strength, salt)
+        flags, stored_method = 1, 99
+        extra = struct.pack("<HHHBBBH", 0x9901, 7, version, ord("A"), ord("E"), strength, method)
+        if version == 2:
+            crc = 0
+    entries.append((name.encode(), flags, stored_method, crc, len(payload), len(data), extra, payload))
+
+text = b"Hello world! (zstd) " * 500
+add("zstd.txt", text, 93)
+add("aes256.txt", text, 8, (3, 2, bytes(range(16))))
+add("aes128-zstd.txt", text, 93, (1, 1, bytes(range(8))))
+
+out = bytearray()
+central = bytearray()
+for name, flags, method, crc, clen, ulen, extra, payload in entries:
+    offset = len(out)
+    out += struct.pack("<IHHHHHIIIHH", 0x04034b50, 63, flags, method, DOS_TIME, DOS_DATE, crc, clen, ulen, len(name), len(extra)) + name + extra + payload
+    central += struct.pack("<IHHHHHHIIIHHHHHII", 0x02014b50, (3 << 8) | 63, 63, flags, method, DOS_TIME, DOS_DATE, crc, clen, ulen, len(name), len(extra), 0, 0, 0, 0o100644 << 16, offset) + name + extra
+cd_offset = len(out)
+out += central
+out += struct.pack("<IHHHHIIH", 0x06054b50, 0, 0, len(entries), len(entries), len(central), cd_offset, 0)
+with open(os.path.join(os.path.dirname(os.path.abspath(__file__)), "aes-zstd.zip"), "wb") as f:
+    f.write(out)
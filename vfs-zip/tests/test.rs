@@ -0,0 +1,148 @@
+use std::{
+    io::{Read, Seek, SeekFrom},
+    os::unix::fs::PermissionsExt,
+    time::{Duration, SystemTime},
+};
+use vfs::{Fs, IoBackedFs};
+
+fn zip(dir: &std::path::Path, args: &[&str]) {
+    assert!(std::process::Command::new("zip")
+        .arg("-q")
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .unwrap()
+        .success());
+}
+
+#[test]
+fn test() {
+    let tmp = tempfile::tempdir().unwrap();
+    let tmp = tmp.path();
+    let b_data = "Hello world! (B)";
+    let d_data = "Hello world! (D) ".repeat(10000);
+    std::fs::create_dir_all(tmp.join("a/nested")).unwrap();
+    std::fs::write(tmp.join("b"), b_data).unwrap();
+    std::os::unix::fs::symlink("/symlink/target/path", tmp.join("c")).unwrap();
+    std::fs::write(tmp.join("d"), &d_data).unwrap();
+    std::fs::write(tmp.join("a/nested/e"), &d_data).unwrap();
+    let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    for (path, mode) in [("a", 0o750), ("b", 0o640), ("a/nested/e", 0o600)] {
+        let path = tmp.join(path);
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        std::fs::File::open(&path)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+    }
+    zip(tmp, &["-0", "test.zip", "b"]);
+    zip(tmp, &["-r", "-y", "test.zip", "a", "c", "d"]);
+    std::fs::write(tmp.join("f"), &d_data).unwrap();
+    zip(tmp, &["-Z", "bzip2", "test.zip", "f"]);
+    zip(tmp, &["-P", "secret", "encrypted.zip", "b", "d"]);
+
+    let mut fs = vfs_zip::ZipFs::from_io(
+        std::fs::File::open(tmp.join("test.zip")).unwrap(),
+        Default::default(),
+    );
+    assert_eq!(
+        fs.metadata(b"a").unwrap(),
+        vfs::Metadata {
+            file_type: vfs::FileType::Dir,
+            len: 0,
+            mode: Some(0o750),
+            mtime: Some(mtime),
+        }
+    );
+    assert_eq!(
+        fs.metadata(b"b").unwrap(),
+        vfs::Metadata {
+            file_type: vfs::FileType::File,
+            len: b_data.len() as u64,
+            mode: Some(0o640),
+            mtime: Some(mtime),
+        }
+    );
+    assert!(matches!(
+        fs.metadata(b"c").unwrap(),
+        vfs::Metadata {
+            file_type: vfs::FileType::SymLink,
+            len: 20,
+            mode: Some(0o777),
+            mtime: Some(_),
+        }
+    ));
+    assert_eq!(
+        fs.metadata(b"/a/./nested/e").unwrap(),
+        vfs::Metadata {
+            file_type: vfs::FileType::File,
+            len: d_data.len() as u64,
+            mode: Some(0o600),
+            mtime: Some(mtime),
+        }
+    );
+    assert!(matches!(
+        fs.metadata(b"missing"),
+        Err(vfs_zip::Error::NotFound)
+    ));
+    let b = fs.open(b"b").unwrap();
+    assert_eq!(std::io::read_to_string(b).unwrap(), b_data);
+    let mut d = fs.open(b"d").unwrap();
+    let mut buf = [0; 16];
+    d.seek(SeekFrom::Start(100_000)).unwrap();
+    d.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, d_data.as_bytes()[100_000..100_016]);
+    d.rewind().unwrap();
+    assert_eq!(std::io::read_to_string(d).unwrap(), d_data);
+    let f = fs.open(b"f").unwrap();
+    assert_eq!(std::io::read_to_string(f).unwrap(), d_data);
+
+    // An archive with data prepended, like a self-extractor
+    let mut prefixed = b"#!/bin/sh\nexit 0\n".to_vec();
+    prefixed.extend(std::fs::read(tmp.join("test.zip")).unwrap());
+    let mut fs = vfs_zip::ZipFs::from_io(std::io::Cursor::new(prefixed), Default::default());
+    let e = fs.open(b"a/nested/e").unwrap();
+    assert_eq!(std::io::read_to_string(e).unwrap(), d_data);
+
+    let encrypted = || std::fs::File::open(tmp.join("encrypted.zip")).unwrap();
+    let mut fs = vfs_zip::ZipFs::from_io(encrypted(), Default::default());
+    assert!(matches!(
+        fs.open(b"b"),
+        Err(vfs_zip::Error::PasswordRequired)
+    ));
+    let mut fs = vfs_zip::ZipFs::from_io(encrypted(), Some(b"wrong".to_vec()));
+    assert!(matches!(
+        fs.open(b"d"),
+        Err(vfs_zip::Error::IncorrectPassword)
+    ));
+    let mut fs = vfs_zip::ZipFs::from_io(encrypted(), Some(b"secret".to_vec()));
+    let b = fs.open(b"b").unwrap();
+    assert_eq!(std::io::read_to_string(b).unwrap(), b_data);
+    let d = fs.open(b"d").unwrap();
+    assert_eq!(std::io::read_to_string(d).unwrap(), d_data);
+
+    // Written by tests/data/generate.py, since the zip CLI writes neither: a zstd entry, an AE-2
+    // AES-256 deflated entry and an AE-1 AES-128 zstd entry, all with the password "secret" and
+    // only a DOS timestamp
+    let fixture = || std::fs::File::open("tests/data/aes-zstd.zip").unwrap();
+    let text = "Hello world! (zstd) ".repeat(500);
+    let mut fs = vfs_zip::ZipFs::from_io(fixture(), Some(b"secret".to_vec()));
+    for path in [b"zstd.txt".as_slice(), b"aes256.txt", b"aes128-zstd.txt"] {
+        assert_eq!(
+            fs.metadata(path).unwrap(),
+            vfs::Metadata {
+                file_type: vfs::FileType::File,
+                len: text.len() as u64,
+                mode: Some(0o644),
+                mtime: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_715_949_296)),
+            }
+        );
+        let file = fs.open(path).unwrap();
+        assert_eq!(std::io::read_to_string(file).unwrap(), text);
+    }
+    let mut fs = vfs_zip::ZipFs::from_io(fixture(), Some(b"wrong".to_vec()));
+    assert!(matches!(
+        fs.open(b"aes256.txt"),
+        Err(vfs_zip::Error::IncorrectPassword)
+    ));
+}
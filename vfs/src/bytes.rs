@@ -0,0 +1,21 @@
+//! Integers at byte offsets of on-disk structures. These panic if the buffer is too short.
+
+pub fn u16_at(buf: &[u8], i: usize) -> u16 {
+    u16::from_le_bytes(buf[i..i + 2].try_into().unwrap())
+}
+
+pub fn u32_at(buf: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes(buf[i..i + 4].try_into().unwrap())
+}
+
+pub fn u64_at(buf: &[u8], i: usize) -> u64 {
+    u64::from_le_bytes(buf[i..i + 8].try_into().unwrap())
+}
+
+pub fn be_u32_at(buf: &[u8], i: usize) -> u32 {
+    u32::from_be_bytes(buf[i..i + 4].try_into().unwrap())
+}
+
+pub fn be_u64_at(buf: &[u8], i: usize) -> u64 {
+    u64::from_be_bytes(buf[i..i + 8].try_into().unwrap())
+}
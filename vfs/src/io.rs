@@ -0,0 +1,37 @@
+//! Helpers for reading images and implementing `Seek` over their files.
+
+use std::{
+    cell::RefCell,
+    io::{Read, Seek, SeekFrom},
+};
+
+/// Reads `len` bytes at `pos` of a shared reader.
+pub fn read_at<R: Read + Seek + ?Sized>(
+    reader: &RefCell<R>,
+    pos: u64,
+    len: usize,
+) -> std::io::Result<Vec<u8>> {
+    let mut reader = reader.borrow_mut();
+    let mut buf = vec![0; len];
+    reader.seek(SeekFrom::Start(pos))?;
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// The error for a seek before the start of a file or past `u64::MAX`.
+pub fn invalid_seek() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        "invalid seek to a negative or overflowing position",
+    )
+}
+
+/// Returns the position a seek to `pos` moves to from `offset`, in a file of length `len`.
+pub fn seek_position(pos: SeekFrom, offset: u64, len: u64) -> std::io::Result<u64> {
+    match pos {
+        SeekFrom::Start(offset) => Some(offset),
+        SeekFrom::End(delta) => len.checked_add_signed(delta),
+        SeekFrom::Current(delta) => offset.checked_add_signed(delta),
+    }
+    .ok_or_else(invalid_seek)
+}
@@ -1,16 +1,28 @@
-use std::io::{Read, Seek};
+pub mod bytes;
+pub mod io;
+pub mod path;
+pub mod time;
 
-#[derive(Debug, PartialEq, Eq)]
+use std::{
+    io::{Read, Seek},
+    time::SystemTime,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FileType {
     Dir,
     File,
     SymLink,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Metadata {
     pub file_type: FileType,
     pub len: u64,
+    /// Unix permission bits, if the backend records them
+    pub mode: Option<u32>,
+    /// Last modification time, if the backend records it
+    pub mtime: Option<SystemTime>,
 }
 
 pub trait Fs {
@@ -32,3 +44,27 @@ pub trait IoBackedFs<R: Read + Seek>: Fs {
 
     fn from_io(io: R, password: Self::Password) -> Self;
 }
+
+/// A filesystem that can be modified in place. Missing parent directories are created as needed.
+pub trait WritableFs: Fs {
+    /// Creates a regular file, or replaces the contents of an existing one.
+    fn write(&mut self, path: &Self::Path, data: &[u8]) -> Result<(), Self::Error>;
+
+    fn create_dir(&mut self, path: &Self::Path) -> Result<(), Self::Error>;
+
+    /// Removes a file, or a directory along with everything below it.
+    fn remove(&mut self, path: &Self::Path) -> Result<(), Self::Error>;
+}
+
+/// An entry of a directory listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    /// The name of the entry, to be joined to the directory's path
+    pub name: Vec<u8>,
+    pub metadata: Metadata,
+}
+
+/// A filesystem whose directories can be listed.
+pub trait ReadDir: Fs {
+    fn read_dir(&mut self, path: &Self::Path) -> Result<Vec<DirEntry>, Self::Error>;
+}
@@ -1 +1,20 @@
+/// Splits a `/`-separated archive path into its components, dropping empty and `.` components
+/// and resolving `..` lexically.
+pub fn components(path: &[u8]) -> Vec<&[u8]> {
+    let mut components = Vec::new();
+    for component in path.split(|&b| b == b'/') {
+        match component {
+            b"" | b"." => {}
+            b".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    components
+}
 
+/// Normalizes an archive path so that `/a/./b/`, `a//b` and `a/b` compare equal.
+pub fn normalize(path: &[u8]) -> Vec<u8> {
+    components(path).join(b"/".as_slice())
+}
@@ -0,0 +1,65 @@
+use std::{
+    cell::RefCell,
+    io::{Read, Seek, SeekFrom},
+    rc::Rc,
+};
+
+/// A view of the byte range `start..start + len` of a reader that is shared with other views.
+pub struct WindowReadSeek<R: Read + Seek> {
+    reader: Rc<RefCell<R>>,
+    start: u64,
+    len: u64,
+    offset: u64,
+}
+
+impl<R: Read + Seek> WindowReadSeek<R> {
+    pub fn new(reader: Rc<RefCell<R>>, start: u64, len: u64) -> Self {
+        Self {
+            reader,
+            start,
+            len,
+            offset: 0,
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<R: Read + Seek> Clone for WindowReadSeek<R> {
+    fn clone(&self) -> Self {
+        Self {
+            reader: self.reader.clone(),
+            start: self.start,
+            len: self.len,
+            offset: self.offset,
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for WindowReadSeek<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.offset);
+        let n = (buf.len() as u64).min(remaining) as usize;
+        if n == 0 {
+            return Ok(0);
+        }
+        let mut reader = self.reader.borrow_mut();
+        reader.seek(SeekFrom::Start(self.start + self.offset))?;
+        let n = reader.read(&mut buf[..n])?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for WindowReadSeek<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.offset = vfs::io::seek_position(pos, self.offset, self.len)?;
+        Ok(self.offset)
+    }
+}